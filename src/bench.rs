@@ -0,0 +1,282 @@
+use crate::{
+    Cli, Command,
+    client::{Auth, elasticsearch::request},
+    comma_formatted,
+    document::Document,
+    generate::DocTemplate,
+    manifest::redact_uri,
+    output::{self, BulkAction, ElasticsearchOutputConfig, Output, OutputPreflightConfig},
+};
+use elasticsearch::{
+    Elasticsearch,
+    http::{Method, StatusCode, headers::HeaderMap},
+};
+use eyre::{Result, eyre};
+use std::{fmt::Write, time::Instant};
+
+/// One `espipe bench` trial: generated documents sent with one batch size and
+/// request concurrency.
+#[derive(Debug, PartialEq)]
+struct Trial {
+    batch_size: usize,
+    requests: usize,
+    docs: u64,
+    sent: u64,
+    bytes: u64,
+    seconds: f64,
+}
+
+impl Trial {
+    fn docs_per_second(&self) -> f64 {
+        self.sent as f64 / self.seconds
+    }
+
+    fn megabytes_per_second(&self) -> f64 {
+        self.bytes as f64 / 1_000_000.0 / self.seconds
+    }
+}
+
+/// Measures bulk throughput to a scratch index at every combination of
+/// `--batch-sizes` and `--concurrency`, sending generated documents through the
+/// same output a pipe uses, then prints a report of the fastest settings.
+///
+/// The index must not exist; it is deleted after every trial, so each starts
+/// from an empty index, and once more when the benchmark ends or fails.
+#[tracing::instrument(name = "espipe_bench", skip_all)]
+pub async fn run(args: Cli) -> Result<()> {
+    let Some(Command::Bench {
+        target,
+        docs,
+        doc_template,
+        batch_sizes,
+        concurrency,
+    }) = &args.command
+    else {
+        unreachable!("bench::run is only called for espipe bench");
+    };
+    let template = match doc_template {
+        Some(path) => DocTemplate::load(path)?,
+        None => DocTemplate::default(),
+    };
+    let config = connection_config(&args);
+    let (client, url) = output::elasticsearch_client(
+        args.insecure,
+        auth(&args)?,
+        target,
+        !args.uncompressed,
+        &config,
+    )?;
+    let index = url.path().trim_start_matches('/').to_string();
+    if index.is_empty() || index.contains('/') || index.contains(['*', ',']) {
+        return Err(eyre!(
+            "espipe bench needs one index to write to, like {url}bench"
+        ));
+    }
+    if index_exists(&client, &index).await? {
+        return Err(eyre!(
+            "{index} already exists; espipe bench deletes the index it writes to, so choose one that does not exist"
+        ));
+    }
+
+    let mut trials = Vec::new();
+    let mut seq = 0;
+    let result = async {
+        for &batch_size in batch_sizes {
+            for &requests in concurrency {
+                let trial = run_trial(&args, &template, batch_size, requests, *docs, seq).await;
+                delete_index(&client, &index).await;
+                let trial = trial?;
+                seq += trial.docs;
+                if !args.quiet {
+                    eprintln!(
+                        "--batch-size {batch_size} --max-requests {requests}: {} docs/s",
+                        comma_formatted(trial.docs_per_second() as usize)
+                    );
+                }
+                trials.push(trial);
+            }
+        }
+        Ok::<_, eyre::Report>(())
+    }
+    .await;
+    delete_index(&client, &index).await;
+    result?;
+    print!("{}", report(redact_uri(url.as_str()).as_str(), &trials));
+    Ok(())
+}
+
+/// Sends `docs` generated documents, numbered from `seq`, and times them from
+/// the first send until the output closes.
+async fn run_trial(
+    args: &Cli,
+    template: &DocTemplate,
+    batch_size: usize,
+    requests: usize,
+    docs: u64,
+    seq: u64,
+) -> Result<Trial> {
+    let config = ElasticsearchOutputConfig::try_new(batch_size, requests)?
+        .with_timeouts(args.request_timeout, args.batch_timeout)
+        .with_proxy(args.proxy.clone())
+        .with_tls_server_name(args.tls_server_name.clone())
+        .with_socket_options(
+            args.connect_timeout,
+            args.tcp_keepalive,
+            args.pool_idle_timeout,
+        )
+        .with_headers(args.headers.clone());
+    let Some(Command::Bench { target, .. }) = &args.command else {
+        unreachable!("run_trial is only called for espipe bench");
+    };
+    let mut output = Output::try_new(
+        args.insecure,
+        auth(args)?,
+        target.clone(),
+        BulkAction::Create,
+        !args.uncompressed,
+        config,
+        OutputPreflightConfig::default(),
+    )
+    .await?;
+    let start = Instant::now();
+    let mut sent = 0;
+    let mut bytes = 0;
+    for seq in seq..seq + docs {
+        let doc = Document::from(template.render(seq)?);
+        bytes += doc.get().len() as u64;
+        sent += output.send(doc).await? as u64;
+    }
+    sent += output.close().await? as u64;
+    Ok(Trial {
+        batch_size,
+        requests,
+        docs,
+        sent,
+        bytes,
+        seconds: start.elapsed().as_secs_f64(),
+    })
+}
+
+/// The trials as aligned columns, fastest first, with the settings to use.
+fn report(target: &str, trials: &[Trial]) -> String {
+    let mut ranked: Vec<&Trial> = trials.iter().collect();
+    ranked.sort_by(|a, b| b.docs_per_second().total_cmp(&a.docs_per_second()));
+    let mut text = format!("Bulk throughput to {target}\n\n");
+    let _ = writeln!(
+        text,
+        "{:>10}  {:>12}  {:>12}  {:>8}  {:>10}",
+        "batch size", "max requests", "docs/s", "MB/s", "failed"
+    );
+    for trial in &ranked {
+        let _ = writeln!(
+            text,
+            "{:>10}  {:>12}  {:>12}  {:>8.1}  {:>10}",
+            comma_formatted(trial.batch_size),
+            trial.requests,
+            comma_formatted(trial.docs_per_second() as usize),
+            trial.megabytes_per_second(),
+            comma_formatted(trial.docs.saturating_sub(trial.sent) as usize)
+        );
+    }
+    if let Some(fastest) = ranked.first() {
+        let _ = writeln!(
+            text,
+            "\nFastest: --batch-size {} --max-requests {}",
+            fastest.batch_size, fastest.requests
+        );
+    }
+    text
+}
+
+fn connection_config(args: &Cli) -> ElasticsearchOutputConfig {
+    ElasticsearchOutputConfig::default()
+        .with_timeouts(args.request_timeout, None)
+        .with_proxy(args.proxy.clone())
+        .with_tls_server_name(args.tls_server_name.clone())
+        .with_socket_options(
+            args.connect_timeout,
+            args.tcp_keepalive,
+            args.pool_idle_timeout,
+        )
+        .with_headers(args.headers.clone())
+}
+
+fn auth(args: &Cli) -> Result<Auth> {
+    Auth::try_new(
+        args.apikey.clone(),
+        args.username.clone(),
+        args.password.clone(),
+    )
+}
+
+async fn index_exists(client: &Elasticsearch, index: &str) -> Result<bool> {
+    let response = client
+        .send(
+            Method::Head,
+            &format!("/{index}"),
+            HeaderMap::new(),
+            Option::<&()>::None,
+            Option::<Vec<u8>>::None,
+            None,
+        )
+        .await?;
+    match response.status_code() {
+        StatusCode::NOT_FOUND => Ok(false),
+        status if status.is_success() => Ok(true),
+        status => Err(eyre!("HEAD /{index} failed with status {status}")),
+    }
+}
+
+/// Deletes the scratch index, which a failed trial may not have created.
+async fn delete_index(client: &Elasticsearch, index: &str) {
+    let path = format!("/{index}");
+    if let Err(err) = request(
+        client,
+        Method::Delete,
+        &path,
+        &[("ignore_unavailable", "true")],
+        None,
+    )
+    .await
+    {
+        log::warn!("Could not delete the benchmark index {index}: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Trial, report};
+
+    #[test]
+    fn report_ranks_trials_by_throughput() {
+        let trial = |batch_size, requests, seconds| Trial {
+            batch_size,
+            requests,
+            docs: 10_000,
+            sent: 10_000,
+            bytes: 5_000_000,
+            seconds,
+        };
+        let trials = [trial(500, 1, 2.0), trial(5000, 4, 0.5), trial(1000, 4, 1.0)];
+
+        let text = report("http://localhost:9200/bench", &trials);
+        let rows: Vec<&str> = text.lines().skip(3).take(3).collect();
+
+        assert!(
+            rows[0].contains("5,000") && rows[0].contains("20,000"),
+            "{text}"
+        );
+        assert!(
+            rows[1].contains("1,000") && rows[1].contains("10,000"),
+            "{text}"
+        );
+        assert!(
+            rows[2].contains("500") && rows[2].contains("5,000"),
+            "{text}"
+        );
+        assert!(
+            text.ends_with("Fastest: --batch-size 5000 --max-requests 4\n"),
+            "{text}"
+        );
+    }
+}
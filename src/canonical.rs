@@ -0,0 +1,44 @@
+use serde_json::Value;
+
+/// Serializes `value` with the keys of every object in sorted order, so
+/// documents that differ only in field order or whitespace serialize alike,
+/// for hashing and comparing them.
+///
+/// `Value::to_string` keeps keys in the order they were read, since serde_json
+/// is built with `preserve_order`.
+pub fn to_string(value: &Value) -> String {
+    sorted(value).to_string()
+}
+
+fn sorted(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut fields: Vec<_> = map.iter().collect();
+            fields.sort_unstable_by_key(|(key, _)| *key);
+            Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(key, value)| (key.clone(), sorted(value)))
+                    .collect(),
+            )
+        }
+        Value::Array(values) => Value::Array(values.iter().map(sorted).collect()),
+        value => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_string;
+    use serde_json::Value;
+
+    #[test]
+    fn object_keys_are_sorted_at_every_depth() {
+        let value: Value =
+            serde_json::from_str(r#"{ "b": [{"y":1,"x":2}], "a": {"d":null,"c":"\""} }"#).unwrap();
+        assert_eq!(
+            to_string(&value),
+            r#"{"a":{"c":"\"","d":null},"b":[{"x":2,"y":1}]}"#
+        );
+    }
+}
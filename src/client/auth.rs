@@ -1,4 +1,5 @@
 use eyre::{Result, eyre};
+use std::io::BufRead;
 
 pub enum Auth {
     Apikey(String),
@@ -21,6 +22,30 @@ impl Auth {
     }
 }
 
+/// Reads the basic authentication password for `username` from the first line
+/// of stdin, or else prompts for it on the terminal without echoing it, so it
+/// stays out of shell history and process lists.
+pub fn read_password(username: &str, from_stdin: bool) -> Result<String> {
+    let password = match from_stdin {
+        true => first_line(std::io::stdin().lock())?,
+        false => {
+            rpassword::prompt_password(format!("Password for {username}: ")).map_err(|err| {
+                eyre!("Cannot prompt for the password of {username}, use --password-stdin: {err}")
+            })?
+        }
+    };
+    match password.is_empty() {
+        true => Err(eyre!("Empty password for {username}")),
+        false => Ok(password),
+    }
+}
+
+fn first_line(mut reader: impl BufRead) -> Result<String> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
 impl std::fmt::Display for Auth {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -30,3 +55,18 @@ impl std::fmt::Display for Auth {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::first_line;
+
+    #[test]
+    fn passwords_are_read_from_the_first_line_without_its_newline() {
+        assert_eq!(
+            first_line(&b"s3cret pass\r\nnext\n"[..]).unwrap(),
+            "s3cret pass"
+        );
+        assert_eq!(first_line(&b"s3cret"[..]).unwrap(), "s3cret");
+        assert_eq!(first_line(&b""[..]).unwrap(), "");
+    }
+}
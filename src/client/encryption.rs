@@ -0,0 +1,94 @@
+use age::secrecy::SecretString;
+use eyre::{Result, eyre};
+use std::{env, sync::Mutex};
+
+/// The first line of an ASCII-armored age file.
+const ARMOR_HEADER: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+
+/// The passphrase used this run, so it is asked for at most once.
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Whether `contents` is an age file, rather than plain YAML.
+pub fn is_encrypted(contents: &str) -> bool {
+    contents.trim_start().starts_with(ARMOR_HEADER)
+}
+
+/// `plaintext` encrypted with `passphrase` as an ASCII-armored age file.
+fn encrypt(plaintext: &str, passphrase: &str) -> Result<String> {
+    let recipient = age::scrypt::Recipient::new(SecretString::from(passphrase.to_string()));
+    age::encrypt_and_armor(&recipient, plaintext.as_bytes())
+        .map_err(|err| eyre!("Failed to encrypt hosts.yml: {err}"))
+}
+
+/// The plaintext of an age file encrypted with `passphrase`.
+fn decrypt(ciphertext: &str, passphrase: &str) -> Result<String> {
+    let identity = age::scrypt::Identity::new(SecretString::from(passphrase.to_string()));
+    let plaintext = age::decrypt(&identity, ciphertext.as_bytes())
+        .map_err(|err| eyre!("Failed to decrypt hosts.yml: {err}"))?;
+    String::from_utf8(plaintext).map_err(|err| eyre!("Decrypted hosts.yml is not UTF-8: {err}"))
+}
+
+/// Decrypts hosts.yml with the passphrase from `$ESPIPE_HOSTS_PASSPHRASE`, or
+/// else the one that decrypted it earlier in the run, or else one prompted for.
+pub fn decrypt_hosts(ciphertext: &str) -> Result<String> {
+    let mut cached = PASSPHRASE.lock().expect("passphrase lock poisoned");
+    let passphrase = match (env::var("ESPIPE_HOSTS_PASSPHRASE"), cached.as_ref()) {
+        (Ok(passphrase), _) => passphrase,
+        (Err(_), Some(passphrase)) => passphrase.clone(),
+        (Err(_), None) => prompt("Passphrase for hosts.yml: ")?,
+    };
+    let plaintext = decrypt(ciphertext, &passphrase)?;
+    *cached = Some(passphrase);
+    Ok(plaintext)
+}
+
+/// Encrypts hosts.yml with the passphrase it was decrypted with, or else a
+/// new one.
+pub fn encrypt_hosts(plaintext: &str) -> Result<String> {
+    let cached = PASSPHRASE.lock().expect("passphrase lock poisoned").clone();
+    let passphrase = match cached {
+        Some(passphrase) => passphrase,
+        None => new_passphrase()?,
+    };
+    encrypt(plaintext, &passphrase)
+}
+
+/// A passphrase to encrypt hosts.yml with, from `$ESPIPE_HOSTS_PASSPHRASE` or
+/// else prompted for twice.
+fn new_passphrase() -> Result<String> {
+    if let Ok(passphrase) = env::var("ESPIPE_HOSTS_PASSPHRASE") {
+        return Ok(passphrase);
+    }
+    let passphrase = prompt("New passphrase for hosts.yml: ")?;
+    if prompt("Repeat the passphrase: ")? != passphrase {
+        return Err(eyre!("The passphrases do not match"));
+    }
+    Ok(passphrase)
+}
+
+fn prompt(prompt: &str) -> Result<String> {
+    let passphrase = rpassword::prompt_password(prompt).map_err(|err| {
+        eyre!("Cannot prompt for the hosts.yml passphrase, set ESPIPE_HOSTS_PASSPHRASE: {err}")
+    })?;
+    match passphrase.is_empty() {
+        true => Err(eyre!("Empty passphrase for hosts.yml")),
+        false => Ok(passphrase),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decrypt, encrypt, is_encrypted};
+
+    #[test]
+    fn hosts_round_trip_through_a_passphrase() {
+        let yaml = "local:\n  auth: None\n  url: http://localhost:9200\n";
+        let encrypted = encrypt(yaml, "correct horse").unwrap();
+
+        assert!(is_encrypted(&encrypted));
+        assert!(!is_encrypted(yaml));
+        assert!(!encrypted.contains("localhost"));
+        assert_eq!(decrypt(&encrypted, "correct horse").unwrap(), yaml);
+        assert!(decrypt(&encrypted, "battery staple").is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use elasticsearch::Elasticsearch;
+use std::{
+    fmt::Display,
+    sync::{Mutex, MutexGuard},
+};
+use url::Url;
+
+/// Consecutive connection failures to a host before switching to the next.
+const FAILOVER_AFTER: usize = 3;
+
+/// A known host's primary client and the clients for its `fallback_urls`,
+/// switching to the next host when the one in use repeatedly fails to connect.
+#[derive(Debug)]
+pub struct Failover {
+    hosts: Vec<(Url, Elasticsearch)>,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Switches so far; the host in use is this modulo the host count.
+    generation: usize,
+    /// Consecutive connection failures to the host in use.
+    failures: usize,
+    /// Hosts given up on since the last successful connection.
+    hosts_failed: usize,
+}
+
+impl Failover {
+    pub fn new(url: Url, client: Elasticsearch) -> Self {
+        Self {
+            hosts: vec![(url, client)],
+            state: Mutex::default(),
+        }
+    }
+
+    /// Adds a host to fail over to, after those already added.
+    pub fn with_fallback(mut self, url: Url, client: Elasticsearch) -> Self {
+        self.hosts.push((url, client));
+        self
+    }
+
+    /// The client of the host in use, with the generation to report its outcome against.
+    pub fn client(&self) -> (usize, Elasticsearch) {
+        let generation = self.lock().generation;
+        let (_, client) = &self.hosts[generation % self.hosts.len()];
+        (generation, client.clone())
+    }
+
+    /// Records that a request from `generation` reached its host.
+    pub fn connected(&self, generation: usize) {
+        let mut state = self.lock();
+        if state.generation == generation {
+            state.failures = 0;
+            state.hosts_failed = 0;
+        }
+    }
+
+    /// Records that a request from `generation` could not reach its host,
+    /// switching hosts after repeated failures. Returns whether the request
+    /// should be retried, which it never is without fallback hosts or once
+    /// every host has failed in turn.
+    pub fn failed(&self, generation: usize, err: &impl Display) -> bool {
+        if self.hosts.len() == 1 {
+            return false;
+        }
+        let mut state = self.lock();
+        // Another request already switched away from the host this one used.
+        if state.generation != generation {
+            return true;
+        }
+        let (url, _) = &self.hosts[generation % self.hosts.len()];
+        state.failures += 1;
+        log::warn!(
+            "Connection to {url} failed ({}/{FAILOVER_AFTER}): {err}",
+            state.failures
+        );
+        if state.failures < FAILOVER_AFTER {
+            return true;
+        }
+        state.hosts_failed += 1;
+        if state.hosts_failed >= self.hosts.len() {
+            log::error!("Every configured host failed {FAILOVER_AFTER} times in a row");
+            return false;
+        }
+        state.generation += 1;
+        state.failures = 0;
+        let (next, _) = &self.hosts[state.generation % self.hosts.len()];
+        log::warn!("Failing over from {url} to {next}");
+        true
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("failover lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FAILOVER_AFTER, Failover};
+    use crate::client::ElasticsearchBuilder;
+    use url::Url;
+
+    fn host(url: &str) -> (Url, elasticsearch::Elasticsearch) {
+        let url = Url::parse(url).unwrap();
+        (url.clone(), ElasticsearchBuilder::new(url).build().unwrap())
+    }
+
+    fn failover(urls: &[&str]) -> Failover {
+        let (url, client) = host(urls[0]);
+        urls[1..]
+            .iter()
+            .fold(Failover::new(url, client), |failover, url| {
+                let (url, client) = host(url);
+                failover.with_fallback(url, client)
+            })
+    }
+
+    #[test]
+    fn a_single_host_is_never_retried() {
+        let failover = failover(&["http://primary:9200"]);
+        let (generation, _) = failover.client();
+        assert!(!failover.failed(generation, &"refused"));
+    }
+
+    #[test]
+    fn repeated_failures_switch_to_the_next_host() {
+        let failover = failover(&["http://primary:9200", "http://fallback:9200"]);
+        for _ in 1..FAILOVER_AFTER {
+            assert!(failover.failed(0, &"refused"));
+            assert_eq!(failover.client().0, 0);
+        }
+        assert!(failover.failed(0, &"refused"));
+        assert_eq!(failover.client().0, 1);
+
+        // A request still in flight to the primary retries on the fallback
+        // without counting against it.
+        assert!(failover.failed(0, &"refused"));
+        assert_eq!(failover.lock().failures, 0);
+    }
+
+    #[test]
+    fn every_host_failing_in_turn_gives_up() {
+        let failover = failover(&["http://primary:9200", "http://fallback:9200"]);
+        for _ in 1..FAILOVER_AFTER {
+            failover.failed(0, &"refused");
+        }
+        failover.failed(0, &"refused");
+        for _ in 1..FAILOVER_AFTER {
+            assert!(failover.failed(1, &"refused"));
+        }
+        assert!(!failover.failed(1, &"refused"));
+    }
+
+    #[test]
+    fn connecting_resets_the_failure_count() {
+        let failover = failover(&["http://primary:9200", "http://fallback:9200"]);
+        for _ in 1..FAILOVER_AFTER {
+            failover.failed(0, &"refused");
+        }
+        failover.connected(0);
+        assert!(failover.failed(0, &"refused"));
+        assert_eq!(failover.client().0, 0);
+    }
+}
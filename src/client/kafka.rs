@@ -0,0 +1,108 @@
+use eyre::{Result, eyre};
+use fluent_uri::UriRef;
+use rdkafka::ClientConfig;
+use url::form_urlencoded;
+
+/// A `kafka://broker:9092/topic?key=value` URI.
+///
+/// Query parameters that espipe does not consume itself are passed through to
+/// librdkafka, so settings like `security.protocol` or `sasl.username` work as-is.
+#[derive(Clone, Debug)]
+pub struct KafkaUri {
+    pub bootstrap_servers: String,
+    pub topic: String,
+    options: Vec<(String, String)>,
+}
+
+impl KafkaUri {
+    pub fn parse(uri: &UriRef<String>) -> Result<Self> {
+        let authority = uri
+            .authority()
+            .ok_or_else(|| eyre!("Kafka URI requires a broker: {uri}"))?;
+        if authority.host().is_empty() {
+            return Err(eyre!("Kafka URI requires a broker: {uri}"));
+        }
+        let bootstrap_servers = match authority.port() {
+            Some(port) => format!("{}:{}", authority.host(), port.as_str()),
+            None => format!("{}:9092", authority.host()),
+        };
+        let topic = uri.path().as_str().trim_matches('/').to_string();
+        if topic.is_empty() || topic.contains('/') {
+            return Err(eyre!("Kafka URI requires a single topic path: {uri}"));
+        }
+        let options = uri
+            .query()
+            .map(|query| {
+                form_urlencoded::parse(query.as_str().as_bytes())
+                    .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Self {
+            bootstrap_servers,
+            topic,
+            options,
+        })
+    }
+
+    /// Removes an espipe-specific option so it is not passed on to librdkafka.
+    pub fn take_option(&mut self, key: &str) -> Option<String> {
+        let index = self.options.iter().rposition(|(name, _)| name == key)?;
+        let (_, value) = self.options.remove(index);
+        self.options.retain(|(name, _)| name != key);
+        Some(value)
+    }
+
+    pub fn client_config(&self) -> ClientConfig {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", &self.bootstrap_servers);
+        for (key, value) in &self.options {
+            config.set(key, value);
+        }
+        config
+    }
+}
+
+impl std::fmt::Display for KafkaUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "kafka://{}/{}", self.bootstrap_servers, self.topic)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KafkaUri;
+    use fluent_uri::UriRef;
+
+    fn parse(uri: &str) -> eyre::Result<KafkaUri> {
+        KafkaUri::parse(&UriRef::parse(uri.to_string()).unwrap())
+    }
+
+    #[test]
+    fn parses_broker_topic_and_options() {
+        let mut uri =
+            parse("kafka://broker:9093/events?group=ingest&security.protocol=SSL").unwrap();
+
+        assert_eq!(uri.bootstrap_servers, "broker:9093");
+        assert_eq!(uri.topic, "events");
+        assert_eq!(uri.take_option("group").as_deref(), Some("ingest"));
+        assert_eq!(uri.take_option("group"), None);
+        assert_eq!(uri.client_config().get("security.protocol"), Some("SSL"));
+        assert_eq!(uri.to_string(), "kafka://broker:9093/events");
+    }
+
+    #[test]
+    fn defaults_the_broker_port() {
+        assert_eq!(
+            parse("kafka://broker/events").unwrap().bootstrap_servers,
+            "broker:9092"
+        );
+    }
+
+    #[test]
+    fn rejects_missing_broker_or_topic() {
+        assert!(parse("kafka:///events").is_err());
+        assert!(parse("kafka://broker").is_err());
+        assert!(parse("kafka://broker/a/b").is_err());
+    }
+}
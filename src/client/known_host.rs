@@ -1,12 +1,14 @@
+use super::{Auth, SocketOptions, encryption};
+use crate::value_parsers::parse_nonzero_duration;
 use eyre::{Result, eyre};
 use serde::{Deserialize, Serialize};
 use serde_yaml;
 use std::collections::BTreeMap;
 use std::env;
 use std::fmt::{self, Display, Formatter};
-use std::fs::{File, create_dir};
-use std::io::BufReader;
-use std::path::PathBuf;
+use std::fs::{File, copy, create_dir_all, read_to_string, remove_file, rename};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use url::Url;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,22 +18,124 @@ pub enum KnownHost {
         insecure: Option<bool>,
         apikey: String,
         url: Url,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        fallback_urls: Vec<Url>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tags: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        proxy: Option<Url>,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        headers: BTreeMap<String, String>,
+        #[serde(flatten)]
+        socket: SocketSettings,
+        #[serde(flatten)]
+        defaults: HostDefaults,
     },
     Basic {
         insecure: Option<bool>,
         password: String,
         url: Url,
         username: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        fallback_urls: Vec<Url>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tags: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        proxy: Option<Url>,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        headers: BTreeMap<String, String>,
+        #[serde(flatten)]
+        socket: SocketSettings,
+        #[serde(flatten)]
+        defaults: HostDefaults,
     },
     None {
         insecure: Option<bool>,
         url: Url,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        fallback_urls: Vec<Url>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tags: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        proxy: Option<Url>,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        headers: BTreeMap<String, String>,
+        #[serde(flatten)]
+        socket: SocketSettings,
+        #[serde(flatten)]
+        defaults: HostDefaults,
     },
 }
 
+/// Socket tuning for a known host's connections, as durations like `30s`,
+/// which `--connect-timeout`, `--tcp-keepalive`, and `--pool-idle-timeout` override.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SocketSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    connect_timeout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tcp_keepalive: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pool_idle_timeout: Option<String>,
+}
+
+/// Settings for outputs to a known host, so cluster-specific tuning lives next
+/// to its credentials. `batch_size` and `compression` are defaults for
+/// `--batch-size` and `--uncompressed`, and `pipeline` applies without
+/// `--pipeline`, `--pipeline-name`, or `--template`, so flags win over each.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HostDefaults {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch_size: Option<usize>,
+    /// Whether bulk request bodies are gzip-compressed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression: Option<bool>,
+    /// An existing ingest pipeline to run documents through.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pipeline: Option<String>,
+    /// Put in front of the index named in an output URI, so `prod:logs`
+    /// writes to `team-a-logs` with a prefix of `team-a-`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index_prefix: Option<String>,
+    /// A PEM CA certificate to trust for the host, in place of the system's.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<PathBuf>,
+}
+
+impl HostDefaults {
+    /// The defaults that stand in for flags, by long flag name, as in `config.yml`.
+    pub fn flags(&self) -> BTreeMap<String, serde_yaml::Value> {
+        let mut flags = BTreeMap::new();
+        if let Some(batch_size) = self.batch_size {
+            flags.insert("batch-size".to_string(), batch_size.into());
+        }
+        if let Some(compression) = self.compression {
+            flags.insert("uncompressed".to_string(), (!compression).into());
+        }
+        flags
+    }
+}
+
+impl SocketSettings {
+    pub fn parse(&self) -> Result<SocketOptions> {
+        let duration = |name: &str, value: &Option<String>| {
+            value
+                .as_deref()
+                .map(parse_nonzero_duration)
+                .transpose()
+                .map_err(|err| eyre!("hosts.yml {name}: {err}"))
+        };
+        Ok(SocketOptions {
+            connect_timeout: duration("connect_timeout", &self.connect_timeout)?,
+            tcp_keepalive: duration("tcp_keepalive", &self.tcp_keepalive)?,
+            idle_timeout: duration("pool_idle_timeout", &self.pool_idle_timeout)?,
+        })
+    }
+}
+
 impl KnownHost {
     pub fn parse(host: &str) -> Option<Self> {
-        // parse the ~/.espipe/hosts.yml file into a HashMap<String, Host>
+        // parse the hosts.yml file into a HashMap<String, Host>
         let hosts = match parse_hosts_yml() {
             Ok(hosts) => hosts,
             Err(e) => {
@@ -51,6 +155,141 @@ impl KnownHost {
         hosts.get(host).cloned()
     }
 
+    /// A host at `url` without credentials or other settings.
+    pub fn from_url(url: Url, insecure: bool) -> Self {
+        Self::None {
+            insecure: insecure.then_some(true),
+            url,
+            fallback_urls: Vec::new(),
+            tags: Vec::new(),
+            proxy: None,
+            headers: BTreeMap::new(),
+            socket: SocketSettings::default(),
+            defaults: HostDefaults::default(),
+        }
+    }
+
+    /// This host with `auth` in place of its own credentials, keeping its
+    /// connection settings, or unchanged when `auth` is `Auth::None`.
+    pub fn with_auth(self, auth: Auth) -> Self {
+        if let Auth::None = auth {
+            return self;
+        }
+        let (Self::ApiKey {
+            insecure,
+            url,
+            fallback_urls,
+            tags,
+            proxy,
+            headers,
+            socket,
+            defaults,
+            ..
+        }
+        | Self::Basic {
+            insecure,
+            url,
+            fallback_urls,
+            tags,
+            proxy,
+            headers,
+            socket,
+            defaults,
+            ..
+        }
+        | Self::None {
+            insecure,
+            url,
+            fallback_urls,
+            tags,
+            proxy,
+            headers,
+            socket,
+            defaults,
+        }) = self;
+        match auth {
+            Auth::Apikey(apikey) => Self::ApiKey {
+                insecure,
+                apikey,
+                url,
+                fallback_urls,
+                tags,
+                proxy,
+                headers,
+                socket,
+                defaults,
+            },
+            Auth::Basic(username, password) => Self::Basic {
+                insecure,
+                password,
+                url,
+                username,
+                fallback_urls,
+                tags,
+                proxy,
+                headers,
+                socket,
+                defaults,
+            },
+            Auth::None => unreachable!("returned above"),
+        }
+    }
+
+    /// Adds this host to hosts.yml as `name`, returning the file's path. An
+    /// existing entry of that name is left alone and an error returned.
+    pub fn save(self, name: &str) -> Result<PathBuf> {
+        let mut entries = read_hosts_yml()?;
+        if expand_groups(entries.clone())?.contains_key(name) {
+            return Err(eyre!("hosts.yml already has a host named {name}"));
+        }
+        entries.insert(name.to_string(), serde_yaml::to_value(self)?);
+        let path = get_hosts_path()?;
+        let yaml = serde_yaml::to_string(&entries)?;
+        let encrypted = encryption::is_encrypted(&read_to_string(&path)?);
+        write_hosts_yml(&path, &yaml, encrypted)?;
+        Ok(path)
+    }
+
+    /// Encrypts hosts.yml with a passphrase, or decrypts it back to plain
+    /// YAML, returning the file's path.
+    pub fn set_encrypted(encrypt: bool) -> Result<PathBuf> {
+        let path = get_hosts_path()?;
+        let contents = read_to_string(&path)
+            .map_err(|err| eyre!("Failed to read {}: {err}", path.display()))?;
+        let yaml = match (encryption::is_encrypted(&contents), encrypt) {
+            (true, true) => return Err(eyre!("{} is already encrypted", path.display())),
+            (false, false) => return Err(eyre!("{} is not encrypted", path.display())),
+            (true, false) => encryption::decrypt_hosts(&contents)?,
+            (false, true) => contents,
+        };
+        expand_groups(serde_yaml::from_str::<Option<_>>(&yaml)?.unwrap_or_default())?;
+        write_hosts_yml(&path, &yaml, encrypt)?;
+        Ok(path)
+    }
+
+    /// The name of `name`'s host in environment `env`: `name.env` when
+    /// hosts.yml has it, or else `name` itself.
+    pub fn name_in_environment(name: &str, env: &str) -> Result<String> {
+        let qualified = format!("{name}.{env}");
+        Ok(match parse_hosts_yml()?.contains_key(&qualified) {
+            true => qualified,
+            false => name.to_string(),
+        })
+    }
+
+    /// Whether the host is tagged `production`, so writes to it are confirmed first.
+    pub fn is_production(&self) -> bool {
+        let (Self::ApiKey { tags, .. } | Self::Basic { tags, .. } | Self::None { tags, .. }) = self;
+        tags.iter().any(|tag| tag == "production")
+    }
+
+    pub fn defaults(&self) -> &HostDefaults {
+        let (Self::ApiKey { defaults, .. }
+        | Self::Basic { defaults, .. }
+        | Self::None { defaults, .. }) = self;
+        defaults
+    }
+
     pub fn get_url(&self) -> Url {
         match self {
             Self::ApiKey { url, .. } => url.clone(),
@@ -58,6 +297,32 @@ impl KnownHost {
             Self::None { url, .. } => url.clone(),
         }
     }
+
+    /// This host pointed at each of its `fallback_urls` in turn, with the same
+    /// credentials and connection settings.
+    pub fn fallbacks(&self) -> Vec<Self> {
+        let (Self::ApiKey { fallback_urls, .. }
+        | Self::Basic { fallback_urls, .. }
+        | Self::None { fallback_urls, .. }) = self;
+        fallback_urls
+            .iter()
+            .map(|fallback| {
+                let mut host = self.clone();
+                let (Self::ApiKey {
+                    url, fallback_urls, ..
+                }
+                | Self::Basic {
+                    url, fallback_urls, ..
+                }
+                | Self::None {
+                    url, fallback_urls, ..
+                }) = &mut host;
+                *url = fallback.clone();
+                fallback_urls.clear();
+                host
+            })
+            .collect()
+    }
 }
 
 impl Display for KnownHost {
@@ -80,33 +345,89 @@ impl TryFrom<&str> for KnownHost {
     }
 }
 
-/// Get the path for the hosts.yml file, fallback to ~/.espipe/hosts.yml
+/// Get the path for the hosts.yml file, `$ESPIPE_HOSTS` or `hosts.yml` in the
+/// platform config directory
 fn get_hosts_path() -> Result<PathBuf> {
-    match env::var("ESPIPE_HOSTS") {
-        Ok(path) => Ok(PathBuf::from(path)),
-        Err(_) => {
-            let home = env::var("HOME").map(|home| PathBuf::from(home))?;
-            // Check if the `.espipe` directory exists, if not, create it
-            let home_dir = home.join(".espipe");
-            if !home_dir.exists() {
-                create_dir(&home_dir)?
-            }
-            let path = home.join(".espipe").join("hosts.yml");
-            Ok(path)
+    match env::var_os("ESPIPE_HOSTS") {
+        Some(path) => Ok(PathBuf::from(path)),
+        None => {
+            let config_dir = dirs::config_dir()
+                .ok_or_else(|| eyre!("Could not find a config directory for hosts.yml"))?;
+            config_hosts_path(&config_dir.join("espipe"), dirs::home_dir().as_deref())
+        }
+    }
+}
+
+/// The `hosts.yml` path in `config_dir`, which is created if needed, such as
+/// `~/.config/espipe` on Linux, `~/Library/Application Support/espipe` on
+/// macOS, and `%APPDATA%\espipe` on Windows. A `~/.espipe/hosts.yml` from
+/// older versions is moved there the first time.
+fn config_hosts_path(config_dir: &Path, home: Option<&Path>) -> Result<PathBuf> {
+    let path = config_dir.join("hosts.yml");
+    if !config_dir.exists() {
+        create_dir_all(config_dir)?;
+    }
+    let legacy = home.map(|home| home.join(".espipe").join("hosts.yml"));
+    if let Some(legacy) = legacy
+        && legacy.is_file()
+        && !path.exists()
+    {
+        log::info!("Moving known hosts from {legacy:?} to {path:?}");
+        if rename(&legacy, &path).is_err() {
+            // The config directory may be on another file system.
+            copy(&legacy, &path)?;
+            remove_file(&legacy)?;
         }
     }
+    Ok(path)
+}
+
+/// Keeps the credentials in hosts.yml readable only by their owner.
+#[cfg(unix)]
+fn restrict_permissions(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Replaces hosts.yml with `yaml`, encrypted when `encrypt` is set, through a
+/// temporary file so an interrupted write cannot lose the hosts.
+fn write_hosts_yml(path: &Path, yaml: &str, encrypt: bool) -> Result<()> {
+    let contents = match encrypt {
+        true => encryption::encrypt_hosts(yaml)?,
+        false => yaml.to_string(),
+    };
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(contents.as_bytes())?;
+    restrict_permissions(file.path())?;
+    file.persist(path)?;
+    Ok(())
 }
 
 /// Tries to load hosts from a yml file, creates an empty file if it doesn't exist
 fn parse_hosts_yml() -> Result<BTreeMap<String, KnownHost>> {
+    expand_groups(read_hosts_yml()?)
+}
+
+/// The entries of the hosts.yml file as written, with groups left as they are
+fn read_hosts_yml() -> Result<BTreeMap<String, serde_yaml::Value>> {
     let path = get_hosts_path()?;
     log::debug!("Parsing {:?}", path);
     match path.is_file() {
         true => {
-            let file = File::open(path)?;
-            let reader = BufReader::new(file);
-            let hosts: BTreeMap<String, KnownHost> = serde_yaml::from_reader(reader)?;
-            Ok(hosts)
+            let mut contents = read_to_string(&path)?;
+            if encryption::is_encrypted(&contents) {
+                contents = encryption::decrypt_hosts(&contents)?;
+            }
+            let entries: Option<BTreeMap<String, serde_yaml::Value>> =
+                serde_yaml::from_str(&contents)?;
+            Ok(entries.unwrap_or_default())
         }
         false => {
             log::info!("No known hosts file, creating: {:?}", path);
@@ -115,3 +436,147 @@ fn parse_hosts_yml() -> Result<BTreeMap<String, KnownHost>> {
         }
     }
 }
+
+/// Hosts by name, with each host of a group keyed by environment, like
+/// `staging` under `mycluster`, named `mycluster.staging`.
+fn expand_groups(
+    entries: BTreeMap<String, serde_yaml::Value>,
+) -> Result<BTreeMap<String, KnownHost>> {
+    let mut hosts = BTreeMap::new();
+    for (name, entry) in entries {
+        match entry {
+            serde_yaml::Value::Mapping(group)
+                if !group.contains_key("auth") && group.values().all(|host| host.is_mapping()) =>
+            {
+                for (env, host) in group {
+                    let env = env
+                        .as_str()
+                        .ok_or_else(|| eyre!("{name}: environment names must be strings"))?;
+                    let host =
+                        serde_yaml::from_value(host).map_err(|err| eyre!("{name}.{env}: {err}"))?;
+                    hosts.insert(format!("{name}.{env}"), host);
+                }
+            }
+            entry => {
+                let host = serde_yaml::from_value(entry).map_err(|err| eyre!("{name}: {err}"))?;
+                hosts.insert(name, host);
+            }
+        }
+    }
+    Ok(hosts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Auth, KnownHost, config_hosts_path, expand_groups};
+    use std::{fs, time::Duration};
+
+    #[test]
+    fn socket_settings_are_read_as_durations() {
+        let host: KnownHost = serde_yaml::from_str(
+            "auth: None\nurl: http://localhost:9200\nconnect_timeout: 5s\npool_idle_timeout: 1m\n",
+        )
+        .unwrap();
+        let KnownHost::None { socket, .. } = host else {
+            panic!("expected a host without auth");
+        };
+        let options = socket.parse().unwrap();
+        assert_eq!(options.connect_timeout, Some(Duration::from_secs(5)));
+        assert_eq!(options.tcp_keepalive, None);
+        assert_eq!(options.idle_timeout, Some(Duration::from_secs(60)));
+
+        let host: KnownHost =
+            serde_yaml::from_str("auth: None\nurl: http://localhost:9200\ntcp_keepalive: soon\n")
+                .unwrap();
+        let KnownHost::None { socket, .. } = host else {
+            panic!("expected a host without auth");
+        };
+        assert!(socket.parse().is_err());
+    }
+
+    #[test]
+    fn fallbacks_keep_the_credentials_of_the_primary() {
+        let host: KnownHost = serde_yaml::from_str(
+            "auth: ApiKey\napikey: secret\nurl: https://primary:9200\nfallback_urls:\n  - https://dr-1:9200\n  - https://dr-2:9200\n",
+        )
+        .unwrap();
+
+        let fallbacks = host.fallbacks();
+
+        assert_eq!(fallbacks.len(), 2);
+        assert_eq!(fallbacks[1].get_url().as_str(), "https://dr-2:9200/");
+        let KnownHost::ApiKey {
+            apikey,
+            fallback_urls,
+            ..
+        } = &fallbacks[0]
+        else {
+            panic!("expected an API key host");
+        };
+        assert_eq!(apikey, "secret");
+        assert!(fallback_urls.is_empty());
+    }
+
+    #[test]
+    fn new_credentials_keep_the_connection_settings() {
+        let host: KnownHost = serde_yaml::from_str(
+            "auth: Basic\nusername: elastic\npassword: changeme\nurl: https://prod:9200\ninsecure: true\nconnect_timeout: 5s\n",
+        )
+        .unwrap();
+
+        let host = host.with_auth(Auth::Apikey("encoded".to_string()));
+
+        assert_eq!(
+            serde_yaml::to_string(&host).unwrap(),
+            "auth: ApiKey\ninsecure: true\napikey: encoded\nurl: https://prod:9200/\nconnect_timeout: 5s\n"
+        );
+    }
+
+    #[test]
+    fn hosts_move_from_the_legacy_dot_espipe_directory_once() {
+        let home = tempfile::tempdir().unwrap();
+        let config_dir = home.path().join("config").join("espipe");
+        let legacy = home.path().join(".espipe").join("hosts.yml");
+        fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+        fs::write(&legacy, "old: {}\n").unwrap();
+
+        let path = config_hosts_path(&config_dir, Some(home.path())).unwrap();
+        assert_eq!(path, config_dir.join("hosts.yml"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old: {}\n");
+        assert!(!legacy.exists());
+
+        // A hosts file already in the config directory wins over a legacy one.
+        fs::write(&legacy, "stale: {}\n").unwrap();
+        let path = config_hosts_path(&config_dir, Some(home.path())).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old: {}\n");
+        assert!(legacy.exists());
+    }
+
+    #[test]
+    fn groups_name_their_hosts_by_environment() {
+        let entries = serde_yaml::from_str(
+            "local:\n  auth: None\n  url: http://localhost:9200\n\
+             mycluster:\n  staging:\n    auth: None\n    url: https://staging:9200\n  \
+             production:\n    auth: None\n    url: https://prod:9200\n    tags: [production]\n",
+        )
+        .unwrap();
+
+        let hosts = expand_groups(entries).unwrap();
+
+        assert_eq!(
+            hosts.keys().collect::<Vec<_>>(),
+            ["local", "mycluster.production", "mycluster.staging"]
+        );
+        assert!(hosts["mycluster.production"].is_production());
+        assert!(!hosts["mycluster.staging"].is_production());
+    }
+
+    #[test]
+    fn the_config_directory_is_created_without_a_home() {
+        let root = tempfile::tempdir().unwrap();
+        let config_dir = root.path().join("AppData").join("Roaming").join("espipe");
+        let path = config_hosts_path(&config_dir, None).unwrap();
+        assert!(config_dir.is_dir());
+        assert_eq!(path.file_name().unwrap(), "hosts.yml");
+    }
+}
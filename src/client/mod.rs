@@ -1,7 +1,18 @@
 mod auth;
 pub mod elasticsearch;
+mod encryption;
+mod failover;
+#[cfg(feature = "kafka")]
+mod kafka;
 mod known_host;
+mod shared;
+mod tunnel;
 
-pub use auth::Auth;
+pub use auth::{Auth, read_password};
 pub use elasticsearch::ElasticsearchBuilder;
+pub use failover::Failover;
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaUri;
 pub use known_host::KnownHost;
+pub use shared::SharedClients;
+pub use tunnel::SocketOptions;
@@ -0,0 +1,77 @@
+use super::ElasticsearchBuilder;
+use elasticsearch::Elasticsearch;
+use eyre::Result;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::Semaphore;
+
+/// Elasticsearch clients shared by pipelines in one `espipe run`, so pipelines
+/// with the same connection settings reuse one connection pool, plus an
+/// optional bulk request budget across all of them.
+#[derive(Clone, Debug, Default)]
+pub struct SharedClients {
+    clients: Arc<Mutex<HashMap<String, Elasticsearch>>>,
+    request_budget: Option<Arc<Semaphore>>,
+}
+
+impl SharedClients {
+    pub fn new(max_requests: Option<usize>) -> Self {
+        Self {
+            clients: Arc::default(),
+            request_budget: max_requests.map(|permits| Arc::new(Semaphore::new(permits))),
+        }
+    }
+
+    /// Returns the client already built with the same settings, or builds and keeps one.
+    pub fn client(&self, builder: ElasticsearchBuilder) -> Result<Elasticsearch> {
+        let key = builder.cache_key();
+        let mut clients = self.clients.lock().expect("shared clients lock poisoned");
+        if let Some(client) = clients.get(&key) {
+            log::debug!("Reusing shared Elasticsearch client");
+            return Ok(client.clone());
+        }
+        let client = builder.build()?;
+        clients.insert(key, client.clone());
+        Ok(client)
+    }
+
+    /// Bulk requests from every pipeline take a permit from this budget while in flight.
+    pub fn request_budget(&self) -> Option<Arc<Semaphore>> {
+        self.request_budget.clone()
+    }
+
+    pub fn client_count(&self) -> usize {
+        self.clients
+            .lock()
+            .expect("shared clients lock poisoned")
+            .len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SharedClients;
+    use crate::client::ElasticsearchBuilder;
+    use url::Url;
+
+    fn builder(url: &str) -> ElasticsearchBuilder {
+        ElasticsearchBuilder::new(Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn clients_are_shared_only_between_identical_settings() {
+        let shared = SharedClients::new(None);
+
+        shared.client(builder("http://localhost:9200")).unwrap();
+        shared.client(builder("http://localhost:9200")).unwrap();
+        assert_eq!(shared.client_count(), 1);
+
+        shared
+            .client(builder("http://localhost:9200").apikey("key".to_string()))
+            .unwrap();
+        shared.client(builder("http://other:9200")).unwrap();
+        assert_eq!(shared.client_count(), 3);
+    }
+}
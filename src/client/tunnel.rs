@@ -0,0 +1,249 @@
+use eyre::{Result, eyre};
+use socket2::{SockRef, TcpKeepalive};
+use std::{
+    net::{SocketAddr, TcpListener},
+    thread,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, copy_bidirectional},
+    net::TcpStream,
+    time::{sleep, timeout},
+};
+
+/// Socket settings for the connections to a cluster, from `--connect-timeout`,
+/// `--tcp-keepalive`, and `--pool-idle-timeout`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SocketOptions {
+    /// How long to wait for a TCP connection to be established.
+    pub connect_timeout: Option<Duration>,
+    /// How long a connection may sit idle before keepalive probes check that
+    /// the other end is still there.
+    pub tcp_keepalive: Option<Duration>,
+    /// How long a connection may sit idle before it is closed.
+    pub idle_timeout: Option<Duration>,
+}
+
+impl SocketOptions {
+    pub fn is_set(&self) -> bool {
+        *self != Self::default()
+    }
+
+    /// These options, with those not set taken from `fallback`.
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            connect_timeout: self.connect_timeout.or(fallback.connect_timeout),
+            tcp_keepalive: self.tcp_keepalive.or(fallback.tcp_keepalive),
+            idle_timeout: self.idle_timeout.or(fallback.idle_timeout),
+        }
+    }
+}
+
+/// How the tunnel's clients reach it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mode {
+    /// As an HTTP proxy, answering a `CONNECT` request before TLS starts.
+    Connect,
+    /// As the cluster itself, relaying plain HTTP from the first byte.
+    Plain,
+}
+
+/// Serves a loopback tunnel whose every connection reaches `target`, on its
+/// own thread and runtime.
+///
+/// The Elasticsearch client neither exposes its sockets nor lets its TLS
+/// server name differ from the URL's host, so `--tls-server-name` and the
+/// socket flags reach the cluster through this tunnel instead. In
+/// [`Mode::Connect`] it serves as the client's proxy and connects whatever
+/// host a `CONNECT` request names to `target`, leaving TLS to run end to end
+/// between the client and the cluster; the tunnel only copies bytes.
+pub fn spawn(target: String, mode: Mode, options: SocketOptions) -> Result<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .map_err(|err| eyre!("Failed to start the tunnel to {target}: {err}"))?;
+    listener.set_nonblocking(true)?;
+    let address = listener.local_addr()?;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    log::debug!("Tunneling connections to {target} through {address}");
+    thread::spawn(move || runtime.block_on(accept(listener, target, mode, options)));
+    Ok(address)
+}
+
+async fn accept(listener: TcpListener, target: String, mode: Mode, options: SocketOptions) {
+    let listener = match tokio::net::TcpListener::from_std(listener) {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::error!("Tunnel to {target} failed to start: {err}");
+            return;
+        }
+    };
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                log::warn!("Tunnel accept failed: {err}");
+                continue;
+            }
+        };
+        let target = target.clone();
+        tokio::spawn(async move {
+            if let Err(err) = tunnel(stream, &target, mode, options).await {
+                log::warn!("Tunnel to {target} failed: {err}");
+            }
+        });
+    }
+}
+
+/// Connects one client to the target, answering its `CONNECT` request in
+/// [`Mode::Connect`], then relays bytes both ways.
+async fn tunnel(stream: TcpStream, target: &str, mode: Mode, options: SocketOptions) -> Result<()> {
+    let mut client = BufReader::new(stream);
+    if mode == Mode::Connect {
+        let mut line = String::new();
+        client.read_line(&mut line).await?;
+        if !line.starts_with("CONNECT ") {
+            client
+                .get_mut()
+                .write_all(b"HTTP/1.1 405 Method Not Allowed\r\ncontent-length: 0\r\n\r\n")
+                .await?;
+            return Err(eyre!("expected a CONNECT request, got {}", line.trim_end()));
+        }
+        loop {
+            line.clear();
+            if client.read_line(&mut line).await? == 0 || line.trim_end().is_empty() {
+                break;
+            }
+        }
+    }
+    let upstream = match connect(target, options).await {
+        Ok(upstream) => upstream,
+        Err(err) => {
+            if mode == Mode::Connect {
+                client
+                    .get_mut()
+                    .write_all(b"HTTP/1.1 502 Bad Gateway\r\ncontent-length: 0\r\n\r\n")
+                    .await?;
+            }
+            return Err(err);
+        }
+    };
+    // A proxy client waits for the reply before starting TLS, and a plain one
+    // has not been read from, so nothing is left in the buffer.
+    let mut client = client.into_inner();
+    if mode == Mode::Connect {
+        client
+            .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+            .await?;
+    }
+    relay(client, upstream, options.idle_timeout).await
+}
+
+async fn connect(target: &str, options: SocketOptions) -> Result<TcpStream> {
+    let stream = match options.connect_timeout {
+        Some(limit) => timeout(limit, TcpStream::connect(target))
+            .await
+            .map_err(|_| eyre!("connecting to {target} timed out after {limit:?}"))??,
+        None => TcpStream::connect(target).await?,
+    };
+    if let Some(idle) = options.tcp_keepalive {
+        SockRef::from(&stream).set_tcp_keepalive(&TcpKeepalive::new().with_time(idle))?;
+    }
+    Ok(stream)
+}
+
+/// Copies bytes both ways until either side closes, or until nothing has
+/// crossed for `idle_timeout`.
+async fn relay(
+    mut client: TcpStream,
+    mut upstream: TcpStream,
+    idle_timeout: Option<Duration>,
+) -> Result<()> {
+    let Some(idle_timeout) = idle_timeout else {
+        copy_bidirectional(&mut client, &mut upstream).await?;
+        return Ok(());
+    };
+    let (mut client_read, mut client_write) = client.split();
+    let (mut upstream_read, mut upstream_write) = upstream.split();
+    let mut sent = vec![0u8; 16 * 1024];
+    let mut received = vec![0u8; 16 * 1024];
+    loop {
+        tokio::select! {
+            read = client_read.read(&mut sent) => match read? {
+                0 => return Ok(()),
+                read => upstream_write.write_all(&sent[..read]).await?,
+            },
+            read = upstream_read.read(&mut received) => match read? {
+                0 => return Ok(()),
+                read => client_write.write_all(&received[..read]).await?,
+            },
+            _ = sleep(idle_timeout) => {
+                log::debug!("Closing a connection to the cluster idle for {idle_timeout:?}");
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Mode, SocketOptions, spawn};
+    use std::time::Duration;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    #[tokio::test]
+    async fn connect_requests_reach_the_target_whatever_host_they_name() {
+        let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = upstream.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut socket, _) = upstream.accept().await.unwrap();
+            let mut ping = [0u8; 4];
+            socket.read_exact(&mut ping).await.unwrap();
+            socket.write_all(b"pong").await.unwrap();
+        });
+        let proxy = spawn(target, Mode::Connect, SocketOptions::default()).unwrap();
+
+        let mut client = TcpStream::connect(proxy).await.unwrap();
+        client
+            .write_all(b"CONNECT es.internal:9200 HTTP/1.1\r\nHost: es.internal:9200\r\n\r\n")
+            .await
+            .unwrap();
+        let mut reply = [0u8; 39];
+        client.read_exact(&mut reply).await.unwrap();
+        assert!(reply.starts_with(b"HTTP/1.1 200"));
+        client.write_all(b"ping").await.unwrap();
+        let mut pong = [0u8; 4];
+        client.read_exact(&mut pong).await.unwrap();
+        assert_eq!(&pong, b"pong");
+    }
+
+    #[tokio::test]
+    async fn idle_plain_connections_are_closed() {
+        let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = upstream.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut socket, _) = upstream.accept().await.unwrap();
+            let mut request = [0u8; 4];
+            socket.read_exact(&mut request).await.unwrap();
+            socket.write_all(b"sent").await.unwrap();
+            let _ = socket.read(&mut request).await;
+        });
+        let options = SocketOptions {
+            connect_timeout: Some(Duration::from_secs(1)),
+            tcp_keepalive: Some(Duration::from_secs(30)),
+            idle_timeout: Some(Duration::from_millis(100)),
+        };
+        let relay = spawn(target, Mode::Plain, options).unwrap();
+
+        let mut client = TcpStream::connect(relay).await.unwrap();
+        client.write_all(b"POST").await.unwrap();
+        let mut reply = [0u8; 4];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(&reply, b"sent");
+        let closed = tokio::time::timeout(Duration::from_secs(5), client.read(&mut reply)).await;
+        assert_eq!(closed.unwrap().unwrap(), 0);
+    }
+}
@@ -0,0 +1,143 @@
+use eyre::{Result, eyre};
+use fluent_uri::UriRef;
+use futures::StreamExt;
+use object_store::{
+    ObjectStore, WriteMultipart, azure::MicrosoftAzureBuilder, gcp::GoogleCloudStorageBuilder,
+    path::Path as ObjectPath,
+};
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+    sync::Arc,
+};
+
+/// Bytes read from a local file per multipart upload part.
+const UPLOAD_CHUNK_BYTES: usize = 8 * 1024 * 1024;
+/// Parts uploaded concurrently.
+const UPLOAD_PARTS_IN_FLIGHT: usize = 4;
+
+/// An object in Azure Blob Storage, `az://container/blob`, or Google Cloud
+/// Storage, `gs://bucket/object`.
+///
+/// Credentials come from each provider's usual chain: `AZURE_STORAGE_*` and
+/// `AZURE_CLIENT_*` variables, then managed identity, or
+/// `GOOGLE_APPLICATION_CREDENTIALS`, then the gcloud application default
+/// credentials, then the metadata server.
+pub struct CloudObject {
+    store: Arc<dyn ObjectStore>,
+    path: ObjectPath,
+    uri: String,
+}
+
+impl CloudObject {
+    pub fn try_new(uri: &UriRef<String>) -> Result<Self> {
+        let container = uri
+            .authority()
+            .map(|authority| authority.host())
+            .filter(|host| !host.is_empty())
+            .ok_or_else(|| eyre!("{uri} has no container or bucket"))?;
+        let path = uri.path().as_str().trim_start_matches('/');
+        if path.is_empty() || path.ends_with('/') {
+            return Err(eyre!("{uri} does not name an object"));
+        }
+        let path = ObjectPath::from_url_path(path)
+            .map_err(|err| eyre!("invalid object path in {uri}: {err}"))?;
+        let store: Arc<dyn ObjectStore> = match uri.scheme().map(|scheme| scheme.as_str()) {
+            Some("az") => Arc::new(
+                MicrosoftAzureBuilder::from_env()
+                    .with_container_name(container)
+                    .build()?,
+            ),
+            Some("gs") => Arc::new(
+                GoogleCloudStorageBuilder::from_env()
+                    .with_bucket_name(container)
+                    .build()?,
+            ),
+            _ => return Err(eyre!("{uri} is not an az:// or gs:// URI")),
+        };
+        Ok(Self {
+            store,
+            path,
+            uri: uri.to_string(),
+        })
+    }
+
+    /// The object name, whose extension picks the file format.
+    pub fn name(&self) -> &str {
+        self.path.as_ref()
+    }
+
+    /// Streams the object into `file`.
+    pub async fn download(&self, file: &mut File) -> Result<()> {
+        let mut stream = self
+            .store
+            .get(&self.path)
+            .await
+            .map_err(|err| eyre!("failed to read {}: {err}", self.uri))?
+            .into_stream();
+        while let Some(chunk) = stream.next().await {
+            file.write_all(&chunk.map_err(|err| eyre!("failed to read {}: {err}", self.uri))?)?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Uploads the file at `path` as the object, in parts, replacing any
+    /// object already there.
+    pub async fn upload(&self, path: &Path) -> Result<()> {
+        let upload = self
+            .store
+            .put_multipart(&self.path)
+            .await
+            .map_err(|err| eyre!("failed to start upload to {}: {err}", self.uri))?;
+        let mut writer = WriteMultipart::new_with_chunk_size(upload, UPLOAD_CHUNK_BYTES);
+        let mut file = File::open(path)?;
+        let mut buffer = vec![0; UPLOAD_CHUNK_BYTES];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            writer.wait_for_capacity(UPLOAD_PARTS_IN_FLIGHT).await?;
+            writer.write(&buffer[..read]);
+        }
+        writer
+            .finish()
+            .await
+            .map_err(|err| eyre!("failed to upload {}: {err}", self.uri))?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for CloudObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.uri)
+    }
+}
+
+impl std::fmt::Debug for CloudObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CloudObject({})", self.uri)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CloudObject;
+    use fluent_uri::UriRef;
+
+    fn uri(text: &str) -> UriRef<String> {
+        UriRef::parse(text.to_string()).unwrap()
+    }
+
+    #[test]
+    fn cloud_uris_name_a_container_and_an_object() {
+        let object = CloudObject::try_new(&uri("gs://exports/2026/logs%20a.ndjson.gz")).unwrap();
+        assert_eq!(object.name(), "2026/logs a.ndjson.gz");
+        assert_eq!(object.to_string(), "gs://exports/2026/logs%20a.ndjson.gz");
+
+        assert!(CloudObject::try_new(&uri("gs://exports/")).is_err());
+        assert!(CloudObject::try_new(&uri("gs:///logs.ndjson")).is_err());
+    }
+}
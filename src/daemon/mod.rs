@@ -0,0 +1,298 @@
+mod schedule;
+
+use crate::{Counts, client::SharedClients, comma_formatted, defaults::Defaults, environment};
+use eyre::{Result, eyre};
+use jiff::Zoned;
+use schedule::Schedule;
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    num::NonZeroUsize,
+    path::Path,
+    time::{Duration, Instant},
+};
+use tokio::{task::JoinSet, time::sleep};
+use tracing::Instrument;
+
+/// The `espipe run --config` file: named pipelines that run side by side.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct RunConfig {
+    /// Bulk requests in flight across every pipeline, on top of each one's `max-requests`.
+    max_requests: Option<NonZeroUsize>,
+    pipelines: BTreeMap<String, PipelineConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PipelineConfig {
+    #[serde(alias = "input")]
+    inputs: OneOrMany,
+    output: String,
+    /// `every <DURATION>` or a five-field cron expression; omitted runs once.
+    schedule: Option<String>,
+    /// Command-line flags by long name, without the leading `--`.
+    #[serde(default)]
+    options: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// A validated pipeline, ready to run.
+struct Pipeline {
+    name: String,
+    args: Vec<String>,
+    schedule: Schedule,
+}
+
+/// Runs every pipeline in the config file as its own task, sharing Elasticsearch
+/// clients between pipelines with the same connection settings.
+///
+/// Pipeline options override the `defaults` from `--config`.
+///
+/// Returns once all unscheduled pipelines finish; scheduled pipelines log
+/// failed runs and keep going until the process is stopped.
+pub async fn run(config: &Path, defaults: Defaults) -> Result<()> {
+    let (pipelines, max_requests) = load(config, &defaults)?;
+    let shared_clients = SharedClients::new(max_requests);
+    let mut tasks = JoinSet::new();
+    for pipeline in pipelines {
+        let span = tracing::info_span!("pipeline", name = %pipeline.name);
+        tasks.spawn(
+            run_pipeline(pipeline, defaults.clone(), shared_clients.clone()).instrument(span),
+        );
+    }
+
+    let mut failed = 0;
+    let mut stats = Vec::new();
+    while let Some(result) = tasks.join_next().await {
+        let pipeline_stats = result?;
+        failed += usize::from(pipeline_stats.failed_runs > 0);
+        stats.push(pipeline_stats);
+    }
+    stats.sort_by(|a, b| a.name.cmp(&b.name));
+    for pipeline_stats in &stats {
+        println!("{pipeline_stats}");
+    }
+    log::debug!(
+        "{} pipelines used {} Elasticsearch clients",
+        stats.len(),
+        shared_clients.client_count()
+    );
+    match failed {
+        0 => Ok(()),
+        _ => Err(eyre!("{failed} of {} pipelines failed", stats.len())),
+    }
+}
+
+fn load(config: &Path, defaults: &Defaults) -> Result<(Vec<Pipeline>, Option<usize>)> {
+    let file = File::open(config)
+        .map_err(|err| eyre!("failed to open config {}: {err}", config.display()))?;
+    let config: RunConfig = serde_yaml::from_reader(file)
+        .map_err(|err| eyre!("invalid config {}: {err}", config.display()))?;
+    if config.pipelines.is_empty() {
+        return Err(eyre!("config defines no pipelines"));
+    }
+    let max_requests = config.max_requests.map(NonZeroUsize::get);
+    let pipelines = config
+        .pipelines
+        .into_iter()
+        .map(|(name, pipeline)| {
+            let args = pipeline_args(&pipeline).map_err(|err| eyre!("pipeline {name}: {err}"))?;
+            let cli = defaults.parse_from(&args).map_err(|err| {
+                let err = err.to_string();
+                let err = err.lines().next().unwrap_or_default();
+                eyre!("pipeline {name}: {}", err.trim_start_matches("error: "))
+            })?;
+            environment::confirm(&cli, false).map_err(|err| eyre!("pipeline {name}: {err}"))?;
+            if cli.metrics_addr.is_some() {
+                return Err(eyre!(
+                    "pipeline {name}: metrics-addr is set on espipe run, not per pipeline"
+                ));
+            }
+            let schedule = Schedule::parse(pipeline.schedule.as_deref())
+                .map_err(|err| eyre!("pipeline {name}: {err}"))?;
+            Ok(Pipeline {
+                name,
+                args,
+                schedule,
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok((pipelines, max_requests))
+}
+
+/// Builds the equivalent command line, so pipelines accept every espipe flag.
+fn pipeline_args(pipeline: &PipelineConfig) -> Result<Vec<String>> {
+    let mut args = vec!["espipe".to_string()];
+    for (name, value) in &pipeline.options {
+        let flag = format!("--{}", name.trim_start_matches('-'));
+        let values = match value {
+            Value::Sequence(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            match value {
+                Value::Bool(false) => {}
+                Value::Bool(true) | Value::Null => args.push(flag.clone()),
+                Value::String(value) => args.push(format!("{flag}={value}")),
+                Value::Number(value) => args.push(format!("{flag}={value}")),
+                _ => return Err(eyre!("option {name} must be a scalar or a list of scalars")),
+            }
+        }
+    }
+    match &pipeline.inputs {
+        OneOrMany::One(input) => args.push(input.clone()),
+        OneOrMany::Many(inputs) => args.extend(inputs.iter().cloned()),
+    }
+    args.push(pipeline.output.clone());
+    Ok(args)
+}
+
+/// Document totals for one pipeline across all of its runs.
+#[derive(Debug, Default)]
+struct PipelineStats {
+    name: String,
+    runs: usize,
+    failed_runs: usize,
+    read: usize,
+    sent: usize,
+}
+
+impl PipelineStats {
+    fn record(&mut self, result: &Result<Counts>) {
+        self.runs += 1;
+        match result {
+            Ok(counts) => {
+                self.read += counts.input_line;
+                self.sent += counts.output_line;
+            }
+            Err(err) => {
+                self.failed_runs += 1;
+                log::error!("pipeline {}: {err}", self.name);
+            }
+        }
+        log::info!("{self}");
+    }
+}
+
+impl std::fmt::Display for PipelineStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "pipeline {}: {} runs, {} failed, piped {} of {} docs",
+            self.name,
+            self.runs,
+            self.failed_runs,
+            comma_formatted(self.sent),
+            comma_formatted(self.read)
+        )
+    }
+}
+
+async fn run_pipeline(
+    pipeline: Pipeline,
+    defaults: Defaults,
+    shared_clients: SharedClients,
+) -> PipelineStats {
+    let Pipeline {
+        name,
+        args,
+        schedule,
+    } = pipeline;
+    let mut stats = PipelineStats {
+        name: name.clone(),
+        ..PipelineStats::default()
+    };
+    let run_once = || async {
+        log::info!("pipeline {name}: starting");
+        let cli = defaults.parse_from(&args)?;
+        crate::run(cli, args[1..].to_vec(), Some(shared_clients.clone())).await
+    };
+    match schedule {
+        Schedule::Once => {
+            let result = run_once().await;
+            stats.record(&result);
+            stats
+        }
+        Schedule::Every(interval) => loop {
+            let started = Instant::now();
+            let result = run_once().await;
+            stats.record(&result);
+            sleep(interval.saturating_sub(started.elapsed())).await;
+        },
+        Schedule::Cron(cron) => loop {
+            let now = Zoned::now();
+            let next = match cron.next_after(&now) {
+                Ok(next) => next,
+                Err(err) => {
+                    stats.record(&Err(err));
+                    return stats;
+                }
+            };
+            let wait = Duration::try_from(next.duration_since(&now)).unwrap_or_default();
+            log::info!("pipeline {}: next run at {next}", stats.name);
+            sleep(wait).await;
+            let result = run_once().await;
+            stats.record(&result);
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PipelineConfig, pipeline_args};
+
+    #[test]
+    fn pipeline_options_become_command_line_flags() {
+        let pipeline: PipelineConfig = serde_yaml::from_str(
+            r#"
+inputs: [a.ndjson, b.ndjson]
+output: http://localhost:9200/logs
+options:
+  batch-size: 500
+  dedupe: event.id
+  header: ["X-Team: search", "X-Env: prod"]
+  insecure: true
+  quiet: false
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            pipeline_args(&pipeline).unwrap(),
+            [
+                "espipe",
+                "--batch-size=500",
+                "--dedupe=event.id",
+                "--header=X-Team: search",
+                "--header=X-Env: prod",
+                "--insecure",
+                "a.ndjson",
+                "b.ndjson",
+                "http://localhost:9200/logs",
+            ]
+        );
+    }
+
+    #[test]
+    fn pipeline_options_reject_nested_values() {
+        let pipeline: PipelineConfig = serde_yaml::from_str(
+            "input: a.ndjson\noutput: '-'\noptions:\n  header: {X-Team: search}\n",
+        )
+        .unwrap();
+
+        let err = pipeline_args(&pipeline).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "option header must be a scalar or a list of scalars"
+        );
+    }
+}
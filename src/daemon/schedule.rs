@@ -0,0 +1,192 @@
+use crate::value_parsers::parse_nonzero_duration;
+use eyre::{Result, eyre};
+use jiff::{ToSpan, Zoned};
+use std::time::Duration;
+
+/// Roughly one year of minutes, enough to find any satisfiable cron time.
+const MAX_CRON_MINUTES: usize = 366 * 24 * 60;
+
+/// When a pipeline runs: once, at a fixed interval, or on a cron expression.
+#[derive(Debug)]
+pub enum Schedule {
+    Once,
+    Every(Duration),
+    Cron(Cron),
+}
+
+impl Schedule {
+    /// Parses `every <DURATION>` or a five-field cron expression; no schedule runs once.
+    pub fn parse(schedule: Option<&str>) -> Result<Self> {
+        let Some(schedule) = schedule.map(str::trim) else {
+            return Ok(Schedule::Once);
+        };
+        if let Some(interval) = schedule.strip_prefix("every ") {
+            return parse_nonzero_duration(interval)
+                .map(Schedule::Every)
+                .map_err(|err| eyre!("invalid schedule '{schedule}': {err}"));
+        }
+        let cron = Cron::parse(schedule)?;
+        cron.next_after(&Zoned::now())?;
+        Ok(Schedule::Cron(cron))
+    }
+}
+
+/// A standard five-field cron expression: minute, hour, day of month, month,
+/// and day of week, evaluated in the local time zone.
+#[derive(Debug)]
+pub struct Cron {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Cron matches either day field when both are restricted, and both otherwise.
+    either_day: bool,
+}
+
+impl Cron {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(eyre!(
+                "invalid schedule '{expression}', expected 'every <DURATION>' or five cron fields"
+            ));
+        };
+        let field = |value: &str, name: &str, min: u8, max: u8| {
+            parse_field(value, min, max)
+                .map_err(|err| eyre!("invalid {name} field in schedule '{expression}': {err}"))
+        };
+        let mut weekdays = field(weekday, "day of week", 0, 7)?;
+        // Both 0 and 7 mean Sunday.
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            expression: expression.to_string(),
+            minutes: field(minute, "minute", 0, 59)?,
+            hours: field(hour, "hour", 0, 23)?,
+            days: field(day, "day of month", 1, 31)?,
+            months: field(month, "month", 1, 12)?,
+            weekdays,
+            either_day: !day.starts_with('*') && !weekday.starts_with('*'),
+        })
+    }
+
+    /// The first matching minute strictly after `now`.
+    pub fn next_after(&self, now: &Zoned) -> Result<Zoned> {
+        let mut time = now
+            .with()
+            .second(0)
+            .subsec_nanosecond(0)
+            .build()?
+            .checked_add(1.minute())?;
+        for _ in 0..MAX_CRON_MINUTES {
+            if self.matches(&time) {
+                return Ok(time);
+            }
+            time = time.checked_add(1.minute())?;
+        }
+        Err(eyre!("schedule '{}' never matches", self.expression))
+    }
+
+    fn matches(&self, time: &Zoned) -> bool {
+        let has = |set: u64, value: i8| set & (1 << value) != 0;
+        let day = has(self.days, time.day());
+        let weekday = has(self.weekdays, time.weekday().to_sunday_zero_offset());
+        let day_matches = match self.either_day {
+            true => day || weekday,
+            false => day && weekday,
+        };
+        has(self.minutes, time.minute())
+            && has(self.hours, time.hour())
+            && has(self.months, time.month())
+            && day_matches
+    }
+}
+
+/// Parses a comma-separated list of `*`, `N`, `N-M`, with optional `/STEP`, into a bit set.
+fn parse_field(value: &str, min: u8, max: u8) -> Result<u64, String> {
+    let mut set = 0u64;
+    for item in value.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u8>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step '{step}'"))?,
+            ),
+            None => (item, 1),
+        };
+        let number = |text: &str| {
+            text.parse::<u8>()
+                .ok()
+                .filter(|number| (min..=max).contains(number))
+                .ok_or_else(|| format!("'{text}' is not between {min} and {max}"))
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (number(start)?, number(end)?),
+                None if step > 1 => (number(range)?, max),
+                None => (number(range)?, number(range)?),
+            },
+        };
+        if start > end {
+            return Err(format!("range '{range}' is reversed"));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cron, Schedule};
+    use jiff::Zoned;
+    use std::time::Duration;
+
+    fn at(time: &str) -> Zoned {
+        format!("{time}[UTC]").parse().unwrap()
+    }
+
+    #[test]
+    fn schedules_parse_intervals_and_cron_expressions() {
+        assert!(matches!(Schedule::parse(None).unwrap(), Schedule::Once));
+        assert!(matches!(
+            Schedule::parse(Some("every 15m")).unwrap(),
+            Schedule::Every(interval) if interval == Duration::from_secs(900)
+        ));
+        assert!(matches!(
+            Schedule::parse(Some("0 2 * * *")).unwrap(),
+            Schedule::Cron(_)
+        ));
+        assert!(Schedule::parse(Some("every 0s")).is_err());
+        assert!(Schedule::parse(Some("0 2 * *")).is_err());
+        assert!(Schedule::parse(Some("61 * * * *")).is_err());
+        assert!(Schedule::parse(Some("0 0 30 2 *")).is_err());
+    }
+
+    #[test]
+    fn cron_finds_the_next_matching_minute() {
+        let cron = Cron::parse("*/15 9-17 * * 1-5").unwrap();
+
+        // Friday 17:50 rolls over the weekend to Monday 09:00.
+        let next = cron.next_after(&at("2026-10-16T17:50:30")).unwrap();
+        assert_eq!(next, at("2026-10-19T09:00:00"));
+        let next = cron.next_after(&at("2026-10-19T09:00:00")).unwrap();
+        assert_eq!(next, at("2026-10-19T09:15:00"));
+    }
+
+    #[test]
+    fn cron_matches_either_restricted_day_field() {
+        let cron = Cron::parse("0 0 1 * 7").unwrap();
+
+        // Sunday the 18th comes before the 1st of next month.
+        let next = cron.next_after(&at("2026-10-16T12:00:00")).unwrap();
+        assert_eq!(next, at("2026-10-18T00:00:00"));
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{document::Document, metrics::METRICS};
+use eyre::{Result, eyre};
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+/// Collects documents that could not be delivered as NDJSON, so they can be
+/// inspected and piped again once the cause is fixed.
+///
+/// The file is opened for appending, so piping a dead-letter file back in with
+/// the same `--dead-letter` path never truncates the input being read.
+#[derive(Clone, Debug)]
+pub struct DeadLetter {
+    path: PathBuf,
+    writer: Arc<Mutex<BufWriter<File>>>,
+    written: Arc<AtomicUsize>,
+}
+
+impl DeadLetter {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| eyre!("failed to open dead-letter file {}: {err}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: Arc::new(Mutex::new(BufWriter::new(file))),
+            written: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// Appends the documents and flushes, so they are on disk even if the run fails afterwards.
+    pub fn write(&self, docs: &[Document]) -> Result<()> {
+        let mut writer = self.writer.lock().expect("Failed to get dead-letter lock");
+        for doc in docs {
+            writer.write_all(doc.get().as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        self.written.fetch_add(docs.len(), Ordering::Relaxed);
+        METRICS
+            .docs_dead_lettered
+            .fetch_add(docs.len() as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Documents written by this run.
+    pub fn written(&self) -> usize {
+        self.written.load(Ordering::Relaxed)
+    }
+}
+
+impl std::fmt::Display for DeadLetter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.path.display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DeadLetter;
+    use crate::document::Document;
+    use std::fs;
+
+    #[test]
+    fn dead_letter_appends_ndjson_and_counts_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rejected.ndjson");
+        fs::write(&path, "{\"old\":true}\n").unwrap();
+        let dead_letter = DeadLetter::open(&path).unwrap();
+        let docs = vec![
+            Document::unvalidated("{\"a\":1}").unwrap(),
+            Document::unvalidated("{\"a\":2}").unwrap(),
+        ];
+
+        dead_letter.clone().write(&docs).unwrap();
+
+        assert_eq!(dead_letter.written(), 2);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\"old\":true}\n{\"a\":1}\n{\"a\":2}\n"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{canonical, document::Document, field_path};
+use eyre::{Result, eyre};
+use serde_json::Value;
+use std::collections::HashSet;
+use xxhash_rust::xxh3::xxh3_128;
+
+/// Drops documents whose content hash or ID field value was already seen in this run.
+///
+/// Only 128-bit hashes are retained, so memory grows by a fixed amount per unique document.
+pub struct Deduplicator {
+    key: DedupeKey,
+    seen: HashSet<u128>,
+    dropped: usize,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum DedupeKey {
+    Content,
+    Field(String),
+}
+
+impl Deduplicator {
+    /// An empty field name deduplicates by document content.
+    pub fn new(field: &str) -> Self {
+        let key = match field {
+            "" => DedupeKey::Content,
+            field => DedupeKey::Field(field.to_string()),
+        };
+        Self {
+            key,
+            seen: HashSet::new(),
+            dropped: 0,
+        }
+    }
+
+    pub fn is_duplicate(&mut self, doc: &Document) -> Result<bool> {
+        let value: Value = serde_json::from_str(doc.get())
+            .map_err(|err| eyre!("Error parsing JSON for --dedupe: {err}"))?;
+        let hash = match &self.key {
+            DedupeKey::Content => xxh3_128(canonical::to_string(&value).as_bytes()),
+            DedupeKey::Field(field) => match field_path::lookup(&value, field) {
+                Some(id) => xxh3_128(canonical::to_string(id).as_bytes()),
+                None => return Ok(false),
+            },
+        };
+        if self.seen.insert(hash) {
+            return Ok(false);
+        }
+        self.dropped += 1;
+        Ok(true)
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DedupeKey, Deduplicator};
+    use crate::document::Document;
+    use serde_json::value::RawValue;
+
+    fn raw(json: &str) -> Document {
+        RawValue::from_string(json.to_string()).unwrap().into()
+    }
+
+    #[test]
+    fn empty_field_selects_content_hashing() {
+        assert_eq!(Deduplicator::new("").key, DedupeKey::Content);
+        assert_eq!(
+            Deduplicator::new("_id").key,
+            DedupeKey::Field("_id".to_string())
+        );
+    }
+
+    #[test]
+    fn content_dedupe_ignores_key_order_and_whitespace() {
+        let mut dedupe = Deduplicator::new("");
+
+        assert!(!dedupe.is_duplicate(&raw(r#"{"a":1,"b":2}"#)).unwrap());
+        assert!(dedupe.is_duplicate(&raw(r#"{ "b": 2, "a": 1 }"#)).unwrap());
+        assert!(
+            !dedupe
+                .is_duplicate(&raw(r#"{"a":1,"b":{"d":4,"c":3}}"#))
+                .unwrap()
+        );
+        assert!(
+            dedupe
+                .is_duplicate(&raw(r#"{"b":{"c":3,"d":4},"a":1}"#))
+                .unwrap()
+        );
+        assert!(!dedupe.is_duplicate(&raw(r#"{"a":1,"b":3}"#)).unwrap());
+        assert_eq!(dedupe.dropped(), 2);
+    }
+
+    #[test]
+    fn field_dedupe_compares_only_the_named_field() {
+        let mut dedupe = Deduplicator::new("event.id");
+
+        assert!(
+            !dedupe
+                .is_duplicate(&raw(r#"{"event":{"id":"1"},"n":1}"#))
+                .unwrap()
+        );
+        assert!(
+            dedupe
+                .is_duplicate(&raw(r#"{"event":{"id":"1"},"n":2}"#))
+                .unwrap()
+        );
+        assert!(
+            !dedupe
+                .is_duplicate(&raw(r#"{"event":{"id":1},"n":3}"#))
+                .unwrap()
+        );
+        assert_eq!(dedupe.dropped(), 1);
+    }
+
+    #[test]
+    fn documents_missing_the_field_are_never_dropped() {
+        let mut dedupe = Deduplicator::new("_id");
+
+        assert!(!dedupe.is_duplicate(&raw(r#"{"n":1}"#)).unwrap());
+        assert!(!dedupe.is_duplicate(&raw(r#"{"n":1}"#)).unwrap());
+        assert_eq!(dedupe.dropped(), 0);
+    }
+}
@@ -0,0 +1,213 @@
+use crate::{Cli, Command, client::KnownHost, environment};
+use clap::{CommandFactory, FromArgMatches, error::ErrorKind};
+use eyre::{Result, eyre};
+use serde_yaml::Value;
+use std::{collections::BTreeMap, ffi::OsString, fs::File, path::Path};
+
+/// Default flag values from a YAML file of long flag names, without the
+/// leading `--`, like the `options` of an `espipe run` pipeline:
+///
+/// ```yaml
+/// batch-size: 2000
+/// max-requests: 4
+/// header: ["X-Team: search"]
+/// quiet: true
+/// ```
+///
+/// Flags given on the command line replace the file's values, including every
+/// value of a repeatable flag. The defaults of an output's known host, like its
+/// `batch_size`, sit between the two.
+#[derive(Clone, Debug, Default)]
+pub struct Defaults {
+    /// Default values by argument ID.
+    values: BTreeMap<String, Vec<String>>,
+}
+
+impl Defaults {
+    /// Loads `--config`, which must exist, or else `config.yml` in the
+    /// platform config directory next to `hosts.yml`, if there is one.
+    pub fn load(config: Option<&Path>) -> Result<Self> {
+        let default_path = dirs::config_dir().map(|dir| dir.join("espipe").join("config.yml"));
+        let path = match (config, &default_path) {
+            (Some(path), _) => path,
+            (None, Some(path)) if path.is_file() => path,
+            (None, _) => return Ok(Self::default()),
+        };
+        log::debug!("Loading default flags from {}", path.display());
+        let file = File::open(path)
+            .map_err(|err| eyre!("failed to open config {}: {err}", path.display()))?;
+        let options: Option<BTreeMap<String, Value>> = serde_yaml::from_reader(file)
+            .map_err(|err| eyre!("invalid config {}: {err}", path.display()))?;
+        Self::try_from_options(options.unwrap_or_default())
+            .map_err(|err| eyre!("config {}: {err}", path.display()))
+    }
+
+    fn try_from_options(options: BTreeMap<String, Value>) -> Result<Self> {
+        let command = Cli::command();
+        let mut values = BTreeMap::new();
+        for (name, value) in options {
+            let long = name.trim_start_matches('-');
+            let arg = command
+                .get_arguments()
+                .find(|arg| arg.get_long() == Some(long) && long != "config")
+                .ok_or_else(|| eyre!("unknown option {name}"))?;
+            let takes_values = arg.get_action().takes_values();
+            let items = match value {
+                Value::Sequence(items) if takes_values => items,
+                value => vec![value],
+            };
+            let mut arg_values = Vec::new();
+            for item in items {
+                match item {
+                    // Kept so it can replace a `true` from an earlier layer.
+                    Value::Bool(false) if !takes_values => arg_values.push("false".to_string()),
+                    Value::Bool(true) if !takes_values => arg_values.push("true".to_string()),
+                    Value::String(value) if takes_values => arg_values.push(value),
+                    Value::Number(value) if takes_values => arg_values.push(value.to_string()),
+                    Value::Bool(value) if takes_values => arg_values.push(value.to_string()),
+                    _ if takes_values => {
+                        return Err(eyre!("option {name} must be a scalar or a list of scalars"));
+                    }
+                    _ => return Err(eyre!("option {name} must be true or false")),
+                }
+            }
+            if !arg_values.is_empty() {
+                values.insert(arg.get_id().to_string(), arg_values);
+            }
+        }
+        Ok(Self { values })
+    }
+
+    /// Parses a command line with these defaults in place of the built-in ones,
+    /// and those of its output's known host over them, with known hosts
+    /// resolved in its `--env` environment.
+    pub fn parse_from<I, T>(&self, args: I) -> Result<Cli, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        let parse = |defaults: &Self| {
+            environment::resolve(defaults.parse_without_host(args.clone())?)
+                .map_err(|err| Cli::command().error(ErrorKind::ValueValidation, err))
+        };
+        let cli = parse(self)?;
+        match self.with_output_host(&cli) {
+            Ok(Some(defaults)) => parse(&defaults),
+            Ok(None) => Ok(cli),
+            Err(err) => Err(Cli::command().error(ErrorKind::ValueValidation, err)),
+        }
+    }
+
+    /// These defaults under those of the known host `cli` outputs to, or `None`
+    /// when it outputs elsewhere or the host sets no flag defaults.
+    fn with_output_host(&self, cli: &Cli) -> Result<Option<Self>> {
+        let output = match (&cli.command, cli.paths.last()) {
+            (Some(Command::Listen { output, .. }), _) => output,
+            (None, Some(output)) => output,
+            _ => return Ok(None),
+        };
+        let host = match output.scheme().map(|scheme| scheme.as_str()) {
+            Some("http" | "https" | "file" | "az" | "gs" | "lumberjack" | "kafka") | None => {
+                return Ok(None);
+            }
+            Some(scheme) => match KnownHost::parse(scheme) {
+                Some(host) => host,
+                None => return Ok(None),
+            },
+        };
+        let flags = host.defaults().flags();
+        if flags.is_empty() {
+            return Ok(None);
+        }
+        let host = Self::try_from_options(flags).map_err(|err| eyre!("hosts.yml: {err}"))?;
+        let mut values = self.values.clone();
+        values.extend(host.values);
+        Ok(Some(Self { values }))
+    }
+
+    fn parse_without_host(&self, args: Vec<OsString>) -> Result<Cli, clap::Error> {
+        let mut command = Cli::command();
+        for (id, values) in &self.values {
+            command = command.mut_arg(id, |arg| arg.default_values(values.clone()));
+        }
+        Cli::from_arg_matches(&command.try_get_matches_from(args)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Defaults;
+
+    fn defaults(yaml: &str) -> eyre::Result<Defaults> {
+        Defaults::try_from_options(serde_yaml::from_str(yaml).unwrap())
+    }
+
+    #[test]
+    fn command_line_flags_replace_config_defaults() {
+        let defaults = defaults(
+            "batch-size: 500\nquiet: true\nuncompressed: false\nheader: [\"X-Team: search\"]\n",
+        )
+        .unwrap();
+
+        let cli = defaults.parse_from(["espipe", "a.ndjson", "-"]).unwrap();
+        assert_eq!(cli.batch_size, 500);
+        assert!(cli.quiet);
+        assert!(!cli.uncompressed);
+        assert_eq!(cli.headers.len(), 1);
+
+        let cli = defaults
+            .parse_from([
+                "espipe",
+                "--batch-size=10",
+                "--header=X-Env: prod",
+                "a",
+                "-",
+            ])
+            .unwrap();
+        assert_eq!(cli.batch_size, 10);
+        assert_eq!(cli.headers[0].0.as_str(), "x-env");
+        assert_eq!(cli.headers.len(), 1);
+    }
+
+    #[test]
+    fn known_host_defaults_sit_between_config_and_command_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let hosts = dir.path().join("hosts.yml");
+        std::fs::write(
+            &hosts,
+            "tuned:\n  auth: None\n  url: http://localhost:9200\n  batch_size: 250\n  compression: true\n",
+        )
+        .unwrap();
+        // SAFETY: no other test reads ESPIPE_HOSTS.
+        unsafe { std::env::set_var("ESPIPE_HOSTS", &hosts) };
+        let defaults = defaults("batch-size: 500\nuncompressed: true\n").unwrap();
+
+        let cli = defaults.parse_from(["espipe", "a", "tuned:logs"]).unwrap();
+        assert_eq!(cli.batch_size, 250);
+        assert!(!cli.uncompressed);
+
+        let cli = defaults
+            .parse_from(["espipe", "--batch-size=10", "a", "tuned:logs"])
+            .unwrap();
+        assert_eq!(cli.batch_size, 10);
+
+        let cli = defaults.parse_from(["espipe", "a", "-"]).unwrap();
+        assert_eq!(cli.batch_size, 500);
+        assert!(cli.uncompressed);
+    }
+
+    #[test]
+    fn config_defaults_must_name_known_flags_with_valid_values() {
+        assert_eq!(
+            defaults("batch-sise: 500\n").unwrap_err().to_string(),
+            "unknown option batch-sise"
+        );
+        assert_eq!(
+            defaults("quiet: yes please\n").unwrap_err().to_string(),
+            "option quiet must be true or false"
+        );
+        let defaults = defaults("batch-size: zero\n").unwrap();
+        assert!(defaults.parse_from(["espipe", "a", "-"]).is_err());
+    }
+}
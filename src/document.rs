@@ -0,0 +1,101 @@
+use crate::memory::MemoryLease;
+use eyre::{Result, eyre};
+use serde_json::value::RawValue;
+
+/// One serialized JSON object on its way from an input to an output.
+///
+/// Documents built from a [`RawValue`] have been parsed; documents built with
+/// [`Document::unvalidated`] are trusted as-is and only sanity-checked, so
+/// outputs must treat the text as opaque and splice it through unchanged.
+///
+/// Documents replayed from a bulk-format input also carry the action line they
+/// were read with, which Elasticsearch outputs send in place of their own.
+///
+/// Under `--max-memory`, a document holds its share of the budget until it is dropped.
+#[derive(Debug)]
+pub struct Document {
+    json: Box<str>,
+    action: Option<Box<str>>,
+    lease: Option<MemoryLease>,
+}
+
+impl Document {
+    /// Wraps a line without parsing it, checking only that it looks like one JSON object.
+    pub fn unvalidated(line: &str) -> Result<Self> {
+        let json = line.trim();
+        if !json.starts_with('{') || !json.ends_with('}') {
+            return Err(eyre!(
+                "--no-validate requires one JSON object per line, starting with '{{' and ending with '}}'"
+            ));
+        }
+        Ok(Self {
+            json: json.into(),
+            action: None,
+            lease: None,
+        })
+    }
+
+    /// Attaches a bulk action line, such as `{"index":{"_index":"logs","_id":"1"}}`.
+    pub fn with_action(mut self, action: &str) -> Self {
+        self.action = Some(action.trim().into());
+        self
+    }
+
+    /// Replaces the JSON text, keeping the action line.
+    pub fn with_json(mut self, json: String) -> Self {
+        self.json = json.into();
+        self
+    }
+
+    /// Attaches the memory budget taken for this document when it was read.
+    pub fn with_lease(mut self, lease: MemoryLease) -> Self {
+        self.lease = Some(lease);
+        self
+    }
+
+    /// Detaches the memory lease, for outputs that keep the bytes after the document is gone.
+    pub fn take_lease(&mut self) -> Option<MemoryLease> {
+        self.lease.take()
+    }
+
+    pub fn get(&self) -> &str {
+        &self.json
+    }
+
+    pub fn action(&self) -> Option<&str> {
+        self.action.as_deref()
+    }
+}
+
+impl From<Box<RawValue>> for Document {
+    fn from(raw: Box<RawValue>) -> Self {
+        Self {
+            json: raw.into(),
+            action: None,
+            lease: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Document;
+    use serde_json::value::RawValue;
+
+    #[test]
+    fn raw_values_convert_without_changes() {
+        let raw = RawValue::from_string("{\"a\": [1, 2]}".to_string()).unwrap();
+        assert_eq!(Document::from(raw).get(), "{\"a\": [1, 2]}");
+    }
+
+    #[test]
+    fn unvalidated_lines_are_trimmed_and_sanity_checked() {
+        assert_eq!(
+            Document::unvalidated("  {\"a\":1}\r\n").unwrap().get(),
+            "{\"a\":1}"
+        );
+        assert!(Document::unvalidated("[1]\n").is_err());
+        assert!(Document::unvalidated("{\n").is_err());
+        assert!(Document::unvalidated("\n").is_err());
+    }
+}
@@ -0,0 +1,344 @@
+use crate::{
+    client::elasticsearch::request, document::Document, manifest::redact_uri, metrics::METRICS,
+    output::Output,
+};
+use elasticsearch::{Elasticsearch, http::Method};
+use eyre::{Result, eyre};
+use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json, value::RawValue};
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+};
+use tracing::Instrument;
+use url::Url;
+
+const MANIFEST_FILE: &str = "manifest.json";
+/// Hits fetched per search request.
+const PAGE_SIZE: usize = 1_000;
+/// How long each point in time is kept open between pages.
+const KEEP_ALIVE: &str = "5m";
+/// Settings Elasticsearch assigns to every index, which a create index request rejects.
+const GENERATED_SETTINGS: [&str; 5] = [
+    "creation_date",
+    "history_uuid",
+    "provided_name",
+    "uuid",
+    "version",
+];
+
+/// What `espipe dump` writes besides each index's documents.
+#[derive(Debug, Default)]
+pub struct DumpOptions {
+    pub mappings: bool,
+    pub settings: bool,
+}
+
+/// The `manifest.json` written next to the exported files.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DumpManifest {
+    espipe_version: String,
+    source: String,
+    started_at: String,
+    finished_at: String,
+    indices: Vec<DumpedIndex>,
+}
+
+/// One exported index, with file names relative to the dump directory.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DumpedIndex {
+    pub index: String,
+    pub documents: usize,
+    pub file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_file: Option<String>,
+}
+
+impl DumpManifest {
+    /// Reads the manifest of a dump directory.
+    pub fn read(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        let file = File::open(&path)
+            .map_err(|err| eyre!("failed to read dump manifest {}: {err}", path.display()))?;
+        serde_json::from_reader(BufReader::new(file))
+            .map_err(|err| eyre!("failed to parse dump manifest {}: {err}", path.display()))
+    }
+
+    pub fn documents(&self) -> usize {
+        self.indices.iter().map(|index| index.documents).sum()
+    }
+
+    pub fn indices(&self) -> &[DumpedIndex] {
+        &self.indices
+    }
+}
+
+/// Exports every open index matching the source URL's pattern into `dir`.
+///
+/// Each index becomes `<index>.ndjson.gz` in bulk format, so the `_index`,
+/// `_id`, and routing of every document replay as they were when the file is
+/// piped back into a cluster. With `--mappings` or `--settings`, a
+/// `<index>.index.json` create index body is written beside it.
+pub async fn dump(
+    client: &Elasticsearch,
+    source: &Url,
+    dir: &Path,
+    options: &DumpOptions,
+) -> Result<DumpManifest> {
+    let pattern = source.path().trim_start_matches('/');
+    if pattern.is_empty() {
+        return Err(eyre!(
+            "espipe dump needs an index pattern, like {source}logs-*"
+        ));
+    }
+    let started_at = Timestamp::now().to_string();
+    fs::create_dir_all(dir).map_err(|err| eyre!("failed to create {}: {err}", dir.display()))?;
+    let names = matching_indices(client, pattern).await?;
+    if names.is_empty() {
+        return Err(eyre!("no open indices match {pattern}"));
+    }
+
+    let mut indices = Vec::with_capacity(names.len());
+    for index in names {
+        let span = tracing::info_span!("dump", index = %index);
+        let dumped = dump_index(client, index, dir, options)
+            .instrument(span)
+            .await?;
+        indices.push(dumped);
+    }
+
+    let manifest = DumpManifest {
+        espipe_version: env!("CARGO_PKG_VERSION").to_string(),
+        source: redact_uri(source.as_str()),
+        started_at,
+        finished_at: Timestamp::now().to_string(),
+        indices,
+    };
+    let path = dir.join(MANIFEST_FILE);
+    let file = File::create(&path)
+        .map_err(|err| eyre!("failed to write manifest {}: {err}", path.display()))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &manifest)?;
+    Ok(manifest)
+}
+
+async fn dump_index(
+    client: &Elasticsearch,
+    index: String,
+    dir: &Path,
+    options: &DumpOptions,
+) -> Result<DumpedIndex> {
+    let index_file = match options.mappings || options.settings {
+        true => Some(write_index_file(client, &index, dir, options).await?),
+        false => None,
+    };
+    let file = format!("{index}.ndjson.gz");
+    let documents = dump_documents(client, &index, &dir.join(&file)).await?;
+    log::info!("Dumped {documents} docs from {index} to {file}");
+    Ok(DumpedIndex {
+        index,
+        documents,
+        file,
+        index_file,
+    })
+}
+
+/// Opens a point in time on the index, pages through it, and closes it again.
+async fn dump_documents(client: &Elasticsearch, index: &str, path: &Path) -> Result<usize> {
+    let mut output = Output::file(path.to_path_buf())?;
+    let pit = request(
+        client,
+        Method::Post,
+        &format!("/{index}/_pit"),
+        &[("keep_alive", KEEP_ALIVE)],
+        None,
+    )
+    .await?;
+    let mut pit_id = pit["id"]
+        .as_str()
+        .ok_or_else(|| eyre!("point in time for {index} has no id"))?
+        .to_string();
+    let result = read_pages(client, index, &mut pit_id, &mut output).await;
+    // An unclosed point in time only lingers until its keep-alive runs out.
+    if let Err(err) = request(
+        client,
+        Method::Delete,
+        "/_pit",
+        &[],
+        Some(&json!({ "id": pit_id })),
+    )
+    .await
+    {
+        log::warn!("Could not close the point in time on {index}: {err}");
+    }
+    let documents = result?;
+    output.close().await?;
+    Ok(documents)
+}
+
+/// Searches the point in time in shard order with `search_after`, following
+/// the point in time ID each response returns.
+async fn read_pages(
+    client: &Elasticsearch,
+    index: &str,
+    pit_id: &mut String,
+    output: &mut Output,
+) -> Result<usize> {
+    let mut search_after = None;
+    let mut documents = 0;
+    loop {
+        let mut body = json!({
+            "size": PAGE_SIZE,
+            "pit": { "id": pit_id, "keep_alive": KEEP_ALIVE },
+            "sort": ["_shard_doc"],
+            "track_total_hits": false,
+        });
+        if let Some(search_after) = search_after.take() {
+            body["search_after"] = search_after;
+        }
+        let page = request(client, Method::Post, "/_search", &[], Some(&body)).await?;
+        if let Some(id) = page["pit_id"].as_str() {
+            *pit_id = id.to_string();
+        }
+        let hits = page["hits"]["hits"]
+            .as_array()
+            .map_or(&[][..], Vec::as_slice);
+        for hit in hits {
+            documents += output.send(hit_document(index, hit)?).await?;
+        }
+        METRICS
+            .docs_read
+            .fetch_add(hits.len() as u64, Ordering::Relaxed);
+        match hits.last() {
+            Some(last) if hits.len() == PAGE_SIZE => search_after = Some(last["sort"].clone()),
+            _ => return Ok(documents),
+        }
+    }
+}
+
+/// A search hit as a bulk `index` action and its source.
+fn hit_document(index: &str, hit: &Value) -> Result<Document> {
+    let source = hit.get("_source").ok_or_else(|| {
+        eyre!("{index} does not store _source, so its documents cannot be dumped")
+    })?;
+    let mut metadata = Map::new();
+    metadata.insert("_index".to_string(), json!(index));
+    for (field, name) in [("_id", "_id"), ("_routing", "routing")] {
+        if let Some(value) = hit.get(field) {
+            metadata.insert(name.to_string(), value.clone());
+        }
+    }
+    let action = json!({ "index": metadata }).to_string();
+    Ok(Document::from(RawValue::from_string(source.to_string())?).with_action(&action))
+}
+
+/// Writes the index's mappings and settings as a body for `PUT /<index>`.
+async fn write_index_file(
+    client: &Elasticsearch,
+    index: &str,
+    dir: &Path,
+    options: &DumpOptions,
+) -> Result<String> {
+    let mut body = Map::new();
+    if options.mappings {
+        let mapping = request(
+            client,
+            Method::Get,
+            &format!("/{index}/_mapping"),
+            &[],
+            None,
+        )
+        .await?;
+        body.insert("mappings".to_string(), mapping[index]["mappings"].clone());
+    }
+    if options.settings {
+        let settings = request(
+            client,
+            Method::Get,
+            &format!("/{index}/_settings"),
+            &[],
+            None,
+        )
+        .await?;
+        body.insert(
+            "settings".to_string(),
+            portable_settings(settings[index]["settings"].clone()),
+        );
+    }
+    let file = format!("{index}.index.json");
+    let path: PathBuf = dir.join(&file);
+    fs::write(&path, serde_json::to_vec_pretty(&body)?)
+        .map_err(|err| eyre!("failed to write {}: {err}", path.display()))?;
+    Ok(file)
+}
+
+/// Drops the settings a cluster generates for each index, so the rest can create a copy.
+fn portable_settings(mut settings: Value) -> Value {
+    if let Some(index) = settings.get_mut("index").and_then(Value::as_object_mut) {
+        for name in GENERATED_SETTINGS {
+            index.remove(name);
+        }
+    }
+    settings
+}
+
+async fn matching_indices(client: &Elasticsearch, pattern: &str) -> Result<Vec<String>> {
+    let indices = request(
+        client,
+        Method::Get,
+        &format!("/_cat/indices/{pattern}"),
+        &[
+            ("format", "json"),
+            ("h", "index"),
+            ("expand_wildcards", "open"),
+        ],
+        None,
+    )
+    .await?;
+    let mut names: Vec<String> = indices
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|index| index["index"].as_str().map(str::to_string))
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hit_document, portable_settings};
+    use serde_json::{Value, json};
+
+    #[test]
+    fn hits_become_bulk_pairs_with_their_metadata() {
+        let doc = hit_document(
+            "logs-a",
+            &json!({"_index": "logs-a", "_id": "1", "_routing": "u1", "_source": {"n": 1}}),
+        )
+        .unwrap();
+        assert_eq!(doc.get(), r#"{"n":1}"#);
+        let action: Value = serde_json::from_str(doc.action().unwrap()).unwrap();
+        assert_eq!(
+            action,
+            json!({"index": {"_id": "1", "_index": "logs-a", "routing": "u1"}})
+        );
+        assert!(hit_document("logs-a", &json!({"_id": "2"})).is_err());
+    }
+
+    #[test]
+    fn generated_settings_are_dropped() {
+        let settings = portable_settings(json!({
+            "index": {
+                "number_of_shards": "1",
+                "uuid": "abc",
+                "creation_date": "1700000000000",
+                "provided_name": "logs-a",
+                "version": { "created": "8500000" },
+            }
+        }));
+        assert_eq!(settings, json!({ "index": { "number_of_shards": "1" } }));
+    }
+}
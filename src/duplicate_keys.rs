@@ -0,0 +1,295 @@
+use crate::{dead_letter::DeadLetter, document::Document};
+use clap::ValueEnum;
+use eyre::{Result, eyre};
+use serde::de::{DeserializeSeed, Deserializer, Error, MapAccess, SeqAccess, Visitor};
+use serde_json::{Map, Number, Value};
+
+/// Objects with more duplicate keys than this are only named up to it in
+/// warnings and errors.
+const MAX_NAMED: usize = 5;
+
+/// The key of the map the `arbitrary-precision` feature reads each float and
+/// each integer too large for 64 bits as, holding the number's digits as read.
+const NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
+/// What `--duplicate-keys` does with a document that repeats a key in one
+/// object.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum DuplicateKeyAction {
+    /// Keep the last value of each key, as JSON parsers usually do, and warn
+    Last,
+    /// Keep the first value of each key, and warn
+    First,
+    /// Write the document to --dead-letter instead of sending it
+    DeadLetter,
+    /// Fail the run
+    Fail,
+}
+
+impl DuplicateKeyAction {
+    /// How the summary describes the documents handled.
+    pub fn resolution(self) -> &'static str {
+        match self {
+            Self::Last => "kept the last values",
+            Self::First => "kept the first values",
+            Self::DeadLetter => "dead-lettered",
+            Self::Fail => "failed",
+        }
+    }
+}
+
+/// Finds documents that repeat a key within one object, which most JSON
+/// parsers, serde's included, read without a word by keeping the last value,
+/// and which Elasticsearch rejects outright.
+///
+/// Documents without duplicates are passed on untouched.
+pub struct DuplicateKeys {
+    action: DuplicateKeyAction,
+    dead_letter: Option<DeadLetter>,
+    handled: usize,
+}
+
+impl DuplicateKeys {
+    pub fn try_new(
+        action: Option<DuplicateKeyAction>,
+        dead_letter: Option<DeadLetter>,
+    ) -> Result<Option<Self>> {
+        let Some(action) = action else {
+            return Ok(None);
+        };
+        if action == DuplicateKeyAction::DeadLetter && dead_letter.is_none() {
+            return Err(eyre!(
+                "--duplicate-keys dead-letter needs a --dead-letter file"
+            ));
+        }
+        Ok(Some(Self {
+            action,
+            dead_letter,
+            handled: 0,
+        }))
+    }
+
+    /// The document with one value per key, or `None` once it is dead-lettered.
+    pub fn apply(&mut self, doc: Document) -> Result<Option<Document>> {
+        let mut duplicates = Vec::new();
+        let value = Checked {
+            path: "",
+            keep_first: self.action == DuplicateKeyAction::First,
+            duplicates: &mut duplicates,
+        }
+        .deserialize(&mut serde_json::Deserializer::from_str(doc.get()))
+        .map_err(|err| eyre!("Error parsing JSON for --duplicate-keys: {err}"))?;
+        if duplicates.is_empty() {
+            return Ok(Some(doc));
+        }
+        self.handled += 1;
+        let named = match duplicates.len() {
+            count if count > MAX_NAMED => format!(
+                "{} and {} more",
+                duplicates[..MAX_NAMED].join(", "),
+                count - MAX_NAMED
+            ),
+            _ => duplicates.join(", "),
+        };
+        match self.action {
+            DuplicateKeyAction::Fail => Err(eyre!(
+                "Document has duplicate keys {named}, stopping for --duplicate-keys fail"
+            )),
+            DuplicateKeyAction::DeadLetter => {
+                let dead_letter = self.dead_letter.as_ref().expect("checked in try_new");
+                log::warn!("Document has duplicate keys {named}, writing it to {dead_letter}");
+                dead_letter.write(&[doc])?;
+                Ok(None)
+            }
+            action => {
+                log::warn!(
+                    "Document has duplicate keys {named}, {}",
+                    action.resolution()
+                );
+                Ok(Some(doc.with_json(value.to_string())))
+            }
+        }
+    }
+
+    pub fn action(&self) -> DuplicateKeyAction {
+        self.action
+    }
+
+    /// Documents found with duplicate keys.
+    pub fn handled(&self) -> usize {
+        self.handled
+    }
+}
+
+/// Reads a JSON value like `Value` does, but keeps the first or last value of
+/// a repeated key on purpose and adds its dotted path to `duplicates`.
+struct Checked<'a> {
+    path: &'a str,
+    keep_first: bool,
+    duplicates: &'a mut Vec<String>,
+}
+
+impl<'de> DeserializeSeed<'de> for Checked<'_> {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Checked<'_> {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a JSON value")
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<Value, E> {
+        Ok(Value::Bool(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Value, E> {
+        Ok(Value::Number(value.into()))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Value, E> {
+        Ok(Value::Number(value.into()))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Value, E> {
+        Ok(Number::from_f64(value).map_or(Value::Null, Value::Number))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Value, E> {
+        Ok(Value::String(value.to_string()))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<Value, E> {
+        Ok(Value::String(value))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element_seed(Checked {
+            path: self.path,
+            keep_first: self.keep_first,
+            duplicates: &mut *self.duplicates,
+        })? {
+            values.push(value);
+        }
+        Ok(Value::Array(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut object = Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if cfg!(feature = "arbitrary-precision") && key == NUMBER_TOKEN && object.is_empty() {
+                let number: String = map.next_value()?;
+                return number.parse().map(Value::Number).map_err(A::Error::custom);
+            }
+            let path = match self.path {
+                "" => key.clone(),
+                path => format!("{path}.{key}"),
+            };
+            let value = map.next_value_seed(Checked {
+                path: &path,
+                keep_first: self.keep_first,
+                duplicates: &mut *self.duplicates,
+            })?;
+            if object.contains_key(&key) {
+                if !self.duplicates.contains(&path) {
+                    self.duplicates.push(path);
+                }
+                if self.keep_first {
+                    continue;
+                }
+            }
+            object.insert(key, value);
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DuplicateKeyAction, DuplicateKeys};
+    use crate::{dead_letter::DeadLetter, document::Document};
+    use serde_json::{Value, json};
+    use std::fs;
+
+    fn apply(keys: &mut DuplicateKeys, json: &str) -> Option<Value> {
+        let doc = Document::unvalidated(json).unwrap();
+        keys.apply(doc)
+            .unwrap()
+            .map(|doc| serde_json::from_str(doc.get()).unwrap())
+    }
+
+    #[test]
+    fn duplicate_keys_are_resolved_dead_lettered_or_fail() {
+        let doc = r#"{"id":1,"user":{"name":"a","name":"b"},"tags":[{"k":1,"k":2}],"id":2}"#;
+
+        let mut last = DuplicateKeys::try_new(Some(DuplicateKeyAction::Last), None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            apply(&mut last, doc),
+            Some(json!({"id":2,"user":{"name":"b"},"tags":[{"k":2}]}))
+        );
+        assert_eq!(
+            apply(&mut last, r#"{"id":1,"user":{"id":2}}"#),
+            Some(json!({"id":1,"user":{"id":2}}))
+        );
+        assert_eq!(last.handled(), 1);
+
+        let mut first = DuplicateKeys::try_new(Some(DuplicateKeyAction::First), None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            apply(&mut first, doc),
+            Some(json!({"id":1,"user":{"name":"a"},"tags":[{"k":1}]}))
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dead.ndjson");
+        let dead_letter = DeadLetter::open(&path).unwrap();
+        let mut dead =
+            DuplicateKeys::try_new(Some(DuplicateKeyAction::DeadLetter), Some(dead_letter))
+                .unwrap()
+                .unwrap();
+        assert_eq!(apply(&mut dead, doc), None);
+        assert_eq!(fs::read_to_string(&path).unwrap(), format!("{doc}\n"));
+
+        let mut fail = DuplicateKeys::try_new(Some(DuplicateKeyAction::Fail), None)
+            .unwrap()
+            .unwrap();
+        let err = fail
+            .apply(Document::unvalidated(doc).unwrap())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("user.name, tags.k, id"), "{err}");
+
+        assert!(DuplicateKeys::try_new(Some(DuplicateKeyAction::DeadLetter), None).is_err());
+        assert!(DuplicateKeys::try_new(None, None).unwrap().is_none());
+    }
+
+    #[cfg(feature = "arbitrary-precision")]
+    #[test]
+    fn resolved_documents_keep_number_digits_as_read() {
+        let mut last = DuplicateKeys::try_new(Some(DuplicateKeyAction::Last), None)
+            .unwrap()
+            .unwrap();
+        let doc = r#"{"id":123456789012345678901234,"price":1.50,"price":2.10,"ratio":1.0E2}"#;
+        let resolved = last
+            .apply(Document::unvalidated(doc).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            resolved.get(),
+            r#"{"id":123456789012345678901234,"price":2.10,"ratio":1.0e+2}"#
+        );
+    }
+}
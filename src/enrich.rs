@@ -0,0 +1,100 @@
+use crate::{document::Document, field_path};
+use eyre::{Result, eyre};
+use serde_json::{Map, Value};
+use std::{collections::HashMap, path::Path};
+
+/// Columns of a lookup CSV merged into the documents whose key field matches
+/// a row, like the Logstash translate filter, for joins too simple to need an
+/// enrich policy in Elasticsearch.
+///
+/// The first column holds the key and the others the fields to add, which may
+/// be dotted paths and replace values already in the document. Cells are
+/// strings, as CSV input is, and empty cells are left out.
+#[derive(Debug)]
+pub struct Enrichment {
+    key: String,
+    rows: HashMap<String, Map<String, Value>>,
+}
+
+impl Enrichment {
+    pub fn load(path: &Path, key: String) -> Result<Self> {
+        let mut reader = csv::Reader::from_path(path)
+            .map_err(|err| eyre!("failed to open --enrich {}: {err}", path.display()))?;
+        Self::from_reader(&mut reader, key)
+            .map_err(|err| eyre!("--enrich {}: {err}", path.display()))
+    }
+
+    fn from_reader<R: std::io::Read>(reader: &mut csv::Reader<R>, key: String) -> Result<Self> {
+        let headers = reader.headers()?.clone();
+        if headers.len() < 2 {
+            return Err(eyre!("needs a key column and at least one column to add"));
+        }
+        let mut rows = HashMap::new();
+        for record in reader.records() {
+            let record = record?;
+            let fields = headers
+                .iter()
+                .zip(record.iter())
+                .skip(1)
+                .filter(|(_, cell)| !cell.is_empty())
+                .map(|(name, cell)| (name.to_string(), Value::String(cell.to_string())))
+                .collect();
+            rows.insert(record.get(0).unwrap_or_default().to_string(), fields);
+        }
+        log::debug!("Loaded {} enrichment rows", rows.len());
+        Ok(Self { key, rows })
+    }
+
+    /// Adds the columns of the row matching the document's key, if any.
+    pub fn apply(&self, doc: Document) -> Result<Document> {
+        let mut value: Value = serde_json::from_str(doc.get())
+            .map_err(|err| eyre!("Error parsing JSON for --enrich: {err}"))?;
+        let row = match field_path::lookup(&value, &self.key) {
+            Some(Value::String(key)) => self.rows.get(key),
+            Some(key @ (Value::Number(_) | Value::Bool(_))) => self.rows.get(&key.to_string()),
+            _ => None,
+        };
+        let Some(row) = row else {
+            return Ok(doc);
+        };
+        for (path, field) in row {
+            field_path::insert(&mut value, path, field.clone());
+        }
+        Ok(doc.with_json(value.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Enrichment;
+    use crate::document::Document;
+    use serde_json::{Value, json};
+
+    #[test]
+    fn matching_rows_add_their_columns() {
+        let csv = "host,owner.team,site\nweb-1,search,ams\n42,infra,\n";
+        let enrichment = Enrichment::from_reader(
+            &mut csv::Reader::from_reader(csv.as_bytes()),
+            "host.name".to_string(),
+        )
+        .unwrap();
+        let enrich = |json: &str| -> Value {
+            let doc = enrichment
+                .apply(Document::unvalidated(json).unwrap())
+                .unwrap();
+            serde_json::from_str(doc.get()).unwrap()
+        };
+        assert_eq!(
+            enrich(r#"{"host":{"name":"web-1"},"site":"old"}"#),
+            json!({"host": {"name": "web-1"}, "owner": {"team": "search"}, "site": "ams"})
+        );
+        assert_eq!(
+            enrich(r#"{"host":{"name":42}}"#),
+            json!({"host": {"name": 42}, "owner": {"team": "infra"}})
+        );
+        assert_eq!(
+            enrich(r#"{"host":{"name":"db-1"}}"#),
+            json!({"host": {"name": "db-1"}})
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{Cli, Command, client::KnownHost};
+use eyre::{Result, eyre};
+use fluent_uri::UriRef;
+use std::io::{BufRead, IsTerminal, Write};
+
+/// `cli` with each known host in its URIs replaced by the host's entry for
+/// `--env`, so `mycluster:logs` becomes `mycluster.staging:logs` with
+/// `--env staging`. Hosts without an entry for the environment are kept.
+pub fn resolve(mut cli: Cli) -> Result<Cli> {
+    let Some(env) = cli.env.clone() else {
+        return Ok(cli);
+    };
+    let mut uris: Vec<&mut UriRef<String>> = cli.paths.iter_mut().collect();
+    match &mut cli.command {
+        Some(Command::Listen { output, .. }) => uris.push(output),
+        Some(Command::Dump { source, .. }) => uris.push(source),
+        Some(Command::Verify { source, target, .. }) => uris.extend([source, target]),
+        Some(
+            Command::Lint { inputs, .. }
+            | Command::Profile { inputs, .. }
+            | Command::InferMapping { inputs, .. }
+            | Command::Head { inputs, .. },
+        ) => uris.extend(inputs.iter_mut()),
+        Some(
+            Command::Restore { target, .. }
+            | Command::Rollback { target, .. }
+            | Command::Keygen { target, .. }
+            | Command::Bench { target, .. },
+        ) => uris.push(target),
+        Some(
+            Command::Run { .. }
+            | Command::Hosts { .. }
+            | Command::Completions { .. }
+            | Command::Examples,
+        )
+        | None => {}
+    }
+    for uri in uris {
+        let Some(scheme) = uri.scheme().map(|scheme| scheme.as_str().to_string()) else {
+            continue;
+        };
+        let name = KnownHost::name_in_environment(&scheme, &env)?;
+        if name != scheme {
+            log::debug!("Using known host {name} for {scheme} in --env {env}");
+            let rest = &uri.as_str()[scheme.len()..];
+            *uri = UriRef::parse(format!("{name}{rest}"))
+                .map_err(|(err, _)| eyre!("{name}: {err}"))?;
+        }
+    }
+    Ok(cli)
+}
+
+/// Asks on the terminal before `cli` writes to a known host tagged
+/// `production`, unless `--yes` is given. Without a terminal to ask on, or
+/// when not `interactive`, writing to one needs `--yes`.
+pub fn confirm(cli: &Cli, interactive: bool) -> Result<()> {
+    if cli.yes {
+        return Ok(());
+    }
+    let target = match &cli.command {
+        Some(Command::Listen { output, .. }) => Some(output),
+        Some(
+            Command::Restore { target, .. }
+            | Command::Rollback { target, .. }
+            | Command::Bench { target, .. },
+        ) => Some(target),
+        None => cli.paths.last(),
+        Some(_) => None,
+    };
+    let Some(name) = target
+        .and_then(|uri| uri.scheme())
+        .map(|scheme| scheme.as_str())
+    else {
+        return Ok(());
+    };
+    if !KnownHost::parse(name).is_some_and(|host| host.is_production()) {
+        return Ok(());
+    }
+    let stdin = std::io::stdin();
+    let reads_stdin = cli.paths.iter().any(|path| path.as_str() == "-");
+    if !interactive || reads_stdin || !stdin.is_terminal() {
+        return Err(eyre!(
+            "{name} is tagged production; pass --yes to write to it"
+        ));
+    }
+    eprint!("{name} is tagged production. Write to it? [y/N] ");
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    stdin.lock().read_line(&mut answer)?;
+    match is_yes(&answer) {
+        true => Ok(()),
+        false => Err(eyre!("Not confirmed, so nothing was written to {name}")),
+    }
+}
+
+fn is_yes(answer: &str) -> bool {
+    ["y", "yes"].contains(&answer.trim().to_lowercase().as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_yes;
+
+    #[test]
+    fn only_yes_confirms() {
+        assert!(is_yes("y\n"));
+        assert!(is_yes(" YES\r\n"));
+        assert!(!is_yes("\n"));
+        assert!(!is_yes("no\n"));
+        assert!(!is_yes("yeah\n"));
+    }
+}
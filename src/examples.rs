@@ -0,0 +1,102 @@
+/// Common invocations printed by `espipe examples`, as a description and the
+/// command line. Inputs and outputs are URIs, so these show what `--help`
+/// cannot.
+const EXAMPLES: &[(&str, &str)] = &[
+    (
+        "Load an NDJSON file into an index",
+        "espipe docs.ndjson http://localhost:9200/docs",
+    ),
+    (
+        "Load a CSV file into an index on a known host from hosts.yml",
+        "espipe users.csv prod:users",
+    ),
+    (
+        "Load every matching file, gzip-compressed or not",
+        "espipe 'logs/*.ndjson.gz' http://localhost:9200/logs",
+    ),
+    (
+        "Copy the matching documents of an index from one cluster to another",
+        "espipe 'prod://logs-api?kql=status:error' http://localhost:9200/logs-api",
+    ),
+    (
+        "Export an index to a gzip-compressed NDJSON file",
+        "espipe prod:orders orders.ndjson.gz",
+    ),
+    (
+        "Dump indices with their mappings and settings, then restore them elsewhere",
+        "espipe dump prod:logs-* ./backup --mappings --settings && espipe restore ./backup staging:",
+    ),
+    (
+        "Stream stdin to Kafka, one message per line",
+        "tail -F app.ndjson | espipe - kafka://broker:9092/app-logs",
+    ),
+    (
+        "Read CSV from stdin and print it as a table",
+        "cat users.csv | espipe --input-format csv --output-format table - -",
+    ),
+    (
+        "Preview the first documents of a CSV file as they would be sent",
+        "espipe head users.csv -n 5 --output-format pretty",
+    ),
+    (
+        "Update documents by _id with authentication",
+        "espipe --action update --apikey \"$ES_API_KEY\" changes.ndjson https://es.example.com:9200/users",
+    ),
+    (
+        "Print shell completions, here for bash",
+        "espipe completions bash > /etc/bash_completion.d/espipe",
+    ),
+];
+
+/// The examples as shell comments and commands.
+pub fn text() -> String {
+    EXAMPLES
+        .iter()
+        .map(|(description, command)| format!("# {description}\n{command}\n"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EXAMPLES;
+    use crate::Cli;
+    use clap::Parser;
+
+    /// Splits a shell command on whitespace outside single and double quotes.
+    fn words(command: &str) -> Vec<String> {
+        let mut words = Vec::new();
+        let mut word = String::new();
+        let mut quote = None;
+        for char in command.chars() {
+            match (quote, char) {
+                (None, '\'' | '"') => quote = Some(char),
+                (Some(open), _) if char == open => quote = None,
+                (None, ' ') if !word.is_empty() => words.push(std::mem::take(&mut word)),
+                (None, ' ') => {}
+                _ => word.push(char),
+            }
+        }
+        words.push(word);
+        words
+    }
+
+    #[test]
+    fn every_example_parses() {
+        for (_, command) in EXAMPLES {
+            for invocation in command.split(['|', '&']) {
+                let words = words(invocation.trim());
+                let Some(start) = words.iter().position(|word| word == "espipe") else {
+                    continue;
+                };
+                let end = words
+                    .iter()
+                    .position(|word| word == ">")
+                    .unwrap_or(words.len());
+                if let Err(err) = Cli::try_parse_from(&words[start..end]) {
+                    panic!("{command} does not parse: {err}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use crate::{document::Document, field_path};
+use eyre::{Result, eyre};
+use serde_json::Value;
+
+/// Fans a document out into one document per element of an array field, with
+/// the element in the array's place and every other field copied from the
+/// parent, so an order with an `items` array can be indexed as one document
+/// per item.
+///
+/// Documents without the field, or where it is not a non-empty array, pass
+/// through unchanged.
+pub struct Exploder {
+    path: String,
+}
+
+impl Exploder {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+        }
+    }
+
+    pub fn apply(&self, mut doc: Document) -> Result<Vec<Document>> {
+        if doc.action().is_some() {
+            return Err(eyre!("--explode cannot be used with bulk-format input"));
+        }
+        let mut value: Value = serde_json::from_str(doc.get())
+            .map_err(|err| eyre!("Error parsing JSON for --explode: {err}"))?;
+        let elements = match field_path::lookup_mut(&mut value, &self.path) {
+            Some(Value::Array(elements)) if !elements.is_empty() => std::mem::take(elements),
+            _ => return Ok(vec![doc]),
+        };
+        // The first child carries the parent's `--max-memory` lease.
+        let mut lease = doc.take_lease();
+        elements
+            .into_iter()
+            .map(|element| {
+                let mut child = value.clone();
+                if let Some(field) = field_path::lookup_mut(&mut child, &self.path) {
+                    *field = element;
+                }
+                let child = Document::unvalidated(&child.to_string())?;
+                Ok(match lease.take() {
+                    Some(lease) => child.with_lease(lease),
+                    None => child,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Exploder;
+    use crate::document::Document;
+
+    fn explode(path: &str, json: &str) -> Vec<String> {
+        Exploder::new(path)
+            .apply(Document::unvalidated(json).unwrap())
+            .unwrap()
+            .iter()
+            .map(|doc| doc.get().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn each_element_replaces_the_array_in_a_copy_of_the_parent() {
+        assert_eq!(
+            explode(
+                "order.items",
+                r#"{"id":7,"order":{"items":[{"sku":"a"},{"sku":"b"}],"total":3}}"#
+            ),
+            vec![
+                r#"{"id":7,"order":{"items":{"sku":"a"},"total":3}}"#,
+                r#"{"id":7,"order":{"items":{"sku":"b"},"total":3}}"#,
+            ]
+        );
+        assert_eq!(
+            explode("tags", r#"{"tags":["x","y"]}"#),
+            vec![r#"{"tags":"x"}"#, r#"{"tags":"y"}"#]
+        );
+    }
+
+    #[test]
+    fn documents_without_a_non_empty_array_pass_through() {
+        for json in [r#"{"a":1}"#, r#"{"items":[]}"#, r#"{"items":{"sku":"a"}}"#] {
+            assert_eq!(explode("items", json), vec![json]);
+        }
+        let bulk = Document::unvalidated(r#"{"items":[1]}"#)
+            .unwrap()
+            .with_action(r#"{"index":{}}"#);
+        assert!(Exploder::new("items").apply(bulk).is_err());
+    }
+}
@@ -0,0 +1,73 @@
+use serde_json::{Map, Value};
+
+/// Looks up a field by dotted path, preferring a literal dotted key at each level
+/// before descending into nested objects.
+pub fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let map = value.as_object()?;
+    if let Some(found) = map.get(path) {
+        return Some(found);
+    }
+    let (head, rest) = path.split_once('.')?;
+    lookup(map.get(head)?, rest)
+}
+
+/// Looks up a field by dotted path for changing it in place, the same way as
+/// [`lookup`].
+pub fn lookup_mut<'a>(value: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    let map = value.as_object_mut()?;
+    if map.contains_key(path) {
+        return map.get_mut(path);
+    }
+    let (head, rest) = path.split_once('.')?;
+    lookup_mut(map.get_mut(head)?, rest)
+}
+
+/// Sets a field by dotted path, creating nested objects along the way and
+/// replacing any non-object value that stands in the path.
+pub fn insert(value: &mut Value, path: &str, field: Value) {
+    if !value.is_object() {
+        *value = Value::Object(Map::new());
+    }
+    let map = value.as_object_mut().expect("value is an object");
+    match path.split_once('.') {
+        Some((head, rest)) => insert(map.entry(head).or_insert(Value::Null), rest, field),
+        None => {
+            map.insert(path.to_string(), field);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{insert, lookup};
+    use serde_json::json;
+
+    #[test]
+    fn lookup_descends_nested_objects() {
+        let value = json!({"host": {"name": "alpha"}});
+        assert_eq!(lookup(&value, "host.name"), Some(&json!("alpha")));
+        assert_eq!(lookup(&value, "host.ip"), None);
+    }
+
+    #[test]
+    fn lookup_prefers_literal_dotted_keys() {
+        let value = json!({"host.name": "literal", "host": {"name": "nested"}});
+        assert_eq!(lookup(&value, "host.name"), Some(&json!("literal")));
+    }
+
+    #[test]
+    fn lookup_rejects_non_object_roots() {
+        assert_eq!(lookup(&json!([1, 2]), "0"), None);
+    }
+
+    #[test]
+    fn insert_creates_and_replaces_intermediate_objects() {
+        let mut value = json!({"ingest": {"source": "a"}, "env": "prod"});
+        insert(&mut value, "ingest.run_id", json!(7));
+        insert(&mut value, "env.name", json!("prod"));
+        assert_eq!(
+            value,
+            json!({"ingest": {"run_id": 7, "source": "a"}, "env": {"name": "prod"}})
+        );
+    }
+}
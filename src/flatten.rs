@@ -0,0 +1,99 @@
+use crate::{document::Document, metadata::is_update};
+use eyre::{Result, eyre};
+use serde_json::{Map, Value};
+
+/// Turns nested objects into top-level keys joined by a separator, so
+/// `{"host": {"name": "a"}}` becomes `{"host.name": "a"}`, for indices with
+/// flattened mappings and for CSV output.
+///
+/// Arrays, and the objects in them, are kept as they are, as are empty
+/// objects, which have no fields to lift. Bulk `update` pairs have their
+/// partial `doc` flattened.
+#[derive(Debug)]
+pub struct Flattener {
+    separator: String,
+}
+
+impl Flattener {
+    pub fn try_new(separator: String) -> Result<Self> {
+        if separator.is_empty() {
+            return Err(eyre!("--flatten separator cannot be empty"));
+        }
+        Ok(Self { separator })
+    }
+
+    pub fn apply(&self, doc: Document) -> Result<Document> {
+        let mut value: Value = serde_json::from_str(doc.get())
+            .map_err(|err| eyre!("Error parsing JSON for --flatten: {err}"))?;
+        let target = match doc.action().is_some_and(is_update) {
+            true => value.get_mut("doc"),
+            false => Some(&mut value),
+        };
+        let Some(Value::Object(object)) = target else {
+            return Ok(doc);
+        };
+        if !object.values().any(is_nested) {
+            return Ok(doc);
+        }
+        let mut flat = Map::new();
+        self.flatten_into(&mut flat, "", std::mem::take(object));
+        *object = flat;
+        Ok(doc.with_json(value.to_string()))
+    }
+
+    fn flatten_into(
+        &self,
+        flat: &mut Map<String, Value>,
+        prefix: &str,
+        object: Map<String, Value>,
+    ) {
+        for (key, value) in object {
+            let key = match prefix {
+                "" => key,
+                prefix => format!("{prefix}{}{key}", self.separator),
+            };
+            match value {
+                Value::Object(object) if !object.is_empty() => {
+                    self.flatten_into(flat, &key, object)
+                }
+                value => {
+                    flat.insert(key, value);
+                }
+            }
+        }
+    }
+}
+
+fn is_nested(value: &Value) -> bool {
+    value.as_object().is_some_and(|object| !object.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Flattener;
+    use crate::document::Document;
+    use serde_json::{Value, json};
+
+    fn flatten(separator: &str, json: &str) -> Value {
+        let doc = Flattener::try_new(separator.to_string())
+            .unwrap()
+            .apply(Document::unvalidated(json).unwrap())
+            .unwrap();
+        serde_json::from_str(doc.get()).unwrap()
+    }
+
+    #[test]
+    fn nested_objects_become_joined_keys() {
+        let json =
+            r#"{"host":{"name":"a","os":{"family":"linux"}},"tags":[{"k":1}],"meta":{},"n":1}"#;
+        assert_eq!(
+            flatten(".", json),
+            json!({"host.name": "a", "host.os.family": "linux", "meta": {}, "n": 1, "tags": [{"k": 1}]})
+        );
+        assert_eq!(
+            flatten("__", json),
+            json!({"host__name": "a", "host__os__family": "linux", "meta": {}, "n": 1, "tags": [{"k": 1}]})
+        );
+        assert!(Flattener::try_new(String::new()).is_err());
+    }
+}
@@ -0,0 +1,479 @@
+use eyre::{Result, eyre};
+use jiff::{Timestamp, civil::Date, tz::TimeZone};
+use serde_json::{Map, Number, Value, value::RawValue};
+use std::{fs, path::Path};
+
+/// The document generated without a template: a small log event.
+const DEFAULT_TEMPLATE: &str = r#"{
+    "@timestamp": "{{timestamp}}",
+    "event": { "sequence": "{{seq}}", "duration": "{{int:1:5000}}" },
+    "host": { "ip": "{{ip}}" },
+    "log": { "level": "{{choice:info=6|warn=3|error=1}}" },
+    "trace": { "id": "{{uuid}}" },
+    "message": "{{words:12}}"
+}"#;
+
+const WORDS: [&str; 32] = [
+    "alpha", "bravo", "cache", "delta", "error", "fetch", "gamma", "hotel", "index", "jolly",
+    "kilo", "lemon", "merge", "node", "oscar", "proxy", "query", "route", "shard", "token",
+    "union", "value", "worker", "xray", "yield", "zulu", "batch", "cluster", "request", "timeout",
+    "replica", "segment",
+];
+
+const LOREM: [&str; 24] = [
+    "lorem",
+    "ipsum",
+    "dolor",
+    "sit",
+    "amet",
+    "consectetur",
+    "adipiscing",
+    "elit",
+    "sed",
+    "do",
+    "eiusmod",
+    "tempor",
+    "incididunt",
+    "ut",
+    "labore",
+    "et",
+    "dolore",
+    "magna",
+    "aliqua",
+    "enim",
+    "ad",
+    "minim",
+    "veniam",
+    "quis",
+];
+
+/// Builds synthetic documents from a JSON template, for load testing.
+///
+/// String values in the template may hold placeholders, replaced afresh in
+/// every document:
+///
+/// - `{{seq}}`: the document's sequence number
+/// - `{{int:MIN:MAX}}` and `{{float:MIN:MAX}}`: a number in the range
+/// - `{{bool}}`: `true` or `false`
+/// - `{{choice:a|b|c}}`: one of the options, weighted like `{{choice:a=8|b=2}}`
+/// - `{{word}}` and `{{words:N}}`: one or `N` words
+/// - `{{lorem:N}}`: a lorem ipsum sentence of `N` words
+/// - `{{timestamp}}`: the current time, or with `{{timestamp:START..END}}` a
+///   time in the range, where each end is an RFC 3339 time, a date, or `now`
+/// - `{{uuid}}` and `{{ip}}`: a random UUID or IPv4 address
+///
+/// A string that is a single placeholder takes its type, so `"{{int:1:9}}"`
+/// becomes a number; placeholders inside longer strings are substituted as
+/// text. Values are drawn from the sequence number, so the same template
+/// generates the same documents, apart from their timestamps.
+#[derive(Debug)]
+pub struct DocTemplate {
+    root: Node,
+}
+
+#[derive(Debug)]
+enum Node {
+    Literal(Value),
+    Object(Vec<(String, Node)>),
+    Array(Vec<Node>),
+    /// A string that is one placeholder.
+    Field(Field),
+    /// A string mixing text and placeholders.
+    Text(Vec<Part>),
+}
+
+#[derive(Debug)]
+enum Part {
+    Text(String),
+    Field(Field),
+}
+
+#[derive(Debug)]
+enum Field {
+    Seq,
+    Int(i64, i64),
+    Float(f64, f64),
+    Bool,
+    /// Options with their weights, and the sum of the weights.
+    Choice(Vec<(String, u64)>, u64),
+    Words(usize),
+    Lorem(usize),
+    /// The current time, or milliseconds since the epoch drawn from a range.
+    Timestamp(Option<(i64, i64)>),
+    Uuid,
+    Ip,
+}
+
+impl DocTemplate {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| eyre!("Failed to read {}: {err}", path.display()))?;
+        Self::parse(&text).map_err(|err| eyre!("{}: {err}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let template: Value = serde_json::from_str(text)
+            .map_err(|err| eyre!("document template is not valid JSON: {err}"))?;
+        if !template.is_object() {
+            return Err(eyre!("document template must be a JSON object"));
+        }
+        Ok(Self {
+            root: Node::compile(template)?,
+        })
+    }
+
+    /// The document numbered `seq`.
+    pub fn render(&self, seq: u64) -> Result<Box<RawValue>> {
+        let mut random = Random::new(seq);
+        let json = self.root.render(seq, &mut random).to_string();
+        Ok(RawValue::from_string(json)?)
+    }
+}
+
+impl Default for DocTemplate {
+    fn default() -> Self {
+        Self::parse(DEFAULT_TEMPLATE).expect("the default template is valid")
+    }
+}
+
+impl Node {
+    fn compile(value: Value) -> Result<Self> {
+        Ok(match value {
+            Value::Object(object) => Node::Object(
+                object
+                    .into_iter()
+                    .map(|(key, value)| Ok((key, Node::compile(value)?)))
+                    .collect::<Result<_>>()?,
+            ),
+            Value::Array(array) => Node::Array(
+                array
+                    .into_iter()
+                    .map(Node::compile)
+                    .collect::<Result<_>>()?,
+            ),
+            Value::String(text) if text.contains("{{") => {
+                let mut parts = Vec::new();
+                let mut rest = text.as_str();
+                while let Some(start) = rest.find("{{") {
+                    let end = rest[start..]
+                        .find("}}")
+                        .ok_or_else(|| eyre!("unclosed placeholder in \"{text}\""))?;
+                    if start > 0 {
+                        parts.push(Part::Text(rest[..start].to_string()));
+                    }
+                    parts.push(Part::Field(Field::parse(&rest[start + 2..start + end])?));
+                    rest = &rest[start + end + 2..];
+                }
+                if !rest.is_empty() {
+                    parts.push(Part::Text(rest.to_string()));
+                }
+                match <[Part; 1]>::try_from(parts) {
+                    Ok([Part::Field(field)]) => Node::Field(field),
+                    Ok(part) => Node::Text(part.into()),
+                    Err(parts) => Node::Text(parts),
+                }
+            }
+            value => Node::Literal(value),
+        })
+    }
+
+    fn render(&self, seq: u64, random: &mut Random) -> Value {
+        match self {
+            Node::Literal(value) => value.clone(),
+            Node::Object(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(key, node)| (key.clone(), node.render(seq, random)))
+                    .collect::<Map<_, _>>(),
+            ),
+            Node::Array(nodes) => {
+                Value::Array(nodes.iter().map(|node| node.render(seq, random)).collect())
+            }
+            Node::Field(field) => field.render(seq, random),
+            Node::Text(parts) => {
+                let mut text = String::new();
+                for part in parts {
+                    match part {
+                        Part::Text(literal) => text.push_str(literal),
+                        Part::Field(field) => match field.render(seq, random) {
+                            Value::String(value) => text.push_str(&value),
+                            value => text.push_str(&value.to_string()),
+                        },
+                    }
+                }
+                Value::String(text)
+            }
+        }
+    }
+}
+
+impl Field {
+    fn parse(placeholder: &str) -> Result<Self> {
+        let (name, args) = placeholder
+            .trim()
+            .split_once(':')
+            .unwrap_or((placeholder.trim(), ""));
+        let range = |kind: &str| {
+            args.split_once(':').ok_or_else(|| {
+                eyre!("{{{{{kind}:MIN:MAX}}}} needs a range, not {{{{{placeholder}}}}}")
+            })
+        };
+        Ok(match name {
+            "seq" => Field::Seq,
+            "int" => {
+                let (min, max) = range("int")?;
+                let (min, max) = (min.trim().parse::<i64>()?, max.trim().parse::<i64>()?);
+                if min > max {
+                    return Err(eyre!("{{{{{placeholder}}}}} has its range backwards"));
+                }
+                Field::Int(min, max)
+            }
+            "float" => {
+                let (min, max) = range("float")?;
+                let (min, max) = (min.trim().parse::<f64>()?, max.trim().parse::<f64>()?);
+                if min > max {
+                    return Err(eyre!("{{{{{placeholder}}}}} has its range backwards"));
+                }
+                Field::Float(min, max)
+            }
+            "bool" => Field::Bool,
+            "choice" if !args.is_empty() => {
+                let options: Vec<(String, u64)> = args.split('|').map(weighted).collect();
+                let total = options.iter().map(|(_, weight)| weight).sum();
+                if total == 0 {
+                    return Err(eyre!("{{{{{placeholder}}}}} has no option with a weight"));
+                }
+                Field::Choice(options, total)
+            }
+            "word" => Field::Words(1),
+            "words" | "lorem" => {
+                let count = args.trim().parse().map_err(|_| {
+                    eyre!("{{{{{name}:N}}}} needs a word count, not {{{{{placeholder}}}}}")
+                })?;
+                match name {
+                    "words" => Field::Words(count),
+                    _ => Field::Lorem(count),
+                }
+            }
+            "timestamp" if args.is_empty() => Field::Timestamp(None),
+            "timestamp" => {
+                let (start, end) = args.split_once("..").ok_or_else(|| {
+                    eyre!("{{{{timestamp:START..END}}}} needs a range, not {{{{{placeholder}}}}}")
+                })?;
+                let (start, end) = (instant(start)?, instant(end)?);
+                if start > end {
+                    return Err(eyre!("{{{{{placeholder}}}}} has its range backwards"));
+                }
+                Field::Timestamp(Some((start, end)))
+            }
+            "uuid" => Field::Uuid,
+            "ip" => Field::Ip,
+            _ => {
+                return Err(eyre!(
+                    "unknown placeholder {{{{{placeholder}}}}}, expected seq, int, float, bool, choice, word, words, lorem, timestamp, uuid, or ip"
+                ));
+            }
+        })
+    }
+
+    fn render(&self, seq: u64, random: &mut Random) -> Value {
+        match self {
+            Field::Seq => Value::from(seq),
+            Field::Int(min, max) => {
+                let span = max.abs_diff(*min).saturating_add(1);
+                Value::from(min.wrapping_add_unsigned(random.next_u64() % span))
+            }
+            Field::Float(min, max) => {
+                let unit = (random.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+                Number::from_f64(*min + (*max - *min) * unit).map_or(Value::Null, Value::Number)
+            }
+            Field::Bool => Value::Bool(random.next_u64() & 1 == 1),
+            Field::Choice(options, total) => {
+                let mut pick = random.next_u64() % total;
+                let (option, _) = options
+                    .iter()
+                    .find(|(_, weight)| match pick.checked_sub(*weight) {
+                        Some(rest) => {
+                            pick = rest;
+                            false
+                        }
+                        None => true,
+                    })
+                    .expect("the pick is below the total weight");
+                Value::String(option.clone())
+            }
+            Field::Words(count) => Value::String(
+                (0..*count)
+                    .map(|_| WORDS[random.below(WORDS.len())])
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            Field::Lorem(count) => {
+                let mut sentence = (0..*count)
+                    .map(|_| LOREM[random.below(LOREM.len())])
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                if let Some(first) = sentence.get_mut(..1) {
+                    first.make_ascii_uppercase();
+                }
+                sentence.push('.');
+                Value::String(sentence)
+            }
+            Field::Timestamp(None) => Value::String(Timestamp::now().to_string()),
+            Field::Timestamp(Some((start, end))) => {
+                let span = end.abs_diff(*start).saturating_add(1);
+                let millis = start.wrapping_add_unsigned(random.next_u64() % span);
+                Timestamp::from_millisecond(millis)
+                    .map_or(Value::Null, |time| Value::String(time.to_string()))
+            }
+            Field::Uuid => {
+                let high = random.next_u64();
+                let low = random.next_u64();
+                Value::String(format!(
+                    "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+                    high >> 32,
+                    (high >> 16) & 0xffff,
+                    high & 0x0fff,
+                    ((low >> 48) & 0x3fff) | 0x8000,
+                    low & 0xffff_ffff_ffff
+                ))
+            }
+            Field::Ip => {
+                let bits = random.next_u64();
+                Value::String(format!(
+                    "10.{}.{}.{}",
+                    (bits >> 16) & 0xff,
+                    (bits >> 8) & 0xff,
+                    bits & 0xff
+                ))
+            }
+        }
+    }
+}
+
+/// A `choice` option, with its weight after a trailing `=N`, or else a weight of 1.
+fn weighted(option: &str) -> (String, u64) {
+    match option.rsplit_once('=') {
+        Some((option, weight)) => match weight.trim().parse() {
+            Ok(weight) => (option.to_string(), weight),
+            Err(_) => (format!("{option}={weight}"), 1),
+        },
+        None => (option.to_string(), 1),
+    }
+}
+
+/// An end of a `timestamp` range, in milliseconds since the epoch: an RFC 3339
+/// time, a date taken as midnight UTC, or `now`.
+fn instant(text: &str) -> Result<i64> {
+    let text = text.trim();
+    let time = match text {
+        "now" => Timestamp::now(),
+        _ => match text.parse::<Timestamp>() {
+            Ok(time) => time,
+            Err(_) => text
+                .parse::<Date>()
+                .and_then(|date| date.to_zoned(TimeZone::UTC))
+                .map_err(|_| {
+                    eyre!(
+                        "invalid timestamp '{text}', expected a time like 2024-01-01T00:00:00Z, a date, or now"
+                    )
+                })?
+                .timestamp(),
+        },
+    };
+    Ok(time.as_millisecond())
+}
+
+/// SplitMix64, seeded by the document's sequence number.
+struct Random(u64);
+
+impl Random {
+    fn new(seq: u64) -> Self {
+        Self(seq.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DocTemplate;
+    use serde_json::Value;
+
+    fn render(template: &str, seq: u64) -> Value {
+        let doc = DocTemplate::parse(template).unwrap().render(seq).unwrap();
+        serde_json::from_str(doc.get()).unwrap()
+    }
+
+    #[test]
+    fn timestamps_weighted_choices_and_lorem_stay_in_bounds() {
+        let template = r#"{"at":"{{timestamp:2024-01-01..2024-01-02T00:00:00Z}}","level":"{{choice:info=3|error=0|warn}}","text":"{{lorem:4}}"}"#;
+        for seq in 0..50 {
+            let doc = render(template, seq);
+            let at = doc["at"].as_str().unwrap();
+            assert!(
+                at.starts_with("2024-01-01T") || at == "2024-01-02T00:00:00Z",
+                "{at}"
+            );
+            assert!(
+                ["info", "warn"].contains(&doc["level"].as_str().unwrap()),
+                "{doc}"
+            );
+            let text = doc["text"].as_str().unwrap();
+            assert_eq!(text.split(' ').count(), 4);
+            assert!(
+                text.ends_with('.') && text.starts_with(char::is_uppercase),
+                "{text}"
+            );
+        }
+        assert!(DocTemplate::parse(r#"{"a":"{{timestamp:now..2020-01-01}}"}"#).is_err());
+        assert!(DocTemplate::parse(r#"{"a":"{{choice:a=0}}"}"#).is_err());
+    }
+
+    #[test]
+    fn placeholders_take_their_type_unless_inside_text() {
+        let doc = render(
+            r#"{"n":"{{seq}}","level":"{{int:1:3}}","id":"user-{{seq}}","tags":["{{choice:a|b}}",true]}"#,
+            7,
+        );
+        assert_eq!(doc["n"], 7);
+        assert!((1..=3).contains(&doc["level"].as_i64().unwrap()), "{doc}");
+        assert_eq!(doc["id"], "user-7");
+        assert!(["a", "b"].contains(&doc["tags"][0].as_str().unwrap()));
+        assert_eq!(doc["tags"][1], true);
+    }
+
+    #[test]
+    fn documents_are_reproducible_by_sequence_number() {
+        let template = r#"{"trace":"{{uuid}}","ip":"{{ip}}","score":"{{float:0:1}}"}"#;
+        assert_eq!(render(template, 3), render(template, 3));
+        assert_ne!(render(template, 3), render(template, 4));
+        let uuid = render(template, 3)["trace"].as_str().unwrap().to_string();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+    }
+
+    #[test]
+    fn unknown_or_malformed_placeholders_are_rejected() {
+        let err = DocTemplate::parse(r#"{"a":"{{name}}"}"#).unwrap_err();
+        assert!(
+            err.to_string().contains("unknown placeholder {{name}}"),
+            "{err}"
+        );
+        assert!(DocTemplate::parse(r#"{"a":"{{int:5}}"}"#).is_err());
+        assert!(DocTemplate::parse(r#"{"a":"{{int:9:1}}"}"#).is_err());
+        assert!(DocTemplate::parse(r#"{"a":"{{seq"}"#).is_err());
+        assert!(DocTemplate::parse("[1]").is_err());
+        DocTemplate::default().render(0).unwrap();
+    }
+}
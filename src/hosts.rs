@@ -0,0 +1,15 @@
+use crate::{HostsAction, client::KnownHost};
+use eyre::Result;
+
+/// Encrypts hosts.yml so the credentials in it are not kept in plain text, or
+/// decrypts it back for editing.
+pub fn run(action: HostsAction, quiet: bool) -> Result<()> {
+    let path = KnownHost::set_encrypted(matches!(action, HostsAction::Encrypt))?;
+    if !quiet {
+        match action {
+            HostsAction::Encrypt => eprintln!("Encrypted {}", path.display()),
+            HostsAction::Decrypt => eprintln!("Decrypted {}", path.display()),
+        }
+    }
+    Ok(())
+}
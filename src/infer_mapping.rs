@@ -0,0 +1,283 @@
+use crate::{
+    Cli, Command, comma_formatted,
+    input::{Input, InputReader, ReadEvent},
+    input_encoding, remote_options,
+    replay::parse_timestamp,
+};
+use eyre::{Result, eyre};
+use serde_json::{Map, Value, json};
+use std::{
+    collections::{BTreeMap, HashSet},
+    net::IpAddr,
+};
+
+/// Distinct string values remembered per field to tell identifiers and
+/// categories from free text.
+const MAX_DISTINCT: usize = 1000;
+
+/// Strings longer than this are mapped as `text`, and `keyword` subfields
+/// ignore them, as in Elasticsearch's dynamic mapping.
+const IGNORE_ABOVE: usize = 256;
+
+/// What the sampled values of one field looked like.
+#[derive(Debug, Default)]
+struct FieldShape {
+    integers: u64,
+    floats: u64,
+    booleans: u64,
+    strings: u64,
+    objects: u64,
+    /// Strings in a format Elasticsearch's default `date` format reads.
+    dates: u64,
+    ips: u64,
+    /// Strings with whitespace, which are more likely prose than identifiers.
+    spaced: u64,
+    longest: usize,
+    distinct: HashSet<String>,
+    /// Whether the field held an array of several objects, whose fields are
+    /// only queried together correctly when mapped as `nested`.
+    nested: bool,
+    properties: BTreeMap<String, FieldShape>,
+}
+
+/// Prints an Elasticsearch mapping for a sample of the inputs' documents, as
+/// a component template for `--component-template`, or with
+/// `--index-pattern`, as an index template for `--template`.
+#[tracing::instrument(name = "espipe_infer_mapping", skip_all)]
+pub async fn run(args: Cli) -> Result<()> {
+    let Some(Command::InferMapping {
+        inputs,
+        sample,
+        index_pattern,
+    }) = &args.command
+    else {
+        unreachable!("infer_mapping::run is only called for espipe infer-mapping");
+    };
+    let input =
+        Input::try_new_with_remote(inputs.clone(), args.content.clone(), remote_options(&args)?)
+            .await?
+            .with_input_encoding(input_encoding(&args))?
+            .with_archive_glob(args.archive_glob.clone(), args.zip_password.clone())?
+            .with_input_format(args.input_format)?;
+    let name = input.to_string();
+    let mut input = InputReader::spawn(input, None);
+    let mut root = FieldShape::default();
+    let mut docs = 0;
+    while docs < *sample {
+        match input.next().await? {
+            ReadEvent::Document(doc) => {
+                root.add(&serde_json::from_str(doc.get())?);
+                docs += 1;
+            }
+            ReadEvent::Malformed(malformed) => {
+                return Err(eyre!("{name}: {malformed}; espipe lint lists every one"));
+            }
+            ReadEvent::Checkpoint { .. } => input.commit()?,
+            ReadEvent::Flush => {}
+            ReadEvent::End => break,
+        }
+    }
+    let properties = root.properties_mapping("");
+    let fields = count_fields(&properties);
+    let mappings = json!({ "properties": properties });
+    let template = match index_pattern {
+        Some(pattern) => json!({
+            "index_patterns": [pattern],
+            "template": { "mappings": mappings },
+        }),
+        None => json!({ "template": { "mappings": mappings } }),
+    };
+    println!("{}", serde_json::to_string_pretty(&template)?);
+    if !args.quiet {
+        eprintln!(
+            "Inferred {} fields from {} docs of {name}",
+            comma_formatted(fields),
+            comma_formatted(docs as usize)
+        );
+    }
+    Ok(())
+}
+
+impl FieldShape {
+    fn add(&mut self, value: &Value) {
+        match value {
+            Value::Null => {}
+            Value::Array(items) => {
+                self.nested |= items.iter().filter(|item| item.is_object()).count() > 1;
+                for item in items {
+                    self.add(item);
+                }
+            }
+            Value::Bool(_) => self.booleans += 1,
+            Value::Number(number) if number.is_i64() || number.is_u64() => self.integers += 1,
+            Value::Number(_) => self.floats += 1,
+            Value::String(text) => self.add_string(text),
+            Value::Object(fields) => {
+                self.objects += 1;
+                for (key, value) in fields {
+                    // Dotted names are objects to Elasticsearch, as if nested.
+                    let mut shape = &mut *self;
+                    let mut parts = key.split('.');
+                    let leaf = parts.next_back().expect("split yields at least one part");
+                    for part in parts {
+                        shape = shape.properties.entry(part.to_string()).or_default();
+                        shape.objects += 1;
+                    }
+                    shape
+                        .properties
+                        .entry(leaf.to_string())
+                        .or_default()
+                        .add(value);
+                }
+            }
+        }
+    }
+
+    fn add_string(&mut self, text: &str) {
+        self.strings += 1;
+        self.longest = self.longest.max(text.chars().count());
+        if text.contains(char::is_whitespace) {
+            self.spaced += 1;
+        }
+        if is_default_date(text) {
+            self.dates += 1;
+        }
+        if text.parse::<IpAddr>().is_ok() {
+            self.ips += 1;
+        }
+        if self.distinct.len() < MAX_DISTINCT {
+            self.distinct.insert(text.to_string());
+        }
+    }
+
+    /// The mapping of this object's fields, leaving out those only ever null.
+    fn properties_mapping(&self, path: &str) -> Map<String, Value> {
+        let mut properties = Map::new();
+        for (name, shape) in &self.properties {
+            let path = match path {
+                "" => name.clone(),
+                path => format!("{path}.{name}"),
+            };
+            match shape.mapping(&path) {
+                Some(mapping) => {
+                    properties.insert(name.clone(), mapping);
+                }
+                None => log::warn!("{path} is only ever null, so it is left out"),
+            }
+        }
+        properties
+    }
+
+    fn mapping(&self, path: &str) -> Option<Value> {
+        let scalars = self.integers + self.floats + self.booleans + self.strings;
+        if self.objects > 0 {
+            if scalars > 0 {
+                log::warn!("{path} holds both objects and values, so it is mapped as an object");
+            }
+            let mut mapping = json!({ "properties": self.properties_mapping(path) });
+            if self.nested {
+                mapping["type"] = json!("nested");
+            }
+            return Some(mapping);
+        }
+        let numbers = self.integers + self.floats;
+        Some(match (self.strings, numbers, self.booleans) {
+            (0, 0, 0) => return None,
+            (strings, 0, 0) if self.dates == strings => json!({ "type": "date" }),
+            (strings, 0, 0) if self.ips == strings => json!({ "type": "ip" }),
+            (strings, 0, 0) if self.is_text(strings) => json!({
+                "type": "text",
+                "fields": { "keyword": { "type": "keyword", "ignore_above": IGNORE_ABOVE } },
+            }),
+            (0, _, 0) if self.floats > 0 => json!({ "type": "double" }),
+            (0, _, 0) => json!({ "type": "long" }),
+            (_, 0, 0) => json!({ "type": "keyword" }),
+            (0, 0, _) => json!({ "type": "boolean" }),
+            _ => {
+                log::warn!("{path} holds values of several types, so it is mapped as a keyword");
+                json!({ "type": "keyword", "ignore_above": IGNORE_ABOVE })
+            }
+        })
+    }
+
+    /// Whether strings look like prose to search by word: too long for a
+    /// keyword, or mostly with whitespace and rarely repeated.
+    fn is_text(&self, strings: u64) -> bool {
+        let varied =
+            self.distinct.len() >= MAX_DISTINCT || self.distinct.len() as u64 * 2 > strings;
+        self.longest > IGNORE_ABOVE || (self.spaced * 2 > strings && varied)
+    }
+}
+
+/// Whether `text` is a date or date and time in Elasticsearch's default
+/// `strict_date_optional_time` format, like `2026-10-16` or `2026-10-16T10:00:00Z`.
+fn is_default_date(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    let shaped = match bytes.len() {
+        10 => bytes[4] == b'-' && bytes[7] == b'-',
+        11.. => bytes[4] == b'-' && bytes[7] == b'-' && bytes[10] == b'T',
+        _ => false,
+    };
+    shaped
+        && (bytes.len() == 10 || parse_timestamp(&Value::String(text.to_string())).is_some())
+        && bytes[..10]
+            .iter()
+            .enumerate()
+            .all(|(i, byte)| [4, 7].contains(&i) || byte.is_ascii_digit())
+}
+
+/// The fields in a mapping's `properties`, objects included.
+fn count_fields(properties: &Map<String, Value>) -> usize {
+    properties
+        .values()
+        .map(|mapping| match mapping["properties"].as_object() {
+            Some(properties) => 1 + count_fields(properties),
+            None => 1,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FieldShape;
+    use serde_json::json;
+
+    #[test]
+    fn mappings_follow_the_sampled_values() {
+        let mut root = FieldShape::default();
+        for n in 0..10 {
+            let level = ["info", "warn"][n % 2];
+            root.add(&json!({
+                "@timestamp": format!("2026-10-16T10:00:0{n}Z"),
+                "status": 200 + n,
+                "latency": n as f64 / 2.0,
+                "client.ip": "10.0.0.1",
+                "level": level,
+                "message": format!("request {n} took longer than expected"),
+                "ok": true,
+                "spans": [{"name": "a", "ms": 1}, {"name": "b", "ms": 2}],
+                "mixed": if n == 0 { json!("n/a") } else { json!(n) },
+                "missing": null,
+            }));
+        }
+
+        let properties = root.properties_mapping("");
+        assert_eq!(properties["@timestamp"], json!({"type": "date"}));
+        assert_eq!(properties["status"], json!({"type": "long"}));
+        assert_eq!(properties["latency"], json!({"type": "double"}));
+        assert_eq!(
+            properties["client"],
+            json!({"properties": {"ip": {"type": "ip"}}})
+        );
+        assert_eq!(properties["level"]["type"], "keyword");
+        assert_eq!(properties["message"]["type"], "text");
+        assert_eq!(properties["ok"], json!({"type": "boolean"}));
+        assert_eq!(properties["spans"]["type"], "nested");
+        assert_eq!(
+            properties["spans"]["properties"]["ms"],
+            json!({"type": "long"})
+        );
+        assert_eq!(properties["mixed"]["type"], "keyword");
+        assert!(!properties.contains_key("missing"));
+    }
+}
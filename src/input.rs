@@ -1,5 +1,36 @@
+mod access_log;
+mod aggregation;
+mod archive;
+mod bulk;
+mod concatenated;
+mod download;
+mod encoding;
+mod file_state;
+mod file_workers;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod kql;
+mod listen;
+mod long_lines;
+mod malformed;
+mod merge;
+mod parse_workers;
+mod reader;
+mod remote_options;
+mod search;
+mod sniff;
+#[cfg(feature = "sql")]
+mod sql;
+#[cfg(unix)]
+mod stream;
+mod synthetic;
+mod syslog;
+mod watch;
+
+use crate::{client::KnownHost, document::Document};
+use clap::ValueEnum;
 use eyre::{Report, Result, eyre};
-use flate2::read::GzDecoder;
+use flate2::read::{GzDecoder, MultiGzDecoder};
 use fluent_uri::UriRef;
 use glob::glob;
 use reqwest::{
@@ -11,17 +42,28 @@ use std::{
     collections::BTreeSet,
     ffi::OsStr,
     fs::{self, File},
-    io::{BufRead, BufReader, Read, Seek, SeekFrom, Stdin, Write, stdin},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write, stdin},
     path::{Path, PathBuf},
     time::Duration,
 };
 use tempfile::{Builder, NamedTempFile};
 
+pub use aggregation::Aggregations;
+pub use encoding::{InputEncoding, InvalidEncoding};
+pub use file_state::FileState;
+pub use long_lines::{LongLineAction, LongLines};
+use malformed::LinePosition;
+pub use malformed::{MalformedRecord, ParseErrorBudget};
+pub use reader::{InputReader, ReadEvent};
+pub use remote_options::RemoteOptions;
+pub use watch::AfterIngest;
+
 pub enum Input {
     FileJson {
         source: String,
         reader: Box<BufReader<Box<dyn Read + Send>>>,
         first_record: bool,
+        position: LinePosition,
         _temp_file: Option<NamedTempFile>,
     },
     FileCsv {
@@ -39,22 +81,99 @@ pub enum Input {
         _temp_file: Option<NamedTempFile>,
     },
     Stdin {
-        reader: Box<BufReader<Stdin>>,
+        reader: Box<BufReader<Box<dyn Read + Send>>>,
+        position: LinePosition,
     },
     FileDocuments {
         source: String,
         paths: Vec<PathBuf>,
         path_index: usize,
-        documents: Vec<Box<RawValue>>,
-        document_index: usize,
+        documents: std::vec::IntoIter<Box<RawValue>>,
         content_field: String,
         include_file_metadata: bool,
+        workers: Option<file_workers::FileWorkers>,
+        state: Option<Box<FileState>>,
+    },
+    #[cfg(feature = "kafka")]
+    Kafka {
+        consumer: Box<kafka::KafkaInput>,
+    },
+    #[cfg(feature = "sql")]
+    Sql {
+        rows: Box<sql::SqlInput>,
+    },
+    #[cfg(unix)]
+    Stream {
+        stream: Box<stream::StreamInput>,
+    },
+    Listen {
+        listener: Box<listen::ListenInput>,
+    },
+    Concatenated {
+        values: Box<concatenated::ConcatenatedInput>,
+    },
+    Search {
+        hits: Box<search::SearchInput>,
+    },
+    Synthetic {
+        generator: Box<synthetic::SyntheticInput>,
+    },
+    Watch {
+        files: Box<watch::WatchInput>,
+    },
+    Merged {
+        files: Box<merge::MergeInput>,
+    },
+    Archive {
+        entries: Box<archive::ArchiveInput>,
+    },
+    ParsedLines {
+        source: String,
+        workers: Box<parse_workers::ParseWorkers>,
+        _temp_file: Option<NamedTempFile>,
+    },
+    UnvalidatedLines {
+        source: String,
+        reader: Box<dyn BufRead + Send>,
+        _temp_file: Option<NamedTempFile>,
+    },
+    /// Lines read in a non-NDJSON `--input-format`.
+    FormattedLines {
+        source: String,
+        reader: Box<dyn BufRead + Send>,
+        format: InputFormat,
+        position: LinePosition,
+        _temp_file: Option<NamedTempFile>,
     },
 }
 
+/// How file and stdin lines are read.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum InputFormat {
+    /// NDJSON, or bulk pairs when a file starts with a bulk action line
+    #[default]
+    Auto,
+    /// One JSON document per line
+    Ndjson,
+    /// Alternating bulk action and source lines, as sent to the `_bulk` API
+    Bulk,
+    /// RFC 5424 or RFC 3164 syslog lines, parsed into ECS fields
+    Syslog,
+    /// Apache and Nginx common or combined access log lines, parsed into ECS fields
+    Combined,
+    /// CSV with a header row
+    Csv,
+    /// A JSON array of documents
+    Json,
+    /// JSON objects back to back, such as pretty-printed ones
+    Concatenated,
+}
+
 type CsvRecord = std::collections::HashMap<String, String>;
 const REMOTE_NDJSON_ERROR: &str = "JSON payload does not look like required NDJSON input format.";
 const JSON_LINE_OPENING_ERROR: &str = "Each record must be a JSON object starting with '{'";
+const INPUT_IDLE: &str = "Input idle";
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
 const REMOTE_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 const REMOTE_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
 
@@ -68,31 +187,585 @@ enum InputKind {
 }
 
 impl Input {
-    pub async fn try_new(uris: Vec<UriRef<String>>, content_field: String) -> Result<Self> {
+    /// Opens the inputs, fetching an `https://` input as `remote` says.
+    pub async fn try_new_with_remote(
+        uris: Vec<UriRef<String>>,
+        content_field: String,
+        remote: RemoteOptions,
+    ) -> Result<Self> {
         validate_content_field(&content_field)?;
         if uris.is_empty() {
             return Err(eyre!("At least one input is required"));
         }
+        let is_remote = match uris.as_slice() {
+            [uri] => {
+                uri.scheme()
+                    .is_some_and(|scheme| scheme.as_str() == "https")
+                    && !search::is_search(uri)
+            }
+            _ => false,
+        };
+        if !remote.is_default() && !is_remote {
+            return Err(eyre!(
+                "--input-header, --input-basic, --input-bearer, and --download-parallelism apply to a single https:// input"
+            ));
+        }
         if uris.len() == 1 {
             let uri = uris.into_iter().next().unwrap();
             return match uri.scheme().map(|scheme| scheme.as_str()) {
-                Some("https") => tokio::task::spawn_blocking(move || fetch_remote_input(uri))
+                Some("https") if !search::is_search(&uri) => {
+                    tokio::task::spawn_blocking(move || fetch_remote_input(uri, &remote))
+                        .await
+                        .map_err(|err| eyre!("Remote input fetch task failed: {err}"))?
+                }
+                Some("az" | "gs") => tokio::task::spawn_blocking(move || fetch_cloud_input(&uri))
                     .await
-                    .map_err(|err| eyre!("Remote input fetch task failed: {err}"))?,
+                    .map_err(|err| eyre!("Cloud storage input fetch task failed: {err}"))?,
                 _ => open_input_values(vec![uri], &content_field),
             };
         }
         open_input_values(uris, &content_field)
     }
 
-    pub fn read_line(&mut self, line_buffer: &mut String) -> Result<Box<RawValue>> {
+    /// Reads multi-file imports with `workers` threads; other inputs are unaffected.
+    pub fn with_input_workers(mut self, input_workers: usize) -> Self {
+        if let Input::FileDocuments { paths, workers, .. } = &mut self
+            && input_workers > 1
+            && paths.len() > 1
+        {
+            *workers = Some(file_workers::FileWorkers::new(
+                input_workers.min(paths.len()),
+            ));
+        }
+        self
+    }
+
+    /// Reads the files of a multi-file or glob input side by side in `format`,
+    /// merging their documents into one stream ordered by the timestamp `field`.
+    pub fn with_merge_by(self, field: Option<String>, format: InputFormat) -> Result<Self> {
+        let Some(field) = field else {
+            return Ok(self);
+        };
+        match self {
+            Input::FileDocuments {
+                paths,
+                content_field,
+                ..
+            } => Ok(Input::Merged {
+                files: Box::new(merge::MergeInput::try_new(
+                    paths,
+                    field,
+                    &content_field,
+                    format,
+                )?),
+            }),
+            input => Err(eyre!(
+                "--merge-by needs a multi-file or glob input of local files, not {input}"
+            )),
+        }
+    }
+
+    /// Records the progress of each file of a multi-file or glob input in
+    /// `state`, skipping the files it shows were already read to the end.
+    pub fn with_file_state(mut self, file_state: Option<FileState>) -> Result<Self> {
+        let Some(file_state) = file_state else {
+            return Ok(self);
+        };
+        match &mut self {
+            Input::FileDocuments { paths, state, .. } if !paths.is_empty() => {
+                *paths = file_state.unfinished(std::mem::take(paths));
+                *state = Some(Box::new(file_state));
+                Ok(self)
+            }
+            input => Err(eyre!(
+                "--file-state needs a multi-file or glob input of local files, not {input}"
+            )),
+        }
+    }
+
+    /// Parses NDJSON and stdin lines on `workers` threads; other inputs are unaffected.
+    pub fn with_parse_workers(self, workers: usize) -> Self {
+        if workers <= 1 {
+            return self;
+        }
         match self {
             Input::FileJson {
+                source,
                 reader,
                 first_record,
+                _temp_file,
+                ..
+            } => Input::ParsedLines {
+                source,
+                workers: Box::new(parse_workers::ParseWorkers::new(
+                    reader,
+                    first_record,
+                    workers,
+                )),
+                _temp_file,
+            },
+            Input::Stdin { reader, .. } => Input::ParsedLines {
+                source: "stdin".to_string(),
+                workers: Box::new(parse_workers::ParseWorkers::new(reader, false, workers)),
+                _temp_file: None,
+            },
+            input => input,
+        }
+    }
+
+    /// Emits the buckets of `aggregations` instead of the hits of a search input.
+    pub fn with_aggregations(mut self, aggregations: Option<Aggregations>) -> Result<Self> {
+        match (&mut self, aggregations) {
+            (_, None) => {}
+            (Input::Search { hits }, Some(aggregations)) => hits.aggregate(aggregations)?,
+            (input, Some(_)) => {
+                return Err(eyre!(
+                    "--agg needs an Elasticsearch index input, like known-host:index, not {input}"
+                ));
+            }
+        }
+        Ok(self)
+    }
+
+    /// Reads only the entries of a zip or tar archive input whose names match
+    /// the glob `pattern`, decrypting zip entries with `password`; other inputs
+    /// reject both.
+    pub fn with_archive_glob(
+        self,
+        pattern: Option<String>,
+        password: Option<String>,
+    ) -> Result<Self> {
+        match self {
+            Input::Archive { entries } => Ok(Input::Archive {
+                entries: Box::new(entries.with_entries(pattern, password)?),
+            }),
+            input if pattern.is_none() && password.is_none() => Ok(input),
+            input => Err(eyre!(
+                "--archive-glob and --zip-password apply to archive inputs, not {input}"
+            )),
+        }
+    }
+
+    /// Serves an HTTP endpoint that accepts NDJSON and `_bulk` POST bodies.
+    pub fn listen(address: &UriRef<String>, token: Option<String>) -> Result<Self> {
+        Ok(Input::Listen {
+            listener: Box::new(listen::ListenInput::bind(address, token)?),
+        })
+    }
+
+    /// Reads the files dropped into the directory at `dir` as they appear, in
+    /// `format`, doing `after_ingest` with each once its documents are delivered.
+    pub fn watch(
+        dir: &UriRef<String>,
+        after_ingest: AfterIngest,
+        content_field: String,
+        format: InputFormat,
+    ) -> Result<Self> {
+        validate_content_field(&content_field)?;
+        if !matches!(
+            dir.scheme().map(|scheme| scheme.as_str()),
+            None | Some("file")
+        ) {
+            return Err(eyre!("--watch needs a local directory, not {dir}"));
+        }
+        let dir = Path::new(dir.path().as_str());
+        Ok(Input::Watch {
+            files: Box::new(watch::WatchInput::try_new(
+                dir,
+                after_ingest,
+                content_field,
+                format,
+            )?),
+        })
+    }
+
+    /// Passes NDJSON and stdin lines through without parsing them; other inputs are unaffected.
+    pub fn without_validation(self) -> Self {
+        match self {
+            Input::FileJson {
+                source,
+                reader,
+                _temp_file,
+                ..
+            } => Input::UnvalidatedLines {
+                source,
+                reader,
+                _temp_file,
+            },
+            Input::Stdin { reader, .. } => Input::UnvalidatedLines {
+                source: "stdin".to_string(),
+                reader,
+                _temp_file: None,
+            },
+            input => input,
+        }
+    }
+
+    /// Transcodes CSV, NDJSON, JSON, and TOON files, including `https://`
+    /// downloads, and stdin to UTF-8 as they are read, before their format is
+    /// sniffed; other inputs are rejected.
+    pub fn with_input_encoding(self, encoding: Option<InputEncoding>) -> Result<Self> {
+        let Some(encoding) = encoding else {
+            return Ok(self);
+        };
+        let transcode = |reader: Box<dyn Read + Send>, source: &str| {
+            Box::new(BufReader::new(encoding.reader(reader, source)))
+        };
+        Ok(match self {
+            Input::FileJson {
+                source,
+                reader,
+                first_record,
+                position,
+                _temp_file,
+            } => Input::FileJson {
+                reader: transcode(reader, &source),
+                source,
+                first_record,
+                position,
+                _temp_file,
+            },
+            Input::FileCsv {
+                source,
+                reader,
+                _temp_file,
+            } => Input::FileCsv {
+                // Nothing has been read, so the CSV reader holds no buffered bytes.
+                reader: Box::new(
+                    csv::ReaderBuilder::new()
+                        .has_headers(true)
+                        .from_reader(encoding.reader(reader.into_inner(), &source)),
+                ),
+                source,
+                _temp_file,
+            },
+            Input::FileToon {
+                source,
+                reader,
+                pending,
+                document_index,
+                buffered_rows,
+                eof,
+                _temp_file,
+            } => Input::FileToon {
+                reader: transcode(reader, &source),
+                source,
+                pending,
+                document_index,
+                buffered_rows,
+                eof,
+                _temp_file,
+            },
+            Input::Stdin { reader, position } => Input::Stdin {
+                reader: transcode(reader, "stdin"),
+                position,
+            },
+            input => {
+                return Err(eyre!(
+                    "--input-encoding and --on-invalid-encoding do not apply to {input}"
+                ));
+            }
+        })
+    }
+
+    /// Holds NDJSON, bulk, syslog, and access log lines from files and stdin to
+    /// the `--max-line-bytes` limit; other inputs are rejected.
+    pub fn with_max_line_bytes(self, limits: Option<LongLines>) -> Result<Self> {
+        let Some(limits) = limits else {
+            return Ok(self);
+        };
+        let limit = |reader: Box<dyn BufRead + Send>, source: &str, text| {
+            let reader =
+                long_lines::LineLimit::new(reader, limits.clone(), source.to_string(), text);
+            BufReader::new(Box::new(reader) as Box<dyn Read + Send>)
+        };
+        Ok(match self {
+            Input::FileJson {
+                source,
+                reader,
+                first_record,
+                position,
+                _temp_file,
+            } => Input::FileJson {
+                reader: Box::new(limit(reader, &source, false)),
+                source,
+                first_record,
+                position,
+                _temp_file,
+            },
+            Input::Stdin { reader, position } => Input::Stdin {
+                reader: Box::new(limit(reader, "stdin", false)),
+                position,
+            },
+            Input::FormattedLines {
+                source,
+                reader,
+                format,
+                position,
+                _temp_file,
+            } => Input::FormattedLines {
+                reader: Box::new(limit(
+                    reader,
+                    &source,
+                    matches!(format, InputFormat::Syslog | InputFormat::Combined),
+                )),
+                source,
+                format,
+                position,
+                _temp_file,
+            },
+            input => return Err(eyre!("--max-line-bytes does not apply to {input}")),
+        })
+    }
+
+    /// Reads NDJSON files and stdin as `format`, sniffing the first line of files
+    /// for a bulk action under `Auto`. `Syslog` and `Combined` also read a single
+    /// text file line by line, and `Csv`, `Json`, and `Concatenated` a single
+    /// text file of any extension. Local CSV and TOON files take any format;
+    /// other inputs only accept `Auto`.
+    ///
+    /// Under `Auto`, stdin and files without an extension are sniffed for gzip,
+    /// CSV, JSON arrays, and concatenated objects, and NDJSON files for the
+    /// last two. The entries of archives are each read in `format`.
+    pub fn with_input_format(self, format: InputFormat) -> Result<Self> {
+        let (mut input, format) = self.with_content_format(format)?;
+        // After any transcoding, which reads the byte order mark itself.
+        if let Input::FileJson { reader, .. }
+        | Input::FileToon { reader, .. }
+        | Input::Stdin { reader, .. } = &mut input
+        {
+            skip_bom(reader)?;
+        }
+        let format = match (&mut input, format) {
+            // Archive entries are each opened in the format as they are read.
+            (Input::Archive { entries }, format) => {
+                entries.set_format(format);
+                None
+            }
+            (Input::FileJson { source, reader, .. }, InputFormat::Auto) => {
+                let bulk = starts_with_bulk_action(reader)?;
+                if bulk {
+                    log::info!("{source} starts with a bulk action, reading it as bulk pairs");
+                }
+                bulk.then_some(InputFormat::Bulk)
+            }
+            // Merged files were each opened in the format already.
+            (_, InputFormat::Auto)
+            | (Input::Merged { .. }, _)
+            | (Input::FileJson { .. } | Input::Stdin { .. }, InputFormat::Ndjson)
+            | (Input::FileCsv { .. }, InputFormat::Csv) => None,
+            (Input::FileJson { .. } | Input::Stdin { .. }, format) => Some(format),
+            (Input::FileDocuments { paths, .. }, InputFormat::Syslog | InputFormat::Combined)
+                if paths.len() == 1 =>
+            {
+                Some(format)
+            }
+            (input, format) => {
+                return Err(eyre!(
+                    "--input-format {} does not apply to {input}",
+                    format
+                        .to_possible_value()
+                        .expect("no skipped values")
+                        .get_name()
+                ));
+            }
+        };
+        let Some(format) = format else {
+            return Ok(input);
+        };
+        Ok(match input {
+            Input::FileJson {
+                source,
+                reader,
+                _temp_file,
+                ..
+            } => Input::FormattedLines {
+                source,
+                reader,
+                format,
+                position: LinePosition::default(),
+                _temp_file,
+            },
+            Input::Stdin { reader, .. } => Input::FormattedLines {
+                source: "stdin".to_string(),
+                reader,
+                format,
+                position: LinePosition::default(),
+                _temp_file: None,
+            },
+            Input::FileDocuments { paths, .. } => {
+                let path = paths.into_iter().next().expect("checked for one path");
+                let file = File::open(&path)?;
+                let mut reader = BufReader::new(local_file_reader(file, &path));
+                skip_bom(&mut reader)?;
+                Input::FormattedLines {
+                    source: path.display().to_string(),
+                    reader: Box::new(reader),
+                    format,
+                    position: LinePosition::default(),
+                    _temp_file: None,
+                }
+            }
+            input => input,
+        })
+    }
+
+    /// Reopens stdin, NDJSON files, and single text files as CSV, JSON array, or
+    /// concatenated JSON inputs by `format`, or under `Auto` by their first bytes, returning the
+    /// format still to apply.
+    fn with_content_format(self, format: InputFormat) -> Result<(Self, InputFormat)> {
+        let (source, reader, temp_file): (_, Box<dyn Read + Send>, _) = match (self, format) {
+            (
+                Input::Stdin {
+                    mut reader,
+                    position,
+                },
+                InputFormat::Auto,
+            ) => {
+                let buffered = reader.fill_buf()?;
+                let sniffed = sniff::is_gzip(buffered)
+                    || matches!(
+                        sniff::sniff(buffered),
+                        Some(
+                            sniff::Sniffed::Csv
+                                | sniff::Sniffed::JsonArray
+                                | sniff::Sniffed::Concatenated
+                        )
+                    );
+                if !sniffed {
+                    return Ok((Input::Stdin { reader, position }, format));
+                }
+                ("stdin".to_string(), reader, None)
+            }
+            (
+                Input::FileJson {
+                    source,
+                    mut reader,
+                    first_record,
+                    position,
+                    _temp_file,
+                },
+                InputFormat::Auto,
+            ) => {
+                if !matches!(
+                    sniff::sniff(reader.fill_buf()?),
+                    Some(sniff::Sniffed::JsonArray | sniff::Sniffed::Concatenated)
+                ) {
+                    let input = Input::FileJson {
+                        source,
+                        reader,
+                        first_record,
+                        position,
+                        _temp_file,
+                    };
+                    return Ok((input, format));
+                }
+                (source, reader, _temp_file)
+            }
+            (Input::FileDocuments { paths, .. }, InputFormat::Auto)
+                if paths.len() == 1 && extension(&paths[0]).is_none() && paths[0].is_file() =>
+            {
+                let path = &paths[0];
+                let mut file = BufReader::new(File::open(path)?);
+                let buffered = file.fill_buf()?;
+                if !sniff::is_gzip(buffered) && sniff::sniff(buffered).is_none() {
+                    return Ok((
+                        open_file_documents(vec![path.display().to_string()], "body")?,
+                        format,
+                    ));
+                }
+                log::info!(
+                    "{} has no extension, reading it by its content",
+                    path.display()
+                );
+                (path.display().to_string(), Box::new(file), None)
+            }
+            (
+                Input::Stdin { reader, .. },
+                InputFormat::Csv | InputFormat::Json | InputFormat::Concatenated,
+            ) => ("stdin".to_string(), reader, None),
+            (
+                Input::FileJson {
+                    source,
+                    reader,
+                    _temp_file,
+                    ..
+                },
+                InputFormat::Csv | InputFormat::Json | InputFormat::Concatenated,
+            ) => (source, reader, _temp_file),
+            (
+                Input::FileDocuments { paths, .. },
+                InputFormat::Csv | InputFormat::Json | InputFormat::Concatenated,
+            ) if paths.len() == 1 => {
+                let path = &paths[0];
+                let file = File::open(path)?;
+                (
+                    path.display().to_string(),
+                    local_file_reader(file, path),
+                    None,
+                )
+            }
+            // Local CSV and TOON files are read again as text in the explicit format.
+            (
+                Input::FileCsv {
+                    source,
+                    reader,
+                    _temp_file: None,
+                },
+                format,
+            ) if !matches!(format, InputFormat::Auto | InputFormat::Csv) => {
+                // Nothing has been read, so the CSV reader holds no buffered bytes.
+                return reread_local_file(source, reader.into_inner(), format);
+            }
+            (
+                Input::FileToon {
+                    source,
+                    reader,
+                    _temp_file: None,
+                    ..
+                },
+                format,
+            ) if format != InputFormat::Auto => return reread_local_file(source, reader, format),
+            (input, format) => return Ok((input, format)),
+        };
+        let input = open_content(source, reader, format, temp_file)?;
+        Ok((input, InputFormat::Auto))
+    }
+
+    pub fn read_line(&mut self, line_buffer: &mut String) -> Result<Document> {
+        match self {
+            Input::UnvalidatedLines { reader, .. } => read_unvalidated_line(reader, line_buffer),
+            Input::FormattedLines {
+                reader,
+                format: InputFormat::Syslog,
+                position,
+                ..
+            } => read_parsed_line(reader, line_buffer, position, syslog::parse_line),
+            Input::FormattedLines {
+                reader,
+                format: InputFormat::Combined,
+                position,
+                ..
+            } => read_parsed_line(reader, line_buffer, position, access_log::parse_line),
+            Input::FormattedLines {
+                reader, position, ..
+            } => bulk::read_pair(reader, line_buffer, position),
+            Input::Watch { files } => files.read_line(line_buffer),
+            Input::Merged { files } => files.read_line(line_buffer),
+            Input::Archive { entries } => entries.read_line(line_buffer),
+            input => input.read_value(line_buffer).map(Document::from),
+        }
+    }
+
+    fn read_value(&mut self, line_buffer: &mut String) -> Result<Box<RawValue>> {
+        match self {
+            Input::FileJson {
+                reader,
+                first_record,
+                position,
                 ..
             } => {
-                let raw = read_json_line(reader, line_buffer, *first_record)?;
+                let raw = read_json_line(reader, line_buffer, *first_record, position)?;
                 *first_record = false;
                 Ok(raw)
             }
@@ -106,18 +779,78 @@ impl Input {
                 eof,
                 ..
             } => read_toon_document(source, reader, pending, document_index, buffered_rows, eof),
-            Input::Stdin { reader, .. } => read_json_line(reader, line_buffer, false),
+            Input::Stdin { reader, position } => {
+                read_json_line(reader, line_buffer, false, position)
+            }
             Input::FileDocuments { .. } => read_file_document_line(self),
+            #[cfg(feature = "kafka")]
+            Input::Kafka { consumer } => consumer.read_line(),
+            #[cfg(feature = "sql")]
+            Input::Sql { rows } => rows.read_line(),
+            #[cfg(unix)]
+            Input::Stream { stream } => stream.read_line(),
+            Input::Listen { listener } => listener.read_line(),
+            Input::Concatenated { values } => values.read_line(),
+            Input::Search { hits } => hits.read_line(),
+            Input::Synthetic { generator } => generator.read_line(),
+            Input::ParsedLines { workers, .. } => workers.read_line(),
+            Input::UnvalidatedLines { .. } => Err(eyre!("Unvalidated lines have no JSON value")),
+            Input::FormattedLines { .. } => Err(eyre!("Formatted lines are read as documents")),
+            Input::Watch { .. } => Err(eyre!("Watched files are read as documents")),
+            Input::Merged { .. } => Err(eyre!("Merged files are read as documents")),
+            Input::Archive { .. } => Err(eyre!("Archive entries are read as documents")),
         }
     }
 
-    pub fn read_next(&mut self, line_buffer: &mut String) -> Result<Option<Box<RawValue>>> {
+    pub fn read_next(&mut self, line_buffer: &mut String) -> Result<Option<Document>> {
         match self.read_line(line_buffer) {
             Ok(value) => Ok(Some(value)),
             Err(err) if is_end_of_input(&err) => Ok(None),
             Err(err) => Err(err),
         }
     }
+
+    /// Whether a streaming input wants its read position committed soon.
+    pub fn commit_due(&self) -> bool {
+        match self {
+            #[cfg(feature = "kafka")]
+            Input::Kafka { consumer } => consumer.commit_due(),
+            Input::Watch { files } => files.commit_due(),
+            Input::FileDocuments {
+                state: Some(state), ..
+            } => state.commit_due(),
+            _ => false,
+        }
+    }
+
+    /// Commits the read position of a streaming input.
+    ///
+    /// Only call this once the output has acknowledged every document read so far.
+    pub fn commit(&mut self) -> Result<()> {
+        match self {
+            #[cfg(feature = "kafka")]
+            Input::Kafka { consumer } => consumer.commit(),
+            Input::Watch { files } => files.commit(),
+            Input::FileDocuments {
+                state: Some(state), ..
+            } => state.commit(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Lets a streaming input read on without committing, after the output
+    /// failed some of the documents read since the last commit.
+    pub fn skip_commit(&mut self) {
+        match self {
+            #[cfg(feature = "kafka")]
+            Input::Kafka { consumer } => consumer.skip_commit(),
+            Input::Watch { files } => files.skip_commit(),
+            Input::FileDocuments {
+                state: Some(state), ..
+            } => state.skip_commit(),
+            _ => {}
+        }
+    }
 }
 
 impl TryFrom<UriRef<String>> for Input {
@@ -125,7 +858,10 @@ impl TryFrom<UriRef<String>> for Input {
 
     fn try_from(uri: UriRef<String>) -> Result<Self, Self::Error> {
         match uri.scheme().map(|scheme| scheme.as_str()) {
-            Some("https") => fetch_remote_input(uri),
+            Some("https") if !search::is_search(&uri) => {
+                fetch_remote_input(uri, &RemoteOptions::default())
+            }
+            Some("az" | "gs") => fetch_cloud_input(&uri),
             _ => open_input_values(vec![uri], "body"),
         }
     }
@@ -139,6 +875,22 @@ impl std::fmt::Display for Input {
             Input::FileToon { source, .. } => write!(f, "{source}"),
             Input::Stdin { .. } => write!(f, "stdin"),
             Input::FileDocuments { source, .. } => write!(f, "{source}"),
+            #[cfg(feature = "kafka")]
+            Input::Kafka { consumer } => write!(f, "{consumer}"),
+            #[cfg(feature = "sql")]
+            Input::Sql { rows } => write!(f, "{rows}"),
+            #[cfg(unix)]
+            Input::Stream { stream } => write!(f, "{stream}"),
+            Input::Listen { listener } => write!(f, "{listener}"),
+            Input::Concatenated { values } => write!(f, "{values}"),
+            Input::Search { hits } => write!(f, "{hits}"),
+            Input::Synthetic { generator } => write!(f, "{generator}"),
+            Input::Watch { files } => write!(f, "{files}"),
+            Input::Merged { files } => write!(f, "{files}"),
+            Input::Archive { entries } => write!(f, "{entries}"),
+            Input::ParsedLines { source, .. } => write!(f, "{source}"),
+            Input::UnvalidatedLines { source, .. } => write!(f, "{source}"),
+            Input::FormattedLines { source, .. } => write!(f, "{source}"),
         }
     }
 }
@@ -156,12 +908,56 @@ fn validate_content_field(content_field: &str) -> Result<()> {
 fn open_input_values(uris: Vec<UriRef<String>>, content_field: &str) -> Result<Input> {
     for uri in &uris {
         match uri.scheme().map(|scheme| scheme.as_str()) {
-            Some("https") if uris.len() == 1 => return fetch_remote_input(uri.clone()),
+            Some("file") | None if archive::is_archive(uri.path().as_str()) && uris.len() > 1 => {
+                return Err(eyre!("Archives cannot be combined with other inputs"));
+            }
+            Some("file") | None => {}
+            Some(_) if search::is_search(uri) && uris.len() == 1 => return open_search_input(uri),
+            Some(_) if search::is_search(uri) => {
+                return Err(eyre!("Search inputs cannot be combined with other inputs"));
+            }
+            Some("https") if uris.len() == 1 => {
+                return fetch_remote_input(uri.clone(), &RemoteOptions::default());
+            }
             Some("https") => {
                 return Err(eyre!("Remote inputs cannot be combined with file imports"));
             }
+            Some("az" | "gs") if uris.len() == 1 => return fetch_cloud_input(uri),
+            Some("az" | "gs") => {
+                return Err(eyre!(
+                    "Cloud storage inputs cannot be combined with other inputs"
+                ));
+            }
+            Some("kafka") if uris.len() == 1 => return open_kafka_input(uri),
+            Some("kafka") => {
+                return Err(eyre!("Kafka inputs cannot be combined with other inputs"));
+            }
+            Some("postgres" | "postgresql" | "mysql" | "mariadb") if uris.len() == 1 => {
+                return open_sql_input(uri);
+            }
+            Some("postgres" | "postgresql" | "mysql" | "mariadb") => {
+                return Err(eyre!("SQL inputs cannot be combined with other inputs"));
+            }
+            Some("unix") if uris.len() == 1 => return open_unix_socket_input(uri),
+            Some("unix") => {
+                return Err(eyre!(
+                    "Unix socket inputs cannot be combined with other inputs"
+                ));
+            }
+            Some("gen") if uris.len() == 1 => {
+                return Ok(Input::Synthetic {
+                    generator: Box::new(synthetic::SyntheticInput::try_new(uri)?),
+                });
+            }
+            Some("gen") => {
+                return Err(eyre!(
+                    "Generated inputs cannot be combined with other inputs"
+                ));
+            }
             Some("http") => return Err(eyre!("Unsupported input scheme: http")),
-            Some("file") | None => {}
+            Some(scheme) if uris.len() == 1 && KnownHost::parse(scheme).is_some() => {
+                return open_search_input(uri);
+            }
             Some(scheme) => return Err(eyre!("Unsupported input scheme: {scheme}")),
         }
     }
@@ -171,11 +967,26 @@ fn open_input_values(uris: Vec<UriRef<String>>, content_field: &str) -> Result<I
         let path_str = uri.path().as_str();
         if uri.scheme().is_none() && path_str == "-" {
             return Ok(Input::Stdin {
-                reader: Box::new(BufReader::new(stdin())),
+                reader: Box::new(BufReader::new(Box::new(stdin()))),
+                position: LinePosition::default(),
             });
         }
         let path = PathBuf::from(path_str);
+        #[cfg(unix)]
+        if stream::is_fifo(&path) {
+            return Ok(Input::Stream {
+                stream: Box::new(stream::StreamInput::open_fifo(&path)?),
+            });
+        }
         if !has_glob_metachar(path_str) {
+            if archive::is_archive(path_str) {
+                if !path.is_file() {
+                    return Err(eyre!("File input does not exist: {path_str}"));
+                }
+                return Ok(Input::Archive {
+                    entries: Box::new(archive::ArchiveInput::new(path)?),
+                });
+            }
             if let Ok(kind) = local_input_kind(&path) {
                 match kind {
                     InputKind::Csv | InputKind::Ndjson | InputKind::Toon => {
@@ -201,10 +1012,70 @@ fn open_input_values(uris: Vec<UriRef<String>>, content_field: &str) -> Result<I
     open_file_documents(values, content_field)
 }
 
+fn open_search_input(uri: &UriRef<String>) -> Result<Input> {
+    Ok(Input::Search {
+        hits: Box::new(search::SearchInput::try_new(uri)?),
+    })
+}
+
+#[cfg(feature = "kafka")]
+fn open_kafka_input(uri: &UriRef<String>) -> Result<Input> {
+    Ok(Input::Kafka {
+        consumer: Box::new(kafka::KafkaInput::try_new(uri)?),
+    })
+}
+
+#[cfg(not(feature = "kafka"))]
+fn open_kafka_input(_uri: &UriRef<String>) -> Result<Input> {
+    Err(eyre!(
+        "kafka:// inputs require espipe to be built with the kafka feature"
+    ))
+}
+
+#[cfg(feature = "sql")]
+fn open_sql_input(uri: &UriRef<String>) -> Result<Input> {
+    Ok(Input::Sql {
+        rows: Box::new(sql::SqlInput::try_new(uri)?),
+    })
+}
+
+#[cfg(not(feature = "sql"))]
+fn open_sql_input(uri: &UriRef<String>) -> Result<Input> {
+    Err(eyre!(
+        "{}:// inputs require espipe to be built with the sql feature",
+        uri.scheme()
+            .map(|scheme| scheme.as_str())
+            .unwrap_or_default()
+    ))
+}
+
+#[cfg(unix)]
+fn open_unix_socket_input(uri: &UriRef<String>) -> Result<Input> {
+    let path = uri.path().as_str();
+    if uri
+        .authority()
+        .is_some_and(|authority| !authority.as_str().is_empty())
+        || path.is_empty()
+    {
+        return Err(eyre!(
+            "Unix socket inputs must be an absolute path like unix:///var/run/app.sock"
+        ));
+    }
+    Ok(Input::Stream {
+        stream: Box::new(stream::StreamInput::listen_unix(Path::new(path))?),
+    })
+}
+
+#[cfg(not(unix))]
+fn open_unix_socket_input(_uri: &UriRef<String>) -> Result<Input> {
+    Err(eyre!("unix:// inputs are only supported on Unix platforms"))
+}
+
 fn read_json_line<R: BufRead>(
     reader: &mut R,
     line_buffer: &mut String,
     first_record: bool,
+    position: &mut LinePosition,
 ) -> Result<Box<RawValue>> {
     reader.read_line(line_buffer)?;
     if line_buffer.is_empty() {
@@ -214,13 +1085,162 @@ fn read_json_line<R: BufRead>(
         let mut rest = String::new();
         reader.read_to_string(&mut rest)?;
         line_buffer.push_str(&rest);
-        let raw: Box<RawValue> =
-            serde_json::from_str(line_buffer).map_err(|e| eyre!("Error parsing JSON: {e}"))?;
-        ensure_json_opening(raw.get(), JSON_LINE_OPENING_ERROR)?;
-        return Ok(raw);
     }
+    let start = position.advance(line_buffer);
+    parse_json_record(line_buffer)
+        .map_err(|err| MalformedRecord::lines(start, line_buffer, err.to_string()).into())
+}
+
+/// Reads `reader` as `Csv`, `Json`, `Concatenated`, or under `Auto` as
+/// whatever its first bytes look like, decompressing it first when it is gzip.
+/// Content that looks like none of them is read as NDJSON.
+fn open_content(
+    source: String,
+    reader: Box<dyn Read + Send>,
+    format: InputFormat,
+    _temp_file: Option<NamedTempFile>,
+) -> Result<Input> {
+    let mut reader = BufReader::new(reader);
+    if sniff::is_gzip(reader.fill_buf()?) {
+        let decoder: Box<dyn Read + Send> = Box::new(GzDecoder::new(reader));
+        reader = BufReader::new(decoder);
+    }
+    skip_bom(&mut reader)?;
+    let sniffed = match format {
+        InputFormat::Csv => Some(sniff::Sniffed::Csv),
+        InputFormat::Json => Some(sniff::Sniffed::JsonArray),
+        InputFormat::Concatenated => Some(sniff::Sniffed::Concatenated),
+        _ => sniff::sniff(reader.fill_buf()?),
+    };
+    log::debug!("Reading {source} as {sniffed:?}");
+    Ok(match sniffed {
+        Some(sniff::Sniffed::Csv) => Input::FileCsv {
+            source,
+            reader: Box::new(
+                csv::ReaderBuilder::new()
+                    .has_headers(true)
+                    .from_reader(Box::new(reader) as Box<dyn Read + Send>),
+            ),
+            _temp_file,
+        },
+        Some(sniff::Sniffed::JsonArray) => {
+            let documents = read_json_array(&source, reader)?;
+            Input::FileDocuments {
+                source,
+                paths: Vec::new(),
+                path_index: 0,
+                documents: documents.into_iter(),
+                content_field: "body".to_string(),
+                include_file_metadata: false,
+                workers: None,
+                state: None,
+            }
+        }
+        Some(sniff::Sniffed::Concatenated) => Input::Concatenated {
+            values: Box::new(concatenated::ConcatenatedInput::new(
+                source,
+                Box::new(reader),
+                _temp_file,
+            )),
+        },
+        Some(sniff::Sniffed::Ndjson) | None => Input::FileJson {
+            source,
+            reader: Box::new(BufReader::new(Box::new(reader) as Box<dyn Read + Send>)),
+            first_record: false,
+            position: LinePosition::default(),
+            _temp_file,
+        },
+    })
+}
+
+/// Reads a local file from the start of `reader` as CSV, a JSON array, or
+/// concatenated JSON, or as lines for the line-based `format` still to apply.
+fn reread_local_file(
+    source: String,
+    reader: Box<dyn Read + Send>,
+    format: InputFormat,
+) -> Result<(Input, InputFormat)> {
+    match format {
+        InputFormat::Csv | InputFormat::Json | InputFormat::Concatenated => Ok((
+            open_content(source, reader, format, None)?,
+            InputFormat::Auto,
+        )),
+        format => Ok((
+            Input::FileJson {
+                source,
+                reader: Box::new(BufReader::new(reader)),
+                first_record: true,
+                position: LinePosition::default(),
+                _temp_file: None,
+            },
+            format,
+        )),
+    }
+}
+
+/// Reads a whole JSON array of objects into documents.
+fn read_json_array<R: Read>(source: &str, reader: R) -> Result<Vec<Box<RawValue>>> {
+    let documents: Vec<Box<RawValue>> = serde_json::from_reader(reader)
+        .map_err(|err| eyre!("{source}: expected a JSON array of documents: {err}"))?;
+    match documents
+        .iter()
+        .position(|document| !document.get().trim_start().starts_with('{'))
+    {
+        Some(index) => Err(eyre!(
+            "{source}: JSON array element {index} is not an object"
+        )),
+        None => Ok(documents),
+    }
+}
+
+fn starts_with_bulk_action<R: BufRead + ?Sized>(reader: &mut R) -> Result<bool> {
+    let buffered = reader.fill_buf()?;
+    Ok(buffered
+        .split(|&byte| byte == b'\n')
+        .next()
+        .and_then(|line| std::str::from_utf8(line).ok())
+        .is_some_and(bulk::is_action_line))
+}
+
+/// Reads the next non-blank line and turns it into a document with `parse`.
+fn read_parsed_line<R: BufRead + ?Sized>(
+    reader: &mut R,
+    line_buffer: &mut String,
+    position: &mut LinePosition,
+    parse: fn(&str) -> Result<Value>,
+) -> Result<Document> {
+    loop {
+        line_buffer.clear();
+        reader.read_line(line_buffer)?;
+        if line_buffer.is_empty() {
+            return Err(eyre!("No JSON record"));
+        }
+        let start = position.advance(line_buffer);
+        let line = line_buffer.trim_end_matches(['\n', '\r']);
+        if line.trim().is_empty() {
+            continue;
+        }
+        return parse(line)
+            .and_then(|document| Ok(RawValue::from_string(document.to_string())?))
+            .map(Document::from)
+            .map_err(|err| MalformedRecord::lines(start, line, err.to_string()).into());
+    }
+}
+
+fn read_unvalidated_line<R: BufRead + ?Sized>(
+    reader: &mut R,
+    line_buffer: &mut String,
+) -> Result<Document> {
+    reader.read_line(line_buffer)?;
+    if line_buffer.is_empty() {
+        return Err(eyre!("No JSON record"));
+    }
+    Document::unvalidated(line_buffer)
+}
+
+fn parse_json_record(text: &str) -> Result<Box<RawValue>> {
     let raw: Box<RawValue> =
-        serde_json::from_str(line_buffer).map_err(|e| eyre!("Error parsing JSON: {e}"))?;
+        serde_json::from_str(text).map_err(|e| eyre!("Error parsing JSON: {e}"))?;
     ensure_json_opening(raw.get(), JSON_LINE_OPENING_ERROR)?;
     Ok(raw)
 }
@@ -231,6 +1251,16 @@ fn read_csv_line(reader: &mut csv::Reader<Box<dyn Read + Send>>) -> Result<Box<R
             let json = serde_json::to_string(&record)?;
             serde_json::value::RawValue::from_string(json).map_err(Into::into)
         }
+        Some(Err(err)) if !err.is_io_error() && err.position().is_some() => {
+            let position = err.position().expect("checked above");
+            Err(MalformedRecord {
+                line: position.line(),
+                offset: position.byte(),
+                record: None,
+                reason: err.to_string(),
+            }
+            .into())
+        }
         Some(Err(err)) => Err(err.into()),
         None => Err(eyre!("No CSV record")),
     }
@@ -277,39 +1307,91 @@ fn read_toon_document<R: BufRead>(
             return Ok(raw);
         }
 
-        pending.push_str(&line);
+        // Lines are passed on with LF endings, whatever the file was written with.
+        pending.push_str(line.trim_end_matches(['\n', '\r']));
+        pending.push('\n');
+    }
+}
+
+/// Moves past the byte order mark some Windows tools start UTF-8 text with,
+/// which parsers take for a character before the first value. Only the start
+/// of a stream can hold one.
+fn skip_bom<R: BufRead + ?Sized>(reader: &mut R) -> std::io::Result<()> {
+    if reader.fill_buf()?.starts_with(UTF8_BOM) {
+        reader.consume(UTF8_BOM.len());
+    }
+    Ok(())
+}
+
+/// Opens a file dropped into a watched directory the way a single file input
+/// would be opened.
+fn open_dropped_file(path: &Path, content_field: &str) -> Result<Input> {
+    match local_input_kind(path) {
+        Ok(InputKind::Csv | InputKind::Ndjson | InputKind::Toon) => {
+            open_local_file(path.to_path_buf())
+        }
+        Ok(InputKind::Json) if !should_use_file_document(path) => {
+            open_local_file(path.to_path_buf())
+        }
+        _ => {
+            let path = path.display().to_string();
+            if is_unsupported_compressed_input(&path) {
+                return Err(eyre!("Unsupported compressed input format: {path}"));
+            }
+            open_file_documents(vec![path], content_field)
+        }
     }
 }
 
 fn open_local_file(path: PathBuf) -> Result<Input> {
     let source = path.display().to_string();
-    let file = File::open(&path)?;
-    match local_input_kind(&path)? {
+    open_file_input(&path, source, None)
+}
+
+/// Opens a CSV, NDJSON, JSON, or TOON file, keeping the temporary file a
+/// download was written to alive for as long as the input.
+fn open_file_input(path: &Path, source: String, temp_file: Option<NamedTempFile>) -> Result<Input> {
+    let file = File::open(path)?;
+    match local_input_kind(path)? {
+        InputKind::FileDocument => open_file_documents(vec![source], "body"),
+        kind => open_reader(kind, source, local_file_reader(file, path), temp_file),
+    }
+}
+
+/// Reads `reader` as a CSV, NDJSON, JSON, or TOON file of `kind`.
+fn open_reader(
+    kind: InputKind,
+    source: String,
+    reader: Box<dyn Read + Send>,
+    temp_file: Option<NamedTempFile>,
+) -> Result<Input> {
+    match kind {
         InputKind::Csv => Ok(Input::FileCsv {
             source,
             reader: Box::new(
                 csv::ReaderBuilder::new()
                     .has_headers(true)
-                    .from_reader(local_file_reader(file, &path)),
+                    .from_reader(reader),
             ),
-            _temp_file: None,
+            _temp_file: temp_file,
         }),
         InputKind::Ndjson | InputKind::Json => Ok(Input::FileJson {
             source,
-            reader: Box::new(BufReader::new(local_file_reader(file, &path))),
+            reader: Box::new(BufReader::new(reader)),
             first_record: true,
-            _temp_file: None,
+            position: LinePosition::default(),
+            _temp_file: temp_file,
         }),
         InputKind::Toon => Ok(Input::FileToon {
             source,
-            reader: Box::new(BufReader::new(local_file_reader(file, &path))),
+            reader: Box::new(BufReader::new(reader)),
             pending: String::new(),
             document_index: 0,
             buffered_rows: Vec::new(),
             eof: false,
-            _temp_file: None,
+            _temp_file: temp_file,
         }),
-        InputKind::FileDocument => open_file_documents(vec![source], "body"),
+        InputKind::FileDocument => Err(eyre!("{source} is not CSV, NDJSON, JSON, or TOON")),
     }
 }
 
@@ -321,10 +1403,11 @@ fn open_file_documents(values: Vec<String>, content_field: &str) -> Result<Input
         source,
         paths,
         path_index: 0,
-        documents: Vec::new(),
-        document_index: 0,
+        documents: Vec::new().into_iter(),
         content_field: content_field.to_string(),
         include_file_metadata,
+        workers: None,
+        state: None,
     })
 }
 
@@ -333,9 +1416,10 @@ fn read_file_document_line(input: &mut Input) -> Result<Box<RawValue>> {
         paths,
         path_index,
         documents,
-        document_index,
         content_field,
         include_file_metadata,
+        workers,
+        state,
         ..
     } = input
     else {
@@ -343,17 +1427,31 @@ fn read_file_document_line(input: &mut Input) -> Result<Box<RawValue>> {
     };
 
     loop {
-        if let Some(document) = documents.get(*document_index) {
-            *document_index += 1;
-            return RawValue::from_string(document.get().to_string()).map_err(Into::into);
+        if let Some(document) = documents.next() {
+            if let Some(state) = state {
+                state.advance();
+            }
+            return Ok(document);
         }
 
         let Some(path) = paths.get(*path_index) else {
             return Err(eyre!("No file document"));
         };
+        let loaded = match workers {
+            Some(workers) => {
+                workers.read(paths, *path_index, content_field, *include_file_metadata)
+            }
+            None => read_file_documents(path, content_field, *include_file_metadata),
+        };
         *path_index += 1;
-        *documents = read_file_documents(path, content_field, *include_file_metadata)?;
-        *document_index = 0;
+        let mut loaded = loaded?;
+        log::info!("Read {} document(s) from {}", loaded.len(), path.display());
+        if let Some(state) = state {
+            // Documents a previous run delivered before it stopped.
+            let delivered = state.start(path, loaded.len());
+            loaded.drain(..delivered);
+        }
+        *documents = loaded.into_iter();
     }
 }
 
@@ -505,10 +1603,25 @@ fn split_markdown_frontmatter(text: &str) -> (Option<&str>, &str) {
 fn is_end_of_input(err: &eyre::Report) -> bool {
     matches!(
         err.to_string().as_str(),
-        "No JSON record" | "No CSV record" | "No file document" | "No Toon document"
+        "No JSON record"
+            | "No CSV record"
+            | "No file document"
+            | "No Toon document"
+            | "No Kafka message"
+            | "No SQL row"
+            | "No stream record"
+            | "No search hit"
+            | "No generated document"
+            | "No merged document"
+            | "No archive entry"
     )
 }
 
+/// A streaming input had nothing to read for a while; pending documents should be flushed.
+fn is_idle(err: &eyre::Report) -> bool {
+    err.to_string() == INPUT_IDLE
+}
+
 fn read_yaml_file_document(
     path: &Path,
     content_field: &str,
@@ -710,17 +1823,22 @@ fn raw_documents(documents: Vec<Map<String, Value>>) -> Result<Vec<Box<RawValue>
         .collect()
 }
 
-fn fetch_remote_input(uri: UriRef<String>) -> Result<Input> {
+fn fetch_remote_input(uri: UriRef<String>, remote: &RemoteOptions) -> Result<Input> {
     let client = Client::builder()
         .https_only(true)
+        .default_headers(remote.headers().clone())
         .connect_timeout(REMOTE_CONNECT_TIMEOUT)
         .timeout(REMOTE_REQUEST_TIMEOUT)
         .build()?;
-    fetch_remote_input_with_client(uri, &client)
+    fetch_remote_input_with_client(uri, &client, remote.download_parallelism())
 }
 
-fn fetch_remote_input_with_client(uri: UriRef<String>, client: &Client) -> Result<Input> {
-    let mut response = client
+fn fetch_remote_input_with_client(
+    uri: UriRef<String>,
+    client: &Client,
+    download_parallelism: usize,
+) -> Result<Input> {
+    let response = client
         .get(uri.as_str())
         .header(
             ACCEPT,
@@ -745,7 +1863,13 @@ fn fetch_remote_input_with_client(uri: UriRef<String>, client: &Client) -> Resul
     };
 
     let mut temp_file = Builder::new().suffix(suffix).tempfile()?;
-    std::io::copy(&mut response, temp_file.as_file_mut())?;
+    download::download(
+        client,
+        uri.as_str(),
+        response,
+        &mut temp_file,
+        download_parallelism,
+    )?;
     temp_file.as_file_mut().flush()?;
 
     if kind == InputKind::Json {
@@ -769,6 +1893,7 @@ fn fetch_remote_input_with_client(uri: UriRef<String>, client: &Client) -> Resul
             source,
             reader: Box::new(BufReader::new(Box::new(reader_file) as Box<dyn Read + Send>)),
             first_record: true,
+            position: LinePosition::default(),
             _temp_file: Some(temp_file),
         }),
         InputKind::Toon => Ok(Input::FileToon {
@@ -784,6 +1909,44 @@ fn fetch_remote_input_with_client(uri: UriRef<String>, client: &Client) -> Resul
     }
 }
 
+/// Downloads an `az://` or `gs://` object to a temporary file and reads it
+/// like a local file of the same name, gzip-compressed `.ndjson.gz` and
+/// `.csv.gz` included.
+#[cfg(feature = "cloud")]
+fn fetch_cloud_input(uri: &UriRef<String>) -> Result<Input> {
+    let object = crate::cloud::CloudObject::try_new(uri)?;
+    let name = object.name().to_string();
+    let suffix = match input_kind_from_path(&name) {
+        Some(InputKind::Csv) if is_compressed_input(&name) => ".csv.gz",
+        Some(InputKind::Ndjson) if is_compressed_input(&name) => ".ndjson.gz",
+        Some(InputKind::Csv) => ".csv",
+        Some(InputKind::Ndjson) => ".ndjson",
+        Some(InputKind::Json) => ".json",
+        Some(InputKind::Toon) => ".toon",
+        _ => return Err(eyre!("Unsupported cloud storage input format: {uri}")),
+    };
+    let mut temp_file = Builder::new().suffix(suffix).tempfile()?;
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(object.download(temp_file.as_file_mut()))?;
+    if suffix == ".json" {
+        validate_ndjson_file(temp_file.as_file_mut())?;
+    }
+    let path = temp_file.path().to_path_buf();
+    open_file_input(&path, object.to_string(), Some(temp_file))
+}
+
+#[cfg(not(feature = "cloud"))]
+fn fetch_cloud_input(uri: &UriRef<String>) -> Result<Input> {
+    Err(eyre!(
+        "{}:// inputs require espipe to be built with the cloud feature",
+        uri.scheme()
+            .map(|scheme| scheme.as_str())
+            .unwrap_or_default()
+    ))
+}
+
 fn remote_input_kind(uri: &UriRef<String>, response: &Response) -> Result<InputKind> {
     if has_path_suffix(uri.path().as_str(), ".gz") {
         return Err(eyre!(
@@ -851,7 +2014,8 @@ fn input_kind_from_path(path: &str) -> Option<InputKind> {
 
 fn local_file_reader(file: File, path: &Path) -> Box<dyn Read + Send> {
     if has_path_suffix(path.to_string_lossy().as_ref(), ".gz") {
-        return Box::new(GzDecoder::new(file));
+        // Appended gzip output has a member per time its file was opened.
+        return Box::new(MultiGzDecoder::new(file));
     }
     Box::new(file)
 }
@@ -909,9 +2073,9 @@ fn ensure_json_opening(input: &str, error_message: &str) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::{
-        Input, InputKind, JSON_LINE_OPENING_ERROR, REMOTE_NDJSON_ERROR,
-        fetch_remote_input_with_client, input_kind_from_path, local_input_kind, open_input_values,
-        validate_content_field, validate_ndjson_file,
+        FileState, Input, InputFormat, InputKind, JSON_LINE_OPENING_ERROR, REMOTE_NDJSON_ERROR,
+        fetch_remote_input_with_client, input_kind_from_path, is_end_of_input, local_input_kind,
+        open_input_values, validate_content_field, validate_ndjson_file,
     };
     use flate2::{Compression, write::GzEncoder};
     use fluent_uri::UriRef;
@@ -1024,6 +2188,26 @@ mod tests {
         fs::remove_file(path).unwrap();
     }
 
+    #[test]
+    fn unvalidated_lines_pass_through_without_parsing() {
+        let path = temp_path("ndjson");
+        fs::write(&path, "{\"a\":1,,}\n[2]\n").unwrap();
+        let mut input =
+            Input::try_from(UriRef::parse(path.to_string_lossy().into_owned()).unwrap())
+                .unwrap()
+                .without_validation();
+
+        let mut line = String::new();
+        assert_eq!(input.read_line(&mut line).unwrap().get(), "{\"a\":1,,}");
+        line.clear();
+        let err = input.read_line(&mut line).unwrap_err();
+        assert!(err.to_string().contains("--no-validate"), "{err}");
+        line.clear();
+        assert!(is_end_of_input(&input.read_line(&mut line).unwrap_err()));
+
+        fs::remove_file(path).unwrap();
+    }
+
     #[test]
     fn read_line_converts_csv_to_raw_json() {
         let path = temp_path("csv");
@@ -1040,6 +2224,74 @@ mod tests {
         fs::remove_file(path).unwrap();
     }
 
+    #[test]
+    fn byte_order_marks_and_mixed_line_endings_are_read_alike() {
+        // As the pipeline opens them, past the byte order mark.
+        let open = |suffix: &str, text: &str| {
+            let path = temp_path(suffix);
+            fs::write(&path, text).unwrap();
+            let input = Input::try_from(uri(&path))
+                .unwrap()
+                .with_input_format(InputFormat::Auto)
+                .unwrap();
+            (input, path)
+        };
+        let ndjson = "\u{feff}{\"a\":1}\r\n{\"a\":2}\n{\"a\":3}\r\n";
+        let expected = vec![
+            serde_json::json!({"a":1}),
+            serde_json::json!({"a":2}),
+            serde_json::json!({"a":3}),
+        ];
+
+        let (input, path) = open("ndjson", ndjson);
+        assert_eq!(collect_values(input), expected);
+        fs::remove_file(path).unwrap();
+
+        let (input, path) = open("ndjson", ndjson);
+        assert_eq!(collect_values(input.with_parse_workers(2)), expected);
+        fs::remove_file(path).unwrap();
+
+        let (input, path) = open("ndjson", ndjson);
+        let mut input = input.without_validation();
+        let mut line = String::new();
+        assert_eq!(input.read_line(&mut line).unwrap().get(), "{\"a\":1}");
+        line.clear();
+        assert_eq!(input.read_line(&mut line).unwrap().get(), "{\"a\":2}");
+        fs::remove_file(path).unwrap();
+
+        let (mut input, path) = open(
+            "ndjson",
+            "\u{feff}{\"index\":{\"_id\":\"1\"}}\r\n{\"a\":1}\n{\"create\":{}}\n{\"a\":2}\r\n",
+        );
+        let mut line = String::new();
+        let doc = input.read_line(&mut line).unwrap();
+        assert_eq!(doc.action(), Some("{\"index\":{\"_id\":\"1\"}}"));
+        assert_eq!(doc.get(), "{\"a\":1}");
+        line.clear();
+        let doc = input.read_line(&mut line).unwrap();
+        assert_eq!(doc.action(), Some("{\"create\":{}}"));
+        assert_eq!(doc.get(), "{\"a\":2}");
+        fs::remove_file(path).unwrap();
+
+        let (input, path) = open("csv", "\u{feff}name,count\r\nalpha,2\nbeta,3\r\n");
+        assert_eq!(
+            collect_values(input),
+            vec![
+                serde_json::json!({"name":"alpha","count":"2"}),
+                serde_json::json!({"name":"beta","count":"3"}),
+            ]
+        );
+        fs::remove_file(path).unwrap();
+
+        // Only the start of a stream can hold a byte order mark.
+        let (mut input, path) = open("ndjson", "{\"a\":1}\n\u{feff}{\"a\":2}\n");
+        let mut line = String::new();
+        assert_eq!(input.read_line(&mut line).unwrap().get(), "{\"a\":1}");
+        line.clear();
+        assert!(input.read_line(&mut line).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
     #[test]
     fn read_line_converts_gzip_csv_to_raw_json() {
         let path = temp_path("csv.gz");
@@ -1143,6 +2395,29 @@ mod tests {
         assert_eq!(values[1]["file"]["name"], "b.txt");
     }
 
+    #[test]
+    fn input_workers_preserve_sequential_file_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<_> = ["d", "a", "c", "b"]
+            .iter()
+            .map(|name| {
+                let path = dir.path().join(format!("{name}.txt"));
+                fs::write(&path, name).unwrap();
+                uri(&path)
+            })
+            .collect();
+
+        let sequential = collect_values(open_input_values(paths.clone(), "body").unwrap());
+        let parallel = collect_values(
+            open_input_values(paths, "body")
+                .unwrap()
+                .with_input_workers(3),
+        );
+
+        assert_eq!(parallel, sequential);
+        assert_eq!(parallel[0]["content"]["body"], "a");
+    }
+
     #[test]
     fn recursive_glob_imports_regular_files_and_filters_directories() {
         let dir = tempfile::tempdir().unwrap();
@@ -1165,6 +2440,33 @@ mod tests {
         assert_eq!(values[1]["content"]["body"], "root");
     }
 
+    #[test]
+    fn file_state_reads_only_files_not_yet_delivered() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = dir.path().join("state.json");
+        let logs = dir.path().join("logs");
+        fs::create_dir(&logs).unwrap();
+        fs::write(logs.join("a.ndjson"), "{\"n\":1}\n{\"n\":2}\n").unwrap();
+        let pattern = logs.join("*.ndjson").to_string_lossy().into_owned();
+        let open = || {
+            open_input_values(vec![UriRef::parse(pattern.clone()).unwrap()], "body")
+                .unwrap()
+                .with_file_state(Some(FileState::open(&state).unwrap()))
+                .unwrap()
+        };
+
+        let mut input = open();
+        let mut line = String::new();
+        while input.read_line(&mut line).is_ok() {}
+        input.commit().unwrap();
+
+        // The next night's file is the only one read.
+        fs::write(logs.join("b.ndjson"), "{\"n\":3}\n").unwrap();
+        let values = collect_values(open());
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0]["n"], 3);
+    }
+
     #[test]
     fn glob_matching_no_regular_files_fails() {
         let dir = tempfile::tempdir().unwrap();
@@ -1575,6 +2877,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn kafka_input_cannot_be_combined_with_other_inputs() {
+        let kafka = UriRef::parse("kafka://broker:9092/events".to_string()).unwrap();
+        let file = uri(&fixture_path("bulk_input.ndjson"));
+
+        let err = input_err(open_input_values(vec![kafka, file], "body"));
+
+        assert!(err.contains("Kafka inputs cannot be combined"));
+    }
+
+    #[cfg(not(feature = "kafka"))]
+    #[test]
+    fn kafka_input_requires_kafka_feature() {
+        let uri = UriRef::parse("kafka://broker:9092/events".to_string()).unwrap();
+
+        let err = input_err(Input::try_from(uri));
+
+        assert!(err.contains("built with the kafka feature"));
+    }
+
     #[test]
     fn json_extension_is_accepted_for_local_input_detection() {
         let path = PathBuf::from("/tmp/example.json");
@@ -1589,7 +2911,7 @@ mod tests {
         let client = test_https_client();
         let uri = UriRef::parse(format!("{base_url}/download").to_string()).unwrap();
 
-        let mut input = fetch_remote_input_with_client(uri, &client).unwrap();
+        let mut input = fetch_remote_input_with_client(uri, &client, 1).unwrap();
         let mut line = String::new();
         let value = input.read_line(&mut line).unwrap();
         let actual: serde_json::Value = serde_json::from_str(value.get()).unwrap();
@@ -1629,7 +2951,7 @@ mod tests {
         let client = test_https_client();
         let uri = UriRef::parse(format!("{base_url}/events.toon").to_string()).unwrap();
 
-        let values = collect_values(fetch_remote_input_with_client(uri, &client).unwrap());
+        let values = collect_values(fetch_remote_input_with_client(uri, &client, 1).unwrap());
 
         assert_eq!(values, vec![serde_json::json!({"id":1,"name":"Alpha"})]);
         handle.join().unwrap();
@@ -1642,7 +2964,7 @@ mod tests {
         let client = test_https_client();
         let uri = UriRef::parse(format!("{base_url}/download").to_string()).unwrap();
 
-        let values = collect_values(fetch_remote_input_with_client(uri, &client).unwrap());
+        let values = collect_values(fetch_remote_input_with_client(uri, &client, 1).unwrap());
 
         assert_eq!(values, vec![serde_json::json!({"id":1,"name":"Alpha"})]);
         handle.join().unwrap();
@@ -1655,7 +2977,7 @@ mod tests {
         let client = test_https_client();
         let uri = UriRef::parse(format!("{base_url}/missing.ndjson").to_string()).unwrap();
 
-        match fetch_remote_input_with_client(uri, &client) {
+        match fetch_remote_input_with_client(uri, &client, 1) {
             Ok(_) => panic!("non-success status should fail"),
             Err(err) => assert!(err.to_string().contains("HTTP status 404")),
         }
@@ -1670,7 +2992,7 @@ mod tests {
         let client = test_https_client();
         let uri = UriRef::parse(format!("{base_url}/events.ndjson.gz").to_string()).unwrap();
 
-        match fetch_remote_input_with_client(uri, &client) {
+        match fetch_remote_input_with_client(uri, &client, 1) {
             Ok(_) => panic!("remote gzip input should fail"),
             Err(err) => assert!(
                 err.to_string()
@@ -1690,7 +3012,7 @@ mod tests {
         let client = test_https_client();
         let uri = UriRef::parse(format!("https://localhost:{port}/missing.ndjson")).unwrap();
 
-        match fetch_remote_input_with_client(uri, &client) {
+        match fetch_remote_input_with_client(uri, &client, 1) {
             Ok(_) => panic!("transport failure should fail"),
             Err(err) => {
                 let message = err.to_string();
@@ -0,0 +1,177 @@
+use eyre::{Result, eyre};
+use jiff::Timestamp;
+use serde_json::{Value, json};
+use std::net::IpAddr;
+
+/// Parses one Apache or Nginx access log line in the common or combined log
+/// format into an ECS-style document.
+///
+/// Fields past the user agent, such as a response time, are ignored.
+pub fn parse_line(line: &str) -> Result<Value> {
+    let mut fields = Fields(line);
+    let mut field = |name: &str| {
+        fields
+            .next()
+            .ok_or_else(|| eyre!("access log line has no {name}"))?
+    };
+    let client = field("client address")?;
+    let _identity = field("identity")?;
+    let user = field("user")?;
+    let time = field("timestamp")?;
+    let request = field("request")?;
+    let status = field("status")?;
+    let bytes = field("response size")?;
+    let referrer = fields.next().transpose()?;
+    let user_agent = fields.next().transpose()?;
+
+    let timestamp = Timestamp::strptime("%d/%b/%Y:%H:%M:%S %z", &time)
+        .map_err(|err| eyre!("invalid access log timestamp '{time}': {err}"))?;
+    let status = status
+        .parse::<u16>()
+        .map_err(|_| eyre!("invalid access log status '{status}'"))?;
+    let mut document = json!({
+        "@timestamp": timestamp.to_string(),
+        "event": { "original": line },
+        "http": { "response": { "status_code": status } },
+        "source": { "address": client },
+    });
+    if let Ok(ip) = client.parse::<IpAddr>() {
+        document["source"]["ip"] = json!(ip.to_string());
+    }
+    if let Some(user) = present(&user) {
+        document["user"] = json!({ "name": user });
+    }
+    if let Ok(bytes) = bytes.parse::<u64>() {
+        document["http"]["response"]["body"] = json!({ "bytes": bytes });
+    }
+    let mut parts = request.splitn(3, ' ');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(url), version) if !method.is_empty() => {
+            document["http"]["request"] = json!({ "method": method });
+            document["url"] = url_fields(url);
+            if let Some(version) = version.and_then(|version| version.strip_prefix("HTTP/")) {
+                document["http"]["version"] = json!(version);
+            }
+        }
+        // Probes and malformed requests are kept as they were sent.
+        _ if present(&request).is_some() => document["url"] = json!({ "original": request }),
+        _ => {}
+    }
+    if let Some(referrer) = referrer.as_deref().and_then(present) {
+        document["http"]["request"]["referrer"] = json!(referrer);
+    }
+    if let Some(user_agent) = user_agent.as_deref().and_then(present) {
+        document["user_agent"] = json!({ "original": user_agent });
+    }
+    Ok(document)
+}
+
+fn url_fields(url: &str) -> Value {
+    match url.split_once('?') {
+        Some((path, query)) => json!({ "original": url, "path": path, "query": query }),
+        None => json!({ "original": url, "path": url }),
+    }
+}
+
+/// Access logs write `-` for a missing value.
+fn present(value: &str) -> Option<&str> {
+    (value != "-").then_some(value)
+}
+
+/// Splits a log line into bare, `"quoted"`, and `[bracketed]` fields.
+struct Fields<'a>(&'a str);
+
+impl Iterator for Fields<'_> {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let text = self.0.trim_start_matches(' ');
+        if text.is_empty() {
+            return None;
+        }
+        let (field, rest) = if let Some(quoted) = text.strip_prefix('"') {
+            match unquote(quoted) {
+                Some(split) => split,
+                None => return Some(Err(eyre!("unterminated quoted field in access log line"))),
+            }
+        } else if let Some(bracketed) = text.strip_prefix('[') {
+            match bracketed.split_once(']') {
+                Some((field, rest)) => (field.to_string(), rest),
+                None => return Some(Err(eyre!("unterminated [ in access log line"))),
+            }
+        } else {
+            let (field, rest) = text.split_once(' ').unwrap_or((text, ""));
+            (field.to_string(), rest)
+        };
+        self.0 = rest;
+        Some(Ok(field))
+    }
+}
+
+/// Reads a quoted field up to its closing quote, undoing the `\"` and `\\` escapes
+/// Apache and Nginx write.
+fn unquote(quoted: &str) -> Option<(String, &str)> {
+    let mut field = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((index, char)) = chars.next() {
+        match char {
+            '"' => return Some((field, &quoted[index + 1..])),
+            '\\' => match chars.next()? {
+                (_, escaped @ ('"' | '\\')) => field.push(escaped),
+                (_, other) => {
+                    field.push('\\');
+                    field.push(other);
+                }
+            },
+            char => field.push(char),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_line;
+    use serde_json::json;
+
+    #[test]
+    fn combined_lines_become_ecs_fields() {
+        let line = r#"203.0.113.7 - frank [10/Oct/2025:13:55:36 -0700] "GET /search?q=rust HTTP/1.1" 200 2326 "https://example.com/" "Mozilla/5.0 (X11; \"Linux\")""#;
+
+        assert_eq!(
+            parse_line(line).unwrap(),
+            json!({
+                "@timestamp": "2025-10-10T20:55:36Z",
+                "event": { "original": line },
+                "http": {
+                    "request": { "method": "GET", "referrer": "https://example.com/" },
+                    "response": { "body": { "bytes": 2326 }, "status_code": 200 },
+                    "version": "1.1",
+                },
+                "source": { "address": "203.0.113.7", "ip": "203.0.113.7" },
+                "url": { "original": "/search?q=rust", "path": "/search", "query": "q=rust" },
+                "user": { "name": "frank" },
+                "user_agent": { "original": "Mozilla/5.0 (X11; \"Linux\")" },
+            })
+        );
+    }
+
+    #[test]
+    fn common_lines_and_probes_keep_what_they_have() {
+        let document =
+            parse_line(r#"web-proxy - - [01/Jan/2026:00:00:00 +0000] "\x16\x03\x01" 400 -"#)
+                .unwrap();
+
+        assert_eq!(document["source"], json!({ "address": "web-proxy" }));
+        assert_eq!(document["url"], json!({ "original": "\\x16\\x03\\x01" }));
+        assert_eq!(
+            document["http"],
+            json!({ "response": { "status_code": 400 } })
+        );
+        assert!(document.get("user").is_none());
+        assert!(document.get("user_agent").is_none());
+
+        assert!(parse_line("127.0.0.1 - - [01/Jan/2026:00:00:00 +0000] \"GET /").is_err());
+        assert!(parse_line("127.0.0.1 - - [yesterday] \"GET / HTTP/1.1\" 200 1").is_err());
+    }
+}
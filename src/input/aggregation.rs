@@ -0,0 +1,262 @@
+use eyre::{Result, eyre};
+use serde_json::{Map, Value, json};
+use std::{fs, path::Path};
+
+/// The aggregations of an `--agg` file, run against a search input in place
+/// of reading its hits.
+///
+/// The file holds either the `aggs` object itself or a search body with
+/// `aggs` or `aggregations`, whose `query` applies when the input has none.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Aggregations {
+    aggs: Map<String, Value>,
+    query: Option<Value>,
+}
+
+impl Aggregations {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| eyre!("failed to read --agg {}: {err}", path.display()))?;
+        let body: Value = serde_json::from_str(&contents)
+            .map_err(|err| eyre!("failed to parse --agg {} as JSON: {err}", path.display()))?;
+        Self::try_from(body).map_err(|err| eyre!("--agg {}: {err}", path.display()))
+    }
+
+    /// The query from the file, if it came with one.
+    pub fn query(&self) -> Option<&Value> {
+        self.query.as_ref()
+    }
+
+    /// The search body for one page. Only a lone top-level `composite`
+    /// aggregation pages, from the `after_key` of the previous response.
+    pub fn request_body(&self, query: &Value, after: Option<Value>) -> Value {
+        let mut aggs = self.aggs.clone();
+        if let (Some(name), Some(after)) = (self.composite(), after) {
+            aggs[name]["composite"]["after"] = after;
+        }
+        json!({ "size": 0, "query": query, "aggs": aggs })
+    }
+
+    /// The `after_key` to request the next page with, once a composite
+    /// aggregation returns a full page.
+    pub fn next_page(&self, results: &Map<String, Value>) -> Option<Value> {
+        let result = results.get(self.composite()?)?;
+        let buckets = result.get("buckets")?.as_array()?;
+        match buckets.is_empty() {
+            true => None,
+            false => result.get("after_key").cloned(),
+        }
+    }
+
+    fn composite(&self) -> Option<&str> {
+        match self.aggs.iter().collect::<Vec<_>>().as_slice() {
+            [(name, agg)] if agg.get("composite").is_some() => Some(name.as_str()),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<Value> for Aggregations {
+    type Error = eyre::Report;
+
+    fn try_from(body: Value) -> Result<Self> {
+        let Value::Object(mut body) = body else {
+            return Err(eyre!("must hold a JSON object"));
+        };
+        let (aggs, query) = match body.remove("aggs").or_else(|| body.remove("aggregations")) {
+            Some(aggs) => (aggs, body.remove("query")),
+            None => (Value::Object(body), None),
+        };
+        match aggs {
+            Value::Object(aggs) if !aggs.is_empty() => Ok(Self { aggs, query }),
+            _ => Err(eyre!("defines no aggregations")),
+        }
+    }
+}
+
+/// One flat document per leaf bucket: the key of each enclosing bucket under
+/// its aggregation's name, or a composite key's sources by name, the bucket's
+/// `doc_count`, and its metrics by name, with multi-value metrics such as
+/// `stats` as dotted `name.field` keys.
+///
+/// Sibling bucket aggregations each produce their own documents. Results with
+/// only metrics produce one document.
+pub fn bucket_documents(results: &Map<String, Value>) -> Vec<Value> {
+    let mut documents = Vec::new();
+    collect_buckets(results, Map::new(), &mut documents);
+    documents.into_iter().map(Value::Object).collect()
+}
+
+fn collect_buckets(
+    results: &Map<String, Value>,
+    mut fields: Map<String, Value>,
+    documents: &mut Vec<Map<String, Value>>,
+) {
+    let mut bucket_aggs = Vec::new();
+    for (name, result) in results {
+        match buckets(result) {
+            Some(buckets) => bucket_aggs.push((name, buckets)),
+            None => add_metric(&mut fields, name.clone(), result),
+        }
+    }
+    if bucket_aggs.is_empty() {
+        documents.push(fields);
+        return;
+    }
+    for (name, buckets) in bucket_aggs {
+        for (key, bucket) in buckets {
+            let mut document = fields.clone();
+            match key {
+                Value::Object(sources) => document.extend(sources),
+                key => {
+                    document.insert(name.clone(), key);
+                }
+            }
+            document.insert("doc_count".to_string(), bucket["doc_count"].clone());
+            let sub_results = bucket
+                .iter()
+                .filter(|(field, result)| *field != "key" && result.is_object())
+                .map(|(field, result)| (field.clone(), result.clone()))
+                .collect();
+            collect_buckets(&sub_results, document, documents);
+        }
+    }
+}
+
+/// A bucket aggregation's buckets with their keys, whether returned as an
+/// array or keyed by name.
+fn buckets(result: &Value) -> Option<Vec<(Value, &Map<String, Value>)>> {
+    match result.get("buckets")? {
+        Value::Array(buckets) => Some(
+            buckets
+                .iter()
+                .filter_map(Value::as_object)
+                .map(|bucket| {
+                    let key = bucket
+                        .get("key_as_string")
+                        .or_else(|| bucket.get("key"))
+                        .cloned()
+                        .unwrap_or(Value::Null);
+                    (key, bucket)
+                })
+                .collect(),
+        ),
+        Value::Object(buckets) => Some(
+            buckets
+                .iter()
+                .filter_map(|(key, bucket)| Some((json!(key), bucket.as_object()?)))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+fn add_metric(fields: &mut Map<String, Value>, path: String, result: &Value) {
+    match result {
+        Value::Object(result) => match result.get("value") {
+            Some(value) if !value.is_object() => {
+                fields.insert(path, value.clone());
+            }
+            _ => {
+                for (key, value) in result {
+                    if key != "meta" && !key.ends_with("_as_string") {
+                        add_metric(fields, format!("{path}.{key}"), value);
+                    }
+                }
+            }
+        },
+        value => {
+            fields.insert(path, value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Aggregations, bucket_documents};
+    use serde_json::{Value, json};
+
+    fn documents(results: Value) -> Vec<Value> {
+        bucket_documents(results.as_object().unwrap())
+    }
+
+    #[test]
+    fn nested_buckets_become_one_flat_document_per_leaf() {
+        let results = json!({
+            "hosts": { "buckets": [
+                { "key": "web-1", "doc_count": 3, "days": { "buckets": [
+                    { "key": 1760000000000u64, "key_as_string": "2025-10-09", "doc_count": 2,
+                      "bytes": { "value": 10.0 } },
+                    { "key": 1760086400000u64, "key_as_string": "2025-10-10", "doc_count": 1,
+                      "bytes": { "value": 4.0 } },
+                ] } },
+            ] },
+        });
+
+        assert_eq!(
+            documents(results),
+            vec![
+                json!({ "hosts": "web-1", "days": "2025-10-09", "doc_count": 2, "bytes": 10.0 }),
+                json!({ "hosts": "web-1", "days": "2025-10-10", "doc_count": 1, "bytes": 4.0 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn composite_keys_and_multi_value_metrics_are_flattened() {
+        let results = json!({
+            "by_service": {
+                "after_key": { "service": "api", "status": 500 },
+                "buckets": [{
+                    "key": { "service": "api", "status": 500 },
+                    "doc_count": 7,
+                    "latency": { "count": 7, "min": 1.0, "max": 9.0, "min_as_string": "1" },
+                }],
+            },
+        });
+        assert_eq!(
+            documents(results),
+            vec![json!({
+                "service": "api",
+                "status": 500,
+                "doc_count": 7,
+                "latency.count": 7,
+                "latency.min": 1.0,
+                "latency.max": 9.0,
+            })]
+        );
+
+        assert_eq!(
+            documents(json!({ "total": { "value": 42 }, "errors": { "doc_count": 3 } })),
+            vec![json!({ "total": 42, "errors.doc_count": 3 })]
+        );
+    }
+
+    #[test]
+    fn lone_composite_aggregations_page_by_after_key() {
+        let aggregations = Aggregations::try_from(json!({
+            "query": { "term": { "env": "prod" } },
+            "aggs": { "by_service": { "composite": { "sources": [] } } },
+        }))
+        .unwrap();
+        assert_eq!(
+            aggregations.query(),
+            Some(&json!({ "term": { "env": "prod" } }))
+        );
+
+        let body =
+            aggregations.request_body(&json!({ "match_all": {} }), Some(json!({ "s": "a" })));
+        assert_eq!(
+            body["aggs"]["by_service"]["composite"]["after"],
+            json!({ "s": "a" })
+        );
+        let page = json!({ "by_service": { "after_key": { "s": "b" }, "buckets": [{}] } });
+        assert_eq!(
+            aggregations.next_page(page.as_object().unwrap()),
+            Some(json!({ "s": "b" }))
+        );
+        let last = json!({ "by_service": { "after_key": { "s": "b" }, "buckets": [] } });
+        assert_eq!(aggregations.next_page(last.as_object().unwrap()), None);
+        assert!(Aggregations::try_from(json!({ "aggs": {} })).is_err());
+    }
+}
@@ -0,0 +1,507 @@
+use super::{
+    Input, InputFormat, InputKind, has_path_suffix, input_kind_from_path, is_end_of_input,
+    open_content, open_reader,
+};
+use crate::document::Document;
+use eyre::{Result, eyre};
+use flate2::read::MultiGzDecoder;
+use glob::Pattern;
+use std::{
+    env,
+    fs::File,
+    io::{self, Read},
+    path::PathBuf,
+    sync::mpsc::{Receiver, SyncSender, sync_channel},
+    thread,
+};
+use zip::{ZipArchive, result::ZipError};
+
+/// Bytes of an entry decompressed at a time.
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// Chunks decompressed ahead of the reader.
+const CHUNKS_AHEAD: usize = 4;
+
+/// Reads the entries of a zip or tar archive one after another, each as the
+/// file it holds, so an archive of NDJSON or CSV exports loads like the files
+/// would.
+///
+/// Entries are decrypted and decompressed by a thread as they are read, so
+/// nothing is extracted to disk. By default only entries named like CSV,
+/// NDJSON, JSON, or TOON files are read; `--archive-glob` picks others, and
+/// entries without a known extension are read by their content.
+pub struct ArchiveInput {
+    path: PathBuf,
+    kind: ArchiveKind,
+    pattern: Option<Pattern>,
+    password: Option<String>,
+    format: InputFormat,
+    entries: Option<Receiver<Result<Entry>>>,
+    current: Option<Box<Input>>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+    TarZst,
+}
+
+/// An entry named in the archive, with its content as it is decompressed.
+struct Entry {
+    name: String,
+    reader: EntryReader,
+}
+
+/// Reads the chunks of an entry sent by the archive thread.
+struct EntryReader {
+    chunks: Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    offset: usize,
+}
+
+/// Whether `path` names a zip archive or a tarball, compressed or not.
+pub fn is_archive(path: &str) -> bool {
+    ArchiveKind::of(path).is_some()
+}
+
+impl ArchiveKind {
+    fn of(path: &str) -> Option<Self> {
+        let suffixes = [
+            (".zip", Self::Zip),
+            (".tar", Self::Tar),
+            (".tar.gz", Self::TarGz),
+            (".tgz", Self::TarGz),
+            (".tar.zst", Self::TarZst),
+            (".tzst", Self::TarZst),
+        ];
+        suffixes
+            .into_iter()
+            .find(|(suffix, _)| has_path_suffix(path, suffix))
+            .map(|(_, kind)| kind)
+    }
+}
+
+impl ArchiveInput {
+    pub fn new(path: PathBuf) -> Result<Self> {
+        let kind = ArchiveKind::of(&path.to_string_lossy())
+            .ok_or_else(|| eyre!("{} is not a zip or tar archive", path.display()))?;
+        Ok(Self {
+            path,
+            kind,
+            pattern: None,
+            password: None,
+            format: InputFormat::Auto,
+            entries: None,
+            current: None,
+        })
+    }
+
+    /// Reads only entries whose names match `pattern`, decrypting zip entries
+    /// with `password` rather than `$ESPIPE_ZIP_PASSWORD`.
+    pub fn with_entries(
+        mut self,
+        pattern: Option<String>,
+        password: Option<String>,
+    ) -> Result<Self> {
+        if password.is_some() && self.kind != ArchiveKind::Zip {
+            return Err(eyre!(
+                "--zip-password applies to zip archives, not {}",
+                self.path.display()
+            ));
+        }
+        self.pattern = pattern
+            .map(|pattern| {
+                Pattern::new(&pattern)
+                    .map_err(|err| eyre!("Invalid --archive-glob {pattern}: {err}"))
+            })
+            .transpose()?;
+        self.password = password;
+        Ok(self)
+    }
+
+    /// Reads each entry in `format`.
+    pub fn set_format(&mut self, format: InputFormat) {
+        self.format = format;
+    }
+
+    pub fn read_line(&mut self, line_buffer: &mut String) -> Result<Document> {
+        loop {
+            if let Some(input) = &mut self.current {
+                match input.read_line(line_buffer) {
+                    Err(err) if is_end_of_input(&err) => self.current = None,
+                    result => return result,
+                }
+                continue;
+            }
+            if self.entries.is_none() {
+                self.entries = Some(self.start()?);
+            }
+            let entries = self.entries.as_ref().expect("started above");
+            // The thread hangs up after the last entry.
+            let Ok(entry) = entries.recv() else {
+                return Err(eyre!("No archive entry"));
+            };
+            let Entry { name, reader } = entry?;
+            log::info!("Reading {}:{name}", self.path.display());
+            let source = format!("{}:{name}", self.path.display());
+            self.current = Some(Box::new(open_entry(source, &name, reader, self.format)?));
+        }
+    }
+
+    /// Starts a thread sending the entries to read in turn. Zip archives are
+    /// listed first, to fail early when there are none or one needs a password.
+    fn start(&self) -> Result<Receiver<Result<Entry>>> {
+        let archive = self.path.display().to_string();
+        let file = File::open(&self.path).map_err(|err| eyre!("{archive}: {err}"))?;
+        let (sender, entries) = sync_channel(0);
+        let pattern = self.pattern.clone();
+        let thread = thread::Builder::new().name("espipe-archive".to_string());
+        match self.kind {
+            ArchiveKind::Zip => {
+                let mut zip = ZipArchive::new(file).map_err(|err| eyre!("{archive}: {err}"))?;
+                let password = self
+                    .password
+                    .clone()
+                    .or_else(|| env::var("ESPIPE_ZIP_PASSWORD").ok());
+                let selected = self.select_zip_entries(&mut zip, &archive, password.is_some())?;
+                thread
+                    .spawn(move || send_zip_entries(zip, &archive, selected, password, sender))?;
+            }
+            kind => {
+                let reader: Box<dyn Read + Send> = match kind {
+                    ArchiveKind::TarGz => Box::new(MultiGzDecoder::new(file)),
+                    ArchiveKind::TarZst => {
+                        Box::new(zstd::Decoder::new(file).map_err(|err| eyre!("{archive}: {err}"))?)
+                    }
+                    _ => Box::new(file),
+                };
+                thread.spawn(move || {
+                    let failure = match send_tar_entries(reader, pattern.as_ref(), &sender) {
+                        Ok(true) => return,
+                        Ok(false) => no_entries(&archive, pattern.as_ref()),
+                        Err(err) => eyre!("{archive}: {err}"),
+                    };
+                    let _ = sender.send(Err(failure));
+                })?;
+            }
+        }
+        Ok(entries)
+    }
+
+    /// The index and name of each zip entry to read.
+    fn select_zip_entries(
+        &self,
+        zip: &mut ZipArchive<File>,
+        archive: &str,
+        has_password: bool,
+    ) -> Result<Vec<(usize, String)>> {
+        let mut selected = Vec::new();
+        for index in 0..zip.len() {
+            let entry = zip
+                .by_index_raw(index)
+                .map_err(|err| eyre!("{archive}: {err}"))?;
+            let name = entry.name().to_string();
+            if entry.is_dir() || !is_wanted(&name, self.pattern.as_ref()) {
+                continue;
+            }
+            if entry.encrypted() && !has_password {
+                return Err(eyre!(
+                    "{archive}:{name} is encrypted; pass --zip-password or set ESPIPE_ZIP_PASSWORD"
+                ));
+            }
+            selected.push((index, name));
+        }
+        match selected.is_empty() {
+            true => Err(no_entries(archive, self.pattern.as_ref())),
+            false => Ok(selected),
+        }
+    }
+}
+
+impl std::fmt::Display for ArchiveInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path.display())
+    }
+}
+
+impl Read for EntryReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.offset == self.chunk.len() {
+            match self.chunks.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk?;
+                    self.offset = 0;
+                }
+                // The whole entry has been sent.
+                Err(_) => return Ok(0),
+            }
+        }
+        let read = buf.len().min(self.chunk.len() - self.offset);
+        buf[..read].copy_from_slice(&self.chunk[self.offset..self.offset + read]);
+        self.offset += read;
+        Ok(read)
+    }
+}
+
+/// Sends each of the `selected` zip entries in turn, until the input is dropped.
+fn send_zip_entries(
+    mut zip: ZipArchive<File>,
+    archive: &str,
+    selected: Vec<(usize, String)>,
+    password: Option<String>,
+    entries: SyncSender<Result<Entry>>,
+) {
+    for (index, name) in selected {
+        let entry = match &password {
+            Some(password) => zip.by_index_decrypt(index, password.as_bytes()),
+            None => zip.by_index(index),
+        };
+        let sent = match entry {
+            Ok(entry) => send_entry(name, entry, &entries),
+            Err(ZipError::InvalidPassword) => {
+                let _ = entries.send(Err(eyre!("{archive}:{name}: wrong --zip-password")));
+                false
+            }
+            Err(err) => {
+                let _ = entries.send(Err(eyre!("{archive}: {err}")));
+                false
+            }
+        };
+        if !sent {
+            return;
+        }
+    }
+}
+
+/// Sends the wanted members of a tar stream as they come, until the input is
+/// dropped, returning whether any were wanted.
+fn send_tar_entries(
+    reader: Box<dyn Read + Send>,
+    pattern: Option<&Pattern>,
+    entries: &SyncSender<Result<Entry>>,
+) -> Result<bool> {
+    let mut tar = tar::Archive::new(reader);
+    let mut any = false;
+    for entry in tar.entries()? {
+        let entry = entry?;
+        let path = entry.path()?;
+        let name = path.to_string_lossy();
+        let name = name.strip_prefix("./").unwrap_or(&name).to_string();
+        if !entry.header().entry_type().is_file() || !is_wanted(&name, pattern) {
+            continue;
+        }
+        any = true;
+        if !send_entry(name, entry, entries) {
+            break;
+        }
+    }
+    Ok(any)
+}
+
+/// Sends an entry, then its content chunk by chunk, returning `false` when
+/// the input is dropped or the content cannot be read, so no entry follows.
+fn send_entry(name: String, mut content: impl Read, entries: &SyncSender<Result<Entry>>) -> bool {
+    let (sender, chunks) = sync_channel(CHUNKS_AHEAD);
+    let reader = EntryReader {
+        chunks,
+        chunk: Vec::new(),
+        offset: 0,
+    };
+    if entries.send(Ok(Entry { name, reader })).is_err() {
+        return false;
+    }
+    loop {
+        let mut buffer = vec![0; CHUNK_BYTES];
+        let chunk = match content.read(&mut buffer) {
+            Ok(0) => return true,
+            Ok(read) => {
+                buffer.truncate(read);
+                Ok(buffer)
+            }
+            Err(err) => Err(err),
+        };
+        let failed = chunk.is_err();
+        if sender.send(chunk).is_err() || failed {
+            return false;
+        }
+    }
+}
+
+/// Whether the entry `name` is read: it matches `pattern`, or without one, is
+/// a data file. The `._` files and `__MACOSX` entries macOS adds are skipped.
+fn is_wanted(name: &str, pattern: Option<&Pattern>) -> bool {
+    let file_name = name.rsplit('/').next().unwrap_or(name);
+    if name.starts_with("__MACOSX/") || file_name.starts_with("._") {
+        return false;
+    }
+    match pattern {
+        Some(pattern) => pattern.matches(name),
+        None => is_data_file(name),
+    }
+}
+
+fn no_entries(archive: &str, pattern: Option<&Pattern>) -> eyre::Report {
+    match pattern {
+        Some(pattern) => eyre!("{archive} has no entries matching {pattern}"),
+        None => eyre!(
+            "{archive} has no CSV, NDJSON, JSON, or TOON entries; pick others with --archive-glob"
+        ),
+    }
+}
+
+/// Whether an entry's name is that of a CSV, NDJSON, JSON, or TOON file,
+/// gzip-compressed or not.
+fn is_data_file(name: &str) -> bool {
+    let name = name.strip_suffix(".gz").unwrap_or(name);
+    matches!(
+        input_kind_from_path(name),
+        Some(InputKind::Csv | InputKind::Ndjson | InputKind::Json | InputKind::Toon)
+    )
+}
+
+/// Opens an entry by its extension under `Auto`, or else by `format`, as a
+/// local file of the same name would be, decompressing `.gz` entries first.
+fn open_entry(
+    source: String,
+    name: &str,
+    reader: EntryReader,
+    format: InputFormat,
+) -> Result<Input> {
+    let reader: Box<dyn Read + Send> = match has_path_suffix(name, ".gz") {
+        true => Box::new(MultiGzDecoder::new(reader)),
+        false => Box::new(reader),
+    };
+    let kind = input_kind_from_path(name.strip_suffix(".gz").unwrap_or(name));
+    match (format, kind) {
+        (
+            InputFormat::Auto,
+            Some(kind @ (InputKind::Csv | InputKind::Ndjson | InputKind::Json | InputKind::Toon)),
+        ) => open_reader(kind, source, reader, None)?.with_input_format(format),
+        (
+            InputFormat::Auto | InputFormat::Csv | InputFormat::Json | InputFormat::Concatenated,
+            _,
+        ) => open_content(source, reader, format, None)?.with_input_format(InputFormat::Auto),
+        // Line formats read the entry as lines, whatever its extension.
+        (format, _) => {
+            open_reader(InputKind::Ndjson, source, reader, None)?.with_input_format(format)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ArchiveInput;
+    use crate::input::{Input, InputFormat};
+    use flate2::{Compression, write::GzEncoder};
+    use serde_json::{Value, json};
+    use std::io::Write;
+    use zip::{AesMode, ZipWriter, write::SimpleFileOptions};
+
+    fn read_all(mut input: Input) -> eyre::Result<Vec<Value>> {
+        let mut line_buffer = String::new();
+        let mut docs = Vec::new();
+        while let Some(doc) = input.read_next(&mut line_buffer)? {
+            docs.push(serde_json::from_str(doc.get())?);
+            line_buffer.clear();
+        }
+        Ok(docs)
+    }
+
+    #[test]
+    fn encrypted_entries_matching_the_glob_are_read_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.zip");
+        let mut zip = ZipWriter::new(std::fs::File::create(&path).unwrap());
+        let options = SimpleFileOptions::default().with_aes_encryption(AesMode::Aes256, "s3cret");
+        for (name, contents) in [
+            ("logs/a.ndjson", "{\"n\":1}\n{\"n\":2}\n"),
+            ("logs/b.csv", "n,level\n3,info\n"),
+            ("README.txt", "not data"),
+        ] {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let archive = |pattern: Option<&str>, password: Option<&str>| {
+            let entries = ArchiveInput::new(path.clone())
+                .unwrap()
+                .with_entries(pattern.map(String::from), password.map(String::from))
+                .unwrap();
+            read_all(
+                Input::Archive {
+                    entries: Box::new(entries),
+                }
+                .with_input_format(InputFormat::Auto)
+                .unwrap(),
+            )
+        };
+
+        assert_eq!(
+            archive(None, Some("s3cret")).unwrap(),
+            [
+                json!({"n": 1}),
+                json!({"n": 2}),
+                json!({"n": "3", "level": "info"})
+            ]
+        );
+        assert_eq!(
+            archive(Some("logs/*.ndjson"), Some("s3cret"))
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(
+            archive(Some("*.ndjson"), Some("wrong"))
+                .unwrap_err()
+                .to_string()
+                .contains("wrong --zip-password")
+        );
+        assert!(archive(Some("*.parquet"), Some("s3cret")).is_err());
+    }
+
+    #[test]
+    fn tarball_members_stream_in_order_without_macos_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs.tar.gz");
+        let encoder = GzEncoder::new(std::fs::File::create(&path).unwrap(), Compression::fast());
+        let mut tar = tar::Builder::new(encoder);
+        for (name, contents) in [
+            ("./logs/00.ndjson", "{\"hour\":0}\n"),
+            ("./logs/._00.ndjson", "\u{0}\u{5}\u{16}\u{7}"),
+            ("./logs/01.ndjson", "{\"hour\":1}\n"),
+            ("./notes.md", "# notes"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, contents.as_bytes())
+                .unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let tarball = |pattern: Option<&str>| {
+            let entries = ArchiveInput::new(path.clone())
+                .unwrap()
+                .with_entries(pattern.map(String::from), None)
+                .unwrap();
+            read_all(Input::Archive {
+                entries: Box::new(entries),
+            })
+        };
+
+        let hours = [json!({"hour": 0}), json!({"hour": 1})];
+        assert_eq!(tarball(None).unwrap(), hours);
+        assert_eq!(tarball(Some("logs/*.ndjson")).unwrap(), hours);
+        assert!(tarball(Some("*.csv")).is_err());
+        assert!(
+            ArchiveInput::new(path.clone())
+                .unwrap()
+                .with_entries(None, Some("s3cret".to_string()))
+                .is_err()
+        );
+    }
+}
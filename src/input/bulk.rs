@@ -0,0 +1,158 @@
+use super::{LinePosition, MalformedRecord, parse_json_record};
+use crate::document::Document;
+use eyre::{Result, eyre};
+use serde_json::{Map, Value};
+use std::io::BufRead;
+
+/// Action metadata that is not `_`-prefixed, as accepted by the `_bulk` API.
+const ACTION_PARAMETERS: [&str; 11] = [
+    "dynamic_templates",
+    "if_primary_term",
+    "if_seq_no",
+    "list_executed_pipelines",
+    "pipeline",
+    "require_alias",
+    "require_data_stream",
+    "retry_on_conflict",
+    "routing",
+    "version",
+    "version_type",
+];
+
+/// Whether a line is a bulk action rather than a document, so NDJSON files
+/// holding `_bulk` bodies can be replayed without `--input-format bulk`.
+///
+/// Only a single `index`, `create`, `update`, or `delete` key holding action
+/// metadata counts, so documents with a field named `index` still read as NDJSON.
+pub fn is_action_line(line: &str) -> bool {
+    let Ok(Value::Object(action)) = serde_json::from_str::<Value>(line) else {
+        return false;
+    };
+    match single_entry(&action) {
+        Some(("index" | "create" | "update" | "delete", Value::Object(metadata))) => metadata
+            .keys()
+            .all(|key| key.starts_with('_') || ACTION_PARAMETERS.contains(&key.as_str())),
+        _ => false,
+    }
+}
+
+/// Reads the next action and source pair, skipping blank lines between pairs.
+///
+/// A bad pair is returned as a [`MalformedRecord`] covering both lines, so the
+/// next read starts on the following action.
+pub fn read_pair<R: BufRead + ?Sized>(
+    reader: &mut R,
+    line_buffer: &mut String,
+    position: &mut LinePosition,
+) -> Result<Document> {
+    let start = loop {
+        line_buffer.clear();
+        reader.read_line(line_buffer)?;
+        if line_buffer.is_empty() {
+            return Err(eyre!("No JSON record"));
+        }
+        let start = position.advance(line_buffer);
+        if !line_buffer.trim().is_empty() {
+            break start;
+        }
+    };
+    let action_len = line_buffer.len();
+    let action = parse_action(line_buffer);
+    if let Ok(false) = action {
+        return Err(MalformedRecord::lines(
+            start,
+            line_buffer,
+            "bulk delete actions are not supported".to_string(),
+        )
+        .into());
+    }
+    reader.read_line(line_buffer)?;
+    if line_buffer.len() == action_len {
+        return Err(MalformedRecord::lines(
+            start,
+            line_buffer,
+            "bulk action has no document".to_string(),
+        )
+        .into());
+    }
+    position.advance(&line_buffer[action_len..]);
+    action
+        .and_then(|_| parse_json_record(&line_buffer[action_len..]))
+        .map(|source| Document::from(source).with_action(&line_buffer[..action_len]))
+        .map_err(|err| MalformedRecord::lines(start, line_buffer, err.to_string()).into())
+}
+
+/// Checks an action line, returning whether a source line follows it.
+fn parse_action(line: &str) -> Result<bool> {
+    let action: Value =
+        serde_json::from_str(line).map_err(|err| eyre!("invalid bulk action: {err}"))?;
+    let Some((name, metadata)) = action.as_object().and_then(single_entry) else {
+        return Err(eyre!(
+            "invalid bulk action, expected a single index, create, update, or delete key"
+        ));
+    };
+    if !metadata.is_object() {
+        return Err(eyre!("bulk action '{name}' must hold an object"));
+    }
+    match name {
+        "index" | "create" | "update" => Ok(true),
+        "delete" => Ok(false),
+        name => Err(eyre!("unknown bulk action '{name}'")),
+    }
+}
+
+fn single_entry(object: &Map<String, Value>) -> Option<(&str, &Value)> {
+    match object.len() {
+        1 => object
+            .iter()
+            .next()
+            .map(|(key, value)| (key.as_str(), value)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LinePosition, MalformedRecord, is_action_line, read_pair};
+    use std::io::Cursor;
+
+    #[test]
+    fn action_lines_are_told_apart_from_documents() {
+        assert!(is_action_line(r#"{"index":{}}"#));
+        assert!(is_action_line(
+            r#"{"create":{"_index":"logs","_id":"1","pipeline":"p"}}"#
+        ));
+        assert!(is_action_line(r#"{"delete":{"_id":"1"}}"#));
+        assert!(!is_action_line(r#"{"index":{"name":"logs"}}"#));
+        assert!(!is_action_line(r#"{"index":"logs"}"#));
+        assert!(!is_action_line(r#"{"index":{},"message":"hi"}"#));
+        assert!(!is_action_line("not json"));
+    }
+
+    #[test]
+    fn pairs_keep_their_action_and_skip_bad_pairs_whole() {
+        let mut reader = Cursor::new(
+            "{\"index\":{\"_index\":\"a\",\"_id\":\"1\"}}\n{\"n\":1}\n\n\
+             {\"delete\":{\"_id\":\"2\"}}\n\
+             {\"update\":{\"_id\":\"3\"}}\n{\"doc\":{\"n\":3}}\n",
+        );
+        let mut line_buffer = String::new();
+        let mut position = LinePosition::default();
+        let mut read = || read_pair(&mut reader, &mut line_buffer, &mut position);
+
+        let doc = read().unwrap();
+        assert_eq!(doc.get(), r#"{"n":1}"#);
+        assert_eq!(doc.action(), Some(r#"{"index":{"_index":"a","_id":"1"}}"#));
+        let malformed = read().unwrap_err().downcast::<MalformedRecord>().unwrap();
+        assert_eq!((malformed.line, malformed.offset), (4, 44));
+        assert_eq!(malformed.reason, "bulk delete actions are not supported");
+        assert_eq!(
+            malformed.record.as_deref(),
+            Some(r#"{"delete":{"_id":"2"}}"#)
+        );
+        let doc = read().unwrap();
+        assert_eq!(doc.get(), r#"{"doc":{"n":3}}"#);
+        assert_eq!(doc.action(), Some(r#"{"update":{"_id":"3"}}"#));
+        assert_eq!(read().unwrap_err().to_string(), "No JSON record");
+    }
+}
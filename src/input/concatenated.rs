@@ -0,0 +1,104 @@
+use eyre::{Result, eyre};
+use serde_json::{Deserializer, StreamDeserializer, de::IoRead, value::RawValue};
+use std::io::Read;
+use tempfile::NamedTempFile;
+
+/// Reads JSON objects written back to back, pretty-printed or not, as some
+/// tools emit them instead of one per line.
+///
+/// Objects are parsed one at a time as the reader is consumed, so files of any
+/// size stream like NDJSON, and pretty-printed ones are compacted onto one
+/// line. A syntax error ends the input, since there is no line to skip to.
+pub struct ConcatenatedInput {
+    source: String,
+    values: StreamDeserializer<'static, IoRead<Box<dyn Read + Send>>, Box<RawValue>>,
+    count: usize,
+    _temp_file: Option<NamedTempFile>,
+}
+
+impl ConcatenatedInput {
+    pub fn new(
+        source: String,
+        reader: Box<dyn Read + Send>,
+        _temp_file: Option<NamedTempFile>,
+    ) -> Self {
+        Self {
+            source,
+            values: Deserializer::from_reader(reader).into_iter(),
+            count: 0,
+            _temp_file,
+        }
+    }
+
+    pub fn read_line(&mut self) -> Result<Box<RawValue>> {
+        let value = match self.values.next() {
+            None => return Err(eyre!("No JSON record")),
+            Some(value) => value.map_err(|err| {
+                eyre!(
+                    "{}: invalid JSON after {} objects: {err}",
+                    self.source,
+                    self.count
+                )
+            })?,
+        };
+        if !value.get().starts_with('{') {
+            return Err(eyre!(
+                "{}: JSON value {} is not an object",
+                self.source,
+                self.count
+            ));
+        }
+        self.count += 1;
+        compact(value)
+    }
+}
+
+/// Drops the whitespace between the tokens of a multi-line value, which JSON
+/// strings cannot contain unescaped, keeping the order of its keys.
+fn compact(value: Box<RawValue>) -> Result<Box<RawValue>> {
+    if !value.get().contains('\n') {
+        return Ok(value);
+    }
+    let mut json = String::with_capacity(value.get().len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for char in value.get().chars() {
+        match char {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ' ' | '\t' | '\n' | '\r' if !in_string => continue,
+            _ => {}
+        }
+        json.push(char);
+    }
+    Ok(RawValue::from_string(json)?)
+}
+
+impl std::fmt::Display for ConcatenatedInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConcatenatedInput;
+
+    #[test]
+    fn pretty_printed_objects_are_read_one_at_a_time() {
+        let text = "{\n  \"a\": 1,\n  \"b\": [\"x \\\" y\", 2]\n}\n{\"a\": 2}{\"a\": 3}\n\n[1]\n";
+        let mut input = ConcatenatedInput::new("test".to_string(), Box::new(text.as_bytes()), None);
+        assert_eq!(
+            input.read_line().unwrap().get(),
+            r#"{"a":1,"b":["x \" y",2]}"#
+        );
+        assert_eq!(input.read_line().unwrap().get(), "{\"a\": 2}");
+        assert_eq!(input.read_line().unwrap().get(), "{\"a\": 3}");
+        assert_eq!(
+            input.read_line().unwrap_err().to_string(),
+            "test: JSON value 3 is not an object"
+        );
+        assert_eq!(input.read_line().unwrap_err().to_string(), "No JSON record");
+    }
+}
@@ -0,0 +1,399 @@
+use crate::manifest::redact_uri;
+use eyre::{Result, eyre};
+use reqwest::{
+    StatusCode,
+    blocking::{Client, Response},
+    header::{ACCEPT_RANGES, CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE},
+};
+use std::{
+    fs::File,
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
+    thread::sleep,
+    time::Duration,
+};
+use tempfile::NamedTempFile;
+
+/// Resumes in a row that may fail to fetch any more of the body before the
+/// download fails. Any progress resets the count, so a long download can
+/// survive any number of interruptions spread over it.
+const MAX_STALLED: u32 = 6;
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Inputs are only split into parts at least this large, as smaller ones
+/// gain less from concurrency than the extra requests cost.
+const MIN_PART_SIZE: u64 = 8 * 1024 * 1024;
+
+/// What a download is checked against when it resumes, taken from the first
+/// response.
+#[derive(Debug)]
+struct Download<'a> {
+    client: &'a Client,
+    url: &'a str,
+    length: Option<u64>,
+    etag: Option<String>,
+    /// The `If-Range` validator: a strong `ETag`, or else `Last-Modified`.
+    validator: Option<String>,
+    resumable: bool,
+    /// Whether the server says it serves byte ranges, which splitting needs.
+    ranges: bool,
+}
+
+/// Copies the body of `response` to `temp_file`, returning its length.
+///
+/// When the connection drops before the `Content-Length` is reached, the rest
+/// is fetched from `url` with a `Range` request, after a backoff. A resumed
+/// response must continue at the byte the download stopped at, with the same
+/// length and `ETag`, so the file is never spliced from two versions of the
+/// source. Servers that give no length or refuse ranges are not resumed.
+///
+/// With a `parallelism` over 1, a large input from a server that serves
+/// ranges is instead fetched as that many ranges at once, each written in
+/// place and resumed on its own, so the file matches the input byte for byte
+/// and the parser never sees a line cut between parts.
+pub fn download(
+    client: &Client,
+    url: &str,
+    response: Response,
+    temp_file: &mut NamedTempFile,
+    parallelism: usize,
+) -> Result<u64> {
+    let download = Download::new(client, url, &response);
+    let parts = match download.length {
+        Some(length) if download.ranges => part_ranges(length, parallelism),
+        _ => Vec::new(),
+    };
+    if parts.len() < 2 {
+        return copy_range(
+            &download,
+            Ok(response),
+            temp_file.as_file_mut(),
+            0,
+            download.length,
+        );
+    }
+    // The first response only told us the length; its body is fetched in parts.
+    drop(response);
+    let length = download.length.unwrap_or_default();
+    log::debug!(
+        "Downloading {} bytes of {} in {} parts",
+        length,
+        redact_uri(url),
+        parts.len()
+    );
+    temp_file.as_file().set_len(length)?;
+    let temp_file = &*temp_file;
+    let download = &download;
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = parts
+            .into_iter()
+            .map(|(start, end)| {
+                scope.spawn(move || -> Result<u64> {
+                    let mut file = temp_file.reopen()?;
+                    file.seek(SeekFrom::Start(start))?;
+                    let response = download.request_range(start, end)?;
+                    copy_range(download, response, &mut file, start, Some(end))
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .map_err(|_| eyre!("A download worker panicked"))?
+            })
+            .sum::<Result<u64>>()
+    })?;
+    temp_file.as_file().seek(SeekFrom::End(0))?;
+    Ok(length)
+}
+
+/// Splits `length` bytes into up to `parallelism` ranges of at least
+/// `MIN_PART_SIZE`, as start and end offsets.
+fn part_ranges(length: u64, parallelism: usize) -> Vec<(u64, u64)> {
+    let parts = (parallelism as u64).min(length / MIN_PART_SIZE).max(1);
+    let size = length.div_ceil(parts);
+    (0..parts)
+        .map(|part| (part * size, ((part + 1) * size).min(length)))
+        .filter(|(start, end)| start < end)
+        .collect()
+}
+
+/// Copies `response` to `file` as the bytes from `start` up to `end`, or to
+/// the end of the body, resuming with range requests when it is cut short.
+/// Returns how many bytes were copied.
+fn copy_range(
+    download: &Download,
+    mut response: std::result::Result<Response, String>,
+    file: &mut File,
+    start: u64,
+    end: Option<u64>,
+) -> Result<u64> {
+    let mut written = start;
+    let mut progress = start;
+    let mut stalled = 0;
+    let mut backoff = Duration::from_secs(1);
+    loop {
+        let reason = match response {
+            Ok(mut body) => match copy_body(&mut body, file, &mut written)? {
+                None if end.is_none_or(|end| written == end) => {
+                    return Ok(written - start);
+                }
+                None => "the connection closed".to_string(),
+                Some(err) => err.to_string(),
+            },
+            Err(reason) => reason,
+        };
+        let Some(end) = end.filter(|_| download.resumable) else {
+            return Err(eyre!(
+                "Remote fetch failed after {written} bytes, and the server does not allow resuming: {reason}"
+            ));
+        };
+        if written > end {
+            return Err(eyre!(
+                "Remote fetch sent {} bytes, more than the {} asked for",
+                written - start,
+                end - start
+            ));
+        }
+        if written > progress {
+            progress = written;
+            stalled = 0;
+            backoff = Duration::from_secs(1);
+        }
+        stalled += 1;
+        if stalled > MAX_STALLED {
+            return Err(eyre!(
+                "Remote fetch stopped at byte {written} of {end} after {MAX_STALLED} resumes: {reason}"
+            ));
+        }
+        log::warn!(
+            "Remote fetch interrupted at byte {written} of {end} by {reason}, resuming (attempt {stalled}, backoff {backoff:?})"
+        );
+        sleep(backoff);
+        backoff = std::cmp::min(backoff * 2, MAX_BACKOFF);
+        response = download.request_range(written, end)?;
+    }
+}
+
+/// Copies `body` to `file` until the body ends, returning the read error
+/// that ended it early, if any. Write errors fail the download.
+fn copy_body(
+    body: &mut Response,
+    file: &mut File,
+    written: &mut u64,
+) -> Result<Option<std::io::Error>> {
+    let mut buf = vec![0; CHUNK_SIZE];
+    loop {
+        match body.read(&mut buf) {
+            Ok(0) => return Ok(None),
+            Ok(count) => {
+                file.write_all(&buf[..count])?;
+                *written += count as u64;
+            }
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Ok(Some(err)),
+        }
+    }
+}
+
+impl<'a> Download<'a> {
+    fn new(client: &'a Client, url: &'a str, response: &Response) -> Self {
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header(ETAG);
+        let validator = etag
+            .clone()
+            .filter(|etag| !etag.starts_with("W/"))
+            .or_else(|| header(LAST_MODIFIED));
+        let accept_ranges = header(ACCEPT_RANGES);
+        Self {
+            client,
+            url,
+            length: response.content_length(),
+            resumable: accept_ranges.as_deref() != Some("none"),
+            ranges: accept_ranges.as_deref() == Some("bytes"),
+            etag,
+            validator,
+        }
+    }
+
+    /// Requests the bytes from `from` up to `end`. Failures worth another
+    /// attempt are returned as the inner error; a changed source fails the
+    /// download.
+    fn request_range(&self, from: u64, end: u64) -> Result<std::result::Result<Response, String>> {
+        let mut request = self
+            .client
+            .get(self.url)
+            .header(RANGE, format!("bytes={from}-{}", end - 1));
+        if let Some(validator) = &self.validator {
+            request = request.header(IF_RANGE, validator);
+        }
+        let response = match request.send() {
+            Ok(response) => response,
+            Err(err) if err.is_connect() || err.is_timeout() || err.is_body() => {
+                return Ok(Err(err.to_string()));
+            }
+            Err(err) => return Err(err.into()),
+        };
+        match response.status() {
+            StatusCode::PARTIAL_CONTENT => {}
+            // The whole body again: the source changed, or ranges are ignored.
+            StatusCode::OK => {
+                return Err(eyre!(
+                    "{} changed since the download began, or does not serve byte ranges",
+                    redact_uri(self.url)
+                ));
+            }
+            status if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() => {
+                return Ok(Err(format!("HTTP {status}")));
+            }
+            status => {
+                return Err(eyre!(
+                    "Resuming remote fetch failed with HTTP status {status}"
+                ));
+            }
+        }
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|value| value.to_str().ok());
+        if self.etag.is_some() && etag.is_some() && self.etag.as_deref() != etag {
+            return Err(eyre!(
+                "{} changed since the download began: its ETag is no longer {}",
+                redact_uri(self.url),
+                self.etag.as_deref().unwrap_or_default()
+            ));
+        }
+        let range = response
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_content_range);
+        match range {
+            Some((first, last, length))
+                if first == from && last + 1 == end && Some(length) == self.length =>
+            {
+                Ok(Ok(response))
+            }
+            _ => Err(eyre!(
+                "{} sent a range other than bytes {from} to {end}",
+                redact_uri(self.url)
+            )),
+        }
+    }
+}
+
+/// The first and last byte and the complete length of a `Content-Range` such
+/// as `bytes 100-199/1000`.
+fn parse_content_range(value: &str) -> Option<(u64, u64, u64)> {
+    let (range, length) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let (first, last) = range.split_once('-')?;
+    Some((
+        first.trim().parse().ok()?,
+        last.trim().parse().ok()?,
+        length.trim().parse().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MIN_PART_SIZE, download, part_ranges};
+    use reqwest::blocking::Client;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+        thread,
+    };
+
+    const BODY: &str = "{\"id\":1}\n{\"id\":2}\n{\"id\":3}\n";
+
+    /// Serves `BODY` but drops the first connection partway, then answers
+    /// each range request with the `ETag` in `etags`, in order.
+    fn spawn_flaky_server(
+        etags: &'static [&'static str],
+    ) -> (String, mpsc::Receiver<String>, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            for (n, etag) in etags.iter().enumerate() {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                    let count = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..count]);
+                }
+                tx.send(String::from_utf8(request).unwrap()).unwrap();
+                let response = match n {
+                    0 => format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\nETag: {etag}\r\nConnection: close\r\n\r\n{}",
+                        BODY.len(),
+                        &BODY[..10]
+                    ),
+                    _ => format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes 10-{}/{}\r\nETag: {etag}\r\nConnection: close\r\n\r\n{}",
+                        BODY.len() - 10,
+                        BODY.len() - 1,
+                        BODY.len(),
+                        &BODY[10..]
+                    ),
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (format!("http://127.0.0.1:{port}/events.ndjson"), rx, handle)
+    }
+
+    #[test]
+    fn interrupted_downloads_resume_from_the_last_byte() {
+        let (url, requests, handle) = spawn_flaky_server(&["\"v1\"", "\"v1\""]);
+        let client = Client::new();
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+
+        let response = client.get(&url).send().unwrap();
+        let length = download(&client, &url, response, &mut file, 1).unwrap();
+
+        assert_eq!(length, BODY.len() as u64);
+        assert_eq!(std::fs::read_to_string(file.path()).unwrap(), BODY);
+        let resume = requests.iter().nth(1).unwrap().to_lowercase();
+        assert!(resume.contains("range: bytes=10-"), "{resume}");
+        assert!(resume.contains("if-range: \"v1\""), "{resume}");
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn downloads_fail_when_the_source_changes() {
+        let (url, _requests, handle) = spawn_flaky_server(&["\"v1\"", "\"v2\""]);
+        let client = Client::new();
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+
+        let response = client.get(&url).send().unwrap();
+        let err = download(&client, &url, response, &mut file, 1).unwrap_err();
+
+        assert!(err.to_string().contains("changed since the download began"));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn large_inputs_split_into_even_ranges() {
+        let length = 5 * MIN_PART_SIZE + 3;
+        let parts = part_ranges(length, 4);
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts[0].0, 0);
+        assert_eq!(parts[3].1, length);
+        assert!(parts.windows(2).all(|pair| pair[0].1 == pair[1].0));
+
+        assert_eq!(part_ranges(MIN_PART_SIZE, 8).len(), 1);
+        assert_eq!(part_ranges(3 * MIN_PART_SIZE, 8).len(), 3);
+        assert_eq!(part_ranges(length, 1), [(0, length)]);
+    }
+}
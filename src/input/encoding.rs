@@ -0,0 +1,197 @@
+use clap::ValueEnum;
+use encoding_rs::{CoderResult, Decoder, DecoderResult, Encoding, UTF_8};
+use std::io::{self, Read};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// What `--on-invalid-encoding` does with bytes that are not valid in the
+/// input encoding.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum InvalidEncoding {
+    /// Fail the read with the byte offset of the invalid bytes
+    #[default]
+    Fail,
+    /// Replace the invalid bytes with U+FFFD and warn once
+    Replace,
+}
+
+/// How file, stdin, and `https://` input bytes are turned into UTF-8, from
+/// `--input-encoding` and `--on-invalid-encoding`.
+#[derive(Clone, Copy, Debug)]
+pub struct InputEncoding {
+    encoding: &'static Encoding,
+    lossy: bool,
+}
+
+impl InputEncoding {
+    /// `None` for strict UTF-8, which is read as it is.
+    pub fn new(encoding: Option<&'static Encoding>, invalid: InvalidEncoding) -> Option<Self> {
+        let encoding = encoding.unwrap_or(UTF_8);
+        let lossy = invalid == InvalidEncoding::Replace;
+        (encoding != UTF_8 || lossy).then_some(Self { encoding, lossy })
+    }
+
+    /// Reads `reader`, named `source` in errors, as UTF-8.
+    pub fn reader(self, reader: Box<dyn Read + Send>, source: &str) -> Box<dyn Read + Send> {
+        Box::new(Transcoder {
+            inner: reader,
+            source: source.to_string(),
+            encoding: self.encoding,
+            decoder: self.encoding.new_decoder(),
+            lossy: self.lossy,
+            input: vec![0; CHUNK_SIZE].into_boxed_slice(),
+            start: 0,
+            end: 0,
+            output: vec![0; CHUNK_SIZE * 3],
+            emitted: 0,
+            written: 0,
+            offset: 0,
+            eof: false,
+            finished: false,
+            warned: false,
+        })
+    }
+}
+
+/// Decodes a reader in another encoding, or UTF-8 with invalid bytes, into
+/// UTF-8 as it is read, so the line and record readers above it never see
+/// the original bytes.
+///
+/// A byte order mark at the start picks UTF-8 or UTF-16 over the encoding
+/// given, and is dropped.
+struct Transcoder<R> {
+    inner: R,
+    source: String,
+    encoding: &'static Encoding,
+    decoder: Decoder,
+    lossy: bool,
+    input: Box<[u8]>,
+    start: usize,
+    end: usize,
+    output: Vec<u8>,
+    emitted: usize,
+    written: usize,
+    /// Input bytes decoded so far.
+    offset: u64,
+    eof: bool,
+    finished: bool,
+    warned: bool,
+}
+
+impl<R: Read> Transcoder<R> {
+    /// Decodes the next run of input into `output`, returning `false` at the end.
+    fn decode(&mut self) -> io::Result<bool> {
+        loop {
+            if self.finished {
+                return Ok(false);
+            }
+            if self.start == self.end && !self.eof {
+                self.start = 0;
+                self.end = self.inner.read(&mut self.input)?;
+                self.eof = self.end == 0;
+            }
+            let input = &self.input[self.start..self.end];
+            let (done, read, written) = match self.lossy {
+                true => {
+                    let (result, read, written, replaced) =
+                        self.decoder
+                            .decode_to_utf8(input, &mut self.output, self.eof);
+                    if replaced && !self.warned {
+                        self.warned = true;
+                        log::warn!(
+                            "{}: replaced bytes that are not valid {} near byte offset {}",
+                            self.source,
+                            self.encoding.name(),
+                            self.offset
+                        );
+                    }
+                    (result == CoderResult::InputEmpty, read, written)
+                }
+                false => match self.decoder.decode_to_utf8_without_replacement(
+                    input,
+                    &mut self.output,
+                    self.eof,
+                ) {
+                    (DecoderResult::Malformed(bad, after), read, _) => {
+                        let offset = self.offset + read as u64 - u64::from(bad) - u64::from(after);
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!(
+                                "{}: byte offset {offset} is not valid {}, see --on-invalid-encoding",
+                                self.source,
+                                self.encoding.name()
+                            ),
+                        ));
+                    }
+                    (result, read, written) => (result == DecoderResult::InputEmpty, read, written),
+                },
+            };
+            self.start += read;
+            self.offset += read as u64;
+            self.finished = done && self.eof;
+            if written > 0 {
+                self.emitted = 0;
+                self.written = written;
+                return Ok(true);
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for Transcoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.emitted == self.written && !self.decode()? {
+            return Ok(0);
+        }
+        let len = buf.len().min(self.written - self.emitted);
+        buf[..len].copy_from_slice(&self.output[self.emitted..self.emitted + len]);
+        self.emitted += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InputEncoding, InvalidEncoding};
+    use encoding_rs::{UTF_8, UTF_16LE, WINDOWS_1252};
+    use std::io::{Cursor, Read};
+
+    fn read(encoding: InputEncoding, bytes: &[u8]) -> std::io::Result<String> {
+        let mut text = String::new();
+        encoding
+            .reader(Box::new(Cursor::new(bytes.to_vec())), "test.ndjson")
+            .read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    #[test]
+    fn inputs_are_transcoded_to_utf8_strictly_or_lossily() {
+        assert!(InputEncoding::new(None, InvalidEncoding::Fail).is_none());
+
+        let latin1 = InputEncoding::new(Some(WINDOWS_1252), InvalidEncoding::Fail).unwrap();
+        assert_eq!(
+            read(latin1, b"{\"name\":\"Jos\xe9\"}\r\n").unwrap(),
+            "{\"name\":\"José\"}\r\n"
+        );
+
+        let utf16: Vec<u8> = "\u{feff}{\"a\":\"ü\"}\n{\"b\":2}\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let utf16le = InputEncoding::new(Some(UTF_16LE), InvalidEncoding::Fail).unwrap();
+        assert_eq!(read(utf16le, &utf16).unwrap(), "{\"a\":\"ü\"}\n{\"b\":2}\n");
+
+        let invalid = b"{\"a\":1}\n{\"b\":\"\xff\"}\n";
+        let lossy = InputEncoding::new(None, InvalidEncoding::Replace).unwrap();
+        assert_eq!(
+            read(lossy, invalid).unwrap(),
+            "{\"a\":1}\n{\"b\":\"\u{fffd}\"}\n"
+        );
+        let strict = InputEncoding {
+            encoding: UTF_8,
+            lossy: false,
+        };
+        let err = read(strict, invalid).unwrap_err().to_string();
+        assert!(err.contains("byte offset 14 is not valid UTF-8"), "{err}");
+    }
+}
@@ -0,0 +1,281 @@
+use eyre::{Result, eyre};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+/// How often a file still being read has its progress committed, so a crash
+/// mid-file loses at most this much of it.
+const COMMIT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What `--file-state` records about one file of a multi-file input.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+struct Recorded {
+    size: u64,
+    /// Modification time in milliseconds since the Unix epoch.
+    modified: u64,
+    /// Documents from the start of the file that the output acknowledged.
+    docs: usize,
+    complete: bool,
+}
+
+impl Recorded {
+    fn is_unchanged(&self, path: &Path) -> bool {
+        fingerprint(path)
+            .is_some_and(|(size, modified)| (size, modified) == (self.size, self.modified))
+    }
+}
+
+/// The per-file progress of a multi-file or glob input, kept in a JSON state
+/// file across runs, so running again over the same files reads only the new
+/// ones, those changed since, and the rest of one a previous run stopped in.
+///
+/// Files are identified by their canonical path, and counted as changed when
+/// their size or modification time differs from when they were read. Progress
+/// is only written once the output has acknowledged it, and not at all for
+/// documents a bulk response failed.
+#[derive(Debug)]
+pub struct FileState {
+    path: PathBuf,
+    files: BTreeMap<String, Recorded>,
+    /// The file being read, its progress so far, and its document count.
+    current: Option<(String, Recorded, usize)>,
+    /// Files read to the end whose documents are not yet acknowledged.
+    finished: Vec<(String, Recorded)>,
+    /// Whether the output failed documents of the current file, which then
+    /// keeps the progress last committed for it.
+    current_failed: bool,
+    last_commit: Instant,
+}
+
+impl FileState {
+    pub fn open(path: &Path) -> Result<Self> {
+        let files = match fs::read_to_string(path) {
+            Ok(state) => serde_json::from_str(&state)
+                .map_err(|err| eyre!("Invalid --file-state {}: {err}", path.display()))?,
+            Err(_) => BTreeMap::new(),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            files,
+            current: None,
+            finished: Vec::new(),
+            current_failed: false,
+            last_commit: Instant::now(),
+        })
+    }
+
+    /// `paths` without the files a previous run read to the end, unchanged since.
+    pub fn unfinished(&self, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        let total = paths.len();
+        let unfinished: Vec<_> = paths
+            .into_iter()
+            .filter(|path| {
+                !self
+                    .files
+                    .get(&key(path))
+                    .is_some_and(|recorded| recorded.complete && recorded.is_unchanged(path))
+            })
+            .collect();
+        if unfinished.len() < total {
+            log::info!(
+                "Skipping {} of {total} files already read to the end per {}",
+                total - unfinished.len(),
+                self.path.display()
+            );
+        }
+        unfinished
+    }
+
+    /// Starts reading `path`, which holds `docs` documents, returning how many
+    /// from its start a previous run already delivered.
+    pub fn start(&mut self, path: &Path, docs: usize) -> usize {
+        let key = key(path);
+        let (size, modified) = fingerprint(path).unwrap_or_default();
+        let delivered = match self.files.get(&key) {
+            Some(recorded) if recorded.is_unchanged(path) => recorded.docs.min(docs),
+            Some(_) => {
+                log::info!(
+                    "{} changed since it was read, reading all of it",
+                    path.display()
+                );
+                0
+            }
+            None => 0,
+        };
+        if delivered > 0 {
+            log::info!(
+                "Resuming {} after the {delivered} docs already delivered",
+                path.display()
+            );
+        }
+        let recorded = Recorded {
+            size,
+            modified,
+            docs: delivered,
+            complete: false,
+        };
+        self.current = Some((key, recorded, docs));
+        self.current_failed = false;
+        self.finish_if_read();
+        delivered
+    }
+
+    /// Counts a document read from the current file.
+    pub fn advance(&mut self) {
+        if let Some((_, recorded, _)) = &mut self.current {
+            recorded.docs += 1;
+        }
+        self.finish_if_read();
+    }
+
+    fn finish_if_read(&mut self) {
+        if let Some((key, mut recorded, docs)) = self.current.take() {
+            if recorded.docs < docs {
+                self.current = Some((key, recorded, docs));
+                return;
+            }
+            recorded.complete = true;
+            if !std::mem::take(&mut self.current_failed) {
+                self.finished.push((key, recorded));
+            }
+        }
+    }
+
+    /// Whether a file has been read to the end, or the one being read has
+    /// progressed for a while, since the last commit.
+    pub fn commit_due(&self) -> bool {
+        !self.finished.is_empty()
+            || (self.last_commit.elapsed() >= COMMIT_INTERVAL
+                && self.current.as_ref().is_some_and(|(key, recorded, _)| {
+                    self.files
+                        .get(key)
+                        .is_none_or(|committed| committed.docs < recorded.docs)
+                }))
+    }
+
+    /// Drops the progress made since the last commit, because the output
+    /// failed some of those documents, so the next run reads them again.
+    pub fn skip_commit(&mut self) {
+        self.last_commit = Instant::now();
+        self.finished.clear();
+        self.current_failed = self.current.is_some();
+        log::warn!(
+            "The output failed some documents, {} keeps the progress from before them",
+            self.path.display()
+        );
+    }
+
+    /// Records the progress of every document read so far, which the output
+    /// has acknowledged.
+    pub fn commit(&mut self) -> Result<()> {
+        self.last_commit = Instant::now();
+        let current = self
+            .current
+            .as_ref()
+            .filter(|_| !self.current_failed)
+            .map(|(key, recorded, _)| (key.clone(), recorded.clone()));
+        let changes: Vec<_> = self.finished.drain(..).chain(current).collect();
+        if changes.is_empty() {
+            return Ok(());
+        }
+        self.files.extend(changes);
+        // Written beside the state file and renamed over it, so a crash never
+        // leaves it half-written.
+        let partial = self.path.with_extension("partial");
+        fs::write(&partial, serde_json::to_vec_pretty(&self.files)?)
+            .map_err(|err| eyre!("Failed to write {}: {err}", partial.display()))?;
+        fs::rename(&partial, &self.path)
+            .map_err(|err| eyre!("Failed to write {}: {err}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+fn key(path: &Path) -> String {
+    fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+fn fingerprint(path: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_millis() as u64;
+    Some((metadata.len(), modified))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileState;
+    use std::fs;
+
+    #[test]
+    fn finished_files_are_skipped_and_partial_ones_resumed() {
+        let dir = tempfile::tempdir().unwrap();
+        let done = dir.path().join("done.ndjson");
+        let partial = dir.path().join("partial.ndjson");
+        fs::write(&done, "{}\n{}\n").unwrap();
+        fs::write(&partial, "{}\n{}\n{}\n").unwrap();
+        let state_path = dir.path().join("state.json");
+
+        let mut state = FileState::open(&state_path).unwrap();
+        assert_eq!(state.start(&done, 2), 0);
+        state.advance();
+        state.advance();
+        assert!(state.commit_due());
+        assert_eq!(state.start(&partial, 3), 0);
+        state.advance();
+        state.commit().unwrap();
+        assert!(!state.commit_due());
+
+        let mut state = FileState::open(&state_path).unwrap();
+        let paths = vec![done.clone(), partial.clone()];
+        assert_eq!(state.unfinished(paths), std::slice::from_ref(&partial));
+        assert_eq!(state.start(&partial, 3), 1);
+
+        // A file rewritten since it was read is read again from the start.
+        fs::write(&partial, "{}\n").unwrap();
+        let mut state = FileState::open(&state_path).unwrap();
+        assert_eq!(
+            state.unfinished(vec![partial.clone()]),
+            std::slice::from_ref(&partial)
+        );
+        assert_eq!(state.start(&partial, 1), 0);
+    }
+
+    #[test]
+    fn progress_past_failed_documents_is_not_committed() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.ndjson");
+        let second = dir.path().join("second.ndjson");
+        fs::write(&first, "{}\n{}\n").unwrap();
+        fs::write(&second, "{}\n{}\n").unwrap();
+        let state_path = dir.path().join("state.json");
+
+        let mut state = FileState::open(&state_path).unwrap();
+        assert_eq!(state.start(&first, 2), 0);
+        state.advance();
+        state.commit().unwrap();
+        state.advance();
+        state.skip_commit();
+        assert_eq!(state.start(&second, 2), 0);
+        state.advance();
+        state.skip_commit();
+        state.advance();
+        state.commit().unwrap();
+
+        let mut state = FileState::open(&state_path).unwrap();
+        let paths = vec![first.clone(), second.clone()];
+        assert_eq!(state.unfinished(paths), vec![first.clone(), second.clone()]);
+        assert_eq!(state.start(&first, 2), 1);
+        assert_eq!(state.start(&second, 2), 0);
+    }
+}
@@ -0,0 +1,125 @@
+use super::read_file_documents;
+use eyre::{Result, eyre};
+use serde_json::value::RawValue;
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        mpsc::{Receiver, Sender, channel, sync_channel},
+    },
+    thread,
+};
+
+type Documents = Result<Vec<Box<RawValue>>>;
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Reads upcoming files of a multi-file import on a pool of threads.
+///
+/// At most one file per worker is read ahead of the file being consumed, and
+/// results are handed back in path order, so output order and completion
+/// logging match a sequential run while memory stays bounded.
+pub struct FileWorkers {
+    jobs: Sender<Job>,
+    workers: usize,
+    submitted: usize,
+    inflight: VecDeque<Receiver<Documents>>,
+}
+
+impl FileWorkers {
+    pub fn new(workers: usize) -> Self {
+        let (jobs, queue) = channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        for _ in 0..workers {
+            let queue = queue.clone();
+            thread::spawn(move || {
+                loop {
+                    let job = queue.lock().expect("Failed to get job queue lock").recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => return,
+                    }
+                }
+            });
+        }
+        Self {
+            jobs,
+            workers,
+            submitted: 0,
+            inflight: VecDeque::new(),
+        }
+    }
+
+    /// Returns the documents of `paths[index]`, reading ahead as workers allow.
+    ///
+    /// Paths must be requested in order, starting from zero.
+    pub fn read(
+        &mut self,
+        paths: &[PathBuf],
+        index: usize,
+        content_field: &str,
+        include_file_metadata: bool,
+    ) -> Documents {
+        let read_ahead_end = paths.len().min(index + self.workers);
+        while self.submitted < read_ahead_end {
+            let path = paths[self.submitted].clone();
+            let content_field = content_field.to_string();
+            let (result, receiver) = sync_channel(1);
+            self.jobs
+                .send(Box::new(move || {
+                    let _ = result.send(read_file_documents(
+                        &path,
+                        &content_field,
+                        include_file_metadata,
+                    ));
+                }))
+                .map_err(|_| eyre!("File reader workers stopped"))?;
+            self.inflight.push_back(receiver);
+            self.submitted += 1;
+        }
+        self.inflight
+            .pop_front()
+            .ok_or_else(|| eyre!("File reader workers have no file queued"))?
+            .recv()
+            .map_err(|_| eyre!("File reader worker stopped before returning documents"))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileWorkers;
+    use std::fs;
+
+    #[test]
+    fn workers_return_files_in_path_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<_> = (0..5)
+            .map(|n| {
+                let path = dir.path().join(format!("{n}.txt"));
+                fs::write(&path, n.to_string()).unwrap();
+                path
+            })
+            .collect();
+        let mut workers = FileWorkers::new(3);
+
+        for (index, _) in paths.iter().enumerate() {
+            let documents = workers.read(&paths, index, "body", false).unwrap();
+            assert_eq!(
+                documents[0].get(),
+                format!("{{\"content\":{{\"body\":\"{index}\"}}}}")
+            );
+        }
+    }
+
+    #[test]
+    fn workers_return_read_errors_for_the_failing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let good = dir.path().join("good.txt");
+        fs::write(&good, "ok").unwrap();
+        let paths = vec![good, dir.path().join("missing.txt")];
+        let mut workers = FileWorkers::new(2);
+
+        assert!(workers.read(&paths, 0, "body", false).is_ok());
+        assert!(workers.read(&paths, 1, "body", false).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+use super::{INPUT_IDLE, JSON_LINE_OPENING_ERROR, ensure_json_opening};
+use crate::client::KafkaUri;
+use eyre::{Result, eyre};
+use fluent_uri::UriRef;
+use rdkafka::{
+    Message, Offset, TopicPartitionList,
+    consumer::{BaseConsumer, CommitMode, Consumer},
+    error::KafkaError,
+};
+use serde_json::value::RawValue;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{Duration, Instant},
+};
+
+const POLL_TIMEOUT: Duration = Duration::from_secs(1);
+const COMMIT_INTERVAL: Duration = Duration::from_secs(5);
+const DEFAULT_GROUP: &str = "espipe";
+
+/// Consumes JSON messages from one topic with auto-commit disabled.
+///
+/// Offsets are only committed through [`KafkaInput::commit`], which the caller
+/// invokes after the output has acknowledged everything read so far.
+pub struct KafkaInput {
+    source: String,
+    consumer: BaseConsumer,
+    topic: String,
+    stop_at_eof: bool,
+    uncommitted: BTreeMap<i32, i64>,
+    eof_partitions: BTreeSet<i32>,
+    last_commit: Instant,
+    /// Set once the output failed documents read from this input. A later
+    /// offset would commit past them, so none is committed for the rest of the run.
+    held: bool,
+}
+
+impl KafkaInput {
+    pub fn try_new(uri: &UriRef<String>) -> Result<Self> {
+        let mut kafka_uri = KafkaUri::parse(uri)?;
+        let group = kafka_uri
+            .take_option("group")
+            .unwrap_or_else(|| DEFAULT_GROUP.to_string());
+        let stop_at_eof = match kafka_uri.take_option("eof").as_deref() {
+            None | Some("false") => false,
+            Some("true") => true,
+            Some(value) => {
+                return Err(eyre!(
+                    "Kafka eof option must be true or false, got '{value}'"
+                ));
+            }
+        };
+        let mut config = kafka_uri.client_config();
+        config
+            .set("group.id", &group)
+            .set("enable.auto.commit", "false")
+            .set("enable.partition.eof", stop_at_eof.to_string());
+        if config.get("auto.offset.reset").is_none() {
+            config.set("auto.offset.reset", "earliest");
+        }
+        let consumer: BaseConsumer = config
+            .create()
+            .map_err(|err| eyre!("Failed to create Kafka consumer for {kafka_uri}: {err}"))?;
+        consumer
+            .subscribe(&[kafka_uri.topic.as_str()])
+            .map_err(|err| eyre!("Failed to subscribe to {kafka_uri}: {err}"))?;
+        log::debug!("Kafka input from {kafka_uri} as consumer group {group}");
+
+        Ok(Self {
+            source: kafka_uri.to_string(),
+            topic: kafka_uri.topic,
+            consumer,
+            stop_at_eof,
+            uncommitted: BTreeMap::new(),
+            eof_partitions: BTreeSet::new(),
+            last_commit: Instant::now(),
+            held: false,
+        })
+    }
+
+    pub fn read_line(&mut self) -> Result<Box<RawValue>> {
+        loop {
+            let message = match self.consumer.poll(POLL_TIMEOUT) {
+                None if !self.uncommitted.is_empty() => return Err(eyre!(INPUT_IDLE)),
+                None => continue,
+                Some(Err(KafkaError::PartitionEOF(partition))) => {
+                    self.eof_partitions.insert(partition);
+                    if self.all_partitions_at_eof()? {
+                        return Err(eyre!("No Kafka message"));
+                    }
+                    continue;
+                }
+                Some(Err(err)) => return Err(eyre!("Kafka consumer error: {err}")),
+                Some(Ok(message)) => message,
+            };
+
+            let partition = message.partition();
+            let offset = message.offset();
+            self.eof_partitions.remove(&partition);
+            self.uncommitted.insert(partition, offset + 1);
+
+            // Tombstones carry no document; their offset is still committed.
+            let Some(payload) = message.payload() else {
+                continue;
+            };
+            let payload = std::str::from_utf8(payload).map_err(|_| {
+                eyre!(
+                    "{}: partition {partition} offset {offset}: message is not valid UTF-8",
+                    self.source
+                )
+            })?;
+            let raw = RawValue::from_string(payload.trim().to_string()).map_err(|err| {
+                eyre!(
+                    "{}: partition {partition} offset {offset}: Error parsing JSON: {err}",
+                    self.source
+                )
+            })?;
+            ensure_json_opening(raw.get(), JSON_LINE_OPENING_ERROR)?;
+            return Ok(raw);
+        }
+    }
+
+    pub fn commit_due(&self) -> bool {
+        !self.uncommitted.is_empty() && self.last_commit.elapsed() >= COMMIT_INTERVAL
+    }
+
+    /// Stops committing offsets after the output failed some of the messages
+    /// read since the last commit, so they are consumed again on the next run.
+    pub fn skip_commit(&mut self) {
+        if !self.held {
+            log::warn!(
+                "{}: the output failed some messages, offsets will not be committed for the rest of the run",
+                self.source
+            );
+        }
+        self.held = true;
+        self.last_commit = Instant::now();
+        self.uncommitted.clear();
+    }
+
+    pub fn commit(&mut self) -> Result<()> {
+        self.last_commit = Instant::now();
+        if self.held {
+            self.uncommitted.clear();
+            return Ok(());
+        }
+        if self.uncommitted.is_empty() {
+            return Ok(());
+        }
+        let mut offsets = TopicPartitionList::new();
+        for (partition, offset) in &self.uncommitted {
+            offsets
+                .add_partition_offset(&self.topic, *partition, Offset::Offset(*offset))
+                .map_err(|err| eyre!("Failed to record Kafka offset: {err}"))?;
+        }
+        self.consumer
+            .commit(&offsets, CommitMode::Sync)
+            .map_err(|err| eyre!("Failed to commit Kafka offsets for {}: {err}", self.source))?;
+        log::debug!(
+            "Committed Kafka offsets {:?} for {}",
+            self.uncommitted,
+            self.source
+        );
+        self.uncommitted.clear();
+        Ok(())
+    }
+
+    fn all_partitions_at_eof(&self) -> Result<bool> {
+        if !self.stop_at_eof {
+            return Ok(false);
+        }
+        let assignment = self
+            .consumer
+            .assignment()
+            .map_err(|err| eyre!("Failed to read Kafka assignment: {err}"))?;
+        Ok(assignment.count() > 0 && self.eof_partitions.len() >= assignment.count())
+    }
+}
+
+impl std::fmt::Display for KafkaInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
@@ -0,0 +1,315 @@
+use eyre::{Result, eyre};
+use serde_json::{Value, json};
+
+/// Translates a Kibana Query Language string into Elasticsearch query DSL.
+///
+/// Supports `field:value`, quoted phrases, `*` wildcards, `field:*` for
+/// existence, `field:(a or b)` value groups, `<`, `<=`, `>`, `>=` ranges,
+/// free text, parentheses, and case-insensitive `and`, `or`, and `not`, which
+/// bind in that order. Unquoted words run together into one value, as in Kibana.
+pub fn to_query(kql: &str) -> Result<Value> {
+    let tokens = tokenize(kql)?;
+    if tokens.is_empty() {
+        return Ok(json!({ "match_all": {} }));
+    }
+    let mut parser = Parser { tokens, next: 0 };
+    let query = parser.or(None)?;
+    match parser.peek() {
+        None => Ok(query),
+        Some(token) => Err(eyre!("unexpected {token} in KQL query '{kql}'")),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Open,
+    Close,
+    Colon,
+    Range(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "'{word}'"),
+            Token::Quoted(phrase) => write!(f, "\"{phrase}\""),
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+            Token::Colon => write!(f, "':'"),
+            Token::Range(_) => write!(f, "range operator"),
+        }
+    }
+}
+
+fn tokenize(kql: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = kql.chars().peekable();
+    while let Some(&char) = chars.peek() {
+        match char {
+            char if char.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | ':' => {
+                chars.next();
+                tokens.push(match char {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    _ => Token::Colon,
+                });
+            }
+            '<' | '>' => {
+                chars.next();
+                let inclusive = chars.next_if_eq(&'=').is_some();
+                tokens.push(Token::Range(match (char, inclusive) {
+                    ('<', false) => "lt",
+                    ('<', true) => "lte",
+                    ('>', false) => "gt",
+                    _ => "gte",
+                }));
+            }
+            '"' => {
+                chars.next();
+                let mut phrase = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => phrase.extend(chars.next()),
+                        Some(char) => phrase.push(char),
+                        None => return Err(eyre!("unterminated quote in KQL query '{kql}'")),
+                    }
+                }
+                tokens.push(Token::Quoted(phrase));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&char) = chars.peek() {
+                    if char.is_whitespace() || "():<>\"".contains(char) {
+                        break;
+                    }
+                    chars.next();
+                    match char {
+                        '\\' => word.extend(chars.next()),
+                        char => word.push(char),
+                    }
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.next += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Clauses joined by `or`. Inside `field:( )` groups, `field` names the
+    /// field each bare value matches.
+    fn or(&mut self, field: Option<&str>) -> Result<Value> {
+        let mut clauses = vec![self.and(field)?];
+        while self.keyword("or") {
+            clauses.push(self.and(field)?);
+        }
+        Ok(match clauses.len() {
+            1 => clauses.remove(0),
+            _ => json!({ "bool": { "should": clauses, "minimum_should_match": 1 } }),
+        })
+    }
+
+    fn and(&mut self, field: Option<&str>) -> Result<Value> {
+        let mut clauses = vec![self.not(field)?];
+        while self.keyword("and") {
+            clauses.push(self.not(field)?);
+        }
+        Ok(match clauses.len() {
+            1 => clauses.remove(0),
+            _ => json!({ "bool": { "filter": clauses } }),
+        })
+    }
+
+    fn not(&mut self, field: Option<&str>) -> Result<Value> {
+        match self.keyword("not") {
+            true => Ok(json!({ "bool": { "must_not": [self.not(field)?] } })),
+            false => self.primary(field),
+        }
+    }
+
+    fn primary(&mut self, field: Option<&str>) -> Result<Value> {
+        if self.peek() == Some(&Token::Open) {
+            self.next += 1;
+            let query = self.or(field)?;
+            return match self.tokens.get(self.next) {
+                Some(Token::Close) => {
+                    self.next += 1;
+                    Ok(query)
+                }
+                Some(token) => Err(eyre!("expected ')' in KQL query, found {token}")),
+                None => Err(eyre!("expected ')' at the end of the KQL query")),
+            };
+        }
+        if field.is_none()
+            && let Some(Token::Word(name)) = self.peek().cloned()
+        {
+            match self.tokens.get(self.next + 1) {
+                Some(Token::Colon) => {
+                    self.next += 2;
+                    return match self.peek() {
+                        Some(Token::Open) => self.primary(Some(&name)),
+                        _ => Ok(field_query(&name, self.term()?)),
+                    };
+                }
+                Some(Token::Range(op)) => {
+                    let op = *op;
+                    self.next += 2;
+                    let (Term::Text(value) | Term::Phrase(value)) = self.term()?;
+                    return Ok(json!({ "range": { name: { op: value } } }));
+                }
+                _ => {}
+            }
+        }
+        let term = self.term()?;
+        Ok(match field {
+            Some(field) => field_query(field, term),
+            None => free_text_query(term),
+        })
+    }
+
+    /// A quoted phrase, or unquoted words up to the next operator, bracket, or field.
+    fn term(&mut self) -> Result<Term> {
+        match self.peek().cloned() {
+            Some(Token::Quoted(phrase)) => {
+                self.next += 1;
+                Ok(Term::Phrase(phrase))
+            }
+            Some(Token::Word(word)) if !is_keyword(&word) => {
+                let mut words = vec![word];
+                self.next += 1;
+                // A word followed by `:` or a range operator starts the next clause.
+                while let Some(Token::Word(word)) = self.peek()
+                    && !is_keyword(word)
+                    && !matches!(
+                        self.tokens.get(self.next + 1),
+                        Some(Token::Colon | Token::Range(_))
+                    )
+                {
+                    words.push(word.clone());
+                    self.next += 1;
+                }
+                Ok(Term::Text(words.join(" ")))
+            }
+            Some(token) => Err(eyre!("expected a value in KQL query, found {token}")),
+            None => Err(eyre!("expected a value at the end of the KQL query")),
+        }
+    }
+}
+
+enum Term {
+    Text(String),
+    Phrase(String),
+}
+
+fn is_keyword(word: &str) -> bool {
+    ["and", "or", "not"]
+        .iter()
+        .any(|keyword| word.eq_ignore_ascii_case(keyword))
+}
+
+fn field_query(field: &str, term: Term) -> Value {
+    match term {
+        Term::Text(text) if text == "*" => json!({ "exists": { "field": field } }),
+        Term::Text(text) if text.contains('*') => {
+            json!({ "wildcard": { field: { "value": text, "case_insensitive": true } } })
+        }
+        Term::Text(text) => json!({ "match": { field: text } }),
+        Term::Phrase(phrase) => json!({ "match_phrase": { field: phrase } }),
+    }
+}
+
+fn free_text_query(term: Term) -> Value {
+    match term {
+        Term::Text(text) if text == "*" => json!({ "match_all": {} }),
+        Term::Text(text) => json!({ "multi_match": { "query": text, "lenient": true } }),
+        Term::Phrase(phrase) => {
+            json!({ "multi_match": { "query": phrase, "type": "phrase", "lenient": true } })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_query;
+    use serde_json::json;
+
+    #[test]
+    fn boolean_operators_bind_not_then_and_then_or() {
+        assert_eq!(
+            to_query("status:error and service:api or not level:info").unwrap(),
+            json!({ "bool": { "minimum_should_match": 1, "should": [
+                { "bool": { "filter": [
+                    { "match": { "status": "error" } },
+                    { "match": { "service": "api" } },
+                ] } },
+                { "bool": { "must_not": [{ "match": { "level": "info" } }] } },
+            ] } })
+        );
+        assert_eq!(
+            to_query("NOT (a:1 OR b:2)").unwrap(),
+            json!({ "bool": { "must_not": [{ "bool": { "minimum_should_match": 1, "should": [
+                { "match": { "a": "1" } },
+                { "match": { "b": "2" } },
+            ] } }] } })
+        );
+    }
+
+    #[test]
+    fn values_become_phrases_wildcards_ranges_and_groups() {
+        assert_eq!(
+            to_query(r#"message:"disk full" and host.name:web-* and user:*"#).unwrap(),
+            json!({ "bool": { "filter": [
+                { "match_phrase": { "message": "disk full" } },
+                { "wildcard": { "host.name": { "value": "web-*", "case_insensitive": true } } },
+                { "exists": { "field": "user" } },
+            ] } })
+        );
+        assert_eq!(
+            to_query("bytes >= 1024 and tags:(a or b)").unwrap(),
+            json!({ "bool": { "filter": [
+                { "range": { "bytes": { "gte": "1024" } } },
+                { "bool": { "minimum_should_match": 1, "should": [
+                    { "match": { "tags": "a" } },
+                    { "match": { "tags": "b" } },
+                ] } },
+            ] } })
+        );
+    }
+
+    #[test]
+    fn free_text_and_empty_queries_search_everything() {
+        assert_eq!(
+            to_query("quick brown").unwrap(),
+            json!({ "multi_match": { "query": "quick brown", "lenient": true } })
+        );
+        assert_eq!(to_query("  ").unwrap(), json!({ "match_all": {} }));
+        assert!(to_query("status:").is_err());
+        assert!(to_query("(status:error").is_err());
+        assert!(to_query("message:\"open").is_err());
+    }
+}
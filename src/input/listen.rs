@@ -0,0 +1,422 @@
+use super::{INPUT_IDLE, JSON_LINE_OPENING_ERROR, ensure_json_opening};
+use bytes::Bytes;
+use eyre::{Result, eyre};
+use flate2::read::GzDecoder;
+use fluent_uri::UriRef;
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::{
+    Method, Request, Response, StatusCode,
+    body::Incoming,
+    header::{AUTHORIZATION, CONTENT_ENCODING, CONTENT_TYPE, HeaderValue},
+    server::conn::http1,
+    service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use serde_json::{Value, json, value::RawValue};
+use std::{
+    collections::VecDeque,
+    io::Read,
+    net::{SocketAddr, TcpListener},
+    sync::{
+        Arc,
+        mpsc::{Receiver, RecvTimeoutError, SyncSender, sync_channel},
+    },
+    thread,
+    time::Duration,
+};
+use subtle::ConstantTimeEq;
+use url::Url;
+
+const IDLE_TIMEOUT: Duration = Duration::from_secs(1);
+const BATCH_QUEUE_CAPACITY: usize = 16;
+const MAX_BODY_BYTES: usize = 100 * 1024 * 1024;
+
+type Batch = Vec<Box<RawValue>>;
+
+/// Accepts NDJSON and Elasticsearch `_bulk` POST bodies over HTTP.
+///
+/// The server runs on its own thread and runtime. Each request body is parsed in
+/// full before any of its documents are queued, so a malformed request is
+/// rejected as a whole. Requests wait for room in a bounded queue before they
+/// are answered, which pushes back on clients when the output falls behind.
+/// The answer is a `202 Accepted`, since the output has not yet sent the
+/// documents and may still fail them.
+pub struct ListenInput {
+    source: String,
+    batches: Receiver<Batch>,
+    pending: VecDeque<Box<RawValue>>,
+    received_since_idle: bool,
+}
+
+struct ServerState {
+    token: Option<String>,
+    batches: SyncSender<Batch>,
+}
+
+impl ListenInput {
+    pub fn bind(address: &UriRef<String>, token: Option<String>) -> Result<Self> {
+        let addr = listen_address(address)?;
+        let listener =
+            TcpListener::bind(addr).map_err(|err| eyre!("Failed to listen on {address}: {err}"))?;
+        listener.set_nonblocking(true)?;
+        let source = format!("http://{}", listener.local_addr()?);
+        let (sender, batches) = sync_channel(BATCH_QUEUE_CAPACITY);
+        let state = Arc::new(ServerState {
+            token,
+            batches: sender,
+        });
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        thread::spawn(move || runtime.block_on(serve(listener, state)));
+        log::debug!("Listening for documents on {source}");
+
+        Ok(Self {
+            source,
+            batches,
+            pending: VecDeque::new(),
+            received_since_idle: false,
+        })
+    }
+
+    pub fn read_line(&mut self) -> Result<Box<RawValue>> {
+        loop {
+            if let Some(doc) = self.pending.pop_front() {
+                self.received_since_idle = true;
+                return Ok(doc);
+            }
+            match self.batches.recv_timeout(IDLE_TIMEOUT) {
+                Ok(batch) => self.pending.extend(batch),
+                Err(RecvTimeoutError::Timeout) if self.received_since_idle => {
+                    self.received_since_idle = false;
+                    return Err(eyre!(INPUT_IDLE));
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(eyre!("{}: HTTP listener stopped", self.source));
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ListenInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+fn listen_address(address: &UriRef<String>) -> Result<SocketAddr> {
+    let url = Url::parse(address.as_str())
+        .map_err(|err| eyre!("Invalid listen address {address}: {err}"))?;
+    if url.scheme() != "http" {
+        return Err(eyre!(
+            "Listen address must use http://, like http://0.0.0.0:8080"
+        ));
+    }
+    url.socket_addrs(|| None)
+        .map_err(|err| eyre!("Invalid listen address {address}: {err}"))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| eyre!("Listen address {address} did not resolve"))
+}
+
+async fn serve(listener: TcpListener, state: Arc<ServerState>) {
+    let listener = match tokio::net::TcpListener::from_std(listener) {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::error!("HTTP listener failed to start: {err}");
+            return;
+        }
+    };
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                log::warn!("HTTP listener accept failed: {err}");
+                continue;
+            }
+        };
+        let state = state.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request| handle(request, state.clone()));
+            if let Err(err) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                log::debug!("HTTP connection from {peer} closed with error: {err}");
+            }
+        });
+    }
+}
+
+async fn handle(
+    request: Request<Incoming>,
+    state: Arc<ServerState>,
+) -> Result<Response<Full<Bytes>>, hyper::Error> {
+    if let Some(token) = &state.token
+        && !is_authorized(request.headers().get(AUTHORIZATION), token)
+    {
+        return Ok(json_response(
+            StatusCode::UNAUTHORIZED,
+            json!({"error": "missing or invalid bearer token"}),
+        ));
+    }
+    if request.method() != Method::POST {
+        return Ok(json_response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            json!({"error": "only POST is supported"}),
+        ));
+    }
+
+    let is_bulk = request
+        .uri()
+        .path()
+        .trim_end_matches('/')
+        .ends_with("/_bulk");
+    let gzip = request
+        .headers()
+        .get(CONTENT_ENCODING)
+        .is_some_and(|encoding| encoding.as_bytes().eq_ignore_ascii_case(b"gzip"));
+    let body = match Limited::new(request.into_body(), MAX_BODY_BYTES)
+        .collect()
+        .await
+    {
+        Ok(body) => body.to_bytes(),
+        Err(err) => {
+            return Ok(json_response(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                json!({"error": format!("failed to read request body: {err}")}),
+            ));
+        }
+    };
+
+    let text = match decode_body(&body, gzip) {
+        Ok(text) => text,
+        Err((status, err)) => {
+            return Ok(json_response(status, json!({"error": err.to_string()})));
+        }
+    };
+    let parsed = if is_bulk {
+        parse_bulk(&text)
+    } else {
+        parse_ndjson(&text)
+    };
+    let docs = match parsed {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            return Ok(json_response(
+                StatusCode::BAD_REQUEST,
+                json!({"error": err.to_string()}),
+            ));
+        }
+    };
+
+    let count = docs.len();
+    let sender = state.batches.clone();
+    let queued = tokio::task::spawn_blocking(move || sender.send(docs).is_ok())
+        .await
+        .unwrap_or(false);
+    if !queued {
+        return Ok(json_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            json!({"error": "espipe is shutting down"}),
+        ));
+    }
+
+    Ok(json_response(
+        StatusCode::ACCEPTED,
+        json!({"accepted": count}),
+    ))
+}
+
+/// Compares the token in constant time, so response timing does not reveal
+/// how much of a guess was right.
+fn is_authorized(header: Option<&HeaderValue>, token: &str) -> bool {
+    header
+        .and_then(|value| value.as_bytes().strip_prefix(b"Bearer "))
+        .is_some_and(|provided| provided.ct_eq(token.as_bytes()).into())
+}
+
+/// Inflates a gzip body up to the same limit as a plain one, so a small
+/// compressed request cannot expand without bound.
+fn decode_body(body: &[u8], gzip: bool) -> Result<String, (StatusCode, eyre::Report)> {
+    let bytes = if gzip {
+        let mut bytes = Vec::new();
+        GzDecoder::new(body)
+            .take(MAX_BODY_BYTES as u64 + 1)
+            .read_to_end(&mut bytes)
+            .map_err(|err| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    eyre!("invalid gzip request body: {err}"),
+                )
+            })?;
+        if bytes.len() > MAX_BODY_BYTES {
+            return Err((
+                StatusCode::PAYLOAD_TOO_LARGE,
+                eyre!("gzip request body inflates past {MAX_BODY_BYTES} bytes"),
+            ));
+        }
+        bytes
+    } else {
+        body.to_vec()
+    };
+    String::from_utf8(bytes).map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            eyre!("request body is not valid UTF-8"),
+        )
+    })
+}
+
+fn parse_ndjson(text: &str) -> Result<Batch> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| parse_document(index + 1, line))
+        .collect()
+}
+
+/// Keeps the source of each `index` or `create` action; the action metadata is
+/// dropped because espipe's output decides the bulk action and target index.
+fn parse_bulk(text: &str) -> Result<Batch> {
+    let mut docs = Vec::new();
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    while let Some((index, line)) = lines.next() {
+        let action: Value = serde_json::from_str(line)
+            .map_err(|err| eyre!("line {}: invalid bulk action: {err}", index + 1))?;
+        let name = match action
+            .as_object()
+            .map(|object| object.keys().collect::<Vec<_>>())
+        {
+            Some(keys) if keys.len() == 1 => keys[0].clone(),
+            _ => return Err(eyre!("line {}: invalid bulk action", index + 1)),
+        };
+        if name != "index" && name != "create" {
+            return Err(eyre!(
+                "line {}: unsupported bulk action '{name}', only index and create are accepted",
+                index + 1
+            ));
+        }
+        let (index, source) = lines
+            .next()
+            .ok_or_else(|| eyre!("line {}: bulk action has no document", index + 1))?;
+        docs.push(parse_document(index + 1, source)?);
+    }
+    Ok(docs)
+}
+
+fn parse_document(line_number: usize, line: &str) -> Result<Box<RawValue>> {
+    let raw: Box<RawValue> = serde_json::from_str(line)
+        .map_err(|err| eyre!("line {line_number}: Error parsing JSON: {err}"))?;
+    ensure_json_opening(raw.get(), JSON_LINE_OPENING_ERROR)
+        .map_err(|err| eyre!("line {line_number}: {err}"))?;
+    Ok(raw)
+}
+
+fn json_response(status: StatusCode, body: Value) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(body.to_string())));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ListenInput, MAX_BODY_BYTES, decode_body, parse_bulk, parse_ndjson};
+    use flate2::{Compression, write::GzEncoder};
+    use fluent_uri::UriRef;
+    use hyper::StatusCode;
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+    };
+
+    fn post(source: &str, path: &str, headers: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(source.trim_start_matches("http://")).unwrap();
+        write!(
+            stream,
+            "POST {path} HTTP/1.1\r\nHost: espipe\r\nConnection: close\r\n{headers}Content-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn bind(token: Option<&str>) -> ListenInput {
+        let address = UriRef::parse("http://127.0.0.1:0".to_string()).unwrap();
+        ListenInput::bind(&address, token.map(str::to_string)).unwrap()
+    }
+
+    #[test]
+    fn ndjson_bodies_skip_blank_lines_and_reject_bad_json() {
+        assert_eq!(parse_ndjson("{\"a\":1}\n\n{\"a\":2}\n").unwrap().len(), 2);
+        let err = parse_ndjson("{\"a\":1}\nnope\n").unwrap_err();
+        assert!(err.to_string().starts_with("line 2:"), "{err}");
+    }
+
+    #[test]
+    fn bulk_bodies_keep_sources_and_reject_other_actions() {
+        let docs =
+            parse_bulk("{\"index\":{\"_index\":\"a\"}}\n{\"n\":1}\n{\"create\":{}}\n{\"n\":2}\n")
+                .unwrap();
+        assert_eq!(docs.len(), 2);
+        assert_eq!(docs[1].get(), "{\"n\":2}");
+
+        let err = parse_bulk("{\"delete\":{\"_id\":\"1\"}}\n").unwrap_err();
+        assert!(err.to_string().contains("unsupported bulk action 'delete'"));
+    }
+
+    #[test]
+    fn gzip_bodies_inflating_past_the_limit_are_too_large() {
+        let gzip = |len: usize| {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+            encoder.write_all(&vec![b' '; len]).unwrap();
+            encoder.finish().unwrap()
+        };
+
+        assert_eq!(decode_body(&gzip(16), true).unwrap(), " ".repeat(16));
+        let (status, _) = decode_body(&gzip(MAX_BODY_BYTES + 1), true).unwrap_err();
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn listener_queues_posted_documents() {
+        let mut input = bind(None);
+
+        let response = post(&input.to_string(), "/", "", "{\"n\":1}\n{\"n\":2}\n");
+
+        assert!(response.starts_with("HTTP/1.1 202"), "{response}");
+        assert!(response.ends_with("{\"accepted\":2}"), "{response}");
+        assert_eq!(input.read_line().unwrap().get(), "{\"n\":1}");
+        assert_eq!(input.read_line().unwrap().get(), "{\"n\":2}");
+    }
+
+    #[test]
+    fn listener_requires_the_bearer_token() {
+        let mut input = bind(Some("secret"));
+        let source = input.to_string();
+
+        let denied = post(&source, "/_bulk", "", "{\"index\":{}}\n{\"n\":1}\n");
+        let allowed = post(
+            &source,
+            "/_bulk",
+            "Authorization: Bearer secret\r\n",
+            "{\"index\":{}}\n{\"n\":1}\n",
+        );
+
+        assert!(denied.starts_with("HTTP/1.1 401"), "{denied}");
+        assert!(allowed.starts_with("HTTP/1.1 202"), "{allowed}");
+        assert!(allowed.ends_with("{\"accepted\":1}"), "{allowed}");
+        assert_eq!(input.read_line().unwrap().get(), "{\"n\":1}");
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{dead_letter::DeadLetter, document::Document};
+use clap::ValueEnum;
+use serde_json::{json, value::RawValue};
+use std::{
+    io::{self, BufRead, Read},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+/// What `--max-line-bytes` does with a longer line.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum LongLineAction {
+    /// Skip the line, writing its start to --dead-letter when there is one
+    #[default]
+    Skip,
+    /// Send the start of the line in a message field
+    Truncate,
+    /// Fail the run
+    Abort,
+}
+
+impl LongLineAction {
+    /// How the summary describes the lines handled.
+    pub fn resolution(self) -> &'static str {
+        match self {
+            Self::Skip => "skipped",
+            Self::Truncate => "truncated",
+            Self::Abort => "aborted",
+        }
+    }
+}
+
+/// The `--max-line-bytes` limit, shared by the reader holding an input to it
+/// and the summary counting the lines over it.
+#[derive(Clone, Debug)]
+pub struct LongLines {
+    max: usize,
+    action: LongLineAction,
+    dead_letter: Option<DeadLetter>,
+    handled: Arc<AtomicUsize>,
+}
+
+impl LongLines {
+    pub fn new(max: usize, action: LongLineAction, dead_letter: Option<DeadLetter>) -> Self {
+        Self {
+            max,
+            action,
+            dead_letter,
+            handled: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn action(&self) -> LongLineAction {
+        self.action
+    }
+
+    /// Lines skipped or truncated so far.
+    pub fn handled(&self) -> usize {
+        self.handled.load(Ordering::Relaxed)
+    }
+}
+
+/// Reads lines of at most `max` bytes, so a single huge line from a bad
+/// producer cannot run the process out of memory.
+///
+/// Each line is held whole only up to the limit; the rest of a longer line is
+/// read past without being kept, then the line is dropped, replaced by its
+/// start, or fails the read. Truncated lines become a document with the start
+/// in `message` and a `truncated` tag, or with `text`, as with syslog and
+/// access log lines, the start of the line itself.
+pub struct LineLimit<R> {
+    inner: R,
+    limits: LongLines,
+    source: String,
+    text: bool,
+    pending: Vec<u8>,
+    emitted: usize,
+    line: u64,
+    offset: u64,
+}
+
+impl<R: BufRead> LineLimit<R> {
+    pub fn new(inner: R, limits: LongLines, source: String, text: bool) -> Self {
+        Self {
+            inner,
+            limits,
+            source,
+            text,
+            pending: Vec::new(),
+            emitted: 0,
+            line: 0,
+            offset: 0,
+        }
+    }
+
+    /// Reads the next line into `pending`, returning `false` at the end of the
+    /// input.
+    fn next_line(&mut self) -> io::Result<bool> {
+        self.pending.clear();
+        self.emitted = 0;
+        let max = self.limits.max;
+        let mut bytes = 0;
+        let mut ended = false;
+        while !ended {
+            let available = self.inner.fill_buf()?;
+            if available.is_empty() {
+                break;
+            }
+            let len = match available.iter().position(|&byte| byte == b'\n') {
+                Some(newline) => {
+                    ended = true;
+                    newline + 1
+                }
+                None => available.len(),
+            };
+            // One byte past the limit keeps the newline of a line that fits.
+            let room = (max + 1).saturating_sub(self.pending.len());
+            self.pending.extend_from_slice(&available[..len.min(room)]);
+            self.inner.consume(len);
+            bytes += len;
+        }
+        if bytes == 0 {
+            return Ok(false);
+        }
+        let (line, offset) = (self.line + 1, self.offset);
+        self.line += 1;
+        self.offset += bytes as u64;
+        let length = bytes - usize::from(ended);
+        if length > max {
+            self.replace(line, offset, length)?;
+        }
+        Ok(true)
+    }
+
+    /// Handles a line over the limit, whose first `max + 1` bytes are pending.
+    fn replace(&mut self, line: u64, offset: u64, length: usize) -> io::Result<()> {
+        let max = self.limits.max;
+        let start = valid_prefix(&self.pending[..max]).to_string();
+        let location = format!("{} line {line}, byte {offset}", self.source);
+        let reason = format!("line of {length} bytes is over --max-line-bytes {max}");
+        self.pending.clear();
+        match self.limits.action {
+            LongLineAction::Abort => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{location}: {reason}"),
+                ));
+            }
+            LongLineAction::Skip => {
+                log::warn!("Skipping long line at {location}: {reason}");
+                if let Some(dead_letter) = &self.limits.dead_letter {
+                    let entry = json!({
+                        "source": self.source,
+                        "line": line,
+                        "offset": offset,
+                        "error": reason,
+                        "record": start,
+                    });
+                    let entry =
+                        RawValue::from_string(entry.to_string()).map_err(io::Error::other)?;
+                    dead_letter
+                        .write(&[Document::from(entry)])
+                        .map_err(|err| io::Error::other(err.to_string()))?;
+                }
+            }
+            LongLineAction::Truncate => {
+                log::warn!("Truncating long line at {location} to {max} bytes");
+                let truncated = match self.text {
+                    true => start,
+                    false => json!({ "message": start, "tags": ["truncated"] }).to_string(),
+                };
+                self.pending.extend_from_slice(truncated.as_bytes());
+                self.pending.push(b'\n');
+            }
+        }
+        self.limits.handled.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for LineLimit<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.emitted == self.pending.len() {
+            if !self.next_line()? {
+                return Ok(0);
+            }
+        }
+        let len = buf.len().min(self.pending.len() - self.emitted);
+        buf[..len].copy_from_slice(&self.pending[self.emitted..self.emitted + len]);
+        self.emitted += len;
+        Ok(len)
+    }
+}
+
+/// The bytes as text, dropping a character the limit cut in two.
+fn valid_prefix(bytes: &[u8]) -> &str {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(err) => std::str::from_utf8(&bytes[..err.valid_up_to()]).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LineLimit, LongLineAction, LongLines};
+    use std::io::{BufRead, BufReader};
+
+    fn read_lines(action: LongLineAction, text: bool, input: &str) -> (Vec<String>, usize) {
+        let limits = LongLines::new(8, action, None);
+        let reader = LineLimit::new(input.as_bytes(), limits.clone(), "test".to_string(), text);
+        let lines = BufReader::new(reader).lines().map(Result::unwrap).collect();
+        (lines, limits.handled())
+    }
+
+    #[test]
+    fn lines_over_the_limit_are_skipped_or_truncated() {
+        let input = "{\"a\":1}\n{\"a\":\"xé12345\"}\n12345678";
+        assert_eq!(
+            read_lines(LongLineAction::Skip, false, input),
+            (vec!["{\"a\":1}".to_string(), "12345678".to_string()], 1)
+        );
+        assert_eq!(
+            read_lines(LongLineAction::Truncate, false, input).0[1],
+            r#"{"message":"{\"a\":\"x","tags":["truncated"]}"#
+        );
+        assert_eq!(
+            read_lines(
+                LongLineAction::Truncate,
+                true,
+                "<13>Oct 16 host app: long message"
+            )
+            .0,
+            vec!["<13>Oct "]
+        );
+    }
+
+    #[test]
+    fn abort_fails_the_read_with_the_location() {
+        let limits = LongLines::new(4, LongLineAction::Abort, None);
+        let reader = LineLimit::new(
+            "ok\ntoo long\n".as_bytes(),
+            limits,
+            "test".to_string(),
+            false,
+        );
+        let mut lines = BufReader::new(reader).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "ok");
+        assert_eq!(
+            lines.next().unwrap().unwrap_err().to_string(),
+            "test line 2, byte 3: line of 8 bytes is over --max-line-bytes 4"
+        );
+    }
+}
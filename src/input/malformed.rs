@@ -0,0 +1,161 @@
+use crate::{dead_letter::DeadLetter, document::Document};
+use eyre::{Result, eyre};
+use serde_json::{json, value::RawValue};
+
+/// A record that could not be parsed. The input has already moved past it, so
+/// reading can carry on with the next record.
+#[derive(Debug)]
+pub struct MalformedRecord {
+    /// One-based line number where the record starts.
+    pub line: u64,
+    /// Byte offset where the record starts.
+    pub offset: u64,
+    /// The raw record text, when the input keeps it.
+    pub record: Option<String>,
+    pub reason: String,
+}
+
+impl MalformedRecord {
+    /// A malformed line or group of lines, starting at the `(line, offset)` from `LinePosition::advance`.
+    pub fn lines(start: (u64, u64), record: &str, reason: String) -> Self {
+        Self {
+            line: start.0,
+            offset: start.1,
+            record: Some(record.trim_end_matches(['\n', '\r']).to_string()),
+            reason,
+        }
+    }
+}
+
+impl std::fmt::Display for MalformedRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl std::error::Error for MalformedRecord {}
+
+/// Where the next line of a line-oriented input starts.
+#[derive(Debug, Default)]
+pub struct LinePosition {
+    line: u64,
+    offset: u64,
+}
+
+impl LinePosition {
+    /// Moves past the text of one record, returning the line and offset it started at.
+    pub fn advance(&mut self, text: &str) -> (u64, u64) {
+        let start = (self.line + 1, self.offset);
+        self.line += text.matches('\n').count().max(1) as u64;
+        self.offset += text.len() as u64;
+        start
+    }
+}
+
+/// Skips up to `--max-parse-errors` malformed records, writing each one with
+/// its location to the dead letter file when there is one.
+pub struct ParseErrorBudget {
+    source: String,
+    max: usize,
+    skipped: usize,
+    dead_letter: Option<DeadLetter>,
+}
+
+impl ParseErrorBudget {
+    pub fn new(source: String, max: usize, dead_letter: Option<DeadLetter>) -> Self {
+        Self {
+            source,
+            max,
+            skipped: 0,
+            dead_letter,
+        }
+    }
+
+    /// Skips the record, or fails once the budget is spent.
+    pub fn skip(&mut self, malformed: MalformedRecord) -> Result<()> {
+        let MalformedRecord {
+            line,
+            offset,
+            record,
+            reason,
+        } = malformed;
+        let location = format!("{} line {line}, byte {offset}", self.source);
+        if self.skipped >= self.max {
+            return Err(match self.max {
+                0 => eyre!("{location}: {reason}"),
+                max => eyre!("{location}: {reason} (more than {max} malformed records)"),
+            });
+        }
+        self.skipped += 1;
+        log::warn!("Skipping malformed record at {location}: {reason}");
+        if let Some(dead_letter) = &self.dead_letter {
+            let entry = json!({
+                "source": self.source,
+                "line": line,
+                "offset": offset,
+                "error": reason,
+                "record": record,
+            });
+            dead_letter.write(&[Document::from(RawValue::from_string(entry.to_string())?)])?;
+        }
+        Ok(())
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LinePosition, MalformedRecord, ParseErrorBudget};
+    use crate::dead_letter::DeadLetter;
+    use serde_json::{Value, json};
+    use std::fs;
+
+    #[test]
+    fn line_positions_track_line_numbers_and_byte_offsets() {
+        let mut position = LinePosition::default();
+
+        assert_eq!(position.advance("{\"a\":1}\n"), (1, 0));
+        assert_eq!(position.advance("{\n\"b\":2\n}\n"), (2, 8));
+        assert_eq!(position.advance("oops\n"), (5, 18));
+    }
+
+    #[test]
+    fn budgets_dead_letter_skipped_records_then_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dead.ndjson");
+        let dead_letter = DeadLetter::open(&path).unwrap();
+        let mut budget = ParseErrorBudget::new("in.ndjson".to_string(), 1, Some(dead_letter));
+        let mut position = LinePosition::default();
+        position.advance("{\"a\":1}\n");
+        let start = position.advance("oops\n");
+
+        budget
+            .skip(MalformedRecord::lines(
+                start,
+                "oops\n",
+                "bad JSON".to_string(),
+            ))
+            .unwrap();
+        let err = budget
+            .skip(MalformedRecord::lines(
+                (3, 13),
+                "again\n",
+                "bad JSON".to_string(),
+            ))
+            .unwrap_err();
+
+        assert_eq!(budget.skipped(), 1);
+        assert_eq!(
+            err.to_string(),
+            "in.ndjson line 3, byte 13: bad JSON (more than 1 malformed records)"
+        );
+        let dead: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            dead,
+            json!({"error": "bad JSON", "line": 2, "offset": 8, "record": "oops", "source": "in.ndjson"})
+        );
+    }
+}
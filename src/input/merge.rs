@@ -0,0 +1,205 @@
+use super::{Input, InputFormat, add_file_metadata, is_end_of_input, open_dropped_file};
+use crate::{document::Document, field_path, replay::parse_timestamp};
+use eyre::{Result, eyre};
+use jiff::Timestamp;
+use serde_json::Value;
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    path::PathBuf,
+};
+
+/// Reads time-sorted files side by side, always emitting the earliest of their
+/// next documents, so the output is ordered by the timestamp field across all
+/// of them rather than file by file.
+///
+/// Only one document per file is held at a time, so files of any size merge
+/// in constant memory. Documents without a readable timestamp sort first and
+/// pass straight through, and ties go to the file that sorts first by path.
+pub struct MergeInput {
+    field: String,
+    files: Vec<MergedFile>,
+    /// The next document of each file that has one, earliest first.
+    heads: BinaryHeap<Reverse<Head>>,
+    /// Files to read another document from before the next is merged.
+    pending: Vec<usize>,
+}
+
+struct MergedFile {
+    path: PathBuf,
+    input: Input,
+    /// The timestamp of the file's last document, to notice it going backwards.
+    last: Option<Timestamp>,
+    unsorted: bool,
+}
+
+struct Head {
+    time: Option<Timestamp>,
+    file: usize,
+    doc: Document,
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.time, self.file).cmp(&(other.time, other.file))
+    }
+}
+
+impl MergeInput {
+    /// Opens each of `paths` in `format` to merge them by `field`.
+    pub fn try_new(
+        paths: Vec<PathBuf>,
+        field: String,
+        content_field: &str,
+        format: InputFormat,
+    ) -> Result<Self> {
+        if field.is_empty() {
+            return Err(eyre!("--merge-by needs a timestamp field name"));
+        }
+        let files = paths
+            .into_iter()
+            .map(|path| {
+                let input = open_dropped_file(&path, content_field)
+                    .and_then(|input| input.with_input_format(format))
+                    .map_err(|err| eyre!("{}: {err}", path.display()))?;
+                Ok(MergedFile {
+                    path,
+                    input,
+                    last: None,
+                    unsorted: false,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            field,
+            pending: (0..files.len()).rev().collect(),
+            files,
+            heads: BinaryHeap::new(),
+        })
+    }
+
+    pub fn read_line(&mut self, line_buffer: &mut String) -> Result<Document> {
+        while let Some(&index) = self.pending.last() {
+            let file = &mut self.files[index];
+            // Each pending file reads into the buffer afresh, as a line left
+            // by the one before would run into its own.
+            line_buffer.clear();
+            let doc = match file.input.read_line(line_buffer) {
+                Ok(doc) => doc,
+                Err(err) if is_end_of_input(&err) => {
+                    self.pending.pop();
+                    continue;
+                }
+                // The file stays pending, so the next read carries on after
+                // the record that failed.
+                Err(err) => return Err(err),
+            };
+            self.pending.pop();
+            let head = file.head(doc, index, &self.field)?;
+            self.heads.push(Reverse(head));
+        }
+        let Reverse(head) = self
+            .heads
+            .pop()
+            .ok_or_else(|| eyre!("No merged document"))?;
+        self.pending.push(head.file);
+        Ok(head.doc)
+    }
+}
+
+impl MergedFile {
+    /// `doc` with its file metadata, keyed by its timestamp.
+    fn head(&mut self, doc: Document, index: usize, field: &str) -> Result<Head> {
+        let mut value: Value = serde_json::from_str(doc.get())
+            .map_err(|err| eyre!("{}: invalid JSON: {err}", self.path.display()))?;
+        let time = field_path::lookup(&value, field).and_then(parse_timestamp);
+        if time.is_some() {
+            if time < self.last && !self.unsorted {
+                self.unsorted = true;
+                log::warn!(
+                    "{} is not sorted by {field}, so its documents are only merged in the order it has them",
+                    self.path.display()
+                );
+            }
+            self.last = time;
+        }
+        if let Value::Object(document) = &mut value {
+            add_file_metadata(document, &self.path, true);
+        }
+        Ok(Head {
+            time,
+            file: index,
+            doc: doc.with_json(value.to_string()),
+        })
+    }
+}
+
+impl std::fmt::Display for MergeInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} file(s) merged by {}", self.files.len(), self.field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MergeInput;
+    use crate::input::InputFormat;
+    use serde_json::Value;
+    use std::fs;
+
+    #[test]
+    fn files_are_merged_in_timestamp_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.ndjson");
+        let b = dir.path().join("b.ndjson");
+        fs::write(
+            &a,
+            "{\"@timestamp\":\"2024-05-01T12:00:00Z\",\"n\":1}\n\
+             {\"@timestamp\":\"2024-05-01T12:00:02Z\",\"n\":3}\n\
+             {\"@timestamp\":\"2024-05-01T12:00:03Z\",\"n\":5}\n",
+        )
+        .unwrap();
+        fs::write(
+            &b,
+            "{\"n\":0}\n\
+             {\"@timestamp\":\"2024-05-01T12:00:01Z\",\"n\":2}\n\
+             {\"@timestamp\":1714564802500,\"n\":4}\n",
+        )
+        .unwrap();
+
+        let mut input = MergeInput::try_new(
+            vec![a, b.clone()],
+            "@timestamp".to_string(),
+            "body",
+            InputFormat::Auto,
+        )
+        .unwrap();
+        let mut line = String::new();
+        let mut docs = Vec::new();
+        while let Ok(doc) = input.read_line(&mut line) {
+            docs.push(serde_json::from_str::<Value>(doc.get()).unwrap());
+        }
+
+        let order: Vec<_> = docs.iter().map(|doc| doc["n"].as_i64().unwrap()).collect();
+        assert_eq!(order, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(docs[0]["file"]["path"], b.display().to_string());
+        assert_eq!(
+            input.read_line(&mut line).unwrap_err().to_string(),
+            "No merged document"
+        );
+    }
+}
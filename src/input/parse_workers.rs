@@ -0,0 +1,197 @@
+use super::{
+    malformed::{LinePosition, MalformedRecord},
+    parse_json_record,
+};
+use eyre::{Result, eyre};
+use serde_json::value::RawValue;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io::BufRead,
+    sync::{
+        Arc, Mutex,
+        mpsc::{Receiver, SyncSender, sync_channel},
+    },
+    thread,
+};
+
+const CHUNK_LINES: usize = 1024;
+
+type Parsed = Result<Box<RawValue>>;
+
+/// A line read from the input, with where it started for reporting parse errors.
+struct Line {
+    start: (u64, u64),
+    text: String,
+}
+
+/// Splits line-delimited JSON parsing across threads.
+///
+/// A reader thread cuts the input into chunks of lines, `workers` threads parse
+/// the chunks, and results are put back into input order before they are
+/// returned. Both queues are bounded, so a slow output still pushes back on
+/// the reader.
+pub struct ParseWorkers {
+    results: Receiver<(u64, Vec<Parsed>)>,
+    reordered: BTreeMap<u64, Vec<Parsed>>,
+    next_chunk: u64,
+    current: VecDeque<Parsed>,
+}
+
+impl ParseWorkers {
+    pub fn new<R: BufRead + Send + 'static>(reader: R, first_record: bool, workers: usize) -> Self {
+        let (chunks, queue) = sync_channel::<(u64, Result<Vec<Line>, String>)>(workers * 2);
+        let (results_sender, results) = sync_channel(workers * 2);
+
+        thread::spawn(move || read_chunks(reader, first_record, &chunks));
+
+        let queue = Arc::new(Mutex::new(queue));
+        for _ in 0..workers {
+            let queue = queue.clone();
+            let results = results_sender.clone();
+            thread::spawn(move || {
+                loop {
+                    let chunk = queue.lock().expect("Failed to get chunk queue lock").recv();
+                    let Ok((sequence, lines)) = chunk else {
+                        return;
+                    };
+                    let parsed = match lines {
+                        Ok(lines) => lines
+                            .iter()
+                            .map(|line| {
+                                parse_json_record(&line.text).map_err(|err| {
+                                    MalformedRecord::lines(line.start, &line.text, err.to_string())
+                                        .into()
+                                })
+                            })
+                            .collect(),
+                        Err(err) => vec![Err(eyre!(err))],
+                    };
+                    if results.send((sequence, parsed)).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+
+        Self {
+            results,
+            reordered: BTreeMap::new(),
+            next_chunk: 0,
+            current: VecDeque::new(),
+        }
+    }
+
+    pub fn read_line(&mut self) -> Result<Box<RawValue>> {
+        loop {
+            if let Some(parsed) = self.current.pop_front() {
+                return parsed;
+            }
+            if let Some(chunk) = self.reordered.remove(&self.next_chunk) {
+                self.current.extend(chunk);
+                self.next_chunk += 1;
+                continue;
+            }
+            match self.results.recv() {
+                Ok((sequence, parsed)) => {
+                    self.reordered.insert(sequence, parsed);
+                }
+                Err(_) => return Err(eyre!("No JSON record")),
+            }
+        }
+    }
+}
+
+fn read_chunks<R: BufRead>(
+    mut reader: R,
+    mut first_record: bool,
+    chunks: &SyncSender<(u64, Result<Vec<Line>, String>)>,
+) {
+    let mut sequence = 0;
+    let mut position = LinePosition::default();
+    loop {
+        let mut lines = Vec::with_capacity(CHUNK_LINES);
+        let mut eof = false;
+        while lines.len() < CHUNK_LINES {
+            let mut line = String::new();
+            match reader.read_line(&mut line) {
+                Ok(0) => {
+                    eof = true;
+                    break;
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    let _ = chunks.send((sequence, Err(err.to_string())));
+                    return;
+                }
+            }
+            // A lone `{` on the first line starts a single pretty-printed document.
+            if first_record && line.trim() == "{" {
+                if let Err(err) = reader.read_to_string(&mut line) {
+                    let _ = chunks.send((sequence, Err(err.to_string())));
+                    return;
+                }
+                eof = true;
+            }
+            first_record = false;
+            lines.push(Line {
+                start: position.advance(&line),
+                text: line,
+            });
+        }
+        if !lines.is_empty() && chunks.send((sequence, Ok(lines))).is_err() {
+            return;
+        }
+        if eof {
+            return;
+        }
+        sequence += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParseWorkers;
+    use std::io::Cursor;
+
+    fn read_all(mut workers: ParseWorkers) -> Vec<String> {
+        let mut values = Vec::new();
+        while let Ok(value) = workers.read_line() {
+            values.push(value.get().to_string());
+        }
+        values
+    }
+
+    #[test]
+    fn parse_workers_keep_input_order_across_chunks() {
+        let input: String = (0..5000).map(|n| format!("{{\"n\":{n}}}\n")).collect();
+
+        let values = read_all(ParseWorkers::new(Cursor::new(input), false, 4));
+
+        assert_eq!(values.len(), 5000);
+        assert!(
+            values
+                .iter()
+                .enumerate()
+                .all(|(n, value)| *value == format!("{{\"n\":{n}}}"))
+        );
+    }
+
+    #[test]
+    fn parse_workers_read_a_pretty_printed_first_document_whole() {
+        let input = "{\n  \"a\": 1\n}\n";
+
+        let values = read_all(ParseWorkers::new(Cursor::new(input), true, 2));
+
+        assert_eq!(values, vec!["{\n  \"a\": 1\n}"]);
+    }
+
+    #[test]
+    fn parse_workers_surface_parse_errors_in_order() {
+        let input = "{\"a\":1}\nnot json\n{\"a\":3}\n";
+        let mut workers = ParseWorkers::new(Cursor::new(input), false, 2);
+
+        assert!(workers.read_line().is_ok());
+        let err = workers.read_line().unwrap_err();
+        assert!(err.to_string().contains("Error parsing JSON"), "{err}");
+    }
+}
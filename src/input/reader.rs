@@ -0,0 +1,193 @@
+use super::{Input, MalformedRecord, is_idle};
+use crate::{document::Document, memory::MemoryBudget};
+use eyre::Result;
+use std::{
+    sync::{
+        Arc, Mutex,
+        mpsc::{Receiver, Sender, channel},
+    },
+    thread,
+    time::Duration,
+};
+use tokio::sync::mpsc;
+
+/// Documents read ahead of the output before the reader thread waits.
+const READ_AHEAD_DOCS: usize = 1024;
+
+/// How long the reader thread waits for `--max-memory` room before asking the
+/// pipe to flush the output's partial batches.
+const MEMORY_WAIT: Duration = Duration::from_millis(100);
+
+/// What the reader thread found next.
+#[derive(Debug)]
+pub enum ReadEvent {
+    Document(Document),
+    /// A record was skipped; the reader thread has already moved on.
+    Malformed(MalformedRecord),
+    /// The input wants its position committed: it went idle or a commit is due.
+    /// The reader thread waits for `InputReader::commit` before reading on.
+    Checkpoint {
+        idle: bool,
+    },
+    /// The `--max-memory` budget is spent: the output should send what it has
+    /// buffered so the reader thread can read on. Unlike a checkpoint, nothing
+    /// is committed.
+    Flush,
+    End,
+}
+
+/// Reads an `Input` on its own thread and hands documents to the async pipe
+/// through a bounded queue.
+///
+/// Inputs read files, stdin, sockets, and Kafka with blocking calls, so the
+/// thread keeps those reads off the runtime, and lets the next documents be
+/// read and parsed while earlier ones are still being sent. The queue is
+/// bounded, so a slow output still pushes back on the input, and with a
+/// [`MemoryBudget`] each document also waits for room in the budget.
+pub struct InputReader {
+    input: Arc<Mutex<Input>>,
+    events: mpsc::Receiver<Result<ReadEvent>>,
+    resume: Sender<()>,
+}
+
+impl InputReader {
+    pub fn spawn(input: Input, budget: Option<MemoryBudget>) -> Self {
+        let input = Arc::new(Mutex::new(input));
+        let (sender, events) = mpsc::channel(READ_AHEAD_DOCS);
+        let (resume, resumed) = channel();
+        let reader_input = input.clone();
+        thread::spawn(move || read_ahead(&reader_input, &sender, &resumed, budget.as_ref()));
+        Self {
+            input,
+            events,
+            resume,
+        }
+    }
+
+    pub async fn next(&mut self) -> Result<ReadEvent> {
+        self.events.recv().await.unwrap_or(Ok(ReadEvent::End))
+    }
+
+    /// Commits the input position and, after a checkpoint, lets the reader thread read on.
+    pub fn commit(&mut self) -> Result<()> {
+        self.input.lock().expect("input lock poisoned").commit()?;
+        let _ = self.resume.send(());
+        Ok(())
+    }
+
+    /// Lets the reader thread read on after a checkpoint without committing the
+    /// input position, because the output failed some of the documents read.
+    pub fn skip_commit(&mut self) {
+        self.input
+            .lock()
+            .expect("input lock poisoned")
+            .skip_commit();
+        let _ = self.resume.send(());
+    }
+}
+
+fn read_ahead(
+    input: &Mutex<Input>,
+    events: &mpsc::Sender<Result<ReadEvent>>,
+    resumed: &Receiver<()>,
+    budget: Option<&MemoryBudget>,
+) {
+    let mut line_buffer = String::with_capacity(1024);
+    loop {
+        line_buffer.clear();
+        let (event, commit_due) = {
+            let mut input = input.lock().expect("input lock poisoned");
+            match input.read_next(&mut line_buffer) {
+                Ok(Some(doc)) => (Ok(ReadEvent::Document(doc)), input.commit_due()),
+                Ok(None) => (Ok(ReadEvent::End), false),
+                Err(err) if is_idle(&err) => (Ok(ReadEvent::Checkpoint { idle: true }), false),
+                Err(err) => match err.downcast::<MalformedRecord>() {
+                    Ok(malformed) => (Ok(ReadEvent::Malformed(malformed)), false),
+                    Err(err) => (Err(err), false),
+                },
+            }
+        };
+        let event = match (event, budget) {
+            (Ok(ReadEvent::Document(doc)), Some(budget)) => {
+                let bytes = doc.get().len() as u64;
+                let lease = match budget.try_acquire(bytes, MEMORY_WAIT) {
+                    Some(lease) => lease,
+                    None => {
+                        if events.blocking_send(Ok(ReadEvent::Flush)).is_err() {
+                            return;
+                        }
+                        budget.acquire(bytes)
+                    }
+                };
+                Ok(ReadEvent::Document(doc.with_lease(lease)))
+            }
+            (event, _) => event,
+        };
+        let waits = commit_due || matches!(event, Ok(ReadEvent::Checkpoint { .. }));
+        let last = matches!(event, Ok(ReadEvent::End) | Err(_));
+        if events.blocking_send(event).is_err() || last {
+            return;
+        }
+        if commit_due
+            && events
+                .blocking_send(Ok(ReadEvent::Checkpoint { idle: false }))
+                .is_err()
+        {
+            return;
+        }
+        // The pipe has gone away if it drops the reader instead of committing.
+        if waits && resumed.recv().is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InputReader, ReadEvent};
+    use crate::{input::Input, memory::MemoryBudget};
+    use fluent_uri::UriRef;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[tokio::test]
+    async fn reader_thread_delivers_documents_then_the_end() {
+        let mut file = NamedTempFile::with_suffix(".ndjson").unwrap();
+        writeln!(file, "{{\"id\":1}}\n{{\"id\":2}}").unwrap();
+        let uri = UriRef::parse(file.path().to_string_lossy().into_owned()).unwrap();
+        let mut reader = InputReader::spawn(Input::try_from(uri).unwrap(), None);
+
+        let mut ids = Vec::new();
+        loop {
+            match reader.next().await.unwrap() {
+                ReadEvent::Document(doc) => ids.push(doc.get().to_string()),
+                ReadEvent::Malformed(malformed) => panic!("unexpected {malformed}"),
+                ReadEvent::Checkpoint { .. } => reader.commit().unwrap(),
+                ReadEvent::Flush => panic!("unexpected flush without a memory budget"),
+                ReadEvent::End => break,
+            }
+        }
+
+        assert_eq!(ids, [r#"{"id":1}"#, r#"{"id":2}"#]);
+        reader.commit().unwrap();
+    }
+
+    #[tokio::test]
+    async fn spent_memory_budget_asks_for_a_flush() {
+        let mut file = NamedTempFile::with_suffix(".ndjson").unwrap();
+        writeln!(file, "{{\"id\":1}}\n{{\"id\":2}}").unwrap();
+        let uri = UriRef::parse(file.path().to_string_lossy().into_owned()).unwrap();
+        let budget = MemoryBudget::new(10);
+        let mut reader = InputReader::spawn(Input::try_from(uri).unwrap(), Some(budget));
+
+        let ReadEvent::Document(first) = reader.next().await.unwrap() else {
+            panic!("expected the first document");
+        };
+        assert!(matches!(reader.next().await.unwrap(), ReadEvent::Flush));
+        drop(first);
+        let ReadEvent::Document(second) = reader.next().await.unwrap() else {
+            panic!("expected the second document");
+        };
+        assert_eq!(second.get(), r#"{"id":2}"#);
+    }
+}
@@ -0,0 +1,77 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use eyre::Result;
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderName, HeaderValue};
+
+/// How an `https://` input is fetched.
+///
+/// Its headers are kept apart from the output's, since an artifact store and
+/// the cluster loaded from it rarely share credentials. Pre-signed URLs need
+/// none, as their query string is the credential.
+#[derive(Clone, Debug)]
+pub struct RemoteOptions {
+    headers: HeaderMap,
+    download_parallelism: usize,
+}
+
+impl Default for RemoteOptions {
+    fn default() -> Self {
+        Self {
+            headers: HeaderMap::new(),
+            download_parallelism: 1,
+        }
+    }
+}
+
+impl RemoteOptions {
+    /// Headers from `--input-header`, with an `Authorization` header from
+    /// `--input-basic` or `--input-bearer` in place of any given there.
+    pub fn try_new<'a>(
+        headers: impl IntoIterator<Item = (&'a str, &'a [u8])>,
+        basic: Option<&(String, String)>,
+        bearer: Option<&str>,
+    ) -> Result<Self> {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_bytes(value)?,
+            );
+        }
+        let authorization = match (basic, bearer) {
+            (Some((username, password)), _) => Some(format!(
+                "Basic {}",
+                STANDARD.encode(format!("{username}:{password}"))
+            )),
+            (None, Some(token)) => Some(format!("Bearer {token}")),
+            (None, None) => None,
+        };
+        if let Some(authorization) = authorization {
+            let mut value = HeaderValue::from_str(&authorization)?;
+            value.set_sensitive(true);
+            map.insert(AUTHORIZATION, value);
+        }
+        Ok(Self {
+            headers: map,
+            ..Self::default()
+        })
+    }
+
+    /// Downloads the input in `parallelism` concurrent byte ranges, when the
+    /// server serves ranges and the input is large enough to split.
+    pub fn with_download_parallelism(mut self, parallelism: usize) -> Self {
+        self.download_parallelism = parallelism;
+        self
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.headers.is_empty() && self.download_parallelism == 1
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn download_parallelism(&self) -> usize {
+        self.download_parallelism
+    }
+}
@@ -0,0 +1,336 @@
+use super::{
+    aggregation::{Aggregations, bucket_documents},
+    kql,
+};
+use crate::{
+    client::{ElasticsearchBuilder, KnownHost, elasticsearch::request},
+    manifest::redact_uri,
+};
+use elasticsearch::{Elasticsearch, http::Method};
+use eyre::{Result, eyre};
+use fluent_uri::UriRef;
+use serde_json::{Value, json, value::RawValue};
+use std::{
+    sync::mpsc::{Receiver, SyncSender, sync_channel},
+    thread,
+};
+use url::{Url, form_urlencoded};
+
+/// Hits fetched per search request.
+const PAGE_SIZE: usize = 1_000;
+/// How long the point in time is kept open between pages.
+const KEEP_ALIVE: &str = "5m";
+
+/// Streams the `_source` of every hit of a search, for `?kql=` and `?q=` URIs
+/// and known host indices, or with `--agg`, one document per aggregation bucket.
+///
+/// The search starts on the first read, on its own thread and runtime, and
+/// stays one page ahead of the pipe, like the SQL input.
+pub struct SearchInput {
+    source: String,
+    search: Option<Search>,
+    hits: Option<Receiver<Result<Box<RawValue>>>>,
+}
+
+struct Search {
+    client: Elasticsearch,
+    index: String,
+    query: Option<Value>,
+    aggregations: Option<Aggregations>,
+}
+
+impl SearchInput {
+    pub fn try_new(uri: &UriRef<String>) -> Result<Self> {
+        let source = redact_uri(uri.as_str());
+        let (index, query) = parse(uri)?;
+        let client = client(uri)?.build()?;
+        log::debug!("Search input from {source}");
+        Ok(Self {
+            source,
+            search: Some(Search {
+                client,
+                index,
+                query,
+                aggregations: None,
+            }),
+            hits: None,
+        })
+    }
+
+    /// Runs `aggregations` instead of reading hits, with the query from the
+    /// URI or else from the aggregation file.
+    pub fn aggregate(&mut self, aggregations: Aggregations) -> Result<()> {
+        let search = self
+            .search
+            .as_mut()
+            .ok_or_else(|| eyre!("{} has already started", self.source))?;
+        if search.query.is_some() && aggregations.query().is_some() {
+            return Err(eyre!(
+                "{} and its --agg file both set a query, keep one",
+                self.source
+            ));
+        }
+        search.aggregations = Some(aggregations);
+        Ok(())
+    }
+
+    pub fn read_line(&mut self) -> Result<Box<RawValue>> {
+        if let Some(search) = self.search.take() {
+            self.hits = Some(search.spawn(self.source.clone()));
+        }
+        match &self.hits {
+            Some(hits) => hits.recv().unwrap_or_else(|_| Err(eyre!("No search hit"))),
+            None => Err(eyre!("No search hit")),
+        }
+    }
+}
+
+impl Search {
+    fn spawn(self, source: String) -> Receiver<Result<Box<RawValue>>> {
+        let (sender, hits) = sync_channel(PAGE_SIZE);
+        thread::spawn(move || {
+            let result = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(Into::into)
+                .and_then(|runtime| runtime.block_on(self.run(&sender)));
+            if let Err(err) = result {
+                let _ = sender.send(Err(eyre!("{source}: {err}")));
+            }
+        });
+        hits
+    }
+
+    async fn run(self, hits: &SyncSender<Result<Box<RawValue>>>) -> Result<()> {
+        let query = self
+            .query
+            .or_else(|| self.aggregations.as_ref()?.query().cloned())
+            .unwrap_or_else(|| json!({ "match_all": {} }));
+        match &self.aggregations {
+            Some(aggregations) => {
+                aggregate(&self.client, &self.index, &query, aggregations, hits).await
+            }
+            None => search(&self.client, &self.index, query, hits).await,
+        }
+    }
+}
+
+impl std::fmt::Display for SearchInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// Whether a URI asks for search hits rather than a file or stream.
+pub fn is_search(uri: &UriRef<String>) -> bool {
+    uri.query().is_some_and(|query| {
+        form_urlencoded::parse(query.as_str().as_bytes()).any(|(key, _)| key == "kql" || key == "q")
+    })
+}
+
+/// The index and the query DSL for a search URI. The index is the path, or
+/// the host of a `known-host://index` URI.
+fn parse(uri: &UriRef<String>) -> Result<(String, Option<Value>)> {
+    let index = match uri.authority().map(|authority| authority.host()) {
+        Some(host) if !is_url(uri) && !host.is_empty() => host.to_string(),
+        _ => uri.path().as_str().trim_matches('/').to_string(),
+    };
+    if index.is_empty() {
+        return Err(eyre!(
+            "Search input has no index: {}",
+            redact_uri(uri.as_str())
+        ));
+    }
+    let query = uri.query().map_or("", |query| query.as_str());
+    let mut search = None;
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        // Quotes around the whole query are shell quoting that ended up in the URI.
+        let value = value.trim();
+        let value = value
+            .strip_prefix('\'')
+            .and_then(|value| value.strip_suffix('\''))
+            .unwrap_or(value);
+        search = match (key.as_ref(), search) {
+            ("kql", None) => Some(kql::to_query(value)?),
+            ("q", None) => Some(json!({ "query_string": { "query": value } })),
+            ("kql" | "q", Some(_)) => {
+                return Err(eyre!("Search input takes one kql or q parameter"));
+            }
+            (key, _) => return Err(eyre!("Unsupported search input parameter: {key}")),
+        };
+    }
+    Ok((index, search))
+}
+
+fn is_url(uri: &UriRef<String>) -> bool {
+    uri.scheme()
+        .is_some_and(|scheme| ["http", "https"].contains(&scheme.as_str()))
+}
+
+fn client(uri: &UriRef<String>) -> Result<ElasticsearchBuilder> {
+    match uri.scheme().map(|scheme| scheme.as_str()) {
+        Some("http" | "https") => {
+            let mut url = Url::parse(uri.as_str())?;
+            url.set_path("");
+            url.set_query(None);
+            Ok(ElasticsearchBuilder::new(url))
+        }
+        Some(scheme) => ElasticsearchBuilder::try_from(KnownHost::try_from(scheme)?),
+        None => Err(eyre!("Search input needs a cluster URL or known host")),
+    }
+}
+
+/// Sends one document per bucket, paging through a lone composite aggregation.
+async fn aggregate(
+    client: &Elasticsearch,
+    index: &str,
+    query: &Value,
+    aggregations: &Aggregations,
+    hits: &SyncSender<Result<Box<RawValue>>>,
+) -> Result<()> {
+    let mut after = None;
+    loop {
+        let body = aggregations.request_body(query, after.take());
+        let response = request(
+            client,
+            Method::Post,
+            &format!("/{index}/_search"),
+            &[],
+            Some(&body),
+        )
+        .await?;
+        let results = response["aggregations"]
+            .as_object()
+            .ok_or_else(|| eyre!("search response has no aggregations"))?;
+        for document in bucket_documents(results) {
+            if hits
+                .send(Ok(RawValue::from_string(document.to_string())?))
+                .is_err()
+            {
+                return Ok(());
+            }
+        }
+        match aggregations.next_page(results) {
+            Some(after_key) => after = Some(after_key),
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Opens a point in time on the index, sends every hit's source down `hits`
+/// in shard order, and closes the point in time again.
+async fn search(
+    client: &Elasticsearch,
+    index: &str,
+    query: Value,
+    hits: &SyncSender<Result<Box<RawValue>>>,
+) -> Result<()> {
+    let pit = request(
+        client,
+        Method::Post,
+        &format!("/{index}/_pit"),
+        &[("keep_alive", KEEP_ALIVE)],
+        None,
+    )
+    .await?;
+    let mut pit_id = pit["id"]
+        .as_str()
+        .ok_or_else(|| eyre!("point in time for {index} has no id"))?
+        .to_string();
+    let result = read_pages(client, &query, &mut pit_id, hits).await;
+    if let Err(err) = request(
+        client,
+        Method::Delete,
+        "/_pit",
+        &[],
+        Some(&json!({ "id": pit_id })),
+    )
+    .await
+    {
+        log::warn!("Could not close the point in time on {index}: {err}");
+    }
+    result
+}
+
+async fn read_pages(
+    client: &Elasticsearch,
+    query: &Value,
+    pit_id: &mut String,
+    hits: &SyncSender<Result<Box<RawValue>>>,
+) -> Result<()> {
+    let mut search_after = None;
+    loop {
+        let mut body = json!({
+            "size": PAGE_SIZE,
+            "query": query,
+            "pit": { "id": pit_id, "keep_alive": KEEP_ALIVE },
+            "sort": ["_shard_doc"],
+            "track_total_hits": false,
+        });
+        if let Some(search_after) = search_after.take() {
+            body["search_after"] = search_after;
+        }
+        let page = request(client, Method::Post, "/_search", &[], Some(&body)).await?;
+        if let Some(id) = page["pit_id"].as_str() {
+            *pit_id = id.to_string();
+        }
+        let page_hits = page["hits"]["hits"]
+            .as_array()
+            .map_or(&[][..], Vec::as_slice);
+        for hit in page_hits {
+            let source = hit
+                .get("_source")
+                .ok_or_else(|| eyre!("search hit has no _source"))?;
+            // The pipe has gone away once the receiver is dropped.
+            if hits
+                .send(Ok(RawValue::from_string(source.to_string())?))
+                .is_err()
+            {
+                return Ok(());
+            }
+        }
+        match page_hits.last() {
+            Some(last) if page_hits.len() == PAGE_SIZE => search_after = Some(last["sort"].clone()),
+            _ => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_search, parse};
+    use fluent_uri::UriRef;
+    use serde_json::json;
+
+    fn uri(text: &str) -> UriRef<String> {
+        UriRef::parse(text.to_string()).unwrap()
+    }
+
+    #[test]
+    fn search_uris_name_an_index_and_a_query() {
+        let search = uri("prod://logs-api?kql='status:error%20and%20service:api'");
+        assert!(is_search(&search));
+        assert_eq!(
+            parse(&search).unwrap(),
+            (
+                "logs-api".to_string(),
+                Some(json!({ "bool": { "filter": [
+                    { "match": { "status": "error" } },
+                    { "match": { "service": "api" } },
+                ] } }))
+            )
+        );
+        assert_eq!(
+            parse(&uri(
+                "http://localhost:9200/logs?q=status:500+AND+bytes:%3E0"
+            ))
+            .unwrap()
+            .1,
+            Some(json!({ "query_string": { "query": "status:500 AND bytes:>0" } }))
+        );
+        assert!(!is_search(&uri(
+            "https://example.com/export.ndjson?sig=abc"
+        )));
+        assert!(parse(&uri("http://localhost:9200/?kql=a:1")).is_err());
+    }
+}
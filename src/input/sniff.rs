@@ -0,0 +1,114 @@
+use serde::de::IgnoredAny;
+
+/// What the first bytes of a stream or extensionless file look like.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Sniffed {
+    Ndjson,
+    JsonArray,
+    /// JSON objects that are not one per line, like pretty-printed ones.
+    Concatenated,
+    Csv,
+}
+
+/// Whether `bytes` start with the gzip magic number.
+pub fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&[0x1f, 0x8b])
+}
+
+/// Guesses the format of decompressed `bytes` from their first lines, or
+/// `None` when they look like none of the formats, such as plain text.
+///
+/// CSV needs a header of at least two short, non-empty names, and a first row
+/// with as many fields, when one has been read, so log lines with a comma in
+/// them are not taken for CSV.
+pub fn sniff(bytes: &[u8]) -> Option<Sniffed> {
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        // The buffer may end partway through a multibyte character.
+        Err(err) => std::str::from_utf8(&bytes[..err.valid_up_to()]).ok()?,
+    };
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    match text.chars().next()? {
+        '{' if is_concatenated(text) => return Some(Sniffed::Concatenated),
+        '{' => return Some(Sniffed::Ndjson),
+        '[' => return Some(Sniffed::JsonArray),
+        _ => {}
+    }
+    let mut lines = text.lines();
+    let header = csv_fields(lines.next()?);
+    let is_header = header.len() >= 2
+        && header
+            .iter()
+            .all(|name| !name.is_empty() && name.len() <= 64 && !name.contains(": "));
+    if !is_header {
+        return None;
+    }
+    // Only a row followed by a newline is known to be complete.
+    let complete = text.lines().count() > 2 || text.ends_with('\n');
+    match lines.next() {
+        Some(row) if complete && csv_fields(row).len() != header.len() => None,
+        _ => Some(Sniffed::Csv),
+    }
+}
+
+/// Whether the first line of `text` holds a complete object followed by
+/// another, or is the start of one that continues on later lines. Lines that
+/// are not JSON at all are left to NDJSON's malformed record handling.
+fn is_concatenated(text: &str) -> bool {
+    let Some((line, _)) = text.split_once('\n') else {
+        return false;
+    };
+    let mut values = serde_json::Deserializer::from_str(line).into_iter::<IgnoredAny>();
+    match values.next() {
+        Some(Ok(_)) => matches!(values.next(), Some(Ok(_))),
+        Some(Err(err)) => err.is_eof(),
+        None => false,
+    }
+}
+
+/// Splits a CSV line on commas outside double quotes, trimming each field and
+/// its quotes.
+fn csv_fields(line: &str) -> Vec<&str> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (index, char) in line.char_indices() {
+        match char {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                fields.push(&line[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push(&line[start..]);
+    fields
+        .into_iter()
+        .map(|field| field.trim().trim_matches('"'))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Sniffed, is_gzip, sniff};
+
+    #[test]
+    fn first_lines_tell_json_from_csv_and_text() {
+        assert_eq!(sniff(b"\n  {\"a\":1}\n"), Some(Sniffed::Ndjson));
+        assert_eq!(sniff(b"{\"a\":1} oops\n"), Some(Sniffed::Ndjson));
+        assert_eq!(sniff(b"{\n  \"a\": 1\n}\n"), Some(Sniffed::Concatenated));
+        assert_eq!(sniff(b"{\"a\":1}{\"a\":2}\n"), Some(Sniffed::Concatenated));
+        assert_eq!(sniff(b"\xef\xbb\xbf[{\"a\":1}]"), Some(Sniffed::JsonArray));
+        assert_eq!(
+            sniff(b"id,name,\"city, state\"\n1,ada,\"london, uk\"\n"),
+            Some(Sniffed::Csv)
+        );
+        assert_eq!(sniff(b"id,name"), Some(Sniffed::Csv));
+        assert_eq!(sniff(b"hello, world\nhow are you\n"), None);
+        assert_eq!(sniff(b"Oct 11 22:14:15 host app: a, b\n"), None);
+        assert_eq!(sniff(b"just some notes\n"), None);
+        assert_eq!(sniff(b""), None);
+        assert!(is_gzip(&[0x1f, 0x8b, 8, 0]));
+    }
+}
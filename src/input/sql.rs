@@ -0,0 +1,244 @@
+use eyre::{Result, eyre};
+use fluent_uri::UriRef;
+use futures::{Stream, TryStreamExt};
+use serde_json::{Map, Number, Value, value::RawValue};
+use sqlx::{
+    Column, ColumnIndex, Connection, Decode, Row, TypeInfo, ValueRef, mysql::MySqlConnection,
+    postgres::PgConnection,
+};
+use std::{
+    sync::mpsc::{Receiver, SyncSender, sync_channel},
+    thread,
+};
+use url::Url;
+
+/// Rows fetched ahead of the pipe before the query thread waits.
+const ROWS_AHEAD: usize = 1024;
+
+/// Streams the rows of one `SELECT` from PostgreSQL or MySQL as JSON objects
+/// keyed by column name.
+///
+/// The query runs on its own thread and runtime through the text protocol, so
+/// every value arrives as text: numbers and booleans are converted by column
+/// type, JSON columns are embedded as JSON, and everything else, including
+/// decimals and timestamps, stays a string.
+pub struct SqlInput {
+    source: String,
+    rows: Receiver<Result<Box<RawValue>>>,
+}
+
+impl SqlInput {
+    pub fn try_new(uri: &UriRef<String>) -> Result<Self> {
+        let (url, query) = split_query(uri)?;
+        let source = redacted(&url);
+        let (sender, rows) = sync_channel(ROWS_AHEAD);
+        let (ready, connected) = sync_channel(1);
+        let thread_source = source.clone();
+        thread::spawn(move || {
+            let mut ready = Some(ready);
+            let result = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(Into::into)
+                .and_then(|runtime| runtime.block_on(run_query(&url, &query, &mut ready, &sender)));
+            if let Err(err) = result {
+                let err = eyre!("{thread_source}: {err}");
+                // Until the connection is up `try_new` is waiting for the error; afterwards the pipe is.
+                match ready {
+                    Some(ready) => drop(ready.send(Err(err))),
+                    None => drop(sender.send(Err(err))),
+                }
+            }
+        });
+        connected
+            .recv()
+            .map_err(|_| eyre!("{source}: query thread stopped"))??;
+        log::debug!("SQL input from {source}");
+        Ok(Self { source, rows })
+    }
+
+    pub fn read_line(&mut self) -> Result<Box<RawValue>> {
+        self.rows
+            .recv()
+            .unwrap_or_else(|_| Err(eyre!("No SQL row")))
+    }
+}
+
+impl std::fmt::Display for SqlInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// Takes the `query` parameter off the URI, leaving the connection URL for sqlx.
+fn split_query(uri: &UriRef<String>) -> Result<(Url, String)> {
+    let mut url = Url::parse(uri.as_str())?;
+    let mut query = None;
+    let options: Vec<(String, String)> = url
+        .query_pairs()
+        .filter_map(|(key, value)| match key.as_ref() {
+            "query" => {
+                query = Some(value.into_owned());
+                None
+            }
+            _ => Some((key.into_owned(), value.into_owned())),
+        })
+        .collect();
+    let query = query
+        .filter(|query| !query.trim().is_empty())
+        .ok_or_else(|| eyre!("SQL input requires a query parameter: {}", redacted(&url)))?;
+    url.set_query(None);
+    if !options.is_empty() {
+        url.query_pairs_mut().extend_pairs(options);
+    }
+    Ok((url, query))
+}
+
+/// The connection URL without its password or query, for logs and the summary.
+fn redacted(url: &Url) -> String {
+    let user = match url.username() {
+        "" => String::new(),
+        user => format!("{user}@"),
+    };
+    let port = url
+        .port()
+        .map(|port| format!(":{port}"))
+        .unwrap_or_default();
+    format!(
+        "{}://{user}{}{port}{}",
+        url.scheme(),
+        url.host_str().unwrap_or_default(),
+        url.path()
+    )
+}
+
+/// Connects, tells `try_new` through `ready`, then streams the query's rows.
+async fn run_query(
+    url: &Url,
+    query: &str,
+    ready: &mut Option<SyncSender<Result<()>>>,
+    rows: &SyncSender<Result<Box<RawValue>>>,
+) -> Result<()> {
+    let connected = |ready: &mut Option<SyncSender<Result<()>>>| {
+        if let Some(ready) = ready.take() {
+            let _ = ready.send(Ok(()));
+        }
+    };
+    match url.scheme() {
+        "postgres" | "postgresql" => {
+            let mut connection = PgConnection::connect(url.as_str()).await?;
+            connected(ready);
+            send_rows(sqlx::raw_sql(query).fetch(&mut connection), rows).await
+        }
+        "mysql" | "mariadb" => {
+            let mut connection = MySqlConnection::connect(url.as_str()).await?;
+            connected(ready);
+            send_rows(sqlx::raw_sql(query).fetch(&mut connection), rows).await
+        }
+        scheme => Err(eyre!("Unsupported SQL scheme: {scheme}")),
+    }
+}
+
+async fn send_rows<R>(
+    stream: impl Stream<Item = Result<R, sqlx::Error>>,
+    rows: &SyncSender<Result<Box<RawValue>>>,
+) -> Result<()>
+where
+    R: Row,
+    usize: ColumnIndex<R>,
+    for<'r> &'r str: Decode<'r, R::Database>,
+{
+    let mut stream = std::pin::pin!(stream);
+    while let Some(row) = stream.try_next().await? {
+        // The pipe has gone away once the receiver is dropped.
+        if rows.send(row_document(&row)).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn row_document<R>(row: &R) -> Result<Box<RawValue>>
+where
+    R: Row,
+    usize: ColumnIndex<R>,
+    for<'r> &'r str: Decode<'r, R::Database>,
+{
+    let mut document = Map::new();
+    for column in row.columns() {
+        let value = match row.try_get_raw(column.ordinal())?.is_null() {
+            true => Value::Null,
+            false => typed_value(
+                column.type_info().name(),
+                row.try_get_unchecked::<&str, _>(column.ordinal())?,
+            ),
+        };
+        document.insert(column.name().to_string(), value);
+    }
+    Ok(RawValue::from_string(Value::Object(document).to_string())?)
+}
+
+/// Converts a text-protocol value by its PostgreSQL or MySQL column type name.
+fn typed_value(type_name: &str, text: &str) -> Value {
+    match type_name.trim_end_matches(" UNSIGNED") {
+        "BOOL" => Value::Bool(text == "t"),
+        "BOOLEAN" => Value::Bool(text != "0"),
+        "INT2" | "INT4" | "INT8" | "OID" | "FLOAT4" | "FLOAT8" | "TINYINT" | "SMALLINT"
+        | "MEDIUMINT" | "INT" | "BIGINT" | "YEAR" | "FLOAT" | "DOUBLE" => text
+            .parse::<Number>()
+            .map(Value::Number)
+            .unwrap_or_else(|_| Value::String(text.to_string())),
+        "JSON" | "JSONB" => {
+            serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string()))
+        }
+        _ => Value::String(text.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_query, typed_value};
+    use fluent_uri::UriRef;
+    use serde_json::json;
+
+    #[test]
+    fn queries_come_off_the_connection_url() {
+        let uri = UriRef::parse(
+            "postgres://app:secret@db:5432/shop?sslmode=require&query=SELECT+*+FROM+orders"
+                .to_string(),
+        )
+        .unwrap();
+
+        let (url, query) = split_query(&uri).unwrap();
+
+        assert_eq!(query, "SELECT * FROM orders");
+        assert_eq!(
+            url.as_str(),
+            "postgres://app:secret@db:5432/shop?sslmode=require"
+        );
+        assert_eq!(super::redacted(&url), "postgres://app@db:5432/shop");
+        let missing = UriRef::parse("mysql://app:secret@db/shop".to_string()).unwrap();
+        assert_eq!(
+            split_query(&missing).unwrap_err().to_string(),
+            "SQL input requires a query parameter: mysql://app@db/shop"
+        );
+    }
+
+    #[test]
+    fn text_values_are_typed_by_column() {
+        assert_eq!(typed_value("INT8", "42"), json!(42));
+        assert_eq!(
+            typed_value("BIGINT UNSIGNED", "18446744073709551615"),
+            json!(18446744073709551615u64)
+        );
+        assert_eq!(typed_value("FLOAT8", "NaN"), json!("NaN"));
+        assert_eq!(typed_value("BOOL", "t"), json!(true));
+        assert_eq!(typed_value("BOOLEAN", "0"), json!(false));
+        assert_eq!(typed_value("JSONB", r#"{"a":[1]}"#), json!({"a": [1]}));
+        assert_eq!(typed_value("NUMERIC", "10.50"), json!("10.50"));
+        assert_eq!(
+            typed_value("TIMESTAMPTZ", "2026-01-02 03:04:05+00"),
+            json!("2026-01-02 03:04:05+00")
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use super::{INPUT_IDLE, JSON_LINE_OPENING_ERROR, ensure_json_opening};
+use eyre::{Result, eyre};
+use serde_json::value::RawValue;
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader, Read},
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, RecvTimeoutError, SyncSender, sync_channel},
+    thread,
+    time::Duration,
+};
+
+const IDLE_TIMEOUT: Duration = Duration::from_secs(1);
+const LINE_QUEUE_CAPACITY: usize = 1024;
+
+/// Reads JSON lines from a local producer that may come and go.
+///
+/// A Unix socket keeps accepting new connections and a FIFO is reopened after
+/// every writer closes it, so the input only ends when espipe is stopped.
+/// Lines are read on background threads and handed over through a bounded
+/// queue, which pushes back on producers when the output falls behind.
+pub struct StreamInput {
+    source: String,
+    lines: Receiver<Result<String, String>>,
+    received_since_idle: bool,
+    _socket: Option<SocketFile>,
+}
+
+/// Removes the socket file when the input is dropped.
+struct SocketFile(PathBuf);
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+impl StreamInput {
+    pub fn listen_unix(path: &Path) -> Result<Self> {
+        remove_stale_socket(path)?;
+        let listener = UnixListener::bind(path)
+            .map_err(|err| eyre!("Failed to listen on unix://{}: {err}", path.display()))?;
+        let socket = SocketFile(path.to_path_buf());
+        let source = format!("unix://{}", path.display());
+        let (sender, lines) = sync_channel(LINE_QUEUE_CAPACITY);
+
+        let accept_source = source.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let sender = sender.clone();
+                        let source = accept_source.clone();
+                        thread::spawn(move || forward_connection(&source, stream, &sender));
+                    }
+                    Err(err) => {
+                        let _ = sender.send(Err(format!("{accept_source}: accept failed: {err}")));
+                        return;
+                    }
+                }
+            }
+        });
+        log::debug!("Listening for JSON lines on {source}");
+
+        Ok(Self {
+            source,
+            lines,
+            received_since_idle: false,
+            _socket: Some(socket),
+        })
+    }
+
+    pub fn open_fifo(path: &Path) -> Result<Self> {
+        let source = path.display().to_string();
+        let (sender, lines) = sync_channel(LINE_QUEUE_CAPACITY);
+
+        let fifo = path.to_path_buf();
+        let fifo_source = source.clone();
+        thread::spawn(move || {
+            loop {
+                // Opening blocks until a writer connects; EOF means that writer went away.
+                let file = match File::open(&fifo) {
+                    Ok(file) => file,
+                    Err(err) => {
+                        let _ = sender.send(Err(format!("{fifo_source}: {err}")));
+                        return;
+                    }
+                };
+                if !forward_lines(&fifo_source, file, &sender) {
+                    return;
+                }
+                log::debug!("{fifo_source}: writer closed, reopening");
+            }
+        });
+        log::debug!("Reading JSON lines from FIFO {source}");
+
+        Ok(Self {
+            source,
+            lines,
+            received_since_idle: false,
+            _socket: None,
+        })
+    }
+
+    pub fn read_line(&mut self) -> Result<Box<RawValue>> {
+        loop {
+            let line = match self.lines.recv_timeout(IDLE_TIMEOUT) {
+                Ok(Ok(line)) => line,
+                Ok(Err(err)) => return Err(eyre!(err)),
+                Err(RecvTimeoutError::Timeout) if self.received_since_idle => {
+                    self.received_since_idle = false;
+                    return Err(eyre!(INPUT_IDLE));
+                }
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return Err(eyre!("No stream record")),
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            self.received_since_idle = true;
+            let raw: Box<RawValue> = serde_json::from_str(&line)
+                .map_err(|err| eyre!("{}: Error parsing JSON: {err}", self.source))?;
+            ensure_json_opening(raw.get(), JSON_LINE_OPENING_ERROR)?;
+            return Ok(raw);
+        }
+    }
+}
+
+impl std::fmt::Display for StreamInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+pub fn is_fifo(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_fifo())
+}
+
+/// Only an existing socket file is replaced; anything else at the path is left alone.
+fn remove_stale_socket(path: &Path) -> Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path)
+            .map_err(|err| eyre!("Failed to remove stale socket {}: {err}", path.display())),
+        Ok(_) => Err(eyre!(
+            "Cannot listen on unix://{}: path exists and is not a socket",
+            path.display()
+        )),
+        Err(_) => Ok(()),
+    }
+}
+
+fn forward_connection(
+    source: &str,
+    stream: UnixStream,
+    sender: &SyncSender<Result<String, String>>,
+) {
+    forward_lines(source, stream, sender);
+    log::debug!("{source}: producer disconnected");
+}
+
+/// Sends each line to the reader, returning false once the reader is gone.
+fn forward_lines<R: Read>(
+    source: &str,
+    reader: R,
+    sender: &SyncSender<Result<String, String>>,
+) -> bool {
+    let mut reader = BufReader::new(reader);
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) => return true,
+            Ok(_) => {
+                if sender.send(Ok(line)).is_err() {
+                    return false;
+                }
+            }
+            Err(err) => {
+                log::warn!("{source}: dropping connection after read error: {err}");
+                return true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StreamInput;
+    use std::{
+        io::Write,
+        os::unix::net::UnixStream,
+        path::PathBuf,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    fn socket_path() -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("espipe-stream-{nanos}.sock"))
+    }
+
+    #[test]
+    fn unix_socket_input_keeps_accepting_after_producer_restarts() {
+        let path = socket_path();
+        let mut input = StreamInput::listen_unix(&path).unwrap();
+
+        for n in 1..=2 {
+            let mut producer = UnixStream::connect(&path).unwrap();
+            writeln!(producer, "{{\"n\":{n}}}").unwrap();
+            let value = input.read_line().unwrap();
+            assert_eq!(value.get(), format!("{{\"n\":{n}}}"));
+        }
+
+        drop(input);
+        assert!(!path.exists(), "socket file should be removed on drop");
+    }
+
+    #[test]
+    fn unix_socket_input_reports_idle_after_documents() {
+        let path = socket_path();
+        let mut input = StreamInput::listen_unix(&path).unwrap();
+        let mut producer = UnixStream::connect(&path).unwrap();
+        writeln!(producer, "{{\"n\":1}}").unwrap();
+
+        input.read_line().unwrap();
+        let err = input.read_line().unwrap_err();
+
+        assert!(super::super::is_idle(&err));
+    }
+
+    #[test]
+    fn unix_socket_input_refuses_to_replace_regular_files() {
+        let path = socket_path();
+        std::fs::write(&path, "not a socket").unwrap();
+
+        let err = StreamInput::listen_unix(&path).err().unwrap();
+
+        assert!(err.to_string().contains("path exists and is not a socket"));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{generate::DocTemplate, value_parsers::parse_count};
+use eyre::{Result, eyre};
+use fluent_uri::UriRef;
+use serde_json::value::RawValue;
+use std::path::Path;
+use url::form_urlencoded;
+
+/// Generates fake documents from a template, for trying out pipelines and
+/// mappings without real data.
+///
+/// `gen://order.json` reads the template from `order.json`, `gen:///abs.json`
+/// from an absolute path, and a bare `gen://` uses a small log event. The
+/// `count` query parameter ends the input after that many documents, like
+/// `gen://?count=10k`; without it documents are generated until espipe stops.
+pub struct SyntheticInput {
+    source: String,
+    template: DocTemplate,
+    count: Option<u64>,
+    seq: u64,
+}
+
+impl SyntheticInput {
+    pub fn try_new(uri: &UriRef<String>) -> Result<Self> {
+        let host = uri.authority().map_or("", |authority| authority.host());
+        let path = format!("{host}{}", uri.path().as_str());
+        let template = match path.as_str() {
+            "" | "/" => DocTemplate::default(),
+            path => DocTemplate::load(Path::new(path))?,
+        };
+        let mut count = None;
+        if let Some(query) = uri.query() {
+            for (key, value) in form_urlencoded::parse(query.as_str().as_bytes()) {
+                match key.as_ref() {
+                    "count" => count = Some(parse_count(&value).map_err(|err| eyre!("{err}"))?),
+                    key => {
+                        return Err(eyre!("Unknown gen:// parameter '{key}', expected count"));
+                    }
+                }
+            }
+        }
+        Ok(Self {
+            source: uri.as_str().to_string(),
+            template,
+            count,
+            seq: 0,
+        })
+    }
+
+    pub fn read_line(&mut self) -> Result<Box<RawValue>> {
+        if self.count.is_some_and(|count| self.seq >= count) {
+            return Err(eyre!("No generated document"));
+        }
+        let doc = self.template.render(self.seq)?;
+        self.seq += 1;
+        Ok(doc)
+    }
+}
+
+impl std::fmt::Display for SyntheticInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SyntheticInput;
+    use fluent_uri::UriRef;
+
+    fn input(uri: &str) -> eyre::Result<SyntheticInput> {
+        SyntheticInput::try_new(&UriRef::parse(uri.to_string()).unwrap())
+    }
+
+    #[test]
+    fn count_ends_the_input() {
+        let mut generated = input("gen://?count=2").unwrap();
+        let first = generated.read_line().unwrap();
+        assert!(first.get().contains("\"sequence\":0"), "{}", first.get());
+        generated.read_line().unwrap();
+        let err = generated.read_line().unwrap_err();
+        assert_eq!(err.to_string(), "No generated document");
+    }
+
+    #[test]
+    fn templates_are_read_from_the_uri_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("order.json");
+        std::fs::write(&path, r#"{"order":"{{seq}}"}"#).unwrap();
+
+        let mut generated = input(&format!("gen://{}?count=1", path.display())).unwrap();
+        assert_eq!(generated.read_line().unwrap().get(), r#"{"order":0}"#);
+        assert!(input("gen://?rate=5").is_err());
+        assert!(input("gen://?count=0").is_err());
+    }
+}
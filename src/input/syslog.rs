@@ -0,0 +1,336 @@
+use eyre::{Result, eyre};
+use jiff::{SignedDuration, Timestamp, Zoned, civil::DateTime};
+use serde_json::{Map, Value, json};
+
+const FACILITIES: [&str; 24] = [
+    "kern",
+    "user",
+    "mail",
+    "daemon",
+    "auth",
+    "syslog",
+    "lpr",
+    "news",
+    "uucp",
+    "cron",
+    "authpriv",
+    "ftp",
+    "ntp",
+    "security",
+    "console",
+    "solaris-cron",
+    "local0",
+    "local1",
+    "local2",
+    "local3",
+    "local4",
+    "local5",
+    "local6",
+    "local7",
+];
+const SEVERITIES: [&str; 8] = [
+    "emergency",
+    "alert",
+    "critical",
+    "error",
+    "warning",
+    "notice",
+    "informational",
+    "debug",
+];
+
+/// Parses one syslog line into an ECS-style document.
+pub fn parse_line(line: &str) -> Result<Value> {
+    parse(line, &Zoned::now())
+}
+
+/// Parses an RFC 5424 or RFC 3164 line, with or without its `<PRI>` header.
+///
+/// RFC 3164 timestamps carry no year or zone, so they are read in the local
+/// time zone and given the year that puts them no later than a day after `now`.
+fn parse(line: &str, now: &Zoned) -> Result<Value> {
+    let (priority, rest) = match line.strip_prefix('<') {
+        Some(rest) => {
+            let (priority, rest) = rest
+                .split_once('>')
+                .ok_or_else(|| eyre!("unterminated syslog priority"))?;
+            let priority = priority
+                .parse::<u8>()
+                .ok()
+                .filter(|priority| *priority < 192)
+                .ok_or_else(|| eyre!("invalid syslog priority '<{priority}>'"))?;
+            (Some(priority), rest)
+        }
+        None => (None, line),
+    };
+    let mut document = match rest.strip_prefix("1 ") {
+        Some(rest) => parse_rfc5424(rest)?,
+        None => parse_rfc3164(rest, now)?,
+    };
+    if let Some(priority) = priority {
+        let (facility, severity) = (priority / 8, priority % 8);
+        let log = document["log"].as_object_mut().expect("parsers add log");
+        log.insert("level".to_string(), json!(SEVERITIES[severity as usize]));
+        let syslog = log["syslog"]
+            .as_object_mut()
+            .expect("parsers add log.syslog");
+        syslog.insert("priority".to_string(), json!(priority));
+        syslog.insert(
+            "facility".to_string(),
+            json!({ "code": facility, "name": FACILITIES[facility as usize] }),
+        );
+        syslog.insert(
+            "severity".to_string(),
+            json!({ "code": severity, "name": SEVERITIES[severity as usize] }),
+        );
+    }
+    if document["log"]["syslog"]
+        .as_object()
+        .is_some_and(Map::is_empty)
+    {
+        document
+            .as_object_mut()
+            .expect("parsers build objects")
+            .remove("log");
+    }
+    document["event"] = json!({ "original": line });
+    Ok(document)
+}
+
+fn parse_rfc5424(rest: &str) -> Result<Value> {
+    let mut fields = rest.splitn(6, ' ');
+    let mut field = |name: &str| {
+        fields
+            .next()
+            .filter(|value| !value.is_empty())
+            .ok_or_else(|| eyre!("RFC 5424 syslog line has no {name}"))
+    };
+    let timestamp = field("timestamp")?;
+    let hostname = field("hostname")?;
+    let app_name = field("app name")?;
+    let proc_id = field("process ID")?;
+    let msg_id = field("message ID")?;
+    let (structured_data, message) = parse_structured_data(fields.next().unwrap_or_default())?;
+
+    let mut syslog = json!({ "version": 1 });
+    if let Some(msg_id) = nil(msg_id) {
+        syslog["msgid"] = json!(msg_id);
+    }
+    if !structured_data.is_empty() {
+        syslog["structured_data"] = Value::Object(structured_data);
+    }
+    let mut document = json!({
+        "log": { "syslog": syslog },
+        "message": message.trim_start_matches('\u{feff}'),
+    });
+    if let Some(timestamp) = nil(timestamp) {
+        let timestamp: Timestamp = timestamp
+            .parse()
+            .map_err(|err| eyre!("invalid syslog timestamp '{timestamp}': {err}"))?;
+        document["@timestamp"] = json!(timestamp.to_string());
+    }
+    if let Some(hostname) = nil(hostname) {
+        document["host"] = json!({ "hostname": hostname });
+    }
+    let mut process = Map::new();
+    if let Some(app_name) = nil(app_name) {
+        process.insert("name".to_string(), json!(app_name));
+    }
+    if let Some(proc_id) = nil(proc_id) {
+        process.insert("pid".to_string(), pid(proc_id));
+    }
+    if !process.is_empty() {
+        document["process"] = Value::Object(process);
+    }
+    Ok(document)
+}
+
+/// Splits `[id name="value" ...]` elements off the front of `text`, returning
+/// them by ID along with the message after them.
+fn parse_structured_data(text: &str) -> Result<(Map<String, Value>, &str)> {
+    let mut elements = Map::new();
+    if let Some(message) = text.strip_prefix('-') {
+        return Ok((elements, message.strip_prefix(' ').unwrap_or(message)));
+    }
+    let mut rest = text;
+    while let Some(element) = rest.strip_prefix('[') {
+        let (id, mut params) = element
+            .split_once([' ', ']'])
+            .map(|(id, _)| (id, &element[id.len()..]))
+            .ok_or_else(|| eyre!("unterminated syslog structured data"))?;
+        let mut values = Map::new();
+        loop {
+            params = params.trim_start_matches(' ');
+            if let Some(after) = params.strip_prefix(']') {
+                rest = after;
+                break;
+            }
+            let (name, quoted) = params
+                .split_once("=\"")
+                .ok_or_else(|| eyre!("invalid syslog structured data parameter in [{id}]"))?;
+            let (value, after) = unescape_param(quoted)
+                .ok_or_else(|| eyre!("unterminated syslog structured data value in [{id}]"))?;
+            values.insert(name.to_string(), json!(value));
+            params = after;
+        }
+        elements.insert(id.to_string(), Value::Object(values));
+    }
+    if elements.is_empty() {
+        return Err(eyre!("invalid syslog structured data"));
+    }
+    Ok((elements, rest.strip_prefix(' ').unwrap_or(rest)))
+}
+
+/// Reads a structured data value up to its closing quote, undoing `\"`, `\\`, and `\]`.
+fn unescape_param(quoted: &str) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((index, char)) = chars.next() {
+        match char {
+            '"' => return Some((value, &quoted[index + 1..])),
+            '\\' => match chars.next() {
+                Some((_, escaped @ ('"' | '\\' | ']'))) => value.push(escaped),
+                Some((_, other)) => {
+                    value.push('\\');
+                    value.push(other);
+                }
+                None => return None,
+            },
+            char => value.push(char),
+        }
+    }
+    None
+}
+
+fn parse_rfc3164(rest: &str, now: &Zoned) -> Result<Value> {
+    let (timestamp, rest) = parse_rfc3164_timestamp(rest, now)?;
+    let (hostname, rest) = rest
+        .trim_start()
+        .split_once(' ')
+        .ok_or_else(|| eyre!("syslog line has no hostname and message"))?;
+    let mut document = json!({
+        "@timestamp": timestamp.to_string(),
+        "host": { "hostname": hostname },
+        "log": { "syslog": {} },
+        "message": rest,
+    });
+    if let Some((tag, message)) = rest.split_once(": ")
+        && !tag.is_empty()
+        && !tag.contains(' ')
+    {
+        let mut process = json!({ "name": tag });
+        if let Some((name, pid_text)) = tag.split_once('[')
+            && let Some(pid_text) = pid_text.strip_suffix(']')
+        {
+            process = json!({ "name": name, "pid": pid(pid_text) });
+        }
+        document["process"] = process;
+        document["message"] = json!(message);
+    }
+    Ok(document)
+}
+
+/// Reads a `Mmm dd hh:mm:ss` timestamp, or the RFC 3339 timestamp rsyslog writes in its place.
+fn parse_rfc3164_timestamp<'a>(text: &'a str, now: &Zoned) -> Result<(Timestamp, &'a str)> {
+    if let Some((first, rest)) = text.split_once(' ')
+        && let Ok(timestamp) = first.parse::<Timestamp>()
+    {
+        return Ok((timestamp, rest));
+    }
+    let mut parts = text.split_whitespace();
+    let (Some(month), Some(day), Some(time)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(eyre!("syslog line has no timestamp"));
+    };
+    let rest = &text[text.find(time).expect("time is part of text") + time.len()..];
+    let at_year = |year: i16| {
+        DateTime::strptime("%Y %b %d %H:%M:%S", format!("{year} {month} {day} {time}"))
+            .and_then(|datetime| datetime.to_zoned(now.time_zone().clone()))
+            .map(|zoned| zoned.timestamp())
+    };
+    let timestamp = at_year(now.year())
+        .map_err(|_| eyre!("invalid syslog timestamp '{month} {day} {time}'"))?;
+    if timestamp > now.timestamp() + SignedDuration::from_hours(24) {
+        let timestamp = at_year(now.year() - 1)
+            .map_err(|_| eyre!("invalid syslog timestamp '{month} {day} {time}'"))?;
+        return Ok((timestamp, rest));
+    }
+    Ok((timestamp, rest))
+}
+
+/// RFC 5424 writes `-` for a missing field.
+fn nil(value: &str) -> Option<&str> {
+    (value != "-").then_some(value)
+}
+
+fn pid(text: &str) -> Value {
+    text.parse::<u64>()
+        .map_or_else(|_| json!(text), |pid| json!(pid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use jiff::Zoned;
+    use serde_json::json;
+
+    fn now() -> Zoned {
+        "2026-03-01T12:00:00+00:00[UTC]".parse().unwrap()
+    }
+
+    #[test]
+    fn rfc5424_lines_become_ecs_fields() {
+        let line = r#"<165>1 2026-02-28T22:14:15.003Z mymachine.example.com evntslog 42 ID47 [exampleSDID@32473 iut="3" eventSource="App\"lication"] An application event"#;
+
+        assert_eq!(
+            parse(line, &now()).unwrap(),
+            json!({
+                "@timestamp": "2026-02-28T22:14:15.003Z",
+                "event": { "original": line },
+                "host": { "hostname": "mymachine.example.com" },
+                "log": {
+                    "level": "notice",
+                    "syslog": {
+                        "facility": { "code": 20, "name": "local4" },
+                        "msgid": "ID47",
+                        "priority": 165,
+                        "severity": { "code": 5, "name": "notice" },
+                        "structured_data": {
+                            "exampleSDID@32473": { "eventSource": "App\"lication", "iut": "3" }
+                        },
+                        "version": 1,
+                    },
+                },
+                "message": "An application event",
+                "process": { "name": "evntslog", "pid": 42 },
+            })
+        );
+    }
+
+    #[test]
+    fn rfc3164_lines_take_the_year_that_is_not_in_the_future() {
+        let line = "<34>Oct  1 22:14:15 mymachine su[311]: 'su root' failed on /dev/pts/8";
+
+        let document = parse(line, &now()).unwrap();
+
+        assert_eq!(document["@timestamp"], "2025-10-01T22:14:15Z");
+        assert_eq!(document["host"]["hostname"], "mymachine");
+        assert_eq!(document["process"], json!({ "name": "su", "pid": 311 }));
+        assert_eq!(document["message"], "'su root' failed on /dev/pts/8");
+        assert_eq!(document["log"]["syslog"]["facility"]["name"], "auth");
+        assert_eq!(document["log"]["level"], "critical");
+    }
+
+    #[test]
+    fn archived_lines_without_priority_still_parse() {
+        let document = parse("Feb 28 09:00:01 web1 CRON[99]: job done", &now()).unwrap();
+        assert_eq!(document["@timestamp"], "2026-02-28T09:00:01Z");
+        assert!(document.get("log").is_none());
+
+        let document = parse("2026-02-28T09:00:01.5+01:00 web1 kernel: oops", &now()).unwrap();
+        assert_eq!(document["@timestamp"], "2026-02-28T08:00:01.5Z");
+        assert_eq!(document["process"]["name"], "kernel");
+
+        assert!(parse("<999>1 - - - - - -", &now()).is_err());
+        assert!(parse("not a syslog line", &now()).is_err());
+    }
+}
@@ -0,0 +1,331 @@
+use super::{INPUT_IDLE, Input, InputFormat, open_dropped_file};
+use crate::document::Document;
+use clap::ValueEnum;
+use eyre::{Result, eyre};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, RecvTimeoutError, channel},
+    time::{Duration, Instant},
+};
+
+/// How long a file must go unchanged before it is read, so files still being
+/// written or copied in are not read half-finished.
+const SETTLE_TIME: Duration = Duration::from_secs(1);
+const IDLE_TIMEOUT: Duration = Duration::from_secs(1);
+const DONE_DIR: &str = "done";
+
+/// What `--watch` does with a file once its documents are delivered.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum AfterIngest {
+    /// Leave the file where it is
+    #[default]
+    Keep,
+    /// Move the file into a `done` directory inside the watched one
+    Move,
+    /// Delete the file
+    Delete,
+}
+
+/// Reads the files dropped into a directory, one at a time, as they appear.
+///
+/// Files already in the directory are read first, then new and renamed-in
+/// files as filesystem notifications report them, each once it has stopped
+/// changing. Hidden files and subdirectories are ignored. A file is moved or
+/// deleted only after the output acknowledges every document read from it, so
+/// a crash mid-file leaves it in place to be read again, as does a bulk
+/// response that fails any of its documents. The input only ends when espipe
+/// is stopped.
+pub struct WatchInput {
+    source: String,
+    done_dir: Option<PathBuf>,
+    after_ingest: AfterIngest,
+    content_field: String,
+    format: InputFormat,
+    events: Receiver<notify::Result<Event>>,
+    _watcher: RecommendedWatcher,
+    /// Files waiting to settle, with when they last changed.
+    pending: BTreeMap<PathBuf, Instant>,
+    current: Option<(PathBuf, Box<Input>)>,
+    /// Files read to the end whose documents are not yet acknowledged.
+    finished: Vec<PathBuf>,
+    /// Whether the output failed documents of the file being read.
+    current_failed: bool,
+    /// Files read in this run that are still in the directory.
+    seen: HashSet<PathBuf>,
+    read_since_idle: bool,
+}
+
+impl WatchInput {
+    pub fn try_new(
+        dir: &Path,
+        after_ingest: AfterIngest,
+        content_field: String,
+        format: InputFormat,
+    ) -> Result<Self> {
+        if !dir.is_dir() {
+            return Err(eyre!("--watch needs a directory, not {}", dir.display()));
+        }
+        let done_dir = match after_ingest {
+            AfterIngest::Move => {
+                let done_dir = dir.join(DONE_DIR);
+                fs::create_dir_all(&done_dir)
+                    .map_err(|err| eyre!("Failed to create {}: {err}", done_dir.display()))?;
+                Some(done_dir)
+            }
+            AfterIngest::Keep | AfterIngest::Delete => None,
+        };
+        // Watch before listing, so a file dropped in between is not missed.
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let _ = sender.send(event);
+        })?;
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|err| eyre!("Failed to watch {}: {err}", dir.display()))?;
+        let now = Instant::now();
+        let pending = fs::read_dir(dir)?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| is_candidate(path))
+            .map(|path| (path, now))
+            .collect();
+        log::debug!("Watching {} for new files", dir.display());
+
+        Ok(Self {
+            source: format!("{} (watched)", dir.display()),
+            done_dir,
+            after_ingest,
+            content_field,
+            format,
+            events,
+            _watcher: watcher,
+            pending,
+            current: None,
+            finished: Vec::new(),
+            current_failed: false,
+            seen: HashSet::new(),
+            read_since_idle: false,
+        })
+    }
+
+    pub fn read_line(&mut self, line_buffer: &mut String) -> Result<Document> {
+        loop {
+            if let Some((path, input)) = &mut self.current {
+                match input.read_next(line_buffer)? {
+                    Some(doc) => {
+                        self.read_since_idle = true;
+                        return Ok(doc);
+                    }
+                    None => {
+                        log::debug!("Finished reading {}", path.display());
+                        match std::mem::take(&mut self.current_failed) {
+                            true => leave_in_place(self.after_ingest, path),
+                            false => self.finished.push(path.clone()),
+                        }
+                        self.current = None;
+                        continue;
+                    }
+                }
+            }
+            if let Some(path) = self.next_settled() {
+                self.seen.insert(path.clone());
+                match open_dropped_file(&path, &self.content_field)
+                    .and_then(|input| input.with_input_format(self.format))
+                {
+                    Ok(input) => {
+                        log::info!("Reading {}", path.display());
+                        self.current = Some((path, Box::new(input)));
+                    }
+                    Err(err) => log::warn!("Skipping {}: {err}", path.display()),
+                }
+                continue;
+            }
+            let wait = self
+                .pending
+                .values()
+                .map(|changed| SETTLE_TIME.saturating_sub(changed.elapsed()))
+                .min()
+                .map_or(IDLE_TIMEOUT, |settle| settle.min(IDLE_TIMEOUT));
+            match self.events.recv_timeout(wait) {
+                Ok(Ok(event)) => self.record(event),
+                Ok(Err(err)) => return Err(eyre!("{}: {err}", self.source)),
+                // A timeout while a file settles means it is ready to read, not
+                // that the input went idle.
+                Err(RecvTimeoutError::Timeout)
+                    if self.read_since_idle && self.pending.is_empty() =>
+                {
+                    self.read_since_idle = false;
+                    return Err(eyre!(INPUT_IDLE));
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(eyre!("{}: the directory watcher stopped", self.source));
+                }
+            }
+        }
+    }
+
+    /// Whether files have been read to the end and are waiting for their
+    /// documents to be acknowledged.
+    pub fn commit_due(&self) -> bool {
+        !self.finished.is_empty()
+    }
+
+    /// Moves or deletes the files read to the end, whose documents the output
+    /// has acknowledged.
+    pub fn commit(&mut self) -> Result<()> {
+        for path in self.finished.drain(..) {
+            match (self.after_ingest, &self.done_dir) {
+                (AfterIngest::Move, Some(done_dir)) => {
+                    let name = path.file_name().expect("watched files have names");
+                    fs::rename(&path, done_dir.join(name))
+                        .map_err(|err| eyre!("Failed to move {}: {err}", path.display()))?;
+                }
+                (AfterIngest::Delete, _) => fs::remove_file(&path)
+                    .map_err(|err| eyre!("Failed to delete {}: {err}", path.display()))?,
+                _ => continue,
+            }
+            self.seen.remove(&path);
+        }
+        Ok(())
+    }
+
+    /// Leaves the files read since the last commit where they are, because the
+    /// output failed some of their documents.
+    pub fn skip_commit(&mut self) {
+        for path in self.finished.drain(..) {
+            leave_in_place(self.after_ingest, &path);
+        }
+        self.current_failed = self.current.is_some();
+    }
+
+    fn record(&mut self, event: Event) {
+        match event.kind {
+            EventKind::Create(_) | EventKind::Modify(_) => {
+                for path in event.paths {
+                    if is_candidate(&path) && !self.seen.contains(&path) {
+                        self.pending.insert(path, Instant::now());
+                    }
+                }
+            }
+            EventKind::Remove(_) => {
+                for path in event.paths {
+                    self.pending.remove(&path);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The file that settled first, if any has.
+    fn next_settled(&mut self) -> Option<PathBuf> {
+        self.pending.retain(|path, _| path.is_file());
+        let path = self
+            .pending
+            .iter()
+            .filter(|(_, changed)| changed.elapsed() >= SETTLE_TIME)
+            .min_by_key(|(_, changed)| **changed)
+            .map(|(path, _)| path.clone())?;
+        self.pending.remove(&path);
+        Some(path)
+    }
+}
+
+impl std::fmt::Display for WatchInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+fn leave_in_place(after_ingest: AfterIngest, path: &Path) {
+    if after_ingest != AfterIngest::Keep {
+        log::warn!(
+            "Leaving {} in place, the output failed some of its documents",
+            path.display()
+        );
+    }
+}
+
+fn is_candidate(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .file_name()
+            .is_some_and(|name| !name.to_string_lossy().starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AfterIngest, WatchInput};
+    use crate::input::InputFormat;
+    use std::fs;
+
+    #[test]
+    fn dropped_files_are_read_and_moved_once_committed() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("first.ndjson"), "{\"id\":1}\n{\"id\":2}\n").unwrap();
+        fs::write(dir.path().join(".partial.ndjson"), "{\"id\":0}\n").unwrap();
+        let mut watch = WatchInput::try_new(
+            dir.path(),
+            AfterIngest::Move,
+            "body".to_string(),
+            InputFormat::Auto,
+        )
+        .unwrap();
+
+        let mut line = String::new();
+        assert_eq!(watch.read_line(&mut line).unwrap().get(), "{\"id\":1}");
+        fs::write(dir.path().join("second.ndjson"), "{\"id\":3}\n").unwrap();
+        line.clear();
+        assert_eq!(watch.read_line(&mut line).unwrap().get(), "{\"id\":2}");
+        line.clear();
+        assert_eq!(watch.read_line(&mut line).unwrap().get(), "{\"id\":3}");
+        assert!(watch.commit_due());
+        assert!(dir.path().join("first.ndjson").exists());
+
+        watch.commit().unwrap();
+        assert!(!watch.commit_due());
+        assert!(dir.path().join("done/first.ndjson").exists());
+        assert!(!dir.path().join("first.ndjson").exists());
+        assert!(dir.path().join(".partial.ndjson").exists());
+    }
+
+    #[test]
+    fn files_whose_documents_failed_are_left_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("first.ndjson"), "{\"id\":1}\n{\"id\":2}\n").unwrap();
+        let mut watch = WatchInput::try_new(
+            dir.path(),
+            AfterIngest::Move,
+            "body".to_string(),
+            InputFormat::Auto,
+        )
+        .unwrap();
+
+        // A failure while the file is still being read holds it back once read.
+        let mut line = String::new();
+        assert_eq!(watch.read_line(&mut line).unwrap().get(), "{\"id\":1}");
+        watch.skip_commit();
+        line.clear();
+        assert_eq!(watch.read_line(&mut line).unwrap().get(), "{\"id\":2}");
+        line.clear();
+        assert!(watch.read_line(&mut line).is_err());
+        assert!(!watch.commit_due());
+
+        // So does one after it was read to the end.
+        fs::write(dir.path().join("second.ndjson"), "{\"id\":3}\n").unwrap();
+        line.clear();
+        assert_eq!(watch.read_line(&mut line).unwrap().get(), "{\"id\":3}");
+        line.clear();
+        assert!(watch.read_line(&mut line).is_err());
+        assert!(watch.commit_due());
+        watch.skip_commit();
+        watch.commit().unwrap();
+
+        assert!(dir.path().join("first.ndjson").exists());
+        assert!(dir.path().join("second.ndjson").exists());
+        assert!(!dir.path().join("done/first.ndjson").exists());
+        assert!(!dir.path().join("done/second.ndjson").exists());
+    }
+}
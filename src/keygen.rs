@@ -0,0 +1,145 @@
+use crate::{
+    Cli, Command,
+    client::{Auth, KnownHost, elasticsearch::request},
+    output::{self, ElasticsearchOutputConfig},
+};
+use elasticsearch::http::Method;
+use eyre::{Result, eyre};
+use fluent_uri::UriRef;
+use serde_json::{Value, json};
+use url::Url;
+
+/// Creates an API key that can only write to the given indices, authenticating
+/// with basic auth, so ingest jobs need not hold a user's password.
+#[tracing::instrument(name = "espipe_keygen", skip_all)]
+pub async fn run(args: Cli) -> Result<()> {
+    let Some(Command::Keygen {
+        target,
+        name,
+        index,
+        privileges,
+        expiration,
+        save,
+    }) = &args.command
+    else {
+        unreachable!("keygen::run is only called for espipe keygen");
+    };
+    let config = ElasticsearchOutputConfig::default()
+        .with_timeouts(args.request_timeout, None)
+        .with_proxy(args.proxy.clone())
+        .with_tls_server_name(args.tls_server_name.clone())
+        .with_socket_options(
+            args.connect_timeout,
+            args.tcp_keepalive,
+            args.pool_idle_timeout,
+        )
+        .with_headers(args.headers.clone());
+    let auth = Auth::try_new(
+        args.apikey.clone(),
+        args.username.clone(),
+        args.password.clone(),
+    )?;
+    let host = target_host(target, args.insecure)?.with_auth(auth);
+    let client = output::known_host_client(host.clone(), &config)?;
+
+    let response = request(
+        &client,
+        Method::Post,
+        "/_security/api_key",
+        &[],
+        Some(&api_key_request(
+            name,
+            index,
+            privileges,
+            expiration.as_deref(),
+        )),
+    )
+    .await?;
+    let encoded = response["encoded"]
+        .as_str()
+        .ok_or_else(|| eyre!("The API key response has no encoded key: {response}"))?;
+    println!("{encoded}");
+    if !args.quiet {
+        eprintln!(
+            "Created API key {name} ({}) with {} on {}",
+            response["id"].as_str().unwrap_or_default(),
+            privileges.join(", "),
+            index.join(", ")
+        );
+    }
+    if let Some(save) = save {
+        let path = host
+            .with_auth(Auth::Apikey(encoded.to_string()))
+            .save(save)?;
+        if !args.quiet {
+            eprintln!("Saved it to {} as {save}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// The known host named by `target`, as `prod` or `prod:`, or a host for a
+/// cluster URL.
+fn target_host(target: &UriRef<String>, insecure: bool) -> Result<KnownHost> {
+    match target.scheme().map(|scheme| scheme.as_str()) {
+        Some("http" | "https") => {
+            let mut url = Url::parse(target.as_str())?;
+            url.set_path("");
+            url.set_query(None);
+            Ok(KnownHost::from_url(url, insecure))
+        }
+        Some(name) => KnownHost::try_from(name),
+        None => KnownHost::try_from(target.path().as_str()),
+    }
+}
+
+/// A key with one role that grants `privileges` on `indices`, and nothing on
+/// the cluster.
+fn api_key_request(
+    name: &str,
+    indices: &[String],
+    privileges: &[String],
+    expiration: Option<&str>,
+) -> Value {
+    let mut body = json!({
+        "name": name,
+        "role_descriptors": {
+            name: {
+                "cluster": [],
+                "indices": [{ "names": indices, "privileges": privileges }],
+            }
+        },
+    });
+    if let Some(expiration) = expiration {
+        body["expiration"] = json!(expiration);
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::api_key_request;
+    use serde_json::json;
+
+    #[test]
+    fn keys_are_scoped_to_the_indices_and_privileges() {
+        let indices = ["logs-*".to_string()];
+        let privileges = ["create_doc".to_string(), "auto_configure".to_string()];
+        assert_eq!(
+            api_key_request("espipe-ingest", &indices, &privileges, Some("90d")),
+            json!({
+                "name": "espipe-ingest",
+                "expiration": "90d",
+                "role_descriptors": {
+                    "espipe-ingest": {
+                        "cluster": [],
+                        "indices": [{
+                            "names": ["logs-*"],
+                            "privileges": ["create_doc", "auto_configure"],
+                        }],
+                    }
+                },
+            })
+        );
+    }
+}
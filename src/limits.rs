@@ -0,0 +1,268 @@
+use crate::{dead_letter::DeadLetter, document::Document};
+use clap::ValueEnum;
+use eyre::{Result, eyre};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+/// What `--max-fields` and `--max-depth` do with a document over a limit.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum OverLimit {
+    /// Drop the fields past the limits
+    #[default]
+    Truncate,
+    /// Keep the objects past the limits as JSON text in a single field
+    Flatten,
+    /// Write the document to --dead-letter instead of sending it
+    DeadLetter,
+}
+
+impl OverLimit {
+    /// How the summary describes the documents handled.
+    pub fn resolution(self) -> &'static str {
+        match self {
+            Self::Truncate => "truncated",
+            Self::Flatten => "flattened",
+            Self::DeadLetter => "dead-lettered",
+        }
+    }
+}
+
+/// Guards against documents with so many unique fields or so much nesting,
+/// typical of scraped JSON, that they would blow up the index mapping.
+///
+/// Fields are counted by unique dotted path, with the elements of an array of
+/// objects sharing paths, and depth by object nesting, with the top-level
+/// fields at depth 1, the way Elasticsearch's mapping limits count them.
+pub struct FieldLimits {
+    max_fields: usize,
+    max_depth: usize,
+    action: OverLimit,
+    dead_letter: Option<DeadLetter>,
+    handled: usize,
+}
+
+impl FieldLimits {
+    pub fn try_new(
+        max_fields: Option<usize>,
+        max_depth: Option<usize>,
+        action: OverLimit,
+        dead_letter: Option<DeadLetter>,
+    ) -> Result<Option<Self>> {
+        if max_fields.is_none() && max_depth.is_none() {
+            return Ok(None);
+        }
+        if action == OverLimit::DeadLetter && dead_letter.is_none() {
+            return Err(eyre!("--over-limit dead-letter needs a --dead-letter file"));
+        }
+        Ok(Some(Self {
+            max_fields: max_fields.unwrap_or(usize::MAX),
+            max_depth: max_depth.unwrap_or(usize::MAX),
+            action,
+            dead_letter,
+            handled: 0,
+        }))
+    }
+
+    /// The document within the limits, or `None` once it is dead-lettered.
+    pub fn apply(&mut self, doc: Document) -> Result<Option<Document>> {
+        let mut value: Value = serde_json::from_str(doc.get())
+            .map_err(|err| eyre!("Error parsing JSON for --max-fields: {err}"))?;
+        let Some(object) = value.as_object_mut() else {
+            return Ok(Some(doc));
+        };
+        let mut paths = HashSet::new();
+        let depth = collect_paths(object, "", 1, usize::MAX, &mut paths);
+        if paths.len() <= self.max_fields && depth <= self.max_depth {
+            return Ok(Some(doc));
+        }
+        self.handled += 1;
+        if self.action == OverLimit::DeadLetter {
+            let dead_letter = self.dead_letter.as_ref().expect("checked in try_new");
+            dead_letter.write(&[doc])?;
+            return Ok(None);
+        }
+        let mut pruner = Pruner {
+            max_fields: self.max_fields,
+            max_depth: self.max_depth,
+            flatten: self.action == OverLimit::Flatten,
+            paths: HashSet::new(),
+        };
+        pruner.prune(object, "", 1);
+        Ok(Some(doc.with_json(value.to_string())))
+    }
+
+    pub fn action(&self) -> OverLimit {
+        self.action
+    }
+
+    /// Documents found over a limit.
+    pub fn handled(&self) -> usize {
+        self.handled
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    match path {
+        "" => key.to_string(),
+        path => format!("{path}.{key}"),
+    }
+}
+
+/// Adds the leaf field paths under `object`, whose fields are at `depth`, and
+/// returns the deepest field depth. Objects in fields at `flatten_depth` count
+/// as one leaf, as they would once flattened.
+fn collect_paths(
+    object: &Map<String, Value>,
+    path: &str,
+    depth: usize,
+    flatten_depth: usize,
+    paths: &mut HashSet<String>,
+) -> usize {
+    let mut deepest = depth;
+    for (key, value) in object {
+        let path = join(path, key);
+        let objects: Vec<&Map<String, Value>> = match value {
+            _ if depth >= flatten_depth => Vec::new(),
+            Value::Object(object) => vec![object],
+            Value::Array(values) => values.iter().filter_map(Value::as_object).collect(),
+            _ => Vec::new(),
+        };
+        if objects.is_empty()
+            || matches!(value, Value::Array(values) if values.len() > objects.len())
+        {
+            paths.insert(path.clone());
+        }
+        for object in objects {
+            deepest = deepest.max(collect_paths(
+                object,
+                &path,
+                depth + 1,
+                flatten_depth,
+                paths,
+            ));
+        }
+    }
+    deepest
+}
+
+/// Brings a document within the limits, dropping what does not fit or with
+/// `flatten`, turning objects that do not fit into JSON text.
+struct Pruner {
+    max_fields: usize,
+    max_depth: usize,
+    flatten: bool,
+    paths: HashSet<String>,
+}
+
+impl Pruner {
+    fn prune(&mut self, object: &mut Map<String, Value>, path: &str, depth: usize) {
+        object.retain(|key, value| self.keep(join(path, key), value, depth));
+    }
+
+    /// Whether the field at `path` and `depth` stays, pruning what it holds.
+    fn keep(&mut self, path: String, value: &mut Value, depth: usize) -> bool {
+        match value {
+            Value::Object(object) if self.fits_below(object, &path, depth) => {
+                self.prune(object, &path, depth + 1);
+                true
+            }
+            Value::Object(_) if self.flatten => {
+                *value = Value::String(value.to_string());
+                self.keep_leaf(path)
+            }
+            Value::Object(_) => false,
+            Value::Array(values) => {
+                values.retain_mut(|value| match value {
+                    Value::Object(_) => self.keep(path.clone(), value, depth),
+                    _ => true,
+                });
+                match values.iter().any(|value| !value.is_object()) {
+                    true => self.keep_leaf(path),
+                    false => true,
+                }
+            }
+            _ => self.keep_leaf(path),
+        }
+    }
+
+    /// Whether an object's fields, at `depth + 1`, are within the depth limit
+    /// and, when flattening, fit in the fields left once flattened themselves.
+    fn fits_below(&self, object: &Map<String, Value>, path: &str, depth: usize) -> bool {
+        if depth >= self.max_depth {
+            return false;
+        }
+        if !self.flatten {
+            return true;
+        }
+        let mut paths = HashSet::new();
+        collect_paths(object, path, depth + 1, self.max_depth, &mut paths);
+        self.paths.len() + paths.difference(&self.paths).count() <= self.max_fields
+    }
+
+    fn keep_leaf(&mut self, path: String) -> bool {
+        if self.paths.contains(&path) {
+            return true;
+        }
+        if self.paths.len() >= self.max_fields {
+            return false;
+        }
+        self.paths.insert(path);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FieldLimits, OverLimit};
+    use crate::document::Document;
+
+    fn limit(max_fields: usize, max_depth: usize, action: OverLimit, json: &str) -> String {
+        let mut limits = FieldLimits::try_new(Some(max_fields), Some(max_depth), action, None)
+            .unwrap()
+            .unwrap();
+        let doc = limits.apply(Document::unvalidated(json).unwrap()).unwrap();
+        doc.unwrap().get().to_string()
+    }
+
+    #[test]
+    fn documents_within_the_limits_are_unchanged() {
+        let json = r#"{"b": 1, "a": {"c": [{"d": 1}, {"d": 2}]}}"#;
+        assert_eq!(limit(2, 3, OverLimit::Truncate, json), json);
+    }
+
+    #[test]
+    fn truncate_drops_fields_past_the_limits() {
+        let json = r#"{"a":1,"b":{"c":{"d":1},"e":2},"f":3,"g":4}"#;
+        assert_eq!(
+            limit(10, 2, OverLimit::Truncate, json),
+            r#"{"a":1,"b":{"e":2},"f":3,"g":4}"#
+        );
+        assert_eq!(
+            limit(3, 5, OverLimit::Truncate, json),
+            r#"{"a":1,"b":{"c":{"d":1},"e":2}}"#
+        );
+    }
+
+    #[test]
+    fn flatten_keeps_objects_past_the_limits_as_text() {
+        let json = r#"{"a":1,"b":{"c":{"d":1},"e":2},"f":3}"#;
+        assert_eq!(
+            limit(10, 2, OverLimit::Flatten, json),
+            r#"{"a":1,"b":{"c":"{\"d\":1}","e":2},"f":3}"#
+        );
+        assert_eq!(
+            limit(2, 5, OverLimit::Flatten, json),
+            r#"{"a":1,"b":"{\"c\":{\"d\":1},\"e\":2}"}"#
+        );
+    }
+
+    #[test]
+    fn dead_letter_needs_a_file() {
+        assert!(FieldLimits::try_new(Some(1), None, OverLimit::DeadLetter, None).is_err());
+        assert!(
+            FieldLimits::try_new(None, None, OverLimit::DeadLetter, None)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
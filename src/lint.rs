@@ -0,0 +1,304 @@
+use crate::{
+    Cli, Command, comma_formatted, field_path,
+    input::{Input, InputReader, MalformedRecord, ReadEvent},
+    input_encoding, remote_options,
+    replay::parse_timestamp,
+};
+use eyre::{Result, eyre};
+use jiff::{Timestamp, ToSpan};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Malformed records listed by line before the rest are only counted.
+const MAX_LISTED: usize = 10;
+
+/// Elasticsearch's default `index.mapping.total_fields.limit`.
+const TOTAL_FIELDS_LIMIT: usize = 1000;
+
+/// Elasticsearch's default `index.mapping.depth.limit`.
+const DEPTH_LIMIT: usize = 20;
+
+/// Timestamps before this are more likely epoch seconds read as milliseconds,
+/// or placeholder dates, than real events.
+const EARLIEST_PLAUSIBLE: &str = "2000-01-01T00:00:00Z";
+
+/// What `espipe lint` found in its inputs.
+#[derive(Debug, Default)]
+struct Report {
+    docs: u64,
+    bytes: u64,
+    malformed: Vec<MalformedRecord>,
+    malformed_count: u64,
+    /// Value counts by JSON type, by field path, with array elements counted
+    /// under their array's field as Elasticsearch maps them.
+    fields: BTreeMap<String, BTreeMap<&'static str, u64>>,
+    max_depth: usize,
+    max_doc_fields: usize,
+    timestamps: TimestampCounts,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct TimestampCounts {
+    missing: u64,
+    unparseable: u64,
+    future: u64,
+    too_early: u64,
+}
+
+/// Scans the inputs for problems that would fail or skew a load, without an
+/// output: malformed records, fields whose type changes between documents,
+/// suspicious timestamps, mappings near Elasticsearch's limits, and the index
+/// size to expect. Prints a report and fails when it finds a problem.
+#[tracing::instrument(name = "espipe_lint", skip_all)]
+pub async fn run(args: Cli) -> Result<()> {
+    let Some(Command::Lint {
+        inputs,
+        timestamp_field,
+    }) = &args.command
+    else {
+        unreachable!("lint::run is only called for espipe lint");
+    };
+    let input =
+        Input::try_new_with_remote(inputs.clone(), args.content.clone(), remote_options(&args)?)
+            .await?
+            .with_input_encoding(input_encoding(&args))?
+            .with_archive_glob(args.archive_glob.clone(), args.zip_password.clone())?
+            .with_input_format(args.input_format)?;
+    let name = input.to_string();
+    let report = scan(
+        InputReader::spawn(input, None),
+        timestamp_field,
+        Timestamp::now(),
+    )
+    .await?;
+    match report.print(&name, timestamp_field) {
+        0 => Ok(()),
+        problems => Err(eyre!("{name} has {problems} kind(s) of problem")),
+    }
+}
+
+/// Reads every record of `input` into a report.
+async fn scan(mut input: InputReader, timestamp_field: &str, now: Timestamp) -> Result<Report> {
+    let mut report = Report::default();
+    loop {
+        match input.next().await? {
+            ReadEvent::Document(doc) => {
+                report.bytes += doc.get().len() as u64;
+                let value: Value = serde_json::from_str(doc.get())?;
+                report.add(&value, timestamp_field, now);
+            }
+            ReadEvent::Malformed(malformed) => {
+                report.malformed_count += 1;
+                if report.malformed.len() < MAX_LISTED {
+                    report.malformed.push(malformed);
+                }
+            }
+            // Nothing is written, so there is nothing to wait for.
+            ReadEvent::Checkpoint { .. } => input.commit()?,
+            ReadEvent::Flush => {}
+            ReadEvent::End => break,
+        }
+    }
+    Ok(report)
+}
+
+/// The JSON type of a value as it bears on the field's mapping, with integers
+/// told from floats, or `None` for nulls and arrays, which are not mapped
+/// themselves.
+pub fn field_type(value: &Value) -> Option<&'static str> {
+    match value {
+        Value::Null | Value::Array(_) => None,
+        Value::Bool(_) => Some("boolean"),
+        Value::Number(number) if number.is_i64() || number.is_u64() => Some("integer"),
+        Value::Number(_) => Some("float"),
+        Value::String(_) => Some("string"),
+        Value::Object(_) => Some("object"),
+    }
+}
+
+impl Report {
+    fn add(&mut self, doc: &Value, timestamp_field: &str, now: Timestamp) {
+        self.docs += 1;
+        let mut doc_fields = BTreeSet::new();
+        if let Value::Object(fields) = doc {
+            for (key, value) in fields {
+                self.add_field(key, value, 1, &mut doc_fields);
+            }
+        }
+        self.max_doc_fields = self.max_doc_fields.max(doc_fields.len());
+
+        let earliest: Timestamp = EARLIEST_PLAUSIBLE.parse().expect("valid timestamp");
+        match field_path::lookup(doc, timestamp_field) {
+            None | Some(Value::Null) => self.timestamps.missing += 1,
+            Some(value) => match parse_timestamp(value) {
+                None => self.timestamps.unparseable += 1,
+                Some(time) if time > now + 24.hours() => self.timestamps.future += 1,
+                Some(time) if time < earliest => self.timestamps.too_early += 1,
+                Some(_) => {}
+            },
+        }
+    }
+
+    /// Counts `value` under `path`, and the fields of the document it is in,
+    /// objects included, as Elasticsearch counts them toward its limits.
+    fn add_field(
+        &mut self,
+        path: &str,
+        value: &Value,
+        depth: usize,
+        doc_fields: &mut BTreeSet<String>,
+    ) {
+        self.max_depth = self.max_depth.max(depth);
+        if let Value::Array(items) = value {
+            for item in items {
+                self.add_field(path, item, depth, doc_fields);
+            }
+            return;
+        }
+        let Some(kind) = field_type(value) else {
+            return;
+        };
+        *self
+            .fields
+            .entry(path.to_string())
+            .or_default()
+            .entry(kind)
+            .or_default() += 1;
+        doc_fields.insert(path.to_string());
+        if let Value::Object(fields) = value {
+            for (key, value) in fields {
+                self.add_field(&format!("{path}.{key}"), value, depth + 1, doc_fields);
+            }
+        }
+    }
+
+    /// Prints the report, returning how many kinds of problem it found.
+    fn print(&self, name: &str, timestamp_field: &str) -> usize {
+        let mut problems = 0;
+        println!(
+            "{name}: {} docs, {} bytes",
+            comma_formatted(self.docs as usize),
+            comma_formatted(self.bytes as usize)
+        );
+
+        if self.malformed_count > 0 {
+            problems += 1;
+            println!(
+                "Malformed records: {}",
+                comma_formatted(self.malformed_count as usize)
+            );
+            for malformed in &self.malformed {
+                println!("  {malformed}");
+            }
+            if self.malformed_count > self.malformed.len() as u64 {
+                println!("  ...");
+            }
+        }
+
+        let conflicts: Vec<_> = self
+            .fields
+            .iter()
+            .filter(|(_, kinds)| kinds.len() > 1)
+            .collect();
+        if !conflicts.is_empty() {
+            problems += 1;
+            println!("Fields with mixed types: {}", conflicts.len());
+            for (path, kinds) in conflicts {
+                let kinds: Vec<_> = kinds
+                    .iter()
+                    .map(|(kind, docs)| format!("{} {kind}", comma_formatted(*docs as usize)))
+                    .collect();
+                println!("  {path}: {}", kinds.join(", "));
+            }
+        }
+
+        let TimestampCounts {
+            missing,
+            unparseable,
+            future,
+            too_early,
+        } = self.timestamps;
+        let suspicious = unparseable + future + too_early;
+        if missing < self.docs {
+            println!(
+                "Timestamps in {timestamp_field}: {} plausible",
+                comma_formatted((self.docs - missing - suspicious) as usize)
+            );
+            for (count, problem) in [
+                (missing, "missing"),
+                (unparseable, "unparseable"),
+                (future, "more than a day in the future"),
+                (
+                    too_early,
+                    "before 2000, perhaps epoch seconds read as milliseconds",
+                ),
+            ] {
+                if count > 0 {
+                    println!("  {} {problem}", comma_formatted(count as usize));
+                }
+            }
+            if suspicious > 0 {
+                problems += 1;
+            }
+        } else if self.docs > 0 {
+            println!("Timestamps in {timestamp_field}: none");
+        }
+
+        println!(
+            "Fields: {} distinct, at most {} in a doc, nested at most {} deep",
+            comma_formatted(self.fields.len()),
+            comma_formatted(self.max_doc_fields),
+            self.max_depth
+        );
+        if self.fields.len() > TOTAL_FIELDS_LIMIT {
+            problems += 1;
+            println!("  over the default index.mapping.total_fields.limit of {TOTAL_FIELDS_LIMIT}");
+        }
+        if self.max_depth > DEPTH_LIMIT {
+            problems += 1;
+            println!("  over the default index.mapping.depth.limit of {DEPTH_LIMIT}");
+        }
+
+        // Indexed data usually takes about as much disk as its JSON source,
+        // give or take the mapping, so the estimate is a range.
+        println!(
+            "Estimated index size: {} to {} bytes per copy",
+            comma_formatted(self.bytes as usize),
+            comma_formatted((self.bytes + self.bytes / 2) as usize)
+        );
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Report;
+    use jiff::Timestamp;
+    use serde_json::json;
+
+    #[test]
+    fn reports_mixed_types_depth_and_suspicious_timestamps() {
+        let now: Timestamp = "2026-10-16T12:00:00Z".parse().unwrap();
+        let mut report = Report::default();
+        for doc in [
+            json!({"@timestamp": "2026-10-16T11:00:00Z", "status": 200, "tags": ["a", "b"]}),
+            json!({"@timestamp": "2030-01-01T00:00:00Z", "status": "ok", "http": {"port": 80}}),
+            json!({"@timestamp": 1760000000, "status": 1.5, "tags": null}),
+            json!({"@timestamp": "yesterday", "http": {"port": {"number": 80}}}),
+        ] {
+            report.add(&doc, "@timestamp", now);
+        }
+
+        assert_eq!(report.docs, 4);
+        let status: Vec<_> = report.fields["status"].keys().copied().collect();
+        assert_eq!(status, ["float", "integer", "string"]);
+        assert_eq!(report.fields["tags"].len(), 1);
+        assert_eq!(report.fields["http.port"].len(), 2);
+        assert_eq!(report.max_depth, 3);
+        assert_eq!(report.max_doc_fields, 4);
+        assert_eq!(report.timestamps.future, 1);
+        assert_eq!(report.timestamps.too_early, 1);
+        assert_eq!(report.timestamps.unparseable, 1);
+        assert_eq!(report.timestamps.missing, 0);
+    }
+}
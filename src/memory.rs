@@ -0,0 +1,106 @@
+use crate::metrics::METRICS;
+use std::{
+    sync::{Arc, Condvar, Mutex, atomic::Ordering},
+    time::Duration,
+};
+
+/// The `--max-memory` limit on the bytes of documents read but not yet
+/// delivered, across the input queue, the output's batches, and the bulk
+/// requests in flight.
+///
+/// The reader takes a [`MemoryLease`] for each document, waiting while the
+/// budget is spent, and the lease travels with the document until it is
+/// written or its bulk request completes. A document larger than the whole
+/// budget is let through once nothing else is held, so it cannot stall the
+/// reader for good.
+#[derive(Clone, Debug)]
+pub struct MemoryBudget {
+    limit: u64,
+    held: Arc<(Mutex<u64>, Condvar)>,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            held: Arc::new((Mutex::new(0), Condvar::new())),
+        }
+    }
+
+    /// Takes `bytes` from the budget, waiting up to `wait` for other leases
+    /// to be released, or `None` if the budget is still spent.
+    pub fn try_acquire(&self, bytes: u64, wait: Duration) -> Option<MemoryLease> {
+        let (held, released) = &*self.held;
+        let held = held.lock().expect("memory budget lock poisoned");
+        let (mut held, _) = released
+            .wait_timeout_while(held, wait, |held| !self.fits(*held, bytes))
+            .expect("memory budget lock poisoned");
+        if !self.fits(*held, bytes) {
+            return None;
+        }
+        *held += bytes;
+        METRICS
+            .buffered_bytes
+            .fetch_add(bytes as i64, Ordering::Relaxed);
+        Some(MemoryLease {
+            budget: self.clone(),
+            bytes,
+        })
+    }
+
+    /// Takes `bytes` from the budget, waiting as long as it takes.
+    pub fn acquire(&self, bytes: u64) -> MemoryLease {
+        loop {
+            if let Some(lease) = self.try_acquire(bytes, Duration::from_secs(1)) {
+                return lease;
+            }
+        }
+    }
+
+    fn fits(&self, held: u64, bytes: u64) -> bool {
+        held == 0 || held + bytes <= self.limit
+    }
+
+    fn release(&self, bytes: u64) {
+        let (held, released) = &*self.held;
+        *held.lock().expect("memory budget lock poisoned") -= bytes;
+        METRICS
+            .buffered_bytes
+            .fetch_sub(bytes as i64, Ordering::Relaxed);
+        released.notify_all();
+    }
+}
+
+/// Bytes taken from a [`MemoryBudget`], given back when dropped.
+#[derive(Debug)]
+pub struct MemoryLease {
+    budget: MemoryBudget,
+    bytes: u64,
+}
+
+impl Drop for MemoryLease {
+    fn drop(&mut self) {
+        self.budget.release(self.bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MemoryBudget;
+    use std::time::Duration;
+
+    #[test]
+    fn leases_wait_for_room_in_the_budget() {
+        let budget = MemoryBudget::new(10);
+        let first = budget.acquire(6);
+        assert!(budget.try_acquire(6, Duration::ZERO).is_none());
+        let second = budget.try_acquire(4, Duration::ZERO).unwrap();
+        drop(first);
+        assert!(budget.try_acquire(6, Duration::ZERO).is_some());
+        drop(second);
+        // A document over the whole budget still goes through on its own.
+        let large = budget.try_acquire(50, Duration::ZERO).unwrap();
+        assert!(budget.try_acquire(1, Duration::ZERO).is_none());
+        drop(large);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::{document::Document, field_path};
+use eyre::{Result, eyre};
+use serde_json::{Map, Value};
+use std::{fs, path::Path};
+
+/// Constant fields from `--set` and `--set-file`, merged into every document so
+/// a run can be traced in the destination index.
+///
+/// Objects merge field by field and anything else replaces the document's value.
+/// Bulk `update` pairs get the fields in their partial `doc`.
+#[derive(Debug, PartialEq)]
+pub struct StaticFields {
+    fields: Value,
+}
+
+impl StaticFields {
+    /// Files are merged in order, then `--set` fields on top, so the command line wins.
+    pub fn try_new(files: &[impl AsRef<Path>], sets: Vec<(String, Value)>) -> Result<Option<Self>> {
+        let mut fields = Value::Object(Map::new());
+        for path in files {
+            for (path, field) in load_file(path.as_ref())? {
+                field_path::insert(&mut fields, &path, field);
+            }
+        }
+        for (path, field) in sets {
+            field_path::insert(&mut fields, &path, field);
+        }
+        Ok(match fields.as_object().is_some_and(Map::is_empty) {
+            true => None,
+            false => Some(Self { fields }),
+        })
+    }
+
+    pub fn apply(&self, doc: Document) -> Result<Document> {
+        let mut value: Value = serde_json::from_str(doc.get())
+            .map_err(|err| eyre!("Error parsing JSON for --set: {err}"))?;
+        let target = match doc.action().is_some_and(is_update) {
+            true => value.get_mut("doc"),
+            false => Some(&mut value),
+        };
+        match target {
+            Some(target) if target.is_object() => merge(target, &self.fields),
+            _ => return Err(eyre!("--set requires JSON object documents")),
+        }
+        Ok(doc.with_json(value.to_string()))
+    }
+}
+
+fn load_file(path: &Path) -> Result<Map<String, Value>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| eyre!("failed to read --set-file {}: {err}", path.display()))?;
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    let fields = match extension.as_deref() {
+        Some("yml" | "yaml") => serde_yaml::from_str::<Value>(&contents).map_err(|err| {
+            eyre!(
+                "failed to parse --set-file {} as YAML: {err}",
+                path.display()
+            )
+        }),
+        _ => serde_json::from_str::<Value>(&contents).map_err(|err| {
+            eyre!(
+                "failed to parse --set-file {} as JSON: {err}",
+                path.display()
+            )
+        }),
+    }?;
+    match fields {
+        Value::Object(fields) => Ok(fields),
+        _ => Err(eyre!("--set-file {} must hold an object", path.display())),
+    }
+}
+
+/// Whether a bulk action line is an `update`.
+pub fn is_update(action: &str) -> bool {
+    serde_json::from_str::<Value>(action).is_ok_and(|action| action.get("update").is_some())
+}
+
+fn merge(target: &mut Value, fields: &Value) {
+    match (target, fields) {
+        (Value::Object(target), Value::Object(fields)) => {
+            for (key, field) in fields {
+                match target.get_mut(key) {
+                    Some(existing) if existing.is_object() && field.is_object() => {
+                        merge(existing, field)
+                    }
+                    _ => {
+                        target.insert(key.clone(), field.clone());
+                    }
+                }
+            }
+        }
+        (target, fields) => *target = fields.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StaticFields;
+    use crate::document::Document;
+    use serde_json::{Value, json, value::RawValue};
+    use std::{fs, path::PathBuf};
+
+    fn raw(json: &str) -> Document {
+        RawValue::from_string(json.to_string()).unwrap().into()
+    }
+
+    fn fields(sets: &[(&str, Value)]) -> Option<StaticFields> {
+        let sets = sets
+            .iter()
+            .map(|(path, field)| (path.to_string(), field.clone()))
+            .collect();
+        StaticFields::try_new(&[] as &[PathBuf], sets).unwrap()
+    }
+
+    #[test]
+    fn fields_merge_into_nested_objects() {
+        let fields = fields(&[
+            ("data_source", json!("nightly")),
+            ("ingest.run_id", json!(42)),
+        ])
+        .unwrap();
+
+        let doc = fields
+            .apply(raw(r#"{"ingest":{"host":"a"},"data_source":"old","n":1}"#))
+            .unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<Value>(doc.get()).unwrap(),
+            json!({"data_source": "nightly", "ingest": {"host": "a", "run_id": 42}, "n": 1})
+        );
+        assert!(fields.apply(raw("[1]")).is_err());
+    }
+
+    #[test]
+    fn update_pairs_get_fields_in_their_partial_doc() {
+        let fields = fields(&[("env", json!("prod"))]).unwrap();
+
+        let doc = fields
+            .apply(raw(r#"{"doc":{"n":1}}"#).with_action(r#"{"update":{"_id":"1"}}"#))
+            .unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<Value>(doc.get()).unwrap(),
+            json!({"doc": {"env": "prod", "n": 1}})
+        );
+        assert_eq!(doc.action(), Some(r#"{"update":{"_id":"1"}}"#));
+    }
+
+    #[test]
+    fn command_line_fields_win_over_files() {
+        let path = std::env::temp_dir().join(format!("espipe-set-{}.yml", std::process::id()));
+        fs::write(&path, "env: staging\ningest.tool: espipe\n").unwrap();
+
+        let merged = StaticFields::try_new(&[&path], vec![("env".to_string(), json!("prod"))])
+            .unwrap()
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            merged.fields,
+            json!({"env": "prod", "ingest": {"tool": "espipe"}})
+        );
+        assert_eq!(fields(&[]), None);
+    }
+}
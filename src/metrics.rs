@@ -0,0 +1,313 @@
+use bytes::Bytes;
+use eyre::{Result, eyre};
+use http_body_util::Full;
+use hyper::{
+    Method, Request, Response, StatusCode,
+    body::Incoming,
+    header::{CONTENT_TYPE, HeaderValue},
+    server::conn::http1,
+    service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    net::{SocketAddr, TcpListener},
+    sync::{
+        Mutex,
+        atomic::{AtomicI64, AtomicU64, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+/// Upper bounds, in seconds, of the bulk request latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Picks the sent or failed count out of an index's `(sent, failed)` pair.
+type IndexCount = fn(&(u64, u64)) -> u64;
+
+/// Process-wide counters served by `--metrics-addr`, summed across every pipeline.
+pub static METRICS: Metrics = Metrics::new();
+
+pub struct Metrics {
+    pub docs_read: AtomicU64,
+    pub docs_sent: AtomicU64,
+    /// Documents rejected by Elasticsearch bulk responses.
+    pub docs_failed: AtomicU64,
+    pub docs_dead_lettered: AtomicU64,
+    pub bulk_retries: AtomicU64,
+    /// Documents handed to an Elasticsearch output but not yet in a bulk request.
+    pub queued_docs: AtomicI64,
+    pub bulk_requests_in_flight: AtomicI64,
+    /// Bytes of documents read but not yet delivered, counted under `--max-memory`.
+    pub buffered_bytes: AtomicI64,
+    bulk_latency: Histogram,
+    /// Documents sent and failed per Elasticsearch target index.
+    index_docs: Mutex<BTreeMap<String, (u64, u64)>>,
+}
+
+struct Histogram {
+    /// Observations per bucket, with the last slot counting those above every bound.
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    sum_micros: AtomicU64,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            docs_read: AtomicU64::new(0),
+            docs_sent: AtomicU64::new(0),
+            docs_failed: AtomicU64::new(0),
+            docs_dead_lettered: AtomicU64::new(0),
+            bulk_retries: AtomicU64::new(0),
+            queued_docs: AtomicI64::new(0),
+            bulk_requests_in_flight: AtomicI64::new(0),
+            buffered_bytes: AtomicI64::new(0),
+            bulk_latency: Histogram {
+                buckets: [const { AtomicU64::new(0) }; LATENCY_BUCKETS.len() + 1],
+                sum_micros: AtomicU64::new(0),
+            },
+            index_docs: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Records how long one bulk HTTP request took, including reading its response.
+    pub fn observe_bulk_latency(&self, latency: Duration) {
+        let seconds = latency.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.bulk_latency.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.bulk_latency
+            .sum_micros
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Adds documents sent and failed to the counts labelled with their index.
+    pub fn add_index_docs(&self, index: &str, sent: u64, failed: u64) {
+        let mut index_docs = self.index_docs.lock().expect("metrics lock poisoned");
+        let counts = match index_docs.get_mut(index) {
+            Some(counts) => counts,
+            None => index_docs.entry(index.to_string()).or_default(),
+        };
+        counts.0 += sent;
+        counts.1 += failed;
+    }
+
+    /// Renders the Prometheus text exposition format.
+    fn render(&self) -> String {
+        let mut text = String::new();
+        let counters = [
+            (
+                "espipe_docs_read_total",
+                "Documents read from inputs",
+                &self.docs_read,
+            ),
+            (
+                "espipe_docs_sent_total",
+                "Documents delivered to outputs",
+                &self.docs_sent,
+            ),
+            (
+                "espipe_docs_failed_total",
+                "Documents rejected by Elasticsearch bulk responses",
+                &self.docs_failed,
+            ),
+            (
+                "espipe_docs_dead_lettered_total",
+                "Documents written to the dead letter file",
+                &self.docs_dead_lettered,
+            ),
+            (
+                "espipe_bulk_retries_total",
+                "Bulk requests retried after a 429 response or timeout",
+                &self.bulk_retries,
+            ),
+        ];
+        for (name, help, counter) in counters {
+            let value = counter.load(Ordering::Relaxed);
+            let _ = writeln!(
+                text,
+                "# HELP {name} {help}\n# TYPE {name} counter\n{name} {value}"
+            );
+        }
+        let index_docs = self.index_docs.lock().expect("metrics lock poisoned");
+        let index_counters: [(&str, &str, IndexCount); 2] = [
+            (
+                "espipe_index_docs_sent_total",
+                "Documents delivered to each Elasticsearch index",
+                |counts| counts.0,
+            ),
+            (
+                "espipe_index_docs_failed_total",
+                "Documents rejected by each Elasticsearch index",
+                |counts| counts.1,
+            ),
+        ];
+        // Runs without an Elasticsearch output have no indices to label.
+        if !index_docs.is_empty() {
+            for (name, help, value) in index_counters {
+                let _ = writeln!(text, "# HELP {name} {help}\n# TYPE {name} counter");
+                for (index, counts) in index_docs.iter() {
+                    let _ = writeln!(text, "{name}{{index=\"{index}\"}} {}", value(counts));
+                }
+            }
+        }
+        drop(index_docs);
+        let gauges = [
+            (
+                "espipe_output_queued_docs",
+                "Documents waiting for a bulk request",
+                &self.queued_docs,
+            ),
+            (
+                "espipe_bulk_requests_in_flight",
+                "Bulk requests waiting for a response",
+                &self.bulk_requests_in_flight,
+            ),
+            (
+                "espipe_buffered_bytes",
+                "Bytes of documents read but not yet delivered, with --max-memory",
+                &self.buffered_bytes,
+            ),
+        ];
+        for (name, help, gauge) in gauges {
+            let value = gauge.load(Ordering::Relaxed);
+            let _ = writeln!(
+                text,
+                "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}"
+            );
+        }
+
+        let name = "espipe_bulk_request_duration_seconds";
+        let _ = writeln!(
+            text,
+            "# HELP {name} Elasticsearch bulk request latency\n# TYPE {name} histogram"
+        );
+        let mut count = 0;
+        for (bound, bucket) in LATENCY_BUCKETS.iter().zip(&self.bulk_latency.buckets) {
+            count += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(text, "{name}_bucket{{le=\"{bound}\"}} {count}");
+        }
+        count += self.bulk_latency.buckets[LATENCY_BUCKETS.len()].load(Ordering::Relaxed);
+        let sum = self.bulk_latency.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(text, "{name}_bucket{{le=\"+Inf\"}} {count}");
+        let _ = writeln!(text, "{name}_sum {sum}\n{name}_count {count}");
+        text
+    }
+}
+
+/// Serves `GET /metrics` on its own thread and runtime, returning the bound address.
+pub fn serve(address: SocketAddr) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(address)
+        .map_err(|err| eyre!("Failed to serve metrics on {address}: {err}"))?;
+    listener.set_nonblocking(true)?;
+    let address = listener.local_addr()?;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    thread::spawn(move || runtime.block_on(accept(listener)));
+    log::debug!("Serving metrics on http://{address}/metrics");
+    Ok(address)
+}
+
+async fn accept(listener: TcpListener) {
+    let listener = match tokio::net::TcpListener::from_std(listener) {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::error!("Metrics listener failed to start: {err}");
+            return;
+        }
+    };
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                log::warn!("Metrics listener accept failed: {err}");
+                continue;
+            }
+        };
+        tokio::spawn(async move {
+            if let Err(err) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service_fn(handle))
+                .await
+            {
+                log::debug!("Metrics connection closed with error: {err}");
+            }
+        });
+    }
+}
+
+async fn handle(request: Request<Incoming>) -> Result<Response<Full<Bytes>>, hyper::Error> {
+    if request.method() != Method::GET || request.uri().path() != "/metrics" {
+        let mut response = Response::new(Full::new(Bytes::from_static(b"not found\n")));
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        return Ok(response);
+    }
+    let mut response = Response::new(Full::new(Bytes::from(METRICS.render())));
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; version=0.0.4"),
+    );
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{METRICS, Metrics, serve};
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+        sync::atomic::Ordering,
+        time::Duration,
+    };
+
+    #[test]
+    fn histograms_render_cumulative_buckets() {
+        let metrics = Metrics::new();
+        metrics.docs_read.fetch_add(3, Ordering::Relaxed);
+        metrics.observe_bulk_latency(Duration::from_millis(20));
+        metrics.observe_bulk_latency(Duration::from_millis(300));
+        metrics.observe_bulk_latency(Duration::from_secs(45));
+        metrics.add_index_docs("logs-a", 5, 1);
+        metrics.add_index_docs("logs-a", 2, 0);
+
+        let text = metrics.render();
+
+        assert!(text.contains("espipe_docs_read_total 3\n"), "{text}");
+        assert!(text.contains("espipe_bulk_request_duration_seconds_bucket{le=\"0.025\"} 1\n"));
+        assert!(text.contains("espipe_bulk_request_duration_seconds_bucket{le=\"0.5\"} 2\n"));
+        assert!(text.contains("espipe_bulk_request_duration_seconds_bucket{le=\"30\"} 2\n"));
+        assert!(text.contains("espipe_bulk_request_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("espipe_bulk_request_duration_seconds_sum 45.32\n"));
+        assert!(text.contains("espipe_bulk_request_duration_seconds_count 3\n"));
+        assert!(text.contains("espipe_index_docs_sent_total{index=\"logs-a\"} 7\n"));
+        assert!(text.contains("espipe_index_docs_failed_total{index=\"logs-a\"} 1\n"));
+    }
+
+    #[test]
+    fn endpoint_serves_metrics_and_rejects_other_paths() {
+        let address = serve("127.0.0.1:0".parse().unwrap()).unwrap();
+        METRICS.docs_sent.fetch_add(1, Ordering::Relaxed);
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            write!(
+                stream,
+                "GET {path} HTTP/1.1\r\nHost: espipe\r\nConnection: close\r\n\r\n"
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let metrics = get("/metrics");
+        assert!(metrics.starts_with("HTTP/1.1 200"), "{metrics}");
+        assert!(metrics.contains("# TYPE espipe_docs_sent_total counter"));
+        assert!(get("/").starts_with("HTTP/1.1 404"));
+    }
+}
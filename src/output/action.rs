@@ -12,3 +12,25 @@ impl Default for BulkAction {
         Self::Create
     }
 }
+
+/// What to do with a `create` operation whose `_id` already exists.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum OnConflict {
+    /// Leave the existing document and count the operation as sent
+    Skip,
+    /// Replace the existing document with an `index` operation
+    Overwrite,
+    /// Stop the run
+    Fail,
+}
+
+impl OnConflict {
+    /// How the summary describes resolved conflicts.
+    pub fn resolution(self) -> &'static str {
+        match self {
+            Self::Skip => "skipped",
+            Self::Overwrite => "overwritten",
+            Self::Fail => "failed",
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use super::{Sender, file::FileOutput};
+use crate::{cloud::CloudObject, document::Document};
+use async_trait::async_trait;
+use eyre::Result;
+use fluent_uri::UriRef;
+use tempfile::{Builder, TempPath};
+
+/// Writes documents to a temporary NDJSON file, gzip-compressed when the
+/// object name ends in `.ndjson.gz`, and uploads it as an `az://` or `gs://`
+/// object when the output closes.
+#[derive(Debug)]
+pub struct CloudOutput {
+    file: Box<FileOutput>,
+    temp_path: TempPath,
+    object: CloudObject,
+}
+
+impl CloudOutput {
+    pub fn try_new(uri: &UriRef<String>) -> Result<Self> {
+        let object = CloudObject::try_new(uri)?;
+        let suffix = match object.name().to_ascii_lowercase() {
+            name if name.ends_with(".ndjson.gz") => ".ndjson.gz",
+            name if name.ends_with(".gz") => {
+                return Err(eyre::eyre!("Unsupported compressed output format: {uri}"));
+            }
+            _ => ".ndjson",
+        };
+        let temp_path = Builder::new().suffix(suffix).tempfile()?.into_temp_path();
+        let file = Box::new(FileOutput::try_from(temp_path.to_path_buf())?);
+        Ok(Self {
+            file,
+            temp_path,
+            object,
+        })
+    }
+}
+
+#[async_trait]
+impl Sender for CloudOutput {
+    async fn send(&mut self, value: Document) -> Result<usize> {
+        self.file.send(value).await
+    }
+
+    async fn flush(&mut self) -> Result<usize> {
+        self.file.flush().await
+    }
+
+    async fn close(self: Box<Self>) -> Result<usize> {
+        let Self {
+            file,
+            temp_path,
+            object,
+        } = *self;
+        let sent = file.close().await?;
+        object.upload(&temp_path).await?;
+        log::debug!("Uploaded {object}");
+        Ok(sent)
+    }
+}
+
+impl std::fmt::Display for CloudOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.object)
+    }
+}
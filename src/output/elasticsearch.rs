@@ -1,31 +1,286 @@
 mod bulk_response;
-
-use super::{BulkAction, Sender};
-use crate::output::OutputPreflightConfig;
+mod capture;
+mod cluster;
+mod mapping;
+mod turbo;
+
+use super::{BulkAction, FailureClass, OnConflict, OutputError, Sender};
+use crate::{
+    canonical,
+    client::{Failover, SharedClients, SocketOptions},
+    dead_letter::DeadLetter,
+    document::Document,
+    field_path,
+    memory::MemoryLease,
+    metrics::METRICS,
+    output::OutputPreflightConfig,
+};
+use async_trait::async_trait;
 use bulk_response::BulkResponse;
+use bytes::Bytes;
+pub use capture::BulkCapture;
+use cluster::{ClusterVersion, Features};
 use elasticsearch::{
     Elasticsearch,
-    http::{Method, StatusCode, headers::HeaderMap, headers::HeaderValue},
+    http::{
+        Method, StatusCode,
+        headers::{HeaderMap, HeaderName, HeaderValue},
+    },
 };
 use eyre::{OptionExt, Result, eyre};
 use futures::{StreamExt, stream::FuturesUnordered};
-use serde_json::{Value, json, value::RawValue};
+use mapping::Mapping;
+use serde_json::{Value, json};
 use std::{
+    collections::BTreeMap,
     fs,
     path::{Path, PathBuf},
-    sync::Arc,
-    time::Duration,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
 };
-use tokio::{sync::mpsc, task::JoinHandle, time::sleep};
+use tokio::{
+    sync::{mpsc, oneshot, watch},
+    task::JoinHandle,
+    time::{sleep, timeout},
+};
+use tracing::Instrument;
+use turbo::Turbo;
 use url::Url;
+use xxhash_rust::xxh3::xxh3_128;
 
 const DEFAULT_BATCH_SIZE: usize = 5_000;
 const DEFAULT_MAX_INFLIGHT_REQUESTS: usize = 16;
+/// Elasticsearch's default `http.max_content_length`.
+const DEFAULT_MAX_REQUEST_BYTES: u64 = 100 * 1024 * 1024;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct ElasticsearchOutputConfig {
     batch_size: usize,
     max_inflight_requests: usize,
+    max_request_bytes: u64,
+    request_timeout: Option<Duration>,
+    batch_timeout: Option<Duration>,
+    close_timeout: Option<Duration>,
+    dead_letter: Option<DeadLetter>,
+    capture: Option<BulkCapture>,
+    proxy: Option<Url>,
+    tls_server_name: Option<String>,
+    socket_options: SocketOptions,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    metadata: BulkMetadata,
+    on_conflict: Option<OnConflict>,
+    coerce_to_mapping: bool,
+    require_alias: bool,
+    list_executed_pipelines: bool,
+    turbo: Option<PathBuf>,
+    sequencer: Option<Sequencer>,
+    shared_clients: Option<SharedClients>,
+    failed_docs: FailedDocs,
+    resolved_conflicts: ResolvedConflicts,
+    index_stats: IndexStats,
+    executed_pipelines: ExecutedPipelines,
+    bulk_latency: BulkLatency,
+}
+
+/// Documents this run handed to Elasticsearch that no bulk response confirmed,
+/// either rejected item by item or lost with a whole rejected request, by
+/// the class of their failure.
+#[derive(Clone, Debug, Default)]
+pub struct FailedDocs(Arc<Mutex<BTreeMap<FailureClass, usize>>>);
+
+impl FailedDocs {
+    fn add(&self, class: FailureClass, count: usize) {
+        if count == 0 {
+            return;
+        }
+        *self
+            .0
+            .lock()
+            .expect("failed docs lock poisoned")
+            .entry(class)
+            .or_default() += count;
+        METRICS
+            .docs_failed
+            .fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> usize {
+        self.by_class().values().sum()
+    }
+
+    pub fn by_class(&self) -> BTreeMap<FailureClass, usize> {
+        self.0.lock().expect("failed docs lock poisoned").clone()
+    }
+
+    /// The most serious class of failure so far, which sets the exit code.
+    pub fn worst(&self) -> Option<FailureClass> {
+        self.by_class().into_keys().next()
+    }
+}
+
+/// Lets `close` give up on the bulk requests still in flight once
+/// `--close-timeout` passes, counting the documents they leave unconfirmed.
+#[derive(Debug)]
+struct Closing {
+    abandon: watch::Sender<bool>,
+    dead_lettered: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+impl Default for Closing {
+    fn default() -> Self {
+        Self {
+            abandon: watch::channel(false).0,
+            dead_lettered: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Closing {
+    fn abandon(&self) {
+        self.abandon.send_replace(true);
+    }
+
+    /// Resolves once `close` gives up on in-flight requests.
+    fn abandoned(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut abandon = self.abandon.subscribe();
+        async move {
+            let abandoned = abandon.wait_for(|abandoned| *abandoned).await.is_ok();
+            if !abandoned {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+
+    /// Accounts for a batch still unconfirmed when `close` gave up on it, writing
+    /// it to the dead letter file when there is one and counting it as failed otherwise.
+    fn abandon_batch(
+        &self,
+        index: &str,
+        targets: &[Option<String>],
+        dead_letter: Option<(DeadLetter, Vec<Document>)>,
+        failed_docs: &FailedDocs,
+        index_stats: &IndexStats,
+        doc_count: usize,
+    ) -> Result<usize> {
+        match dead_letter {
+            Some((dead_letter, docs)) => {
+                dead_letter.write(&docs)?;
+                self.dead_lettered.fetch_add(doc_count, Ordering::Relaxed);
+                log::warn!(
+                    "Bulk request to {index} was still running at --close-timeout, wrote {doc_count} docs to {dead_letter}"
+                );
+            }
+            None => {
+                self.dropped.fetch_add(doc_count, Ordering::Relaxed);
+                failed_docs.add(FailureClass::Shutdown, doc_count);
+                index_stats.record(index, targets, &[]);
+            }
+        }
+        Ok(0)
+    }
+}
+
+/// `create` operations whose `_id` already existed, skipped or overwritten by
+/// the conflict policy.
+#[derive(Clone, Debug, Default)]
+pub struct ResolvedConflicts(Arc<AtomicUsize>);
+
+impl ResolvedConflicts {
+    fn add(&self, count: usize) {
+        self.0.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Documents sent and failed per target index, which tell apart the indices
+/// and data streams of a run that routes documents with `--index-from-doc` or
+/// bulk action lines.
+#[derive(Clone, Debug, Default)]
+pub struct IndexStats(Arc<Mutex<BTreeMap<String, IndexCounts>>>);
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Serialize)]
+pub struct IndexCounts {
+    pub sent: usize,
+    pub failed: usize,
+}
+
+impl IndexStats {
+    /// Credits each document of a batch to its target, or to `index` when it
+    /// has none, as sent if its position was accepted and as failed otherwise.
+    fn record(&self, index: &str, targets: &[Option<String>], accepted: &[usize]) {
+        let mut is_accepted = vec![false; targets.len()];
+        for &position in accepted {
+            if let Some(slot) = is_accepted.get_mut(position) {
+                *slot = true;
+            }
+        }
+        let mut batch = BTreeMap::<&str, IndexCounts>::new();
+        for (target, accepted) in targets.iter().zip(is_accepted) {
+            let counts = batch.entry(target.as_deref().unwrap_or(index)).or_default();
+            match accepted {
+                true => counts.sent += 1,
+                false => counts.failed += 1,
+            }
+        }
+        let mut indices = self.0.lock().expect("index stats lock poisoned");
+        for (index, counts) in batch {
+            METRICS.add_index_docs(index, counts.sent as u64, counts.failed as u64);
+            let total = indices.entry(index.to_string()).or_default();
+            total.sent += counts.sent;
+            total.failed += counts.failed;
+        }
+    }
+
+    pub fn counts(&self) -> BTreeMap<String, IndexCounts> {
+        self.0.lock().expect("index stats lock poisoned").clone()
+    }
+}
+
+/// Under `--ordered`, chains each bulk request to the one before it, so a batch
+/// is only sent once the previous batch's request has finished, and batches are
+/// applied and acknowledged in input order.
+#[derive(Clone, Debug, Default)]
+struct Sequencer(Arc<Mutex<Option<oneshot::Receiver<()>>>>);
+
+impl Sequencer {
+    /// Takes the next place in line, after every request already sequenced.
+    fn next_turn(&self) -> Turn {
+        let (done, next) = oneshot::channel();
+        let previous = self
+            .0
+            .lock()
+            .expect("sequencer lock poisoned")
+            .replace(next);
+        Turn {
+            previous,
+            _done: done,
+        }
+    }
+}
+
+/// One bulk request's place in an `--ordered` sequence. Dropping it, once the
+/// request has finished or failed, lets the next request go.
+struct Turn {
+    previous: Option<oneshot::Receiver<()>>,
+    _done: oneshot::Sender<()>,
+}
+
+impl Turn {
+    /// Waits for the previous request to finish.
+    async fn wait(&mut self) {
+        if let Some(previous) = &mut self.previous {
+            let _ = previous.await;
+            self.previous = None;
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -59,6 +314,88 @@ impl TemplateConfig {
     }
 }
 
+/// Documents each ingest pipeline ran on, from bulk responses to requests with
+/// `list_executed_pipelines`.
+#[derive(Clone, Debug, Default)]
+pub struct ExecutedPipelines(Arc<Mutex<BTreeMap<String, usize>>>);
+
+impl ExecutedPipelines {
+    fn add(&self, counts: &BTreeMap<String, usize>) {
+        let mut pipelines = self.0.lock().expect("executed pipelines lock poisoned");
+        for (pipeline, count) in counts {
+            *pipelines.entry(pipeline.clone()).or_default() += count;
+        }
+    }
+
+    pub fn counts(&self) -> BTreeMap<String, usize> {
+        self.0
+            .lock()
+            .expect("executed pipelines lock poisoned")
+            .clone()
+    }
+}
+
+/// Elasticsearch's `took` and the client's round trip for each bulk request
+/// answered with a `took`, which tell time spent in the cluster apart from
+/// time spent on the network and in queues.
+#[derive(Clone, Debug, Default)]
+pub struct BulkLatency(Arc<Mutex<Vec<(Duration, Duration)>>>);
+
+/// Percentiles of one kind of bulk latency.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LatencyPercentiles {
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl BulkLatency {
+    fn add(&self, took: Duration, round_trip: Duration) {
+        self.0
+            .lock()
+            .expect("bulk latency lock poisoned")
+            .push((took, round_trip));
+    }
+
+    /// Percentiles of `took` and of the round trip, or `None` before any
+    /// bulk request was answered.
+    pub fn percentiles(&self) -> Option<(LatencyPercentiles, LatencyPercentiles)> {
+        let samples = self.0.lock().expect("bulk latency lock poisoned");
+        let took = LatencyPercentiles::of(samples.iter().map(|(took, _)| *took).collect())?;
+        let round_trip = LatencyPercentiles::of(samples.iter().map(|(_, trip)| *trip).collect())?;
+        Some((took, round_trip))
+    }
+}
+
+impl LatencyPercentiles {
+    fn of(mut samples: Vec<Duration>) -> Option<Self> {
+        samples.sort_unstable();
+        let max = *samples.last()?;
+        // Nearest rank, so every percentile is a latency that was seen.
+        let rank = |percent: usize| samples[(samples.len() * percent).div_ceil(100).max(1) - 1];
+        Some(Self {
+            p50: rank(50),
+            p90: rank(90),
+            p99: rank(99),
+            max,
+        })
+    }
+}
+
+impl std::fmt::Display for LatencyPercentiles {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "p50 {}ms, p90 {}ms, p99 {}ms, max {}ms",
+            self.p50.as_millis(),
+            self.p90.as_millis(),
+            self.p99.as_millis(),
+            self.max.as_millis()
+        )
+    }
+}
+
 impl ElasticsearchOutputConfig {
     pub const DEFAULT_BATCH_SIZE: usize = DEFAULT_BATCH_SIZE;
     pub const DEFAULT_MAX_INFLIGHT_REQUESTS: usize = DEFAULT_MAX_INFLIGHT_REQUESTS;
@@ -74,10 +411,245 @@ impl ElasticsearchOutputConfig {
         Ok(Self {
             batch_size,
             max_inflight_requests,
+            ..Self::default()
         })
     }
 
-    fn channel_capacity(self) -> usize {
+    /// `request_timeout` bounds each HTTP attempt, while `batch_timeout` bounds a
+    /// batch across all of its retries.
+    pub fn with_timeouts(
+        self,
+        request_timeout: Option<Duration>,
+        batch_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            request_timeout,
+            batch_timeout,
+            ..self
+        }
+    }
+
+    /// Bounds how long closing the output waits for in-flight bulk requests,
+    /// after which their batches are dead-lettered or reported as unconfirmed.
+    pub fn with_close_timeout(self, close_timeout: Option<Duration>) -> Self {
+        Self {
+            close_timeout,
+            ..self
+        }
+    }
+
+    /// Batches are sent early rather than grow past this many uncompressed bytes.
+    pub fn with_max_request_bytes(self, max_request_bytes: u64) -> Self {
+        Self {
+            max_request_bytes,
+            ..self
+        }
+    }
+
+    /// Batches that miss their deadline, and documents too large for any bulk
+    /// request, are written here instead of failing the run.
+    pub fn with_dead_letter(self, dead_letter: Option<DeadLetter>) -> Self {
+        Self {
+            dead_letter,
+            ..self
+        }
+    }
+
+    /// Failed bulk requests, or every one at trace level, are written here
+    /// with their responses.
+    pub fn with_capture(self, capture: Option<BulkCapture>) -> Self {
+        Self { capture, ..self }
+    }
+
+    /// Overrides any `proxy` set on a known host.
+    pub fn with_proxy(self, proxy: Option<Url>) -> Self {
+        Self { proxy, ..self }
+    }
+
+    /// Socket tuning for the connections to the cluster, overriding a known
+    /// host's settings one by one.
+    pub fn with_socket_options(
+        self,
+        connect_timeout: Option<Duration>,
+        tcp_keepalive: Option<Duration>,
+        idle_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            socket_options: SocketOptions {
+                connect_timeout,
+                tcp_keepalive,
+                idle_timeout,
+            },
+            ..self
+        }
+    }
+
+    /// The name to verify the cluster's certificate against and send as TLS SNI.
+    pub fn with_tls_server_name(self, tls_server_name: Option<String>) -> Self {
+        Self {
+            tls_server_name,
+            ..self
+        }
+    }
+
+    /// Extra headers for every request, applied after any known host `headers`.
+    pub fn with_headers(self, headers: Vec<(HeaderName, HeaderValue)>) -> Self {
+        Self { headers, ..self }
+    }
+
+    /// Sets each operation's `routing` from this field, by dotted path. Documents
+    /// without the field are sent without routing.
+    pub fn with_routing_field(mut self, routing_field: Option<String>) -> Self {
+        self.metadata.routing_field = routing_field;
+        self
+    }
+
+    /// Sends each document to the index named by this field, by dotted path,
+    /// falling back to the output URL's index. A `_`-prefixed top-level field
+    /// such as `_index` is removed from the document.
+    pub fn with_index_field(mut self, index_field: Option<String>) -> Self {
+        self.metadata.index_field = index_field;
+        self
+    }
+
+    /// Sets each document's `_id` to a hash of its content or selected fields,
+    /// so re-running the same ingest with `create` adds no duplicates.
+    pub fn with_id_hash(mut self, id_hash: Option<IdHash>) -> Self {
+        self.metadata.id_hash = id_hash;
+        self
+    }
+
+    /// Renames the `_index` of documents that name their own, from replayed bulk
+    /// actions or `--index-from-doc`.
+    pub fn with_index_renames(mut self, renames: Vec<(String, String)>) -> Self {
+        self.metadata.renames = renames;
+        self
+    }
+
+    /// What to do with `create` operations whose `_id` already exists, which
+    /// otherwise count as failed.
+    pub fn with_on_conflict(self, on_conflict: Option<OnConflict>) -> Self {
+        Self {
+            on_conflict,
+            ..self
+        }
+    }
+
+    /// The conflict policy, which `--id-hash` defaults to `skip`, since an
+    /// existing hashed `_id` holds the same document.
+    pub fn conflict_policy(&self) -> Option<OnConflict> {
+        self.on_conflict
+            .or(self.metadata.id_hash.as_ref().map(|_| OnConflict::Skip))
+    }
+
+    pub fn resolved_conflicts(&self) -> ResolvedConflicts {
+        self.resolved_conflicts.clone()
+    }
+
+    /// Coerces document values to the field types of the target index's
+    /// mapping before they are sent.
+    pub fn with_coerce_to_mapping(self, coerce_to_mapping: bool) -> Self {
+        Self {
+            coerce_to_mapping,
+            ..self
+        }
+    }
+
+    /// Sends each bulk request only after the one before it has finished, so a
+    /// later document overwriting an earlier one by `_id` is applied last.
+    /// Batches are still built ahead, up to the in-flight request limit.
+    pub fn with_ordered(self, ordered: bool) -> Self {
+        Self {
+            sequencer: ordered.then(Sequencer::default),
+            ..self
+        }
+    }
+
+    /// Bulk requests fail documents whose target index is not an alias, rather
+    /// than creating the index.
+    pub fn with_require_alias(self, require_alias: bool) -> Self {
+        Self {
+            require_alias,
+            ..self
+        }
+    }
+
+    /// Bulk responses list the ingest pipelines run on each document, which
+    /// are counted per pipeline.
+    pub fn with_list_executed_pipelines(self, list_executed_pipelines: bool) -> Self {
+        Self {
+            list_executed_pipelines,
+            ..self
+        }
+    }
+
+    /// Turns off refreshes and replicas on the target index for the load and
+    /// restores them on close, saving the originals in `state_dir` meanwhile.
+    pub fn with_turbo(self, state_dir: Option<PathBuf>) -> Self {
+        Self {
+            turbo: state_dir,
+            ..self
+        }
+    }
+
+    /// Reuses clients and draws bulk requests from a budget shared with other pipelines.
+    pub fn with_shared_clients(self, shared_clients: Option<SharedClients>) -> Self {
+        Self {
+            shared_clients,
+            ..self
+        }
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    pub fn request_timeout(&self) -> Option<Duration> {
+        self.request_timeout
+    }
+
+    pub fn proxy(&self) -> Option<&Url> {
+        self.proxy.as_ref()
+    }
+
+    pub fn tls_server_name(&self) -> Option<&str> {
+        self.tls_server_name.as_deref()
+    }
+
+    pub fn socket_options(&self) -> SocketOptions {
+        self.socket_options
+    }
+
+    pub fn headers(&self) -> &[(HeaderName, HeaderValue)] {
+        &self.headers
+    }
+
+    pub fn shared_clients(&self) -> Option<&SharedClients> {
+        self.shared_clients.as_ref()
+    }
+
+    /// A handle on this run's failed document count, still readable after the output closes.
+    pub fn failed_docs(&self) -> FailedDocs {
+        self.failed_docs.clone()
+    }
+
+    /// A handle on this run's per-index document counts, still readable after the output closes.
+    pub fn index_stats(&self) -> IndexStats {
+        self.index_stats.clone()
+    }
+
+    /// A handle on the documents each ingest pipeline ran on, counted with
+    /// `--list-executed-pipelines`.
+    pub fn executed_pipelines(&self) -> ExecutedPipelines {
+        self.executed_pipelines.clone()
+    }
+
+    /// A handle on the latency of this run's bulk requests, still readable after the output closes.
+    pub fn bulk_latency(&self) -> BulkLatency {
+        self.bulk_latency.clone()
+    }
+
+    fn channel_capacity(&self) -> usize {
         self.batch_size
     }
 }
@@ -87,6 +659,29 @@ impl Default for ElasticsearchOutputConfig {
         Self {
             batch_size: DEFAULT_BATCH_SIZE,
             max_inflight_requests: DEFAULT_MAX_INFLIGHT_REQUESTS,
+            max_request_bytes: DEFAULT_MAX_REQUEST_BYTES,
+            request_timeout: None,
+            batch_timeout: None,
+            close_timeout: None,
+            dead_letter: None,
+            capture: None,
+            proxy: None,
+            tls_server_name: None,
+            socket_options: SocketOptions::default(),
+            headers: Vec::new(),
+            metadata: BulkMetadata::default(),
+            on_conflict: None,
+            coerce_to_mapping: false,
+            require_alias: false,
+            list_executed_pipelines: false,
+            turbo: None,
+            sequencer: None,
+            shared_clients: None,
+            failed_docs: FailedDocs::default(),
+            resolved_conflicts: ResolvedConflicts::default(),
+            index_stats: IndexStats::default(),
+            executed_pipelines: ExecutedPipelines::default(),
+            bulk_latency: BulkLatency::default(),
         }
     }
 }
@@ -95,13 +690,26 @@ impl Default for ElasticsearchOutputConfig {
 pub struct ElasticsearchOutput {
     hostname: String,
     index: String,
-    sender: Option<mpsc::Sender<Box<RawValue>>>,
+    sender: Option<mpsc::Sender<WorkerMessage>>,
     worker: JoinHandle<Result<usize>>,
+    mapping: Option<Mapping>,
+    close_timeout: Option<Duration>,
+    closing: Arc<Closing>,
+    hosts: Arc<Failover>,
+    turbo: Option<Turbo>,
+}
+
+#[derive(Debug)]
+enum WorkerMessage {
+    Document(Document),
+    /// Send any partial batch, wait for all in-flight requests, and reply with
+    /// the documents acknowledged since the previous flush.
+    Flush(oneshot::Sender<usize>),
 }
 
 impl ElasticsearchOutput {
     pub async fn try_new(
-        client: Elasticsearch,
+        hosts: Failover,
         url: Url,
         action: BulkAction,
         config: ElasticsearchOutputConfig,
@@ -112,28 +720,81 @@ impl ElasticsearchOutput {
             .ok_or_eyre("Url missing host_str")?
             .to_string();
         let index = url.path().trim_start_matches('/').to_string();
+        if index.is_empty() && config.metadata.index_field.is_none() {
+            return Err(eyre!(
+                "Elasticsearch output {url} has no index, add one like {url}my-index or send each document to its own with --index-from-doc"
+            ));
+        }
         log::debug!("Elasticsearch output to {hostname}/{index}");
 
         let preflight = PreparedPreflight::try_from(preflight)?;
-        preflight.run(&client, &index).await?;
+        let (_, client) = hosts.client();
+        let span =
+            tracing::info_span!("preflight", index = %index, cluster = tracing::field::Empty);
+        let (mapping, turbo) = async {
+            // A cluster that hides `GET /` still gets the data; only the checks are skipped.
+            match ClusterVersion::fetch(&client).await {
+                Ok(version) => {
+                    log::info!("{hostname} runs {version}");
+                    tracing::Span::current().record("cluster", version.to_string());
+                    version.check(&preflight.features(&index))?;
+                }
+                Err(err) => log::warn!(
+                    "Could not read the cluster version from {hostname}, skipping compatibility checks: {err}"
+                ),
+            }
+            preflight.run(&client, &index).await?;
+            // After the preflight, so a template it installs shapes the mapping.
+            let mapping = match (config.coerce_to_mapping, action) {
+                (false, _) => Ok(None),
+                (true, BulkAction::Update) => Err(eyre!(
+                    "--coerce-to-mapping cannot be used with --action update, which sends partial documents"
+                )),
+                (true, _) if index.is_empty() => Err(eyre!(
+                    "--coerce-to-mapping needs the index in the output URL, not --index-from-doc"
+                )),
+                (true, _) => {
+                    let mapping = Mapping::fetch(&client, &index).await?;
+                    if mapping.is_none() {
+                        log::warn!("{index} has no mapping yet, sending documents as they are");
+                    }
+                    Ok(mapping)
+                }
+            }?;
+            let turbo = match &config.turbo {
+                Some(state_dir) => Turbo::start(&client, state_dir, &hostname, &index).await?,
+                None => None,
+            };
+            Ok::<_, eyre::Report>((mapping, turbo))
+        }
+        .instrument(span)
+        .await?;
 
-        let client = Arc::new(client);
         let (sender, receiver) = mpsc::channel(config.channel_capacity());
-        let worker = tokio::spawn(run_bulk_worker(
-            Arc::clone(&client),
-            hostname.clone(),
-            index.clone(),
+        let close_timeout = config.close_timeout;
+        let closing = Arc::new(Closing::default());
+        let hosts = Arc::new(hosts);
+        let worker = BulkWorker {
+            hosts: Arc::clone(&hosts),
+            hostname: hostname.clone(),
+            index: index.clone(),
             action,
             config,
-            preflight.bulk_pipeline,
-            receiver,
-        ));
+            bulk_pipeline: preflight.bulk_pipeline,
+            closing: Arc::clone(&closing),
+        };
+        let worker = tokio::spawn(run_bulk_worker(worker, receiver).in_current_span());
 
         Ok(Self {
             hostname,
             index,
             sender: Some(sender),
             worker,
+            mapping,
+            close_timeout,
+            closing,
+            hosts,
+            turbo,
         })
     }
 }
@@ -150,7 +811,9 @@ async fn install_template(
     target_index: &str,
     parsed: &ParsedTemplate,
 ) -> Result<()> {
-    warn_for_index_patterns(&parsed.body, target_index);
+    if !target_index.is_empty() {
+        warn_for_index_patterns(&parsed.body, target_index);
+    }
 
     let mut headers = HeaderMap::new();
     headers.insert("content-type", HeaderValue::from_static("application/json"));
@@ -204,6 +867,19 @@ fn parse_template(config: TemplateConfig) -> Result<ParsedTemplate> {
     })
 }
 
+fn parse_component_template(path: &Path) -> Result<NamedJson> {
+    let body = fs::read_to_string(path).map_err(|err| {
+        eyre!(
+            "failed to read component template '{}': {err}",
+            path.display()
+        )
+    })?;
+    Ok(NamedJson {
+        name: derive_template_name(path)?,
+        body: parse_config_body("component template", path, &body)?,
+    })
+}
+
 fn derive_template_name(path: &Path) -> Result<String> {
     let name = path
         .file_stem()
@@ -298,22 +974,94 @@ fn wildcard_match(pattern: &str, value: &str) -> bool {
     pattern_index == pattern.len()
 }
 
+#[async_trait]
 impl Sender for ElasticsearchOutput {
-    async fn send(&mut self, value: Box<RawValue>) -> Result<usize> {
+    async fn send(&mut self, value: Document) -> Result<usize> {
+        let value = match &mut self.mapping {
+            Some(mapping) => coerce(mapping, value)?,
+            None => value,
+        };
         let sender = self
             .sender
             .as_ref()
             .ok_or_eyre("Elasticsearch output already closed")?;
         sender
-            .send(value)
+            .send(WorkerMessage::Document(value))
             .await
             .map_err(|_| eyre!("Elasticsearch output worker closed unexpectedly"))?;
+        METRICS.queued_docs.fetch_add(1, Ordering::Relaxed);
         Ok(0)
     }
 
-    async fn close(mut self) -> Result<usize> {
+    async fn flush(&mut self) -> Result<usize> {
+        let sender = self
+            .sender
+            .as_ref()
+            .ok_or_eyre("Elasticsearch output already closed")?;
+        let (reply, response) = oneshot::channel();
+        if sender.send(WorkerMessage::Flush(reply)).await.is_ok()
+            && let Ok(sent) = response.await
+        {
+            return Ok(sent);
+        }
+        // The worker exited, so surface its error instead of a closed-channel error.
+        self.sender.take();
+        (&mut self.worker).await.map_err(eyre::Report::new)??;
+        Err(eyre!("Elasticsearch output worker closed unexpectedly"))
+    }
+
+    async fn close(mut self: Box<Self>) -> Result<usize> {
+        if let Some(mapping) = &self.mapping
+            && mapping.coerced() > 0
+        {
+            log::info!(
+                "Coerced {} values to the mapping of {}",
+                mapping.coerced(),
+                self.index
+            );
+        }
         self.sender.take();
-        self.worker.await.map_err(eyre::Report::new)?
+        let result = self.drain().await;
+        // Even when the load failed, so the index is not left without replicas.
+        if let Some(turbo) = self.turbo.take() {
+            let (_, client) = self.hosts.client();
+            turbo.restore(&client).await?;
+        }
+        result
+    }
+}
+
+impl ElasticsearchOutput {
+    /// Waits for the worker to send the remaining batches, giving up on them
+    /// after `--close-timeout`.
+    async fn drain(&mut self) -> Result<usize> {
+        let Some(limit) = self.close_timeout else {
+            return (&mut self.worker).await.map_err(eyre::Report::new)?;
+        };
+        if let Ok(result) = timeout(limit, &mut self.worker).await {
+            return result.map_err(eyre::Report::new)?;
+        }
+        // Batches still queued or in flight give up now and account for their
+        // documents, so the worker finishes promptly.
+        self.closing.abandon();
+        let sent = (&mut self.worker).await.map_err(eyre::Report::new)??;
+        let dead_lettered = self.closing.dead_lettered.load(Ordering::Relaxed);
+        let dropped = self.closing.dropped.load(Ordering::Relaxed);
+        if dead_lettered > 0 {
+            log::error!(
+                "{self} did not close within --close-timeout {limit:?}, dead-lettered {dead_lettered} unconfirmed docs"
+            );
+        }
+        if dropped > 0 {
+            return Err(OutputError::new(
+                FailureClass::Shutdown,
+                format!(
+                    "{self} did not close within --close-timeout {limit:?}, {dropped} docs were not confirmed; set --dead-letter to keep them"
+                ),
+            )
+            .into());
+        }
+        Ok(sent)
     }
 }
 
@@ -323,48 +1071,88 @@ impl std::fmt::Display for ElasticsearchOutput {
     }
 }
 
-async fn run_bulk_worker(
-    client: Arc<Elasticsearch>,
+/// Rewrites a document whose values `mapping` coerces, leaving bulk-format
+/// `update` pairs, which hold a partial document, as they are.
+fn coerce(mapping: &mut Mapping, doc: Document) -> Result<Document> {
+    if doc
+        .action()
+        .is_some_and(|action| action.trim_start().starts_with("{\"update\""))
+    {
+        return Ok(doc);
+    }
+    let mut source: Value = serde_json::from_str(doc.get())
+        .map_err(|err| eyre!("Error parsing JSON for --coerce-to-mapping: {err}"))?;
+    Ok(match mapping.coerce(&mut source) {
+        true => doc.with_json(source.to_string()),
+        false => doc,
+    })
+}
+
+/// What the bulk worker sends each of its requests with.
+struct BulkWorker {
+    hosts: Arc<Failover>,
     hostname: String,
     index: String,
     action: BulkAction,
     config: ElasticsearchOutputConfig,
     bulk_pipeline: Option<String>,
-    mut receiver: mpsc::Receiver<Box<RawValue>>,
+    closing: Arc<Closing>,
+}
+
+async fn run_bulk_worker(
+    worker: BulkWorker,
+    mut receiver: mpsc::Receiver<WorkerMessage>,
 ) -> Result<usize> {
+    let BulkWorker {
+        index,
+        action,
+        config,
+        ..
+    } = &worker;
+    let action = *action;
     let mut batch = Vec::with_capacity(config.batch_size);
+    let mut batch_bytes = 0u64;
     let mut docs_sent = 0usize;
     let mut inflight = FuturesUnordered::<JoinHandle<Result<usize>>>::new();
 
-    while let Some(doc) = receiver.recv().await {
+    while let Some(message) = receiver.recv().await {
+        let doc = match message {
+            WorkerMessage::Document(doc) => doc,
+            WorkerMessage::Flush(reply) => {
+                if !batch.is_empty() {
+                    spawn_flush(&mut inflight, &worker, &mut batch)?;
+                    batch_bytes = 0;
+                }
+                while let Some(result) = inflight.next().await {
+                    docs_sent += result.map_err(eyre::Report::new)??;
+                }
+                let _ = reply.send(std::mem::take(&mut docs_sent));
+                continue;
+            }
+        };
+        let doc_bytes = bulk_entry_bytes(action, &config.metadata, &doc);
+        if doc_bytes > config.max_request_bytes {
+            reject_oversized(config, index, action, doc, doc_bytes)?;
+            continue;
+        }
+        if batch_bytes + doc_bytes > config.max_request_bytes {
+            spawn_flush(&mut inflight, &worker, &mut batch)?;
+            batch_bytes = 0;
+            docs_sent +=
+                reap_inflight_if_needed(&mut inflight, config.max_inflight_requests).await?;
+        }
         batch.push(doc);
+        batch_bytes += doc_bytes;
         if batch.len() >= config.batch_size {
-            spawn_flush(
-                &mut inflight,
-                &client,
-                &hostname,
-                &index,
-                action,
-                config,
-                bulk_pipeline.as_deref(),
-                &mut batch,
-            )?;
+            spawn_flush(&mut inflight, &worker, &mut batch)?;
+            batch_bytes = 0;
             docs_sent +=
                 reap_inflight_if_needed(&mut inflight, config.max_inflight_requests).await?;
         }
     }
 
     if !batch.is_empty() {
-        spawn_flush(
-            &mut inflight,
-            &client,
-            &hostname,
-            &index,
-            action,
-            config,
-            bulk_pipeline.as_deref(),
-            &mut batch,
-        )?;
+        spawn_flush(&mut inflight, &worker, &mut batch)?;
     }
 
     while let Some(result) = inflight.next().await {
@@ -374,86 +1162,486 @@ async fn run_bulk_worker(
     Ok(docs_sent)
 }
 
-fn spawn_flush(
-    inflight: &mut FuturesUnordered<JoinHandle<Result<usize>>>,
-    client: &Arc<Elasticsearch>,
-    hostname: &str,
+/// A document that alone exceeds `--max-request-bytes` can never be sent, so it
+/// is dead-lettered when there is a dead letter file and counted as failed otherwise.
+fn reject_oversized(
+    config: &ElasticsearchOutputConfig,
     index: &str,
     action: BulkAction,
-    config: ElasticsearchOutputConfig,
-    bulk_pipeline: Option<&str>,
-    batch: &mut Vec<Box<RawValue>>,
+    doc: Document,
+    doc_bytes: u64,
 ) -> Result<()> {
-    let docs = std::mem::replace(batch, Vec::with_capacity(config.batch_size));
-    let body = build_bulk_body(action, &docs)?;
+    METRICS.queued_docs.fetch_sub(1, Ordering::Relaxed);
+    let limit = config.max_request_bytes;
+    match &config.dead_letter {
+        Some(dead_letter) => {
+            dead_letter.write(std::slice::from_ref(&doc))?;
+            log::error!(
+                "Document of {doc_bytes} bytes exceeds --max-request-bytes {limit}, wrote it to {dead_letter}"
+            );
+        }
+        None => {
+            config.failed_docs.add(FailureClass::RequestTooLarge, 1);
+            let target = append_operation(&mut Vec::new(), action, &config.metadata, &doc)
+                .ok()
+                .flatten();
+            config.index_stats.record(index, &[target], &[]);
+            log::error!(
+                "Document of {doc_bytes} bytes exceeds --max-request-bytes {limit} and was dropped, set --dead-letter to keep it"
+            );
+        }
+    }
+    Ok(())
+}
+
+fn spawn_flush(
+    inflight: &mut FuturesUnordered<JoinHandle<Result<usize>>>,
+    worker: &BulkWorker,
+    batch: &mut Vec<Document>,
+) -> Result<()> {
+    let BulkWorker {
+        hosts,
+        hostname,
+        index,
+        action,
+        config,
+        bulk_pipeline,
+        closing,
+    } = worker;
+    let action = *action;
+    let mut docs = std::mem::replace(batch, Vec::with_capacity(config.batch_size));
+    let doc_count = docs.len();
+    // `--max-memory` leases are held until the request completes, whether or
+    // not the documents themselves are kept.
+    let leases: Vec<MemoryLease> = docs.iter_mut().filter_map(Document::take_lease).collect();
+    METRICS
+        .queued_docs
+        .fetch_sub(doc_count as i64, Ordering::Relaxed);
+    let (body, targets) = tracing::info_span!("transform", docs = doc_count)
+        .in_scope(|| build_bulk_body(action, &config.metadata, &docs))?;
     log::debug!("Bulk sending {} docs to {hostname}/{index}", docs.len());
-    let client = Arc::clone(client);
+    let hosts = Arc::clone(hosts);
     let index = index.to_string();
-    let bulk_pipeline = bulk_pipeline.map(str::to_string);
-
-    inflight.push(tokio::spawn(async move {
-        let mut headers = HeaderMap::new();
-        headers.insert("content-type", HeaderValue::from_static("application/x-ndjson"));
-        let query = bulk_pipeline.as_ref().map(|pipeline| [("pipeline", pipeline.as_str())]);
-
-        let mut attempt = 0u64;
-        let mut backoff = Duration::from_secs(1);
-        let max_backoff = Duration::from_secs(30);
-
-        loop {
-            attempt += 1;
-            let response = client
-                .send(
-                    Method::Post,
-                    &format!("/{index}/_bulk"),
-                    headers.clone(),
-                    query.as_ref(),
-                    Some(body.clone()),
-                    None,
-                )
-                .await?;
-
-            let status_code = response.status_code();
-            let bulk_response = response.json::<BulkResponse>().await?;
-            match status_code {
-                StatusCode::BAD_REQUEST => {
-                    log::error!(
-                        "Bulk response: 400 - Bad request ({})",
-                        bulk_response.error_cause()
-                    );
-                    return Ok(0);
+    let query = BulkQuery {
+        pipeline: bulk_pipeline.clone(),
+        require_alias: config.require_alias,
+        list_executed_pipelines: config.list_executed_pipelines,
+    };
+    let executed_pipelines = config.executed_pipelines();
+    let bulk_latency = config.bulk_latency();
+    let batch_timeout = config.batch_timeout;
+    let dead_letter = config.dead_letter.clone();
+    let capture = config.capture.clone();
+    let failed_docs = config.failed_docs();
+    let index_stats = config.index_stats();
+    let request_budget = config
+        .shared_clients
+        .as_ref()
+        .and_then(SharedClients::request_budget);
+    let on_conflict = config.conflict_policy();
+    let resolved_conflicts = config.resolved_conflicts();
+    let metadata = config.metadata.clone();
+    let mut turn = config.sequencer.as_ref().map(Sequencer::next_turn);
+    let closing = Arc::clone(closing);
+    // The documents are only needed again if the batch ends up dead-lettered
+    // or its conflicts are overwritten.
+    let keep_docs = dead_letter.is_some() || on_conflict == Some(OnConflict::Overwrite);
+    let docs = keep_docs.then_some(docs);
+
+    let span = tracing::info_span!("bulk_send", index = %index, docs = doc_count);
+    inflight.push(tokio::spawn(
+        async move {
+        let _leases = leases;
+        let ready = async {
+            // Waiting for the turn before taking a shared request permit keeps a
+            // queued request from holding a permit the one before it needs.
+            if let Some(turn) = &mut turn {
+                turn.wait().await;
+            }
+            match request_budget {
+                Some(budget) => Ok(Some(budget.acquire_owned().await?)),
+                None => Ok::<_, eyre::Report>(None),
+            }
+        };
+        let permit = tokio::select! {
+            biased;
+            () = closing.abandoned() => None,
+            permit = ready => Some(permit?),
+        };
+        let Some(_permit) = permit else {
+            return closing.abandon_batch(
+                &index,
+                &targets,
+                dead_letter.zip(docs),
+                &failed_docs,
+                &index_stats,
+                doc_count,
+            );
+        };
+        METRICS
+            .bulk_requests_in_flight
+            .fetch_add(1, Ordering::Relaxed);
+        let request = async {
+            let BulkResult {
+                mut accepted,
+                conflicts,
+                failure,
+                pipelines,
+                latency,
+            } = send_bulk_request(
+                &hosts,
+                &index,
+                &query,
+                body,
+                batch_timeout.is_some(),
+                on_conflict.is_some(),
+                capture.as_ref(),
+            )
+            .await?;
+            executed_pipelines.add(&pipelines);
+            if let Some((took, round_trip)) = latency {
+                bulk_latency.add(took, round_trip);
+            }
+            match on_conflict {
+                _ if conflicts.is_empty() => Ok((accepted, failure)),
+                None => Ok((accepted, failure)),
+                Some(OnConflict::Skip) => {
+                    resolved_conflicts.add(conflicts.len());
+                    accepted.extend(conflicts);
+                    Ok((accepted, failure))
                 }
-                StatusCode::TOO_MANY_REQUESTS => {
-                    log::warn!(
-                        "Bulk response: 429 - Too many requests (attempt {attempt}, backoff {:?}): {}",
-                        backoff,
-                        bulk_response.error_cause()
+                Some(OnConflict::Fail) => Err(eyre!(
+                    "{} docs already exist in {index}, stopping for --on-conflict fail",
+                    conflicts.len()
+                )),
+                Some(OnConflict::Overwrite) => {
+                    let docs = docs.as_deref().expect("documents are kept to overwrite conflicts");
+                    let conflicting: Vec<&Document> =
+                        conflicts.iter().filter_map(|&position| docs.get(position)).collect();
+                    let body = build_overwrite_body(&metadata, &conflicting)?;
+                    let overwritten = send_bulk_request(
+                        &hosts,
+                        &index,
+                        &query,
+                        body,
+                        batch_timeout.is_some(),
+                        false,
+                        capture.as_ref(),
+                    )
+                    .await?;
+                    executed_pipelines.add(&overwritten.pipelines);
+                    resolved_conflicts.add(overwritten.accepted.len());
+                    accepted.extend(
+                        overwritten
+                            .accepted
+                            .into_iter()
+                            .filter_map(|position| conflicts.get(position).copied()),
                     );
+                    Ok((accepted, failure.min(overwritten.failure)))
+                }
+            }
+        };
+        let deadline = async {
+            match batch_timeout {
+                Some(limit) => timeout(limit, request).await,
+                None => Ok(request.await),
+            }
+        };
+        let result = tokio::select! {
+            biased;
+            () = closing.abandoned() => None,
+            result = deadline => Some(result),
+        };
+        METRICS
+            .bulk_requests_in_flight
+            .fetch_sub(1, Ordering::Relaxed);
+        let Some(result) = result else {
+            return closing.abandon_batch(
+                &index,
+                &targets,
+                dead_letter.zip(docs),
+                &failed_docs,
+                &index_stats,
+                doc_count,
+            );
+        };
+        if let Ok(Ok((accepted, failure))) = &result {
+            failed_docs.add(*failure, doc_count.saturating_sub(accepted.len()));
+            index_stats.record(&index, &targets, accepted);
+        }
+        let Ok(result) = result else {
+            let limit = batch_timeout.expect("only a batch deadline can elapse");
+            return match (dead_letter, docs) {
+            (Some(dead_letter), Some(docs)) => {
+                dead_letter.write(&docs)?;
+                log::warn!(
+                    "Bulk request to {index} did not complete within {limit:?}, wrote {} docs to {dead_letter}",
+                    docs.len()
+                );
+                Ok(0)
+            }
+            _ => Err(OutputError::new(
+                FailureClass::Throttled,
+                format!("Bulk request to {index} did not complete within {limit:?}"),
+            )
+            .into()),
+            };
+        };
+        result.map(|(accepted, _)| accepted.len())
+        }
+        .instrument(span),
+    ));
+
+    Ok(())
+}
+
+/// Sends one bulk body until Elasticsearch accepts it, backing off on `429`
+/// responses and, when `retry_timeouts` is set, on request timeouts. Requests
+/// that cannot reach the host are retried while `hosts` has another to fail over to.
+///
+/// `create` operations rejected because their `_id` exists are only logged as
+/// errors unless `conflicts_handled`. Hosts that cannot be reached, or that
+/// refuse the credentials, fail with an [`OutputError`]. Attempts that get a
+/// response are written to `capture` when it wants them.
+async fn send_bulk_request(
+    hosts: &Failover,
+    index: &str,
+    bulk_query: &BulkQuery,
+    body: Bytes,
+    retry_timeouts: bool,
+    conflicts_handled: bool,
+    capture: Option<&BulkCapture>,
+) -> Result<BulkResult> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "content-type",
+        HeaderValue::from_static("application/x-ndjson"),
+    );
+    let query = bulk_query.pairs();
+    let query = (!query.is_empty()).then_some(query);
+    // Without an index in the URL, every action line names its own.
+    let bulk_path = match index {
+        "" => "/_bulk".to_string(),
+        index => format!("/{index}/_bulk"),
+    };
+
+    let mut attempt = 0u64;
+    let mut backoff = Duration::from_secs(1);
+    let max_backoff = Duration::from_secs(30);
+
+    loop {
+        attempt += 1;
+        let started = Instant::now();
+        let attempt_span =
+            tracing::info_span!("bulk_request", attempt, status = tracing::field::Empty);
+        let (generation, client) = hosts.client();
+        let response = match client
+            .send(
+                Method::Post,
+                &bulk_path,
+                headers.clone(),
+                query.as_ref(),
+                Some(body.clone()),
+                None,
+            )
+            .instrument(attempt_span.clone())
+            .await
+        {
+            Ok(response) => response,
+            Err(err) if retry_timeouts && err.is_timeout() => {
+                METRICS.bulk_retries.fetch_add(1, Ordering::Relaxed);
+                log::warn!("Bulk request timed out (attempt {attempt}, backoff {backoff:?})");
+                sleep(backoff).await;
+                backoff = std::cmp::min(backoff * 2, max_backoff);
+                continue;
+            }
+            Err(err) if hosts.failed(generation, &err) => {
+                // A host just failed over to is tried right away.
+                if hosts.client().0 == generation {
                     sleep(backoff).await;
-                    if backoff < max_backoff {
-                        backoff = std::cmp::min(backoff * 2, max_backoff);
-                    }
+                    backoff = std::cmp::min(backoff * 2, max_backoff);
                 }
-                _ => {
-                    log::debug!("Bulk response status: {status_code}");
-                    if bulk_response.has_errors() {
-                        log::warn!(
-                            "Bulk response contained errors: {}",
-                            bulk_response.error_counts()
-                        );
+                continue;
+            }
+            Err(err) => {
+                let class = match err.is_timeout() {
+                    true => FailureClass::Throttled,
+                    false => FailureClass::Connection,
+                };
+                return Err(OutputError::new(class, format!("Bulk request failed: {err}")).into());
+            }
+        };
+        hosts.connected(generation);
+
+        let status_code = response.status_code();
+        attempt_span.record("status", status_code.as_u16());
+        let raw = response.bytes().await;
+        let bulk_response = match &raw {
+            Ok(raw) => serde_json::from_slice::<BulkResponse>(raw).map_err(eyre::Report::from),
+            Err(err) => Err(eyre!("Failed to read bulk response: {err}")),
+        };
+        METRICS.observe_bulk_latency(started.elapsed());
+        if let (Some(capture), Ok(raw)) = (capture, &raw) {
+            let failed = !status_code.is_success()
+                || bulk_response.as_ref().is_ok_and(BulkResponse::has_errors);
+            if capture.wants(failed) {
+                let mut path = bulk_path.clone();
+                if let Some(query) = &query {
+                    path.push('?');
+                    path.push_str(
+                        &url::form_urlencoded::Serializer::new(String::new())
+                            .extend_pairs(query)
+                            .finish(),
+                    );
+                }
+                if let Err(err) = capture.write(&path, &body, &status_code.to_string(), raw) {
+                    log::warn!("Failed to capture a bulk request to {capture}: {err}");
+                }
+            }
+        }
+        // Error responses from proxies and load balancers may not be JSON.
+        let error_cause = || {
+            bulk_response
+                .as_ref()
+                .map_or_else(|_| "unknown".to_string(), BulkResponse::error_cause)
+        };
+        match status_code {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                return Err(OutputError::new(
+                    FailureClass::Auth,
+                    format!("Bulk response: {status_code} ({})", error_cause()),
+                )
+                .into());
+            }
+            StatusCode::PAYLOAD_TOO_LARGE => {
+                log::error!(
+                    "Bulk response: 413 - Payload too large ({}), lower --max-request-bytes",
+                    error_cause()
+                );
+                return Ok(BulkResult::rejected(FailureClass::RequestTooLarge));
+            }
+            StatusCode::BAD_REQUEST => {
+                log::error!("Bulk response: 400 - Bad request ({})", error_cause());
+                return Ok(BulkResult::rejected(FailureClass::Rejected));
+            }
+            StatusCode::TOO_MANY_REQUESTS => {
+                METRICS.bulk_retries.fetch_add(1, Ordering::Relaxed);
+                log::warn!(
+                    "Bulk response: 429 - Too many requests (attempt {attempt}, backoff {:?}): {}",
+                    backoff,
+                    error_cause()
+                );
+                sleep(backoff).await;
+                if backoff < max_backoff {
+                    backoff = std::cmp::min(backoff * 2, max_backoff);
+                }
+            }
+            status_code if !status_code.is_success() => {
+                log::error!("Bulk response: {status_code} ({})", error_cause());
+                return Ok(BulkResult::rejected(FailureClass::Rejected));
+            }
+            _ => {
+                log::debug!("Bulk response status: {status_code}");
+                let bulk_response = bulk_response?;
+                if bulk_query.require_alias
+                    && let Some(missing) = bulk_response.missing_alias()
+                {
+                    return Err(OutputError::new(
+                        FailureClass::Rejected,
+                        format!("{missing} is not an alias, stopping for --require-alias"),
+                    )
+                    .into());
+                }
+                let mut pipelines = BTreeMap::<String, usize>::new();
+                for (index, id, executed) in bulk_response.executed_pipelines() {
+                    log::debug!(
+                        "{index}/{} ran pipelines {}",
+                        id.unwrap_or("-"),
+                        executed.join(", ")
+                    );
+                    for pipeline in executed {
+                        *pipelines.entry(pipeline.clone()).or_default() += 1;
                     }
-                    return Ok(bulk_response.success_count());
                 }
+                let conflicts = bulk_response.conflict_positions();
+                let handled = match conflicts_handled {
+                    true => conflicts.len(),
+                    false => 0,
+                };
+                if bulk_response.has_errors() && bulk_response.error_count() > handled {
+                    log::warn!(
+                        "Bulk response contained errors: {}",
+                        bulk_response.error_counts()
+                    );
+                }
+                return Ok(BulkResult {
+                    accepted: bulk_response.success_positions(),
+                    conflicts,
+                    failure: FailureClass::Item,
+                    pipelines,
+                    latency: bulk_response.took().map(|took| (took, started.elapsed())),
+                });
             }
         }
-    }));
+    }
+}
 
-    Ok(())
+/// What Elasticsearch made of one bulk body.
+#[derive(Debug)]
+struct BulkResult {
+    /// Positions of the documents accepted.
+    accepted: Vec<usize>,
+    /// Positions of the `create` operations rejected because their `_id` exists.
+    conflicts: Vec<usize>,
+    /// Why the other documents failed, one by one or with the whole request.
+    failure: FailureClass,
+    /// Documents each ingest pipeline ran on, with `list_executed_pipelines`.
+    pipelines: BTreeMap<String, usize>,
+    /// Elasticsearch's `took` and the round trip of the attempt it answered.
+    latency: Option<(Duration, Duration)>,
+}
+
+impl BulkResult {
+    fn rejected(failure: FailureClass) -> Self {
+        Self {
+            accepted: Vec::new(),
+            conflicts: Vec::new(),
+            failure,
+            pipelines: BTreeMap::new(),
+            latency: None,
+        }
+    }
+}
+
+/// Query parameters of every bulk request from one output.
+#[derive(Debug)]
+struct BulkQuery {
+    pipeline: Option<String>,
+    require_alias: bool,
+    list_executed_pipelines: bool,
+}
+
+impl BulkQuery {
+    fn pairs(&self) -> Vec<(&str, &str)> {
+        let mut pairs = Vec::new();
+        if let Some(pipeline) = &self.pipeline {
+            pairs.push(("pipeline", pipeline.as_str()));
+        }
+        if self.require_alias {
+            pairs.push(("require_alias", "true"));
+        }
+        if self.list_executed_pipelines {
+            pairs.push(("list_executed_pipelines", "true"));
+        }
+        pairs
+    }
 }
 
 #[derive(Debug)]
 struct PreparedPreflight {
+    ilm_policy: Option<NamedJson>,
     pipeline: Option<NamedJson>,
+    component_templates: Vec<NamedJson>,
     template: Option<ParsedTemplate>,
     bulk_pipeline: Option<String>,
     template_pipeline: Option<String>,
@@ -503,10 +1691,21 @@ impl PreparedPreflight {
             config.template_overwrite,
         )?;
         let template = template_config.map(parse_template).transpose()?;
-
-        let template_pipeline = template
-            .as_ref()
-            .and_then(|template| extract_default_pipeline(&template.body).map(str::to_string));
+        let component_templates = config
+            .component_templates
+            .iter()
+            .map(|path| parse_component_template(path))
+            .collect::<Result<Vec<_>>>()?;
+        let ilm_policy = config
+            .ilm_policy
+            .map(|path| load_pipeline_config("ILM policy", &path, None))
+            .transpose()?;
+
+        let template_pipeline = template.as_ref().and_then(|template| {
+            extract_default_pipeline(&template.body)
+                .or_else(|| composed_default_pipeline(&template.body, &component_templates))
+                .map(str::to_string)
+        });
 
         if let (Some(template), Some(pipeline)) = (&template, &pipeline) {
             match template_pipeline.as_deref() {
@@ -531,21 +1730,46 @@ impl PreparedPreflight {
             match (&pipeline, config.pipeline_name.as_deref()) {
                 (Some(pipeline), _) => Some(pipeline.name.clone()),
                 (None, Some("_none")) => Some("_none".to_string()),
-                _ => None,
+                _ => config.host_pipeline.clone(),
             }
         } else {
             None
         };
 
         Ok(Self {
+            ilm_policy,
             pipeline,
+            component_templates,
             template,
             bulk_pipeline,
             template_pipeline,
         })
     }
 
+    fn features(&self, target_index: &str) -> Features {
+        Features {
+            composable_templates: self.template.is_some() || !self.component_templates.is_empty(),
+            data_stream_template: self
+                .template
+                .as_ref()
+                .is_some_and(|template| template.body.get("data_stream").is_some()),
+            ilm_policy: self.ilm_policy.is_some(),
+            streams: target_index == "logs" || target_index.starts_with("logs."),
+        }
+    }
+
+    /// Installs resources in dependency order: the ILM policy and ingest pipeline
+    /// that templates may reference, then component templates, then the index template.
     async fn run(&self, client: &Elasticsearch, target_index: &str) -> Result<()> {
+        if let Some(policy) = &self.ilm_policy {
+            put_json(
+                client,
+                &format!("/_ilm/policy/{}", policy.name),
+                &policy.body,
+            )
+            .await?;
+        }
+
         if let Some(pipeline) = &self.pipeline {
             put_json(
                 client,
@@ -559,6 +1783,15 @@ impl PreparedPreflight {
             ensure_pipeline_exists(client, pipeline_name).await?;
         }
 
+        for component in &self.component_templates {
+            put_json(
+                client,
+                &format!("/_component_template/{}", component.name),
+                &component.body,
+            )
+            .await?;
+        }
+
         if let Some(template) = &self.template {
             install_template(client, target_index, template).await?;
         }
@@ -629,214 +1862,1210 @@ fn parse_config_body(kind: &str, path: &Path, body: &str) -> Result<Value> {
             )
         }),
     }
-}
+}
+
+fn normalized_extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+async fn put_json(client: &Elasticsearch, path: &str, body: &Value) -> Result<()> {
+    let mut headers = HeaderMap::new();
+    headers.insert("content-type", HeaderValue::from_static("application/json"));
+    let body = serde_json::to_vec(body)?;
+    let response = client
+        .send(
+            Method::Put,
+            path,
+            headers,
+            Option::<&()>::None,
+            Some(body),
+            None,
+        )
+        .await?;
+    ensure_success(response.status_code(), response.text().await?, path)
+}
+
+async fn ensure_pipeline_exists(client: &Elasticsearch, name: &str) -> Result<()> {
+    let response = client
+        .send(
+            Method::Get,
+            &format!("/_ingest/pipeline/{name}"),
+            HeaderMap::new(),
+            Option::<&()>::None,
+            Option::<Vec<u8>>::None,
+            None,
+        )
+        .await?;
+    ensure_success(
+        response.status_code(),
+        response.text().await?,
+        &format!("/_ingest/pipeline/{name}"),
+    )
+    .map_err(|err| {
+        eyre!("template references missing or unavailable ingest pipeline '{name}': {err}")
+    })
+}
+
+fn ensure_success(status: StatusCode, body: String, path: &str) -> Result<()> {
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(eyre!(
+            "Elasticsearch request to {path} failed with status {status}: {body}"
+        ))
+    }
+}
+
+fn extract_default_pipeline(template: &Value) -> Option<&str> {
+    let settings = template.get("template")?.get("settings")?;
+    settings
+        .get("index.default_pipeline")
+        .and_then(Value::as_str)
+        .or_else(|| {
+            settings
+                .get("index")
+                .and_then(|index| index.get("default_pipeline"))
+                .and_then(Value::as_str)
+        })
+}
+
+/// Finds a default pipeline set by a component template the index template is composed of.
+fn composed_default_pipeline<'a>(template: &Value, components: &'a [NamedJson]) -> Option<&'a str> {
+    let composed_of = template.get("composed_of")?.as_array()?;
+    components
+        .iter()
+        .filter(|component| {
+            composed_of
+                .iter()
+                .any(|name| name == component.name.as_str())
+        })
+        .find_map(|component| extract_default_pipeline(&component.body))
+}
+
+async fn reap_inflight_if_needed(
+    inflight: &mut FuturesUnordered<JoinHandle<Result<usize>>>,
+    max_inflight_requests: usize,
+) -> Result<usize> {
+    let mut docs_sent = 0usize;
+    while inflight.len() >= max_inflight_requests {
+        if let Some(result) = inflight.next().await {
+            docs_sent += result.map_err(eyre::Report::new)??;
+        }
+    }
+    Ok(docs_sent)
+}
+
+/// The bytes a document adds to a bulk body. Exact for `create`, `index`, and
+/// replayed bulk pairs, and an upper bound for `update`, whose `_id` moves from
+/// the document into the action line.
+fn bulk_entry_bytes(action: BulkAction, metadata: &BulkMetadata, doc: &Document) -> u64 {
+    let framing = match (doc.action(), action) {
+        _ if metadata.rewrites(doc) => {
+            // Metadata rewrites the action line, so measure the entry itself.
+            let mut entry = Vec::new();
+            if append_operation(&mut entry, action, metadata, doc).is_ok() {
+                return entry.len() as u64;
+            }
+            0
+        }
+        (Some(line), _) => line.len() + 2,
+        (None, BulkAction::Create) => "{\"create\":{}}\n\n".len(),
+        (None, BulkAction::Index) => "{\"index\":{}}\n\n".len(),
+        (None, BulkAction::Update) => "{\"update\":{\"_id\":}}\n{\"doc\":}\n".len(),
+    };
+    (framing + doc.get().len()) as u64
+}
+
+/// Builds the request body once as `Bytes`, so retries share it instead of copying it.
+///
+/// Documents read from a bulk-format input keep their own action line, so its
+/// `_index`, `_id`, routing, and action are replayed as they were. Also
+/// returns each document's target index, when it names its own.
+fn build_bulk_body(
+    action: BulkAction,
+    metadata: &BulkMetadata,
+    batch: &[Document],
+) -> Result<(Bytes, Vec<Option<String>>)> {
+    let mut body = Vec::with_capacity(batch.len() * 64);
+    let targets = batch
+        .iter()
+        .map(|doc| append_operation(&mut body, action, metadata, doc))
+        .collect::<Result<_>>()?;
+    Ok((Bytes::from(body), targets))
+}
+
+/// Resends `create` operations that conflicted with existing documents as
+/// `index` operations, which replace them.
+fn build_overwrite_body(metadata: &BulkMetadata, docs: &[&Document]) -> Result<Bytes> {
+    let mut body = Vec::new();
+    for doc in docs {
+        let Some(line) = doc.action() else {
+            append_operation(&mut body, BulkAction::Index, metadata, doc)?;
+            continue;
+        };
+        let mut action = metadata.rename_action(line)?;
+        if let Some(action) = action.as_object_mut()
+            && let Some(fields) = action.remove("create")
+        {
+            action.insert("index".to_string(), fields);
+        }
+        serde_json::to_writer(&mut body, &action)?;
+        body.push(b'\n');
+        body.extend_from_slice(doc.get().as_bytes());
+        body.push(b'\n');
+    }
+    Ok(Bytes::from(body))
+}
+
+/// Appends a document's bulk entry, returning the index its action line names.
+fn append_operation(
+    body: &mut Vec<u8>,
+    action: BulkAction,
+    metadata: &BulkMetadata,
+    doc: &Document,
+) -> Result<Option<String>> {
+    if let Some(line) = doc.action() {
+        let target = match metadata.rewrites(doc) {
+            true => {
+                let action = metadata.rename_action(line)?;
+                serde_json::to_writer(&mut *body, &action)?;
+                action_index(&action)
+            }
+            false => {
+                body.extend_from_slice(line.as_bytes());
+                serde_json::from_str(line)
+                    .ok()
+                    .as_ref()
+                    .and_then(action_index)
+            }
+        };
+        body.push(b'\n');
+        body.extend_from_slice(doc.get().as_bytes());
+        body.push(b'\n');
+        return Ok(target);
+    }
+    match action {
+        BulkAction::Create | BulkAction::Index if !metadata.rewrites(doc) => {
+            body.extend_from_slice(match action {
+                BulkAction::Create => b"{\"create\":{}}\n",
+                _ => b"{\"index\":{}}\n",
+            });
+            body.extend_from_slice(doc.get().as_bytes());
+            body.push(b'\n');
+            Ok(None)
+        }
+        BulkAction::Create | BulkAction::Index => {
+            let mut source = serde_json::from_str::<Value>(doc.get())?;
+            let fields = metadata.take(&mut source)?;
+            let target = field_index(&fields);
+            let name = match action {
+                BulkAction::Create => "create",
+                _ => "index",
+            };
+            serde_json::to_writer(&mut *body, &json!({ name: fields }))?;
+            body.push(b'\n');
+            // The document is only rewritten when a metadata field comes out of it.
+            match metadata.strips_index() {
+                true => serde_json::to_writer(&mut *body, &source)?,
+                false => body.extend_from_slice(doc.get().as_bytes()),
+            }
+            body.push(b'\n');
+            Ok(target)
+        }
+        BulkAction::Update => append_update_operation(body, metadata, doc),
+    }
+}
+
+fn append_update_operation(
+    body: &mut Vec<u8>,
+    metadata: &BulkMetadata,
+    doc: &Document,
+) -> Result<Option<String>> {
+    let (id, mut doc) = extract_update_id(doc)?;
+    let mut fields = metadata.take(&mut doc)?;
+    let target = field_index(&fields);
+    fields.insert("_id".to_string(), Value::String(id));
+    serde_json::to_writer(&mut *body, &json!({ "update": fields }))?;
+    body.push(b'\n');
+    serde_json::to_writer(&mut *body, &json!({ "doc": doc }))?;
+    body.push(b'\n');
+    Ok(target)
+}
+
+/// The `_index` of a bulk action line like `{"index":{"_index":"logs"}}`.
+fn action_index(action: &Value) -> Option<String> {
+    field_index(action.as_object()?.values().next()?.as_object()?)
+}
+
+fn field_index(fields: &serde_json::Map<String, Value>) -> Option<String> {
+    fields.get("_index")?.as_str().map(str::to_string)
+}
+
+/// Derives document `_id`s for `--id-hash` from a hash of the whole document,
+/// or of the values of selected fields by dotted path.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IdHash {
+    fields: Vec<String>,
+}
+
+impl IdHash {
+    /// Parses `a,b,c` or `fields=a,b,c`; an empty value hashes whole documents.
+    pub fn new(value: &str) -> Self {
+        let value = value.strip_prefix("fields=").unwrap_or(value);
+        let fields = value
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .map(str::to_string)
+            .collect();
+        Self { fields }
+    }
+
+    /// The hex `xxh3_128` of the document, or of a JSON array of the field
+    /// values, with object keys sorted so reordered fields hash the same.
+    fn id(&self, doc: &Value) -> Result<String> {
+        let hashed = match self.fields.is_empty() {
+            true => canonical::to_string(doc),
+            false => {
+                let values = self
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        field_path::lookup(doc, field).ok_or_else(|| {
+                            eyre!("--id-hash field {field} is missing from a document")
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                canonical::to_string(&Value::Array(values.into_iter().cloned().collect()))
+            }
+        };
+        Ok(format!("{:032x}", xxh3_128(hashed.as_bytes())))
+    }
+}
+
+/// Document fields that set bulk action metadata, from `--index-from-doc`,
+/// `--routing-field`, and `--id-hash`, and the index renames from `--rename`.
+#[derive(Clone, Debug, Default)]
+struct BulkMetadata {
+    index_field: Option<String>,
+    routing_field: Option<String>,
+    id_hash: Option<IdHash>,
+    renames: Vec<(String, String)>,
+}
+
+impl BulkMetadata {
+    /// Whether the document's action line differs from the plain or replayed one.
+    fn rewrites(&self, doc: &Document) -> bool {
+        match doc.action() {
+            Some(_) => !self.renames.is_empty(),
+            None => {
+                self.index_field.is_some() || self.routing_field.is_some() || self.id_hash.is_some()
+            }
+        }
+    }
+
+    fn renamed(&self, index: String) -> String {
+        self.renames
+            .iter()
+            .find(|(from, _)| *from == index)
+            .map_or(index, |(_, to)| to.clone())
+    }
+
+    /// A replayed action line with its `_index` renamed.
+    fn rename_action(&self, line: &str) -> Result<Value> {
+        let mut action: Value = serde_json::from_str(line)?;
+        if let Some(metadata) = action
+            .as_object_mut()
+            .and_then(|action| action.values_mut().next())
+            .and_then(Value::as_object_mut)
+            && let Some(Value::String(index)) = metadata.get_mut("_index")
+        {
+            *index = self.renamed(std::mem::take(index));
+        }
+        Ok(action)
+    }
+
+    /// Elasticsearch rejects `_`-prefixed metadata fields such as `_index` in a
+    /// document source, so those are moved into the action line.
+    fn strips_index(&self) -> bool {
+        self.index_field
+            .as_deref()
+            .is_some_and(|field| field.starts_with('_') && !field.contains('.'))
+    }
+
+    /// The action line fields for a document, taking a `_`-prefixed index field out of it.
+    fn take(&self, doc: &mut Value) -> Result<serde_json::Map<String, Value>> {
+        let mut fields = serde_json::Map::new();
+        if let Some(id_hash) = &self.id_hash {
+            fields.insert("_id".to_string(), Value::String(id_hash.id(doc)?));
+        }
+        if let Some(field) = &self.index_field {
+            let index = match self.strips_index() {
+                true => doc.as_object_mut().and_then(|doc| doc.remove(field)),
+                false => field_path::lookup(doc, field).cloned(),
+            };
+            match index {
+                None | Some(Value::Null) => {}
+                Some(Value::String(index)) if !index.is_empty() => {
+                    fields.insert("_index".to_string(), Value::String(self.renamed(index)));
+                }
+                Some(_) => {
+                    return Err(eyre!(
+                        "--index-from-doc {field} must hold a non-empty string"
+                    ));
+                }
+            }
+        }
+        if let Some(field) = &self.routing_field {
+            match field_path::lookup(doc, field) {
+                None | Some(Value::Null) => {}
+                Some(Value::String(routing)) => {
+                    fields.insert("routing".to_string(), Value::String(routing.clone()));
+                }
+                Some(Value::Number(routing)) => {
+                    fields.insert("routing".to_string(), Value::String(routing.to_string()));
+                }
+                Some(_) => {
+                    return Err(eyre!(
+                        "--routing-field {field} must hold a string or number"
+                    ));
+                }
+            }
+        }
+        Ok(fields)
+    }
+}
+
+fn extract_update_id(doc: &Document) -> Result<(String, Value)> {
+    match serde_json::from_str::<Value>(doc.get())? {
+        Value::Object(mut map) => {
+            let id_value = map
+                .remove("_id")
+                .ok_or_eyre("Update action requires an _id field on each document")?;
+            let id = id_value
+                .as_str()
+                .ok_or_eyre("Update action requires _id to be a string")?
+                .to_string();
+            Ok((id, Value::Object(map)))
+        }
+        _ => Err(eyre!(
+            "Update action requires each document to be a JSON object"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BulkMetadata, BulkWorker, Closing, DEFAULT_BATCH_SIZE, DEFAULT_MAX_INFLIGHT_REQUESTS,
+        ElasticsearchOutputConfig, IdHash, IndexCounts, LatencyPercentiles, OutputPreflightConfig,
+        PreparedPreflight, Sequencer, TemplateConfig, Turbo, WorkerMessage, build_bulk_body,
+        build_overwrite_body, bulk_entry_bytes, extract_default_pipeline, extract_update_id,
+        index_patterns_match, parse_template, run_bulk_worker, spawn_flush, wildcard_match,
+    };
+    use crate::{
+        client::{ElasticsearchBuilder, Failover},
+        dead_letter::DeadLetter,
+        document::Document,
+        output::{BulkAction, FailureClass, OutputError},
+    };
+    use futures::{FutureExt, StreamExt, stream::FuturesUnordered};
+    use serde_json::{Value, json, value::RawValue};
+    use std::{
+        collections::BTreeMap,
+        fs,
+        path::PathBuf,
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+    use tokio::sync::mpsc;
+    use url::Url;
+
+    fn temp_json_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "espipe-pipeline-test-{}-{}-{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos(),
+            name
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{name}.json"));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn build_bulk_body_uses_create_ndjson() {
+        let docs = vec![
+            RawValue::from_string("{\"a\":1}".to_string())
+                .unwrap()
+                .into(),
+            RawValue::from_string("{\"b\":2}".to_string())
+                .unwrap()
+                .into(),
+        ];
+
+        let (body, _) =
+            build_bulk_body(BulkAction::Create, &BulkMetadata::default(), &docs).unwrap();
+        assert_eq!(
+            String::from_utf8(body.to_vec()).unwrap(),
+            "{\"create\":{}}\n{\"a\":1}\n{\"create\":{}}\n{\"b\":2}\n"
+        );
+    }
+
+    #[test]
+    fn build_bulk_body_uses_index_ndjson() {
+        let docs = vec![
+            RawValue::from_string("{\"a\":1}".to_string())
+                .unwrap()
+                .into(),
+        ];
+        let (body, _) =
+            build_bulk_body(BulkAction::Index, &BulkMetadata::default(), &docs).unwrap();
+        assert_eq!(
+            String::from_utf8(body.to_vec()).unwrap(),
+            "{\"index\":{}}\n{\"a\":1}\n"
+        );
+    }
+
+    #[test]
+    fn build_bulk_body_wraps_update_docs() {
+        let docs = vec![
+            RawValue::from_string("{\"_id\":\"1\",\"a\":1}".to_string())
+                .unwrap()
+                .into(),
+        ];
+        let (body, _) =
+            build_bulk_body(BulkAction::Update, &BulkMetadata::default(), &docs).unwrap();
+        let lines: Vec<Value> = String::from_utf8(body.to_vec())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines[0]["update"]["_id"], "1");
+        assert_eq!(lines[1], json!({ "doc": { "a": 1 } }));
+    }
+
+    #[test]
+    fn build_bulk_body_replays_document_actions() {
+        let docs = vec![
+            Document::unvalidated("{\"a\":1}")
+                .unwrap()
+                .with_action(r#"{"index":{"_index":"other","_id":"7"}}"#),
+            Document::unvalidated("{\"a\":2}").unwrap(),
+        ];
+        let (body, _) =
+            build_bulk_body(BulkAction::Create, &BulkMetadata::default(), &docs).unwrap();
+        assert_eq!(
+            String::from_utf8(body.to_vec()).unwrap(),
+            "{\"index\":{\"_index\":\"other\",\"_id\":\"7\"}}\n{\"a\":1}\n{\"create\":{}}\n{\"a\":2}\n"
+        );
+        assert_eq!(
+            docs.iter()
+                .map(|doc| bulk_entry_bytes(BulkAction::Create, &BulkMetadata::default(), doc))
+                .sum::<u64>(),
+            body.len() as u64
+        );
+    }
+
+    /// Each line of a bulk body, parsed, as key order is not significant.
+    fn bulk_lines(body: &[u8]) -> Vec<Value> {
+        std::str::from_utf8(body)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn build_bulk_body_routes_by_field() {
+        let routing = BulkMetadata {
+            routing_field: Some("join.parent".to_string()),
+            ..BulkMetadata::default()
+        };
+        let docs = vec![
+            Document::unvalidated(r#"{"_id":"1","join":{"parent":"p1"}}"#).unwrap(),
+            Document::unvalidated(r#"{"_id":"2","join":{"parent":7}}"#).unwrap(),
+            Document::unvalidated(r#"{"_id":"3"}"#).unwrap(),
+        ];
+        let (body, _) = build_bulk_body(BulkAction::Index, &routing, &docs).unwrap();
+        assert_eq!(
+            bulk_lines(&body),
+            [
+                json!({ "index": { "routing": "p1" } }),
+                json!({ "_id": "1", "join": { "parent": "p1" } }),
+                json!({ "index": { "routing": "7" } }),
+                json!({ "_id": "2", "join": { "parent": 7 } }),
+                json!({ "index": {} }),
+                json!({ "_id": "3" }),
+            ]
+        );
+        assert_eq!(
+            docs.iter()
+                .map(|doc| bulk_entry_bytes(BulkAction::Index, &routing, doc))
+                .sum::<u64>(),
+            body.len() as u64
+        );
+
+        let (update, _) = build_bulk_body(BulkAction::Update, &routing, &docs[..1]).unwrap();
+        assert_eq!(
+            bulk_lines(&update)[0],
+            json!({ "update": { "_id": "1", "routing": "p1" } })
+        );
+        let object = [Document::unvalidated(r#"{"join":{"parent":{}}}"#).unwrap()];
+        assert!(build_bulk_body(BulkAction::Create, &routing, &object).is_err());
+    }
+
+    #[test]
+    fn build_bulk_body_hashes_ids_from_content_or_fields() {
+        let content = BulkMetadata {
+            id_hash: Some(IdHash::new("")),
+            ..BulkMetadata::default()
+        };
+        let docs = [
+            Document::unvalidated(r#"{"a":1,"b":{"c":2,"d":3}}"#).unwrap(),
+            Document::unvalidated(r#"{"b":{"d":3,"c":2},"a":1}"#).unwrap(),
+            Document::unvalidated(r#"{"a":1,"b":{"c":3}}"#).unwrap(),
+        ];
+        let ids = |metadata: &BulkMetadata| -> Vec<String> {
+            let (body, _) = build_bulk_body(BulkAction::Create, metadata, &docs).unwrap();
+            String::from_utf8(body.to_vec())
+                .unwrap()
+                .lines()
+                .step_by(2)
+                .map(|line| {
+                    let action: Value = serde_json::from_str(line).unwrap();
+                    action["create"]["_id"].as_str().unwrap().to_string()
+                })
+                .collect()
+        };
+        let content_ids = ids(&content);
+        assert_eq!(content_ids[0].len(), 32);
+        assert_eq!(content_ids[0], content_ids[1]);
+        assert_ne!(content_ids[0], content_ids[2]);
+
+        let fields = BulkMetadata {
+            id_hash: Some(IdHash::new("fields=a")),
+            ..BulkMetadata::default()
+        };
+        let field_ids = ids(&fields);
+        assert_eq!(field_ids[0], field_ids[2]);
+        assert_ne!(field_ids[0], content_ids[0]);
+        assert_eq!(IdHash::new("a, b.c,"), IdHash::new("fields=a,b.c"));
+        let object_ids = ids(&BulkMetadata {
+            id_hash: Some(IdHash::new("b")),
+            ..BulkMetadata::default()
+        });
+        assert_eq!(object_ids[0], object_ids[1]);
+        assert_ne!(object_ids[0], object_ids[2]);
+
+        let missing = [Document::unvalidated(r#"{"b":1}"#).unwrap()];
+        let err = build_bulk_body(BulkAction::Create, &fields, &missing).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "--id-hash field a is missing from a document"
+        );
+    }
+
+    #[test]
+    fn build_overwrite_body_replaces_create_with_index() {
+        let metadata = BulkMetadata {
+            renames: vec![("logs-old".to_string(), "logs-new".to_string())],
+            ..BulkMetadata::default()
+        };
+        let replayed = Document::unvalidated(r#"{"n":1}"#)
+            .unwrap()
+            .with_action(r#"{"create":{"_index":"logs-old","_id":"7"}}"#);
+        let plain = Document::unvalidated(r#"{"n":2}"#).unwrap();
+        let body = build_overwrite_body(&metadata, &[&replayed, &plain]).unwrap();
+        assert_eq!(
+            bulk_lines(&body),
+            [
+                json!({ "index": { "_id": "7", "_index": "logs-new" } }),
+                json!({ "n": 1 }),
+                json!({ "index": {} }),
+                json!({ "n": 2 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn build_bulk_body_sends_documents_to_their_own_index() {
+        let by_index = BulkMetadata {
+            index_field: Some("_index".to_string()),
+            ..BulkMetadata::default()
+        };
+        let docs = vec![
+            Document::unvalidated(r#"{"_index":"logs-a","n":1}"#).unwrap(),
+            Document::unvalidated(r#"{"n":2}"#).unwrap(),
+        ];
+        let (body, targets) = build_bulk_body(BulkAction::Create, &by_index, &docs).unwrap();
+        assert_eq!(targets, [Some("logs-a".to_string()), None]);
+        assert_eq!(
+            bulk_lines(&body),
+            [
+                json!({ "create": { "_index": "logs-a" } }),
+                json!({ "n": 1 }),
+                json!({ "create": {} }),
+                json!({ "n": 2 }),
+            ]
+        );
+        assert_eq!(
+            docs.iter()
+                .map(|doc| bulk_entry_bytes(BulkAction::Create, &by_index, doc))
+                .sum::<u64>(),
+            body.len() as u64
+        );
+
+        let by_field = BulkMetadata {
+            index_field: Some("meta.index".to_string()),
+            ..BulkMetadata::default()
+        };
+        let docs = [Document::unvalidated(r#"{"_id":"1","meta":{"index":"b"}}"#).unwrap()];
+        let (body, targets) = build_bulk_body(BulkAction::Update, &by_field, &docs).unwrap();
+        assert_eq!(targets, [Some("b".to_string())]);
+        assert_eq!(
+            bulk_lines(&body),
+            [
+                json!({ "update": { "_id": "1", "_index": "b" } }),
+                json!({ "doc": { "meta": { "index": "b" } } }),
+            ]
+        );
+        let numeric = [Document::unvalidated(r#"{"_index":7}"#).unwrap()];
+        assert!(build_bulk_body(BulkAction::Index, &by_index, &numeric).is_err());
+    }
+
+    #[test]
+    fn build_bulk_body_renames_document_indices() {
+        let renames = BulkMetadata {
+            renames: vec![("logs-old".to_string(), "logs-new".to_string())],
+            ..BulkMetadata::default()
+        };
+        let docs = vec![
+            Document::unvalidated("{\"a\":1}")
+                .unwrap()
+                .with_action(r#"{"index":{"_index":"logs-old","_id":"7"}}"#),
+            Document::unvalidated("{\"a\":2}")
+                .unwrap()
+                .with_action(r#"{"create":{"_index":"other"}}"#),
+            Document::unvalidated("{\"a\":3}").unwrap(),
+        ];
+        let (body, targets) = build_bulk_body(BulkAction::Create, &renames, &docs).unwrap();
+        assert_eq!(
+            targets,
+            [
+                Some("logs-new".to_string()),
+                Some("other".to_string()),
+                None
+            ]
+        );
+        assert_eq!(
+            bulk_lines(&body),
+            [
+                json!({ "index": { "_id": "7", "_index": "logs-new" } }),
+                json!({ "a": 1 }),
+                json!({ "create": { "_index": "other" } }),
+                json!({ "a": 2 }),
+                json!({ "create": {} }),
+                json!({ "a": 3 }),
+            ]
+        );
+        assert_eq!(
+            docs.iter()
+                .map(|doc| bulk_entry_bytes(BulkAction::Create, &renames, doc))
+                .sum::<u64>(),
+            body.len() as u64
+        );
+    }
+
+    #[test]
+    fn extract_update_id_requires_id() {
+        let doc: Document = RawValue::from_string("{\"message\":\"hello\"}".to_string())
+            .unwrap()
+            .into();
+        let err = extract_update_id(&doc).err().expect("expected error");
+        assert!(err.to_string().contains("_id"));
+    }
+
+    #[test]
+    fn default_worker_limits_are_bounded() {
+        let config = ElasticsearchOutputConfig::default();
+        assert_eq!(config.batch_size, DEFAULT_BATCH_SIZE);
+        assert_eq!(config.channel_capacity(), DEFAULT_BATCH_SIZE);
+        assert_eq!(config.max_inflight_requests, DEFAULT_MAX_INFLIGHT_REQUESTS);
+    }
+
+    fn worker(hosts: &Arc<Failover>, index: &str, config: ElasticsearchOutputConfig) -> BulkWorker {
+        BulkWorker {
+            hosts: Arc::clone(hosts),
+            hostname: "localhost".to_string(),
+            index: index.to_string(),
+            action: BulkAction::Create,
+            config,
+            bulk_pipeline: None,
+            closing: Arc::default(),
+        }
+    }
+
+    fn hosts(url: Url) -> Arc<Failover> {
+        Arc::new(Failover::new(
+            url.clone(),
+            ElasticsearchBuilder::new(url).build().unwrap(),
+        ))
+    }
+
+    /// Accepts connections but never answers, like an overloaded cluster.
+    async fn unresponsive_cluster() -> Url {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn ordered_requests_wait_for_the_one_before() {
+        let sequencer = Sequencer::default();
+        let first = sequencer.next_turn();
+        let mut second = sequencer.next_turn();
+        let mut third = sequencer.next_turn();
+
+        assert!(second.wait().now_or_never().is_none());
+        drop(first);
+        assert!(third.wait().now_or_never().is_none());
+        second.wait().await;
+        drop(second);
+        third.wait().await;
+    }
+
+    #[tokio::test]
+    async fn batches_past_their_deadline_are_dead_lettered() {
+        let url = unresponsive_cluster().await;
+        let client = Arc::new(Failover::new(
+            url.clone(),
+            ElasticsearchBuilder::new(url)
+                .timeout(Some(Duration::from_millis(50)))
+                .build()
+                .unwrap(),
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dead.ndjson");
+        let dead_letter = DeadLetter::open(&path).unwrap();
+        let config = ElasticsearchOutputConfig::try_new(10, 1)
+            .unwrap()
+            .with_timeouts(None, Some(Duration::from_millis(200)))
+            .with_dead_letter(Some(dead_letter.clone()));
+        let mut batch = vec![Document::unvalidated("{\"a\":1}").unwrap()];
+        let mut inflight = FuturesUnordered::new();
+
+        spawn_flush(
+            &mut inflight,
+            &worker(&client, "test", config.clone()),
+            &mut batch,
+        )
+        .unwrap();
+        let sent = inflight.next().await.unwrap().unwrap().unwrap();
+
+        assert_eq!(sent, 0);
+        assert_eq!(dead_letter.written(), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"a\":1}\n");
+    }
+
+    /// Answers every request with `status` and `body` once the request has been
+    /// read, counting the requests served.
+    async fn cluster_answering(
+        status: &'static str,
+        body: &'static str,
+    ) -> (Url, Arc<AtomicUsize>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let served = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                served.fetch_add(1, Ordering::Relaxed);
+                let mut request = Vec::new();
+                let mut chunk = [0u8; 4096];
+                loop {
+                    let read = socket.read(&mut chunk).await.unwrap();
+                    request.extend_from_slice(&chunk[..read]);
+                    let text = String::from_utf8_lossy(&request).to_lowercase();
+                    let Some(header_end) = text.find("\r\n\r\n") else {
+                        continue;
+                    };
+                    let length = text
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length: "))
+                        .map_or(0, |length| length.trim().parse::<usize>().unwrap());
+                    if read == 0 || request.len() >= header_end + 4 + length {
+                        break;
+                    }
+                }
+                let response = format!(
+                    "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, requests)
+    }
 
-fn normalized_extension(path: &Path) -> Option<String> {
-    path.extension()
-        .and_then(|extension| extension.to_str())
-        .map(str::to_ascii_lowercase)
-}
+    async fn flush_two_docs(url: Url, config: &ElasticsearchOutputConfig) -> usize {
+        let client = hosts(url);
+        let mut batch = vec![
+            Document::unvalidated("{\"a\":1}").unwrap(),
+            Document::unvalidated("{\"a\":2}").unwrap(),
+        ];
+        let mut inflight = FuturesUnordered::new();
+        spawn_flush(
+            &mut inflight,
+            &worker(&client, "test", config.clone()),
+            &mut batch,
+        )
+        .unwrap();
+        inflight.next().await.unwrap().unwrap().unwrap()
+    }
 
-async fn put_json(client: &Elasticsearch, path: &str, body: &Value) -> Result<()> {
-    let mut headers = HeaderMap::new();
-    headers.insert("content-type", HeaderValue::from_static("application/json"));
-    let body = serde_json::to_vec(body)?;
-    let response = client
-        .send(
-            Method::Put,
-            path,
-            headers,
-            Option::<&()>::None,
-            Some(body),
-            None,
+    #[tokio::test]
+    async fn only_confirmed_items_count_as_sent() {
+        let (url, _) = cluster_answering(
+            "200 OK",
+            r#"{"errors":true,"items":[{"create":{"_index":"test","_id":"1","status":201}},{"create":{"_index":"test","_id":"2","status":400,"error":{"caused_by":{"type":"mapper_parsing_exception","reason":"bad"}}}}]}"#,
         )
-        .await?;
-    ensure_success(response.status_code(), response.text().await?, path)
-}
+        .await;
+        let config = ElasticsearchOutputConfig::default();
 
-async fn ensure_pipeline_exists(client: &Elasticsearch, name: &str) -> Result<()> {
-    let response = client
-        .send(
-            Method::Get,
-            &format!("/_ingest/pipeline/{name}"),
-            HeaderMap::new(),
-            Option::<&()>::None,
-            Option::<Vec<u8>>::None,
-            None,
+        assert_eq!(flush_two_docs(url, &config).await, 1);
+        assert_eq!(config.failed_docs().count(), 1);
+        assert_eq!(config.failed_docs().worst(), Some(FailureClass::Item));
+    }
+
+    #[tokio::test]
+    async fn rejected_requests_count_every_doc_as_failed() {
+        let (url, _) = cluster_answering(
+            "503 Service Unavailable",
+            r#"{"error":{"type":"unavailable_shards_exception"},"status":503}"#,
         )
-        .await?;
-    ensure_success(
-        response.status_code(),
-        response.text().await?,
-        &format!("/_ingest/pipeline/{name}"),
-    )
-    .map_err(|err| {
-        eyre!("template references missing or unavailable ingest pipeline '{name}': {err}")
-    })
-}
+        .await;
+        let config = ElasticsearchOutputConfig::default();
 
-fn ensure_success(status: StatusCode, body: String, path: &str) -> Result<()> {
-    if status.is_success() {
-        Ok(())
-    } else {
-        Err(eyre!(
-            "Elasticsearch request to {path} failed with status {status}: {body}"
-        ))
+        assert_eq!(flush_two_docs(url, &config).await, 0);
+        assert_eq!(config.failed_docs().count(), 2);
+        assert_eq!(config.failed_docs().worst(), Some(FailureClass::Rejected));
+        assert_eq!(
+            config.index_stats().counts()["test"],
+            IndexCounts { sent: 0, failed: 2 }
+        );
     }
-}
 
-fn extract_default_pipeline(template: &Value) -> Option<&str> {
-    let settings = template.get("template")?.get("settings")?;
-    settings
-        .get("index.default_pipeline")
-        .and_then(Value::as_str)
-        .or_else(|| {
-            settings
-                .get("index")
-                .and_then(|index| index.get("default_pipeline"))
-                .and_then(Value::as_str)
-        })
-}
+    #[tokio::test]
+    async fn executed_pipelines_are_counted_per_pipeline() {
+        let (url, _) = cluster_answering(
+            "200 OK",
+            r#"{"errors":false,"items":[{"create":{"_index":"test","_id":"1","status":201,"executed_pipelines":["geoip","normalize"]}},{"create":{"_index":"test","_id":"2","status":201,"executed_pipelines":["geoip"]}}]}"#,
+        )
+        .await;
+        let config = ElasticsearchOutputConfig::default().with_list_executed_pipelines(true);
 
-async fn reap_inflight_if_needed(
-    inflight: &mut FuturesUnordered<JoinHandle<Result<usize>>>,
-    max_inflight_requests: usize,
-) -> Result<usize> {
-    let mut docs_sent = 0usize;
-    while inflight.len() >= max_inflight_requests {
-        if let Some(result) = inflight.next().await {
-            docs_sent += result.map_err(eyre::Report::new)??;
-        }
+        assert_eq!(flush_two_docs(url, &config).await, 2);
+        assert_eq!(
+            config.executed_pipelines().counts(),
+            BTreeMap::from([("geoip".to_string(), 2), ("normalize".to_string(), 1)])
+        );
     }
-    Ok(docs_sent)
-}
 
-fn build_bulk_body(action: BulkAction, batch: &[Box<RawValue>]) -> Result<Vec<u8>> {
-    let mut body = Vec::with_capacity(batch.len() * 64);
-    for doc in batch {
-        match action {
-            BulkAction::Create => {
-                body.extend_from_slice(b"{\"create\":{}}\n");
-                body.extend_from_slice(doc.get().as_bytes());
-                body.push(b'\n');
-            }
-            BulkAction::Index => {
-                body.extend_from_slice(b"{\"index\":{}}\n");
-                body.extend_from_slice(doc.get().as_bytes());
-                body.push(b'\n');
-            }
-            BulkAction::Update => append_update_operation(&mut body, doc)?,
-        }
+    #[tokio::test]
+    async fn bulk_took_is_kept_apart_from_the_round_trip() {
+        let (url, _) = cluster_answering(
+            "200 OK",
+            r#"{"took":7,"errors":false,"items":[{"create":{"_index":"test","_id":"1","status":201}},{"create":{"_index":"test","_id":"2","status":201}}]}"#,
+        )
+        .await;
+        let config = ElasticsearchOutputConfig::default();
+
+        assert_eq!(flush_two_docs(url, &config).await, 2);
+        let (took, round_trip) = config.bulk_latency().percentiles().unwrap();
+        assert_eq!(took.max, Duration::from_millis(7));
+        assert!(round_trip.max > Duration::ZERO);
+
+        let samples = (1..=100).map(Duration::from_millis).collect();
+        let percentiles = LatencyPercentiles::of(samples).unwrap();
+        assert_eq!(
+            percentiles.to_string(),
+            "p50 50ms, p90 90ms, p99 99ms, max 100ms"
+        );
+        assert_eq!(LatencyPercentiles::of(Vec::new()), None);
     }
-    Ok(body)
-}
 
-fn append_update_operation(body: &mut Vec<u8>, doc: &RawValue) -> Result<()> {
-    let (id, doc) = extract_update_id(doc)?;
-    body.extend_from_slice(b"{\"update\":{\"_id\":");
-    serde_json::to_writer(&mut *body, &id)?;
-    body.extend_from_slice(b"}}\n");
-    serde_json::to_writer(&mut *body, &json!({ "doc": doc }))?;
-    body.push(b'\n');
-    Ok(())
-}
+    #[tokio::test]
+    async fn missing_aliases_stop_the_output_with_require_alias() {
+        let (url, _) = cluster_answering(
+            "200 OK",
+            r#"{"errors":true,"items":[{"create":{"_index":"logs","_id":null,"status":404,"error":{"type":"index_not_found_exception","reason":"no such index [logs] and [require_alias] request flag is [true] and [logs] is not an alias"}}}]}"#,
+        )
+        .await;
+        let client = hosts(url);
+        let config = ElasticsearchOutputConfig::default().with_require_alias(true);
+        let mut batch = vec![Document::unvalidated("{\"a\":1}").unwrap()];
+        let mut inflight = FuturesUnordered::new();
+        spawn_flush(
+            &mut inflight,
+            &worker(&client, "logs", config.clone()),
+            &mut batch,
+        )
+        .unwrap();
 
-fn extract_update_id(doc: &RawValue) -> Result<(String, Value)> {
-    match serde_json::from_str::<Value>(doc.get())? {
-        Value::Object(mut map) => {
-            let id_value = map
-                .remove("_id")
-                .ok_or_eyre("Update action requires an _id field on each document")?;
-            let id = id_value
-                .as_str()
-                .ok_or_eyre("Update action requires _id to be a string")?
-                .to_string();
-            Ok((id, Value::Object(map)))
-        }
-        _ => Err(eyre!(
-            "Update action requires each document to be a JSON object"
-        )),
+        let err = inflight.next().await.unwrap().unwrap().unwrap_err();
+        assert!(err.to_string().contains("logs is not an alias"), "{err}");
+        assert_eq!(OutputError::class_of(&err), Some(FailureClass::Rejected));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::{
-        DEFAULT_BATCH_SIZE, DEFAULT_MAX_INFLIGHT_REQUESTS, ElasticsearchOutputConfig,
-        OutputPreflightConfig, PreparedPreflight, TemplateConfig, build_bulk_body,
-        extract_default_pipeline, extract_update_id, index_patterns_match, parse_template,
-        wildcard_match,
-    };
-    use crate::output::BulkAction;
-    use serde_json::{Value, json, value::RawValue};
-    use std::{fs, path::PathBuf};
+    #[tokio::test]
+    async fn turbo_restores_the_settings_a_crashed_run_saved() {
+        let (url, requests) = cluster_answering("200 OK", r#"{"acknowledged":true}"#).await;
+        let (_, client) = hosts(url).client();
+        let dir = tempfile::tempdir().unwrap();
+        let state = dir.path().join("localhost-logs.json");
+        fs::write(
+            &state,
+            r#"{"logs":{"refresh_interval":"30s","number_of_replicas":"1"}}"#,
+        )
+        .unwrap();
 
-    fn temp_json_path(name: &str) -> PathBuf {
-        let dir = std::env::temp_dir().join(format!(
-            "espipe-pipeline-test-{}-{}-{}",
-            std::process::id(),
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_nanos(),
-            name
-        ));
-        fs::create_dir_all(&dir).unwrap();
-        let path = dir.join(format!("{name}.json"));
-        let _ = fs::remove_file(&path);
-        path
+        // The saved originals are used rather than the settings the crash left.
+        let turbo = Turbo::start(&client, dir.path(), "localhost", "logs")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(requests.load(Ordering::Relaxed), 1);
+        turbo.restore(&client).await.unwrap();
+        assert_eq!(requests.load(Ordering::Relaxed), 2);
+        assert!(!state.exists());
     }
 
-    #[test]
-    fn build_bulk_body_uses_create_ndjson() {
-        let docs = vec![
-            RawValue::from_string("{\"a\":1}".to_string()).unwrap(),
-            RawValue::from_string("{\"b\":2}".to_string()).unwrap(),
-        ];
+    #[tokio::test]
+    async fn refused_credentials_stop_the_output_with_an_auth_error() {
+        let (url, _) = cluster_answering(
+            "401 Unauthorized",
+            r#"{"error":{"type":"security_exception"},"status":401}"#,
+        )
+        .await;
+        let client = hosts(url);
+        let config = ElasticsearchOutputConfig::default();
+        let mut batch = vec![Document::unvalidated("{\"a\":1}").unwrap()];
+        let mut inflight = FuturesUnordered::new();
+        spawn_flush(
+            &mut inflight,
+            &worker(&client, "test", config.clone()),
+            &mut batch,
+        )
+        .unwrap();
+
+        let err = inflight.next().await.unwrap().unwrap().unwrap_err();
+        assert_eq!(OutputError::class_of(&err), Some(FailureClass::Auth));
+    }
+
+    #[tokio::test]
+    async fn oversized_requests_are_counted_as_too_large() {
+        let (url, _) = cluster_answering("413 Payload Too Large", "").await;
+        let config = ElasticsearchOutputConfig::default();
 
-        let body = build_bulk_body(BulkAction::Create, &docs).unwrap();
+        assert_eq!(flush_two_docs(url, &config).await, 0);
         assert_eq!(
-            String::from_utf8(body).unwrap(),
-            "{\"create\":{}}\n{\"a\":1}\n{\"create\":{}}\n{\"b\":2}\n"
+            config.failed_docs().by_class(),
+            BTreeMap::from([(FailureClass::RequestTooLarge, 2)])
         );
     }
 
-    #[test]
-    fn build_bulk_body_uses_index_ndjson() {
-        let docs = vec![RawValue::from_string("{\"a\":1}".to_string()).unwrap()];
-        let body = build_bulk_body(BulkAction::Index, &docs).unwrap();
-        assert_eq!(
-            String::from_utf8(body).unwrap(),
-            "{\"index\":{}}\n{\"a\":1}\n"
+    #[tokio::test]
+    async fn documents_are_counted_against_their_own_index() {
+        let (url, _) = cluster_answering(
+            "200 OK",
+            r#"{"errors":true,"items":[{"create":{"_index":"logs-a","_id":"1","status":201}},{"create":{"_index":"logs-b","_id":"2","status":400,"error":{"caused_by":{"type":"mapper_parsing_exception","reason":"bad"}}}},{"create":{"_index":"test","_id":"3","status":201}}]}"#,
+        )
+        .await;
+        let client = hosts(url);
+        let config = ElasticsearchOutputConfig::default();
+        let mut batch = vec![
+            Document::unvalidated("{\"a\":1}")
+                .unwrap()
+                .with_action(r#"{"create":{"_index":"logs-a"}}"#),
+            Document::unvalidated("{\"a\":2}")
+                .unwrap()
+                .with_action(r#"{"create":{"_index":"logs-b"}}"#),
+            Document::unvalidated("{\"a\":3}").unwrap(),
+        ];
+        let mut inflight = FuturesUnordered::new();
+        spawn_flush(
+            &mut inflight,
+            &worker(&client, "test", config.clone()),
+            &mut batch,
+        )
+        .unwrap();
+
+        assert_eq!(inflight.next().await.unwrap().unwrap().unwrap(), 2);
+        let counts = config.index_stats().counts();
+        assert_eq!(counts["logs-a"], IndexCounts { sent: 1, failed: 0 });
+        assert_eq!(counts["logs-b"], IndexCounts { sent: 0, failed: 1 });
+        assert_eq!(counts["test"], IndexCounts { sent: 1, failed: 0 });
+    }
+
+    #[tokio::test]
+    async fn batches_fail_over_when_the_primary_stops_accepting_connections() {
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let primary = Url::parse(&format!("http://{}", closed.local_addr().unwrap())).unwrap();
+        drop(closed);
+        let (fallback, requests) = cluster_answering(
+            "200 OK",
+            r#"{"errors":false,"items":[{"create":{"_index":"test","_id":"1","status":201}},{"create":{"_index":"test","_id":"2","status":201}}]}"#,
+        )
+        .await;
+        let hosts = Arc::new(
+            Failover::new(
+                primary.clone(),
+                ElasticsearchBuilder::new(primary).build().unwrap(),
+            )
+            .with_fallback(
+                fallback.clone(),
+                ElasticsearchBuilder::new(fallback).build().unwrap(),
+            ),
         );
+        let config = ElasticsearchOutputConfig::default();
+        let mut batch = vec![
+            Document::unvalidated("{\"a\":1}").unwrap(),
+            Document::unvalidated("{\"a\":2}").unwrap(),
+        ];
+        let mut inflight = FuturesUnordered::new();
+        spawn_flush(
+            &mut inflight,
+            &worker(&hosts, "test", config.clone()),
+            &mut batch,
+        )
+        .unwrap();
+
+        assert_eq!(inflight.next().await.unwrap().unwrap().unwrap(), 2);
+        assert_eq!(requests.load(Ordering::Relaxed), 1);
+        assert_eq!(hosts.client().0, 1);
     }
 
-    #[test]
-    fn build_bulk_body_wraps_update_docs() {
-        let docs = vec![RawValue::from_string("{\"_id\":\"1\",\"a\":1}".to_string()).unwrap()];
-        let body = build_bulk_body(BulkAction::Update, &docs).unwrap();
-        let lines: Vec<Value> = String::from_utf8(body)
+    #[tokio::test]
+    async fn batches_split_at_the_request_size_limit() {
+        let (url, requests) = cluster_answering("200 OK", r#"{"errors":false,"items":[]}"#).await;
+        let client = hosts(url);
+        let dir = tempfile::tempdir().unwrap();
+        let dead_letter = DeadLetter::open(&dir.path().join("dead.ndjson")).unwrap();
+        // Each small document takes 22 bytes of bulk body, so two fit in 50.
+        let config = ElasticsearchOutputConfig::try_new(10, 1)
             .unwrap()
-            .lines()
-            .map(|line| serde_json::from_str(line).unwrap())
-            .collect();
-        assert_eq!(lines[0]["update"]["_id"], "1");
-        assert_eq!(lines[1], json!({ "doc": { "a": 1 } }));
+            .with_max_request_bytes(50)
+            .with_dead_letter(Some(dead_letter.clone()));
+        let big = format!("{{\"big\":\"{}\"}}", "x".repeat(50));
+        let (sender, receiver) = mpsc::channel(10);
+        for doc in [r#"{"a":1}"#, r#"{"a":2}"#, &big, r#"{"a":3}"#] {
+            let doc = Document::unvalidated(doc).unwrap();
+            sender.send(WorkerMessage::Document(doc)).await.unwrap();
+        }
+        drop(sender);
+
+        run_bulk_worker(worker(&client, "test", config), receiver)
+            .await
+            .unwrap();
+
+        assert_eq!(requests.load(Ordering::Relaxed), 2);
+        assert_eq!(dead_letter.written(), 1);
     }
 
     #[test]
-    fn extract_update_id_requires_id() {
-        let doc = RawValue::from_string("{\"message\":\"hello\"}".to_string()).unwrap();
-        let err = extract_update_id(&doc).err().expect("expected error");
-        assert!(err.to_string().contains("_id"));
+    fn bulk_entry_bytes_match_the_bulk_body() {
+        let docs = [Document::unvalidated(r#"{"_id":"1","a":1}"#).unwrap()];
+        for action in [BulkAction::Create, BulkAction::Index, BulkAction::Update] {
+            let (body, _) = build_bulk_body(action, &BulkMetadata::default(), &docs).unwrap();
+            assert!(
+                bulk_entry_bytes(action, &BulkMetadata::default(), &docs[0]) >= body.len() as u64
+            );
+        }
+        let (create, _) =
+            build_bulk_body(BulkAction::Create, &BulkMetadata::default(), &docs).unwrap();
+        assert_eq!(
+            bulk_entry_bytes(BulkAction::Create, &BulkMetadata::default(), &docs[0]),
+            create.len() as u64
+        );
     }
 
-    #[test]
-    fn default_worker_limits_are_bounded() {
-        let config = ElasticsearchOutputConfig::default();
-        assert_eq!(config.batch_size, DEFAULT_BATCH_SIZE);
-        assert_eq!(config.channel_capacity(), DEFAULT_BATCH_SIZE);
-        assert_eq!(config.max_inflight_requests, DEFAULT_MAX_INFLIGHT_REQUESTS);
+    #[tokio::test]
+    async fn batches_past_their_deadline_fail_without_a_dead_letter() {
+        let url = unresponsive_cluster().await;
+        let client = hosts(url);
+        let config = ElasticsearchOutputConfig::try_new(10, 1)
+            .unwrap()
+            .with_timeouts(None, Some(Duration::from_millis(100)));
+        let mut batch = vec![Document::unvalidated("{\"a\":1}").unwrap()];
+        let mut inflight = FuturesUnordered::new();
+
+        spawn_flush(
+            &mut inflight,
+            &worker(&client, "test", config.clone()),
+            &mut batch,
+        )
+        .unwrap();
+        let err = inflight.next().await.unwrap().unwrap().unwrap_err();
+
+        assert!(err.to_string().contains("did not complete within"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn abandoned_batches_are_dead_lettered_or_counted_as_failed() {
+        let url = unresponsive_cluster().await;
+        let client = hosts(url);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dead.ndjson");
+        let dead_letter = DeadLetter::open(&path).unwrap();
+
+        for (dead_letter, kept) in [(Some(dead_letter.clone()), true), (None, false)] {
+            let config = ElasticsearchOutputConfig::default().with_dead_letter(dead_letter);
+            let closing = Arc::new(Closing::default());
+            let mut batch = vec![Document::unvalidated("{\"a\":1}").unwrap()];
+            let mut inflight = FuturesUnordered::new();
+            spawn_flush(
+                &mut inflight,
+                &BulkWorker {
+                    closing: Arc::clone(&closing),
+                    ..worker(&client, "test", config.clone())
+                },
+                &mut batch,
+            )
+            .unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            closing.abandon();
+
+            assert_eq!(inflight.next().await.unwrap().unwrap().unwrap(), 0);
+            let (dead_lettered, dropped) = match kept {
+                true => (1, 0),
+                false => (0, 1),
+            };
+            assert_eq!(closing.dead_lettered.load(Ordering::Relaxed), dead_lettered);
+            assert_eq!(closing.dropped.load(Ordering::Relaxed), dropped);
+            assert_eq!(config.failed_docs().count(), dropped);
+            if !kept {
+                assert_eq!(config.failed_docs().worst(), Some(FailureClass::Shutdown));
+            }
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"a\":1}\n");
     }
 
     #[test]
@@ -1229,4 +3458,33 @@ template:
         let _ = fs::remove_file(pipeline_path);
         let _ = fs::remove_file(template_path);
     }
+
+    #[test]
+    fn prepared_preflight_finds_default_pipeline_in_composed_component_template() {
+        let pipeline_path = temp_json_path("geoip");
+        let component_path = temp_json_path("logs-settings");
+        let template_path = temp_json_path("template-logs");
+        fs::write(&pipeline_path, r#"{"processors":[]}"#).unwrap();
+        fs::write(
+            &component_path,
+            r#"{"template":{"settings":{"index.default_pipeline":"geoip"}}}"#,
+        )
+        .unwrap();
+        fs::write(&template_path, r#"{"composed_of":["logs-settings"]}"#).unwrap();
+
+        let preflight = PreparedPreflight::try_from(OutputPreflightConfig {
+            pipeline: Some(pipeline_path.clone()),
+            template: Some(template_path.clone()),
+            component_templates: vec![component_path.clone()],
+            ..OutputPreflightConfig::default()
+        })
+        .unwrap();
+
+        assert_eq!(preflight.component_templates[0].name, "logs-settings");
+        assert_eq!(preflight.template_pipeline.as_deref(), Some("geoip"));
+
+        let _ = fs::remove_file(pipeline_path);
+        let _ = fs::remove_file(component_path);
+        let _ = fs::remove_file(template_path);
+    }
 }
@@ -1,10 +1,10 @@
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 #[derive(Deserialize)]
 pub struct BulkResponse {
     error: Option<ErrorType>,
-    //took: u64,
+    took: Option<u64>,
     errors: Option<bool>,
     items: Option<Vec<BulkAction>>,
 }
@@ -26,6 +26,11 @@ impl std::fmt::Display for ErrorType {
 }
 
 impl BulkResponse {
+    /// How long Elasticsearch spent on the request, from its `took`.
+    pub fn took(&self) -> Option<Duration> {
+        self.took.map(Duration::from_millis)
+    }
+
     pub fn error_cause(&self) -> String {
         match &self.error {
             Some(cause) => format!("{cause}"),
@@ -57,12 +62,69 @@ impl BulkResponse {
         }
     }
 
-    pub fn success_count(&self) -> usize {
+    /// Items that failed for any reason.
+    pub fn error_count(&self) -> usize {
         match &self.items {
-            Some(items) => items.iter().filter(|item| item.is_success()).count(),
+            Some(items) => items.iter().filter(|item| !item.is_success()).count(),
             None => 0,
         }
     }
+
+    /// Positions of the `create` items rejected because a document with their
+    /// `_id` exists.
+    pub fn conflict_positions(&self) -> Vec<usize> {
+        self.items
+            .iter()
+            .flatten()
+            .enumerate()
+            .filter(
+                |(_, item)| matches!(item, BulkAction::Create { create } if create.status == 409),
+            )
+            .map(|(position, _)| position)
+            .collect()
+    }
+
+    /// The first index a `require_alias` request named that is not an alias.
+    pub fn missing_alias(&self) -> Option<&str> {
+        self.items
+            .iter()
+            .flatten()
+            .map(BulkAction::item)
+            .find(|item| {
+                item.error
+                    .as_ref()
+                    .is_some_and(|error| error.r#type == "index_not_found_exception")
+            })
+            .map(|item| item._index.as_str())
+    }
+
+    /// The `_index`, `_id`, and ingest pipelines run for each item, from a
+    /// request with `list_executed_pipelines`.
+    pub fn executed_pipelines(&self) -> impl Iterator<Item = (&str, Option<&str>, &[String])> {
+        self.items
+            .iter()
+            .flatten()
+            .map(BulkAction::item)
+            .filter(|item| !item.executed_pipelines.is_empty())
+            .map(|item| {
+                (
+                    item._index.as_str(),
+                    item._id.as_deref(),
+                    item.executed_pipelines.as_slice(),
+                )
+            })
+    }
+
+    /// Positions of the items Elasticsearch confirmed, in request order.
+    pub fn success_positions(&self) -> Vec<usize> {
+        self.items
+            .iter()
+            .flatten()
+            .enumerate()
+            .filter(|(_, item)| item.is_success())
+            .map(|(position, _)| position)
+            .collect()
+    }
 }
 
 #[derive(Deserialize)]
@@ -82,13 +144,23 @@ impl std::fmt::Display for ErrorCause {
 enum BulkAction {
     Create { create: BulkResponseItem },
     Index { index: BulkResponseItem },
+    Update { update: BulkResponseItem },
 }
 
 impl BulkAction {
+    fn item(&self) -> &BulkResponseItem {
+        match self {
+            BulkAction::Create { create } => create,
+            BulkAction::Index { index } => index,
+            BulkAction::Update { update } => update,
+        }
+    }
+
     fn is_success(&self) -> bool {
         match self {
             BulkAction::Create { create } => create.status == 201,
             BulkAction::Index { index } => index.status == 200 || index.status == 201,
+            BulkAction::Update { update } => update.status == 200 || update.status == 201,
         }
     }
 
@@ -96,6 +168,7 @@ impl BulkAction {
         match self {
             BulkAction::Create { create } => create.error.as_ref().map(|e| e.to_string()),
             BulkAction::Index { index } => index.error.as_ref().map(|e| e.to_string()),
+            BulkAction::Update { update } => update.error.as_ref().map(|e| e.to_string()),
         }
     }
 
@@ -103,6 +176,7 @@ impl BulkAction {
         match self {
             BulkAction::Create { create } => create._index.clone(),
             BulkAction::Index { index } => index._index.clone(),
+            BulkAction::Update { update } => update._index.clone(),
         }
     }
 
@@ -114,16 +188,22 @@ impl BulkAction {
 #[derive(Deserialize)]
 struct BulkResponseItem {
     _index: String,
-    _id: String,
+    _id: Option<String>,
     status: u16,
     error: Option<ResponseError>,
+    #[serde(default)]
+    executed_pipelines: Vec<String>,
 }
 
+/// Version conflicts and other item errors without an underlying cause only
+/// have their own type and reason.
 #[derive(Deserialize)]
 struct ResponseError {
-    //r#type: String,
-    //reason: String,
-    caused_by: CausedBy,
+    #[serde(default)]
+    r#type: String,
+    #[serde(default)]
+    reason: String,
+    caused_by: Option<CausedBy>,
 }
 
 #[derive(Deserialize)]
@@ -134,7 +214,10 @@ struct CausedBy {
 
 impl std::fmt::Display for ResponseError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{} - {}", self.caused_by.r#type, self.caused_by.reason)
+        match &self.caused_by {
+            Some(cause) => write!(f, "{} - {}", cause.r#type, cause.reason),
+            None => write!(f, "{} - {}", self.r#type, self.reason),
+        }
     }
 }
 
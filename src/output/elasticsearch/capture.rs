@@ -0,0 +1,119 @@
+use eyre::{Result, eyre};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+/// Writes the exact body and raw response of bulk requests to a directory, so
+/// a cluster-side failure can be replayed with curl or attached to a support
+/// ticket.
+///
+/// Only failed attempts are captured, unless trace logging is on, when every
+/// attempt is. Each one leaves three files named after its sequence number:
+/// the `.request.ndjson` body, the `.response.json` body, and a `.curl`
+/// script that sends the body again to `$ES_URL`.
+#[derive(Clone, Debug)]
+pub struct BulkCapture {
+    dir: PathBuf,
+    captured: Arc<AtomicU64>,
+}
+
+impl BulkCapture {
+    pub fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir).map_err(|err| {
+            eyre!(
+                "failed to create capture directory {}: {err}",
+                dir.display()
+            )
+        })?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            captured: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// Whether an attempt with this outcome is captured.
+    pub fn wants(&self, failed: bool) -> bool {
+        failed || log::log_enabled!(log::Level::Trace)
+    }
+
+    /// Writes one attempt, `status` being the HTTP status line it got.
+    pub fn write(
+        &self,
+        path_and_query: &str,
+        body: &[u8],
+        status: &str,
+        response: &[u8],
+    ) -> Result<()> {
+        let number = self.captured.fetch_add(1, Ordering::Relaxed) + 1;
+        let name = format!("bulk-{number:06}");
+        let request = format!("{name}.request.ndjson");
+        fs::write(self.dir.join(&request), body)?;
+        fs::write(self.dir.join(format!("{name}.response.json")), response)?;
+        let script = format!(
+            "# HTTP {status}\n\
+             curl -X POST \"$ES_URL{path_and_query}\" \\\n  \
+             -H 'Content-Type: application/x-ndjson' \\\n  \
+             --data-binary @{request}\n"
+        );
+        let script_path = self.dir.join(format!("{name}.curl"));
+        fs::write(&script_path, script)?;
+        log::info!(
+            "Captured bulk request with HTTP {status} to {}",
+            script_path.display()
+        );
+        Ok(())
+    }
+
+    /// Attempts captured by this run.
+    pub fn captured(&self) -> u64 {
+        self.captured.load(Ordering::Relaxed)
+    }
+}
+
+impl std::fmt::Display for BulkCapture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.dir.display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BulkCapture;
+    use std::fs;
+
+    #[test]
+    fn captures_hold_the_request_the_response_and_a_curl_script() {
+        let dir = tempfile::tempdir().unwrap();
+        let capture = BulkCapture::open(&dir.path().join("capture")).unwrap();
+
+        capture
+            .write(
+                "/logs/_bulk?pipeline=geoip",
+                b"{\"create\":{}}\n{\"a\":1}\n",
+                "400 Bad Request",
+                b"{\"error\":{\"type\":\"parse_exception\"}}",
+            )
+            .unwrap();
+
+        let dir = dir.path().join("capture");
+        assert_eq!(capture.captured(), 1);
+        assert_eq!(
+            fs::read_to_string(dir.join("bulk-000001.request.ndjson")).unwrap(),
+            "{\"create\":{}}\n{\"a\":1}\n"
+        );
+        assert!(
+            fs::read_to_string(dir.join("bulk-000001.response.json"))
+                .unwrap()
+                .contains("parse_exception")
+        );
+        let script = fs::read_to_string(dir.join("bulk-000001.curl")).unwrap();
+        assert!(script.starts_with("# HTTP 400 Bad Request\n"));
+        assert!(script.contains("\"$ES_URL/logs/_bulk?pipeline=geoip\""));
+        assert!(script.contains("--data-binary @bulk-000001.request.ndjson"));
+    }
+}
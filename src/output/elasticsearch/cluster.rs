@@ -0,0 +1,207 @@
+use elasticsearch::{
+    Elasticsearch,
+    http::{Method, headers::HeaderMap},
+};
+use eyre::{Result, eyre};
+use serde_json::Value;
+
+/// The cluster behind an Elasticsearch output, as reported by `GET /`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClusterVersion {
+    number: String,
+    major: u32,
+    minor: u32,
+    flavor: Flavor,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Flavor {
+    Default,
+    Serverless,
+    OpenSearch,
+}
+
+/// Features a run needs from the cluster, checked before anything is installed or sent.
+#[derive(Debug, Default)]
+pub struct Features {
+    pub composable_templates: bool,
+    pub data_stream_template: bool,
+    pub ilm_policy: bool,
+    /// Writing to the `logs` stream or one of its `logs.*` children.
+    pub streams: bool,
+}
+
+impl ClusterVersion {
+    pub async fn fetch(client: &Elasticsearch) -> Result<Self> {
+        let response = client
+            .send(
+                Method::Get,
+                "/",
+                HeaderMap::new(),
+                Option::<&()>::None,
+                Option::<Vec<u8>>::None,
+                None,
+            )
+            .await?;
+        let status = response.status_code();
+        if !status.is_success() {
+            return Err(eyre!("GET / failed with status {status}"));
+        }
+        Self::parse(&response.json::<Value>().await?)
+    }
+
+    fn parse(info: &Value) -> Result<Self> {
+        let version = &info["version"];
+        let number = version["number"]
+            .as_str()
+            .ok_or_else(|| eyre!("GET / response has no version.number"))?;
+        let mut parts = number
+            .split(['.', '-'])
+            .map(|part| part.parse::<u32>().ok());
+        let (Some(Some(major)), Some(Some(minor))) = (parts.next(), parts.next()) else {
+            return Err(eyre!("unrecognized version number '{number}'"));
+        };
+        let flavor = match (
+            version["distribution"].as_str(),
+            version["build_flavor"].as_str(),
+        ) {
+            (Some("opensearch"), _) => Flavor::OpenSearch,
+            (_, Some("serverless")) => Flavor::Serverless,
+            _ => Flavor::Default,
+        };
+        Ok(Self {
+            number: number.to_string(),
+            major,
+            minor,
+            flavor,
+        })
+    }
+
+    fn at_least(&self, major: u32, minor: u32) -> bool {
+        (self.major, self.minor) >= (major, minor)
+    }
+
+    /// Fails with the first requested feature this cluster cannot provide.
+    pub fn check(&self, features: &Features) -> Result<()> {
+        let unsupported = |feature: &str, needs: &str| {
+            Err(eyre!(
+                "{self} does not support {feature}, which needs {needs}"
+            ))
+        };
+        match self.flavor {
+            Flavor::OpenSearch if features.ilm_policy => {
+                unsupported("--ilm-policy", "Elasticsearch index lifecycle management")
+            }
+            Flavor::OpenSearch => Ok(()),
+            Flavor::Serverless if features.ilm_policy => unsupported(
+                "--ilm-policy",
+                "a self-managed or hosted Elasticsearch deployment",
+            ),
+            Flavor::Serverless => Ok(()),
+            Flavor::Default if !self.at_least(7, 0) => unsupported(
+                "bulk requests without mapping types",
+                "Elasticsearch 7.0 or later",
+            ),
+            Flavor::Default if features.composable_templates && !self.at_least(7, 8) => {
+                unsupported(
+                    "composable index and component templates",
+                    "Elasticsearch 7.8 or later",
+                )
+            }
+            Flavor::Default if features.data_stream_template && !self.at_least(7, 9) => {
+                unsupported("data stream templates", "Elasticsearch 7.9 or later")
+            }
+            Flavor::Default if features.streams && !self.at_least(9, 1) => {
+                unsupported("the logs stream", "Elasticsearch 9.1 or later")
+            }
+            Flavor::Default => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Display for ClusterVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.flavor {
+            Flavor::Default => write!(f, "Elasticsearch {}", self.number),
+            Flavor::Serverless => write!(f, "Elasticsearch Serverless"),
+            Flavor::OpenSearch => write!(f, "OpenSearch {}", self.number),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClusterVersion, Features};
+    use serde_json::json;
+
+    fn version(version: serde_json::Value) -> ClusterVersion {
+        ClusterVersion::parse(&json!({ "version": version })).unwrap()
+    }
+
+    #[test]
+    fn versions_parse_from_the_root_endpoint() {
+        assert_eq!(
+            version(json!({"number": "8.15.0-SNAPSHOT"})).to_string(),
+            "Elasticsearch 8.15.0-SNAPSHOT"
+        );
+        assert_eq!(
+            version(json!({"number": "8.11.0", "build_flavor": "serverless"})).to_string(),
+            "Elasticsearch Serverless"
+        );
+        assert_eq!(
+            version(json!({"number": "2.11.0", "distribution": "opensearch"})).to_string(),
+            "OpenSearch 2.11.0"
+        );
+        assert!(ClusterVersion::parse(&json!({"acknowledged": true})).is_err());
+    }
+
+    #[test]
+    fn features_are_checked_against_the_version() {
+        let templates = Features {
+            composable_templates: true,
+            ..Features::default()
+        };
+        let streams = Features {
+            streams: true,
+            ..Features::default()
+        };
+        let ilm = Features {
+            ilm_policy: true,
+            ..Features::default()
+        };
+
+        assert_eq!(
+            version(json!({"number": "6.8.23"}))
+                .check(&Features::default())
+                .unwrap_err()
+                .to_string(),
+            "Elasticsearch 6.8.23 does not support bulk requests without mapping types, which needs Elasticsearch 7.0 or later"
+        );
+        assert!(
+            version(json!({"number": "7.7.1"}))
+                .check(&templates)
+                .is_err()
+        );
+        assert!(
+            version(json!({"number": "7.17.0"}))
+                .check(&templates)
+                .is_ok()
+        );
+        assert!(
+            version(json!({"number": "8.19.0"}))
+                .check(&streams)
+                .is_err()
+        );
+        assert!(version(json!({"number": "9.1.0"})).check(&streams).is_ok());
+        assert!(
+            version(json!({"number": "8.11.0", "build_flavor": "serverless"}))
+                .check(&ilm)
+                .is_err()
+        );
+        assert!(
+            version(json!({"number": "2.11.0", "distribution": "opensearch"}))
+                .check(&templates)
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use elasticsearch::{
+    Elasticsearch,
+    http::{Method, StatusCode, headers::HeaderMap},
+};
+use eyre::{Result, eyre};
+use jiff::Timestamp;
+use serde_json::{Map, Number, Value};
+use std::collections::{HashMap, HashSet};
+
+/// Epoch values below this are taken as seconds rather than milliseconds; in
+/// milliseconds it is early 1973.
+const EPOCH_SECONDS_LIMIT: f64 = 1e11;
+
+/// The field types of the target index for `--coerce-to-mapping`, which turns
+/// values Elasticsearch would reject into the mapped type and warns once per
+/// field about fields the mapping does not have.
+#[derive(Debug)]
+pub struct Mapping {
+    index: String,
+    fields: HashMap<String, FieldType>,
+    /// How each object, by path with `""` for the root, handles unmapped fields.
+    dynamic: HashMap<String, Dynamic>,
+    warned: HashSet<String>,
+    coerced: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FieldType {
+    Numeric,
+    /// A date whose `format` does not accept epoch numbers.
+    Date,
+    Object,
+    Other,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Dynamic {
+    Mapped,
+    Ignored,
+    Rejected,
+}
+
+impl Mapping {
+    /// The mapping of an existing index, or else the one its index templates
+    /// would give it, or `None` when neither has any fields.
+    pub async fn fetch(client: &Elasticsearch, index: &str) -> Result<Option<Self>> {
+        let (status, body) = get(client, Method::Get, &format!("/{index}/_mapping")).await?;
+        let mappings = match status {
+            status if status.is_success() => {
+                // Aliases and data streams answer with every backing index; the
+                // last by name is the newest.
+                body.as_object()
+                    .and_then(|indices| indices.iter().max_by_key(|(name, _)| *name))
+                    .map(|(_, index)| index["mappings"].clone())
+            }
+            StatusCode::NOT_FOUND => {
+                let path = format!("/_index_template/_simulate_index/{index}");
+                match get(client, Method::Post, &path).await? {
+                    (status, body) if status.is_success() => {
+                        Some(body["template"]["mappings"].clone())
+                    }
+                    _ => None,
+                }
+            }
+            status => return Err(eyre!("GET /{index}/_mapping failed with status {status}")),
+        };
+        Ok(mappings
+            .map(|mappings| Self::parse(index, &mappings))
+            .filter(|mapping| !mapping.fields.is_empty()))
+    }
+
+    fn parse(index: &str, mappings: &Value) -> Self {
+        let mut mapping = Self {
+            index: index.to_string(),
+            fields: HashMap::new(),
+            dynamic: HashMap::new(),
+            warned: HashSet::new(),
+            coerced: 0,
+        };
+        mapping.add_object("", mappings, Dynamic::Mapped);
+        mapping
+    }
+
+    fn add_object(&mut self, path: &str, object: &Value, inherited: Dynamic) {
+        let dynamic = match &object["dynamic"] {
+            Value::Bool(false) => Dynamic::Ignored,
+            Value::String(dynamic) if dynamic == "false" => Dynamic::Ignored,
+            Value::String(dynamic) if dynamic == "strict" => Dynamic::Rejected,
+            Value::Bool(true) | Value::String(_) => Dynamic::Mapped,
+            _ => inherited,
+        };
+        self.dynamic.insert(path.to_string(), dynamic);
+        let Some(properties) = object["properties"].as_object() else {
+            return;
+        };
+        for (name, field) in properties {
+            let field_path = match path {
+                "" => name.clone(),
+                path => format!("{path}.{name}"),
+            };
+            let field_type = match field["type"].as_str() {
+                None | Some("object" | "nested") if field["enabled"] != false => {
+                    self.add_object(&field_path, field, dynamic);
+                    FieldType::Object
+                }
+                Some(
+                    "long" | "integer" | "short" | "byte" | "double" | "float" | "half_float"
+                    | "scaled_float" | "unsigned_long",
+                ) => FieldType::Numeric,
+                Some("date" | "date_nanos")
+                    if !field["format"]
+                        .as_str()
+                        .is_some_and(|format| format.contains("epoch")) =>
+                {
+                    FieldType::Date
+                }
+                _ => FieldType::Other,
+            };
+            self.fields.insert(field_path, field_type);
+        }
+    }
+
+    /// Coerces the values of a document source in place, returning whether any
+    /// changed.
+    pub fn coerce(&mut self, source: &mut Value) -> bool {
+        let Some(object) = source.as_object_mut() else {
+            return false;
+        };
+        let before = self.coerced;
+        self.coerce_object("", object);
+        self.coerced > before
+    }
+
+    fn coerce_object(&mut self, path: &str, object: &mut Map<String, Value>) {
+        for (name, value) in object.iter_mut() {
+            let field_path = match path {
+                "" => name.clone(),
+                path => format!("{path}.{name}"),
+            };
+            match self.fields.get(&field_path) {
+                Some(&field_type) => self.coerce_value(&field_path, field_type, value),
+                None => self.warn_unmapped(path, field_path),
+            }
+        }
+    }
+
+    fn coerce_value(&mut self, path: &str, field_type: FieldType, value: &mut Value) {
+        match (field_type, value) {
+            (_, Value::Array(values)) => {
+                for value in values {
+                    self.coerce_value(path, field_type, value);
+                }
+            }
+            (FieldType::Object, Value::Object(object)) => self.coerce_object(path, object),
+            (FieldType::Numeric, value @ Value::String(_)) => {
+                if let Some(number) = value
+                    .as_str()
+                    .and_then(|text| text.trim().parse::<Number>().ok())
+                {
+                    *value = Value::Number(number);
+                    self.coerced += 1;
+                }
+            }
+            (FieldType::Date, value @ Value::Number(_)) => {
+                if let Some(date) = value.as_f64().and_then(epoch_to_date) {
+                    *value = Value::String(date);
+                    self.coerced += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn warn_unmapped(&mut self, parent: &str, path: String) {
+        if self.warned.contains(&path) {
+            return;
+        }
+        let outcome = match self.dynamic.get(parent) {
+            Some(Dynamic::Ignored) => "will be stored but not indexed",
+            Some(Dynamic::Rejected) => "will be rejected by its strict mapping",
+            _ => "will be mapped dynamically",
+        };
+        log::warn!(
+            "Field {path} is not in the mapping of {} and {outcome}",
+            self.index
+        );
+        self.warned.insert(path);
+    }
+
+    pub fn coerced(&self) -> usize {
+        self.coerced
+    }
+}
+
+/// An epoch in seconds or milliseconds as an RFC 3339 timestamp.
+fn epoch_to_date(epoch: f64) -> Option<String> {
+    let millis = match epoch.abs() < EPOCH_SECONDS_LIMIT {
+        true => epoch * 1000.0,
+        false => epoch,
+    };
+    Timestamp::from_millisecond(millis as i64)
+        .ok()
+        .map(|timestamp| timestamp.to_string())
+}
+
+async fn get(client: &Elasticsearch, method: Method, path: &str) -> Result<(StatusCode, Value)> {
+    let response = client
+        .send(
+            method,
+            path,
+            HeaderMap::new(),
+            Option::<&()>::None,
+            Option::<Vec<u8>>::None,
+            None,
+        )
+        .await?;
+    let status = response.status_code();
+    let body = match status.is_success() {
+        true => response.json::<Value>().await?,
+        false => Value::Null,
+    };
+    Ok((status, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dynamic, Mapping};
+    use serde_json::json;
+
+    #[test]
+    fn mismatched_values_are_coerced_to_the_mapping() {
+        let mut mapping = Mapping::parse(
+            "orders",
+            &json!({ "properties": {
+                "total": { "type": "scaled_float", "scaling_factor": 100 },
+                "placed": { "type": "date" },
+                "shipped": { "type": "date", "format": "epoch_second" },
+                "customer": { "properties": { "id": { "type": "long" } } },
+                "tags": { "type": "keyword" },
+            } }),
+        );
+        let mut doc = json!({
+            "total": "12.5",
+            "placed": 1_700_000_000,
+            "shipped": 1_700_000_000,
+            "customer": { "id": ["7", "x"] },
+            "tags": 5,
+        });
+        assert!(mapping.coerce(&mut doc));
+        assert_eq!(
+            doc,
+            json!({
+                "total": 12.5,
+                "placed": "2023-11-14T22:13:20Z",
+                "shipped": 1_700_000_000,
+                "customer": { "id": [7, "x"] },
+                "tags": 5,
+            })
+        );
+        assert_eq!(mapping.coerced(), 3);
+        assert!(!mapping.coerce(&mut json!({ "total": 1 })));
+    }
+
+    #[test]
+    fn unmapped_fields_are_warned_about_once() {
+        let mut mapping = Mapping::parse(
+            "logs",
+            &json!({ "dynamic": "strict", "properties": {
+                "labels": { "type": "object", "dynamic": true },
+            } }),
+        );
+        mapping.coerce(&mut json!({ "extra": 1, "labels": { "team": "a" } }));
+        mapping.coerce(&mut json!({ "extra": 2 }));
+        let mut warned: Vec<_> = mapping.warned.iter().map(String::as_str).collect();
+        warned.sort();
+        assert_eq!(warned, vec!["extra", "labels.team"]);
+        assert_eq!(mapping.dynamic[""], Dynamic::Rejected);
+        assert_eq!(mapping.dynamic["labels"], Dynamic::Mapped);
+    }
+}
@@ -0,0 +1,239 @@
+use elasticsearch::{
+    Elasticsearch,
+    http::{
+        Method, StatusCode,
+        headers::{HeaderMap, HeaderValue},
+    },
+};
+use eyre::{Result, eyre};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The settings `--turbo` changes for the load, as they were before it.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+struct Original {
+    /// `None` when the index had no explicit interval and used the default.
+    refresh_interval: Option<String>,
+    number_of_replicas: Option<String>,
+}
+
+/// Turns off refreshes and replicas on the indices behind the output's index
+/// for the load, and puts them back on close.
+///
+/// The original settings are saved to a state file before anything changes,
+/// and removed only once they are restored, so the next `--turbo` run to the
+/// same index restores them after a crash instead of snapshotting the
+/// settings the crashed run left behind.
+#[derive(Debug)]
+pub struct Turbo {
+    state: PathBuf,
+    indices: BTreeMap<String, Original>,
+}
+
+impl Turbo {
+    /// Snapshots and changes the settings of `index`, or returns `None` when it
+    /// does not exist yet and there is nothing to change.
+    pub async fn start(
+        client: &Elasticsearch,
+        state_dir: &Path,
+        hostname: &str,
+        index: &str,
+    ) -> Result<Option<Self>> {
+        if index.is_empty() {
+            return Err(eyre!(
+                "--turbo needs the index in the output URL, not --index-from-doc"
+            ));
+        }
+        let state = state_dir.join(state_file_name(hostname, index));
+        let indices = match fs::read_to_string(&state) {
+            Ok(saved) => {
+                log::warn!(
+                    "A previous --turbo run to {index} did not restore its settings, restoring them from {} on close",
+                    state.display()
+                );
+                serde_json::from_str(&saved).map_err(|err| {
+                    eyre!("failed to parse --turbo state {}: {err}", state.display())
+                })?
+            }
+            Err(_) => match snapshot(client, index).await? {
+                Some(indices) => {
+                    fs::create_dir_all(state_dir)?;
+                    fs::write(&state, serde_json::to_vec_pretty(&indices)?)?;
+                    indices
+                }
+                None => {
+                    log::warn!(
+                        "{index} does not exist yet, --turbo leaves its settings as they are"
+                    );
+                    return Ok(None);
+                }
+            },
+        };
+        let turbo = Self { state, indices };
+        for name in turbo.indices.keys() {
+            put_settings(
+                client,
+                name,
+                json!({ "refresh_interval": "-1", "number_of_replicas": 0 }),
+            )
+            .await?;
+        }
+        log::info!(
+            "Turned off refreshes and replicas on {} for the load",
+            turbo.indices.keys().cloned().collect::<Vec<_>>().join(", ")
+        );
+        Ok(Some(turbo))
+    }
+
+    /// Puts back the original settings and removes the state file.
+    pub async fn restore(self, client: &Elasticsearch) -> Result<()> {
+        for (name, original) in &self.indices {
+            put_settings(
+                client,
+                name,
+                json!({
+                    "refresh_interval": original.refresh_interval,
+                    "number_of_replicas": original.number_of_replicas,
+                }),
+            )
+            .await
+            .map_err(|err| {
+                eyre!(
+                    "{err}; the next --turbo run restores the settings from {}",
+                    self.state.display()
+                )
+            })?;
+        }
+        fs::remove_file(&self.state)?;
+        log::info!(
+            "Restored the refresh interval and replicas of {}",
+            self.indices.keys().cloned().collect::<Vec<_>>().join(", ")
+        );
+        Ok(())
+    }
+}
+
+/// The original settings of every concrete index behind `index`, which may be
+/// an alias or data stream, or `None` when it does not exist.
+async fn snapshot(
+    client: &Elasticsearch,
+    index: &str,
+) -> Result<Option<BTreeMap<String, Original>>> {
+    let path = format!("/{index}/_settings/index.refresh_interval,index.number_of_replicas");
+    let response = client
+        .send(
+            Method::Get,
+            &path,
+            HeaderMap::new(),
+            Some(&[("flat_settings", "true")][..]),
+            Option::<Vec<u8>>::None,
+            None,
+        )
+        .await?;
+    match response.status_code() {
+        status if status.is_success() => {
+            let body = response.json::<Value>().await?;
+            Ok(Some(parse_settings(&body)))
+        }
+        StatusCode::NOT_FOUND => Ok(None),
+        status => Err(eyre!("GET {path} failed with status {status}")),
+    }
+}
+
+fn parse_settings(body: &Value) -> BTreeMap<String, Original> {
+    let setting = |settings: &Value, name: &str| settings[name].as_str().map(str::to_string);
+    body.as_object()
+        .into_iter()
+        .flatten()
+        .map(|(name, index)| {
+            let settings = &index["settings"];
+            let original = Original {
+                refresh_interval: setting(settings, "index.refresh_interval"),
+                number_of_replicas: setting(settings, "index.number_of_replicas"),
+            };
+            (name.clone(), original)
+        })
+        .collect()
+}
+
+async fn put_settings(client: &Elasticsearch, index: &str, settings: Value) -> Result<()> {
+    let mut headers = HeaderMap::new();
+    headers.insert("content-type", HeaderValue::from_static("application/json"));
+    let path = format!("/{index}/_settings");
+    let body = serde_json::to_vec(&json!({ "index": settings }))?;
+    let response = client
+        .send(
+            Method::Put,
+            &path,
+            headers,
+            Option::<&()>::None,
+            Some(body),
+            None,
+        )
+        .await?;
+    let status = response.status_code();
+    if !status.is_success() {
+        let details = response.text().await.unwrap_or_default();
+        return Err(eyre!("PUT {path} failed with status {status}: {details}"));
+    }
+    Ok(())
+}
+
+/// One state file per cluster and index, with characters that are not safe
+/// in file names, like the `*` of a pattern, replaced.
+fn state_file_name(hostname: &str, index: &str) -> String {
+    let safe = |name: &str| {
+        name.chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+                _ => '_',
+            })
+            .collect::<String>()
+    };
+    format!("{}-{}.json", safe(hostname), safe(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Original, parse_settings, state_file_name};
+    use serde_json::json;
+
+    #[test]
+    fn settings_are_snapshotted_per_backing_index() {
+        let indices = parse_settings(&json!({
+            ".ds-logs-2024.01.01-000001": { "settings": {
+                "index.number_of_replicas": "1",
+            }},
+            ".ds-logs-2024.01.02-000002": { "settings": {
+                "index.number_of_replicas": "2",
+                "index.refresh_interval": "30s",
+            }},
+        }));
+        assert_eq!(
+            indices[".ds-logs-2024.01.01-000001"],
+            Original {
+                refresh_interval: None,
+                number_of_replicas: Some("1".to_string()),
+            }
+        );
+        assert_eq!(
+            indices[".ds-logs-2024.01.02-000002"]
+                .refresh_interval
+                .as_deref(),
+            Some("30s")
+        );
+    }
+
+    #[test]
+    fn state_files_are_named_for_the_cluster_and_index() {
+        assert_eq!(
+            state_file_name("es.example.com", "logs-*"),
+            "es.example.com-logs-_.json"
+        );
+    }
+}
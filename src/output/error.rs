@@ -0,0 +1,118 @@
+use serde::Serialize;
+use std::fmt::{self, Display, Formatter};
+
+/// Why documents failed to reach Elasticsearch, which the summary, the
+/// manifest, and the exit code report so automation can branch on it.
+///
+/// Classes are ordered from the most to the least serious, and a run with
+/// failures of several classes exits with the code of the first.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureClass {
+    /// The cluster could not be reached, from DNS, TCP, TLS, or proxy errors.
+    Connection,
+    /// The cluster refused the credentials with `401` or their privileges with `403`.
+    Auth,
+    /// A document over `--max-request-bytes`, or a request the cluster refused with `413`.
+    RequestTooLarge,
+    /// The cluster accepted nothing within `--batch-timeout` or `--request-timeout`.
+    Throttled,
+    /// The cluster refused a whole bulk request with another error status.
+    Rejected,
+    /// The cluster refused single documents in a bulk response.
+    Item,
+    /// Bulk requests still unanswered at `--close-timeout` were given up on.
+    Shutdown,
+}
+
+impl FailureClass {
+    /// The process exit code for a run that fails with this class.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::Connection => 3,
+            Self::Auth => 4,
+            Self::RequestTooLarge => 5,
+            Self::Throttled => 6,
+            Self::Rejected => 7,
+            Self::Item => 8,
+            Self::Shutdown => 9,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Connection => "connection",
+            Self::Auth => "auth",
+            Self::RequestTooLarge => "request_too_large",
+            Self::Throttled => "throttled",
+            Self::Rejected => "rejected",
+            Self::Item => "item",
+            Self::Shutdown => "shutdown",
+        }
+    }
+}
+
+impl Display for FailureClass {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An output error of a known class, which stops the run.
+#[derive(Debug)]
+pub struct OutputError {
+    class: FailureClass,
+    message: String,
+}
+
+impl OutputError {
+    pub fn new(class: FailureClass, message: impl Into<String>) -> Self {
+        Self {
+            class,
+            message: message.into(),
+        }
+    }
+
+    pub fn class(&self) -> FailureClass {
+        self.class
+    }
+
+    /// The class of the first `OutputError` behind `err`, if any.
+    pub fn class_of(err: &eyre::Report) -> Option<FailureClass> {
+        err.chain()
+            .find_map(|cause| cause.downcast_ref::<Self>())
+            .map(Self::class)
+    }
+}
+
+impl Display for OutputError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} ({} error)", self.message, self.class)
+    }
+}
+
+impl std::error::Error for OutputError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{FailureClass, OutputError};
+
+    #[test]
+    fn classes_are_found_behind_context() {
+        let err = eyre::Report::new(OutputError::new(FailureClass::Auth, "refused"))
+            .wrap_err("pipeline logs failed");
+        assert_eq!(OutputError::class_of(&err), Some(FailureClass::Auth));
+        assert_eq!(OutputError::class_of(&eyre::eyre!("other")), None);
+    }
+
+    #[test]
+    fn the_most_serious_class_sorts_first() {
+        let mut classes = [FailureClass::Item, FailureClass::Connection];
+        classes.sort();
+        assert_eq!(classes[0].exit_code(), 3);
+        assert_eq!(
+            serde_json::to_value(FailureClass::RequestTooLarge).unwrap(),
+            "request_too_large"
+        );
+    }
+}
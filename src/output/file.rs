@@ -1,12 +1,13 @@
 use super::Sender;
+use crate::document::Document;
 
+use async_trait::async_trait;
 use eyre::Result;
 use flate2::{Compression, write::GzEncoder};
-use serde_json::value::RawValue;
 use std::{
     fs::{File, OpenOptions},
     io::{BufWriter, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
 };
 
@@ -17,13 +18,28 @@ pub struct FileOutput {
 }
 
 #[derive(Debug)]
-enum FileWriter {
+pub(super) enum FileWriter {
     Plain(BufWriter<File>),
     Gzip(GzEncoder<BufWriter<File>>),
 }
 
 impl FileWriter {
-    fn finish(self) -> Result<()> {
+    /// Opens `path` for NDJSON, gzip-compressed for `.ndjson.gz`, truncating it
+    /// unless `append`, which starts a new gzip member after any earlier ones.
+    pub(super) fn open(path: &Path, append: bool) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(!append)
+            .append(append)
+            .write(true)
+            .open(path)?;
+        Ok(match is_gzip_ndjson_output(path) {
+            true => FileWriter::Gzip(GzEncoder::new(BufWriter::new(file), Compression::default())),
+            false => FileWriter::Plain(BufWriter::new(file)),
+        })
+    }
+
+    pub(super) fn finish(self) -> Result<()> {
         match self {
             FileWriter::Plain(mut writer) => writer.flush().map_err(Into::into),
             FileWriter::Gzip(writer) => {
@@ -50,15 +66,25 @@ impl Write for FileWriter {
     }
 }
 
+#[async_trait]
 impl Sender for FileOutput {
-    async fn send(&mut self, value: Box<RawValue>) -> Result<usize> {
+    async fn send(&mut self, value: Document) -> Result<usize> {
         let mut guard = self.writer.lock().expect("Failed to get writer lock");
+        if let Some(action) = value.action() {
+            writeln!(&mut *guard, "{action}")?;
+        }
         guard.write_all(value.get().as_bytes())?;
         writeln!(&mut *guard)?;
         Ok(1)
     }
 
-    async fn close(self) -> Result<usize> {
+    async fn flush(&mut self) -> Result<usize> {
+        let mut guard = self.writer.lock().expect("Failed to get writer lock");
+        guard.flush()?;
+        Ok(0)
+    }
+
+    async fn close(self: Box<Self>) -> Result<usize> {
         let writer = Arc::try_unwrap(self.writer)
             .map_err(|_| eyre::eyre!("File output writer is still shared"))?
             .into_inner()
@@ -78,17 +104,7 @@ impl TryFrom<PathBuf> for FileOutput {
                 path.display()
             ));
         }
-        let file = OpenOptions::new()
-            .create(true)
-            .truncate(true)
-            .write(true)
-            .open(&path)?;
-        let writer = if is_gzip_ndjson_output(&path) {
-            FileWriter::Gzip(GzEncoder::new(BufWriter::new(file), Compression::default()))
-        } else {
-            FileWriter::Plain(BufWriter::new(file))
-        };
-        let writer = Arc::new(Mutex::new(writer));
+        let writer = Arc::new(Mutex::new(FileWriter::open(&path, false)?));
         let filename = path.to_string_lossy().to_string();
         Ok(Self { writer, filename })
     }
@@ -100,13 +116,13 @@ impl std::fmt::Display for FileOutput {
     }
 }
 
-fn is_gzip_ndjson_output(path: &PathBuf) -> bool {
+fn is_gzip_ndjson_output(path: &Path) -> bool {
     path.to_string_lossy()
         .to_ascii_lowercase()
         .ends_with(".ndjson.gz")
 }
 
-fn is_unsupported_gzip_output(path: &PathBuf) -> bool {
+pub(super) fn is_unsupported_gzip_output(path: &Path) -> bool {
     let lower_path = path.to_string_lossy().to_ascii_lowercase();
     lower_path.ends_with(".gz") && !lower_path.ends_with(".ndjson.gz")
 }
@@ -137,10 +153,14 @@ mod tests {
         let mut output = FileOutput::try_from(path.clone()).unwrap();
 
         output
-            .send(RawValue::from_string("{\"a\":1}".to_string()).unwrap())
+            .send(
+                RawValue::from_string("{\"a\":1}".to_string())
+                    .unwrap()
+                    .into(),
+            )
             .await
             .unwrap();
-        output.close().await.unwrap();
+        Box::new(output).close().await.unwrap();
 
         assert_eq!(fs::read_to_string(&path).unwrap(), "{\"a\":1}\n");
         fs::remove_file(path).unwrap();
@@ -152,10 +172,14 @@ mod tests {
         let mut output = FileOutput::try_from(path.clone()).unwrap();
 
         output
-            .send(RawValue::from_string("{\"a\":1}".to_string()).unwrap())
+            .send(
+                RawValue::from_string("{\"a\":1}".to_string())
+                    .unwrap()
+                    .into(),
+            )
             .await
             .unwrap();
-        output.close().await.unwrap();
+        Box::new(output).close().await.unwrap();
 
         let file = fs::File::open(&path).unwrap();
         let mut decoder = GzDecoder::new(file);
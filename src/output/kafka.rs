@@ -0,0 +1,174 @@
+use super::Sender;
+use crate::{client::KafkaUri, document::Document, field_path};
+
+use async_trait::async_trait;
+use eyre::{Result, eyre};
+use fluent_uri::UriRef;
+use futures::{FutureExt, StreamExt, stream::FuturesUnordered};
+use rdkafka::{
+    error::{KafkaError, RDKafkaErrorCode},
+    producer::{DeliveryFuture, FutureProducer, FutureRecord, Producer},
+    util::Timeout,
+};
+use serde_json::Value;
+use std::time::Duration;
+
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Publishes each document as one message to a Kafka topic.
+///
+/// Documents count as sent once the broker acknowledges delivery, so the
+/// returned counts line up with what the Elasticsearch output reports.
+pub struct KafkaOutput {
+    producer: FutureProducer,
+    topic: String,
+    key_field: Option<String>,
+    pending: FuturesUnordered<DeliveryFuture>,
+    destination: String,
+}
+
+impl KafkaOutput {
+    pub fn try_new(uri: &UriRef<String>) -> Result<Self> {
+        let mut kafka_uri = KafkaUri::parse(uri)?;
+        let key_field = kafka_uri.take_option("key");
+        let producer: FutureProducer = kafka_uri
+            .client_config()
+            .create()
+            .map_err(|err| eyre!("Failed to create Kafka producer for {kafka_uri}: {err}"))?;
+        log::debug!("Kafka output to {kafka_uri}");
+
+        Ok(Self {
+            producer,
+            destination: kafka_uri.to_string(),
+            topic: kafka_uri.topic,
+            key_field,
+            pending: FuturesUnordered::new(),
+        })
+    }
+
+    /// Counts deliveries that have already completed without waiting on the rest.
+    fn collect_completed(&mut self) -> Result<usize> {
+        let mut delivered = 0;
+        while let Some(Some(result)) = self.pending.next().now_or_never() {
+            check_delivery(result)?;
+            delivered += 1;
+        }
+        Ok(delivered)
+    }
+
+    async fn wait_for_one(&mut self) -> Result<usize> {
+        match self.pending.next().await {
+            Some(result) => check_delivery(result).map(|_| 1),
+            None => Ok(0),
+        }
+    }
+}
+
+/// Reads the message key from a document field; strings are used without JSON quoting.
+fn message_key(field: &str, value: &Document) -> Result<Option<String>> {
+    let doc: Value = serde_json::from_str(value.get())
+        .map_err(|err| eyre!("Error parsing JSON for Kafka message key: {err}"))?;
+    Ok(field_path::lookup(&doc, field).map(|key| match key {
+        Value::String(key) => key.clone(),
+        key => key.to_string(),
+    }))
+}
+
+fn check_delivery(result: <DeliveryFuture as Future>::Output) -> Result<()> {
+    match result {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err((err, _))) => Err(eyre!("Kafka delivery failed: {err}")),
+        Err(_) => Err(eyre!("Kafka producer dropped a pending delivery")),
+    }
+}
+
+#[async_trait]
+impl Sender for KafkaOutput {
+    async fn send(&mut self, value: Document) -> Result<usize> {
+        let key = match &self.key_field {
+            Some(field) => message_key(field, &value)?,
+            None => None,
+        };
+        let mut delivered = self.collect_completed()?;
+        loop {
+            let mut record = FutureRecord::to(&self.topic).payload(value.get());
+            if let Some(key) = &key {
+                record = record.key(key.as_str());
+            }
+            match self.producer.send_result(record) {
+                Ok(delivery) => {
+                    self.pending.push(delivery);
+                    return Ok(delivered);
+                }
+                // The local queue is full; wait for the broker to catch up and retry.
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), _))
+                    if !self.pending.is_empty() =>
+                {
+                    delivered += self.wait_for_one().await?;
+                }
+                Err((err, _)) => {
+                    return Err(eyre!("Failed to queue Kafka message: {err}"));
+                }
+            }
+        }
+    }
+
+    async fn flush(&mut self) -> Result<usize> {
+        let mut delivered = 0;
+        while !self.pending.is_empty() {
+            delivered += self.wait_for_one().await?;
+        }
+        Ok(delivered)
+    }
+
+    async fn close(mut self: Box<Self>) -> Result<usize> {
+        let delivered = self.flush().await?;
+        self.producer
+            .flush(Timeout::After(CLOSE_TIMEOUT))
+            .map_err(|err| eyre!("Failed to flush Kafka producer for {}: {err}", self))?;
+        Ok(delivered)
+    }
+}
+
+impl std::fmt::Debug for KafkaOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KafkaOutput")
+            .field("destination", &self.destination)
+            .field("key_field", &self.key_field)
+            .field("pending", &self.pending.len())
+            .finish()
+    }
+}
+
+impl std::fmt::Display for KafkaOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.destination)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::message_key;
+    use crate::document::Document;
+    use serde_json::value::RawValue;
+
+    fn raw(json: &str) -> Document {
+        RawValue::from_string(json.to_string()).unwrap().into()
+    }
+
+    #[test]
+    fn message_key_uses_string_fields_unquoted() {
+        let doc = raw(r#"{"user":{"id":"u-1"},"n":7}"#);
+
+        assert_eq!(
+            message_key("user.id", &doc).unwrap().as_deref(),
+            Some("u-1")
+        );
+        assert_eq!(message_key("n", &doc).unwrap().as_deref(), Some("7"));
+    }
+
+    #[test]
+    fn message_key_is_absent_for_missing_fields() {
+        assert_eq!(message_key("user.id", &raw(r#"{"n":7}"#)).unwrap(), None);
+    }
+}
@@ -0,0 +1,232 @@
+use super::Sender;
+use crate::document::Document;
+use async_trait::async_trait;
+use eyre::{Result, eyre};
+use flate2::{Compression, write::ZlibEncoder};
+use fluent_uri::UriRef;
+use std::{io::Write, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+use url::Url;
+
+/// The port of the Logstash beats input.
+const DEFAULT_PORT: u16 = 5044;
+/// Zlib level for window frames, as the Beats default.
+const DEFAULT_COMPRESSION: u32 = 3;
+/// How long to wait for Logstash to acknowledge a window. Logstash sends
+/// keepalive ACKs while it works through a large one, which restart the wait.
+const ACK_TIMEOUT: Duration = Duration::from_secs(60);
+const VERSION: u8 = b'2';
+
+/// Sends documents to a Logstash beats input, or anything else speaking the
+/// Beats lumberjack v2 protocol, as `lumberjack://host:5044`.
+///
+/// Each batch is one window of JSON frames, zlib-compressed unless
+/// `?compression=0`, and counts as sent once Logstash acknowledges it.
+#[derive(Debug)]
+pub struct LumberjackOutput {
+    stream: TcpStream,
+    compression: u32,
+    window_size: usize,
+    window: Vec<String>,
+    destination: String,
+}
+
+impl LumberjackOutput {
+    pub async fn try_new(uri: &UriRef<String>, window_size: usize) -> Result<Self> {
+        let url = Url::parse(uri.as_str())?;
+        let host = url
+            .host_str()
+            .filter(|host| !host.is_empty())
+            .ok_or_else(|| {
+                eyre!("Lumberjack output needs a host, like lumberjack://logstash:5044")
+            })?;
+        let port = url.port().unwrap_or(DEFAULT_PORT);
+        let mut compression = DEFAULT_COMPRESSION;
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "compression" => match value.parse() {
+                    Ok(level @ 0..=9) => compression = level,
+                    _ => return Err(eyre!("Lumberjack compression must be 0 to 9, not {value}")),
+                },
+                key => return Err(eyre!("Unsupported lumberjack output parameter: {key}")),
+            }
+        }
+        let destination = format!("lumberjack://{host}:{port}");
+        let stream = TcpStream::connect((host, port))
+            .await
+            .map_err(|err| eyre!("Failed to connect to {destination}: {err}"))?;
+        log::debug!("Lumberjack output to {destination}");
+        Ok(Self {
+            stream,
+            compression,
+            window_size,
+            window: Vec::new(),
+            destination,
+        })
+    }
+
+    /// Writes the pending window and waits for its ACK, returning how many
+    /// documents it held.
+    async fn send_window(&mut self) -> Result<usize> {
+        if self.window.is_empty() {
+            return Ok(0);
+        }
+        let count = self.window.len();
+        let frames = window_frames(&self.window, self.compression)?;
+        self.stream.write_all(&frames).await?;
+        loop {
+            let mut ack = [0u8; 6];
+            timeout(ACK_TIMEOUT, self.stream.read_exact(&mut ack))
+                .await
+                .map_err(|_| eyre!("{} did not acknowledge a window", self.destination))?
+                .map_err(|err| eyre!("Lost connection to {}: {err}", self.destination))?;
+            if ack[..2] != [VERSION, b'A'] {
+                return Err(eyre!(
+                    "{} sent an unexpected frame instead of an ACK",
+                    self.destination
+                ));
+            }
+            let sequence = u32::from_be_bytes([ack[2], ack[3], ack[4], ack[5]]);
+            if sequence as usize == count {
+                self.window.clear();
+                return Ok(count);
+            }
+            log::trace!("{} acknowledged {sequence} of {count}", self.destination);
+        }
+    }
+}
+
+/// A window frame announcing the batch, then one JSON frame per document with
+/// sequence numbers from 1, wrapped in a compressed frame when `compression`
+/// is above 0.
+fn window_frames(documents: &[String], compression: u32) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    for (sequence, document) in (1u32..).zip(documents) {
+        data.extend([VERSION, b'J']);
+        data.extend(sequence.to_be_bytes());
+        data.extend((document.len() as u32).to_be_bytes());
+        data.extend(document.as_bytes());
+    }
+    let mut frames = vec![VERSION, b'W'];
+    frames.extend((documents.len() as u32).to_be_bytes());
+    match compression {
+        0 => frames.extend(data),
+        level => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+            encoder.write_all(&data)?;
+            let compressed = encoder.finish()?;
+            frames.extend([VERSION, b'C']);
+            frames.extend((compressed.len() as u32).to_be_bytes());
+            frames.extend(compressed);
+        }
+    }
+    Ok(frames)
+}
+
+#[async_trait]
+impl Sender for LumberjackOutput {
+    async fn send(&mut self, value: Document) -> Result<usize> {
+        self.window.push(value.get().to_string());
+        match self.window.len() >= self.window_size {
+            true => self.send_window().await,
+            false => Ok(0),
+        }
+    }
+
+    async fn flush(&mut self) -> Result<usize> {
+        self.send_window().await
+    }
+
+    async fn close(mut self: Box<Self>) -> Result<usize> {
+        let sent = self.send_window().await?;
+        self.stream.shutdown().await?;
+        Ok(sent)
+    }
+}
+
+impl std::fmt::Display for LumberjackOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.destination)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LumberjackOutput, Sender};
+    use crate::document::Document;
+    use flate2::read::ZlibDecoder;
+    use fluent_uri::UriRef;
+    use std::io::Read;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Reads one compressed window like a Logstash beats input, sends a
+    /// keepalive ACK and then the full ACK, and returns the window's documents.
+    async fn logstash() -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let uri = format!("lumberjack://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut header = [0u8; 6];
+            socket.read_exact(&mut header).await.unwrap();
+            assert_eq!(&header[..2], b"2W");
+            let count = u32::from_be_bytes(header[2..].try_into().unwrap());
+            socket.read_exact(&mut header).await.unwrap();
+            assert_eq!(&header[..2], b"2C");
+            let mut compressed =
+                vec![0; u32::from_be_bytes(header[2..].try_into().unwrap()) as usize];
+            socket.read_exact(&mut compressed).await.unwrap();
+            let mut data = Vec::new();
+            ZlibDecoder::new(&compressed[..])
+                .read_to_end(&mut data)
+                .unwrap();
+
+            let mut documents = Vec::new();
+            let mut frames = &data[..];
+            while !frames.is_empty() {
+                assert_eq!(&frames[..2], b"2J");
+                let sequence = u32::from_be_bytes(frames[2..6].try_into().unwrap());
+                assert_eq!(sequence as usize, documents.len() + 1);
+                let length = u32::from_be_bytes(frames[6..10].try_into().unwrap()) as usize;
+                documents.push(String::from_utf8(frames[10..10 + length].to_vec()).unwrap());
+                frames = &frames[10 + length..];
+            }
+            assert_eq!(count as usize, documents.len());
+            socket.write_all(b"2A\0\0\0\0").await.unwrap();
+            socket
+                .write_all(&[b'2', b'A', 0, 0, 0, count as u8])
+                .await
+                .unwrap();
+            documents
+        });
+        (uri, server)
+    }
+
+    #[tokio::test]
+    async fn windows_count_as_sent_once_acknowledged() {
+        let (uri, server) = logstash().await;
+        let uri = UriRef::parse(uri).unwrap();
+        let mut output = LumberjackOutput::try_new(&uri, 10).await.unwrap();
+
+        assert_eq!(
+            output
+                .send(Document::unvalidated("{\"a\":1}").unwrap())
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            output
+                .send(Document::unvalidated("{\"a\":2}").unwrap())
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(output.flush().await.unwrap(), 2);
+        assert_eq!(server.await.unwrap(), vec!["{\"a\":1}", "{\"a\":2}"]);
+        assert_eq!(Box::new(output).close().await.unwrap(), 0);
+    }
+}
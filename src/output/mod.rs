@@ -1,25 +1,57 @@
 mod action;
+#[cfg(feature = "cloud")]
+mod cloud;
 mod elasticsearch;
+mod error;
 mod file;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod lumberjack;
+mod partition;
+mod stdout;
+mod webhook;
 
-extern crate elasticsearch as elasticsearch_client;
-use crate::client::{Auth, ElasticsearchBuilder, KnownHost};
-pub use action::BulkAction;
+use crate::{
+    client::{Auth, ElasticsearchBuilder, Failover, KnownHost},
+    document::Document,
+};
+use ::elasticsearch::Elasticsearch;
+pub use action::{BulkAction, OnConflict};
+use async_trait::async_trait;
+use clap::ValueEnum;
 use elasticsearch::ElasticsearchOutput;
-pub use elasticsearch::ElasticsearchOutputConfig;
-use elasticsearch_client::Elasticsearch;
+pub use elasticsearch::{BulkCapture, ElasticsearchOutputConfig, FailedDocs, IdHash, IndexCounts};
+pub use error::{FailureClass, OutputError};
 use eyre::{Result, eyre};
 use file::FileOutput;
 use fluent_uri::UriRef;
-use serde_json::value::RawValue;
+use lumberjack::LumberjackOutput;
+use partition::{PartitionedFileOutput, PathTemplate};
 use std::path::PathBuf;
+use stdout::StdoutOutput;
 use url::Url;
+pub use webhook::HttpBody;
+use webhook::WebhookOutput;
 
+/// A document destination, wrapping the `Sender` selected by the output URI.
 #[derive(Debug)]
-pub enum Output {
-    Elasticsearch(ElasticsearchOutput),
-    File(FileOutput),
-    Stdout,
+pub struct Output(Box<dyn Sender>);
+
+/// How documents are written: the stdout formats for people, or `http-json`,
+/// which POSTs batches to a webhook instead of Elasticsearch.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// One JSON document per line
+    #[default]
+    Ndjson,
+    /// Indented JSON, for reading documents by eye
+    Pretty,
+    /// Selected fields as aligned columns
+    Table,
+    /// Selected fields as CSV with a header row
+    Csv,
+    /// Batches POSTed to any HTTP endpoint
+    HttpJson,
 }
 
 #[derive(Debug, Default)]
@@ -29,6 +61,10 @@ pub struct OutputPreflightConfig {
     pub template: Option<PathBuf>,
     pub template_name: Option<String>,
     pub template_overwrite: Option<bool>,
+    pub component_templates: Vec<PathBuf>,
+    pub ilm_policy: Option<PathBuf>,
+    /// The known host's `pipeline`, used when no pipeline or template flag is set.
+    pub host_pipeline: Option<String>,
 }
 
 impl OutputPreflightConfig {
@@ -68,6 +104,8 @@ impl OutputPreflightConfig {
             || self.template.is_some()
             || self.template_name.is_some()
             || self.template_overwrite.is_some()
+            || !self.component_templates.is_empty()
+            || self.ilm_policy.is_some()
     }
 
     fn has_pipeline_options(&self) -> bool {
@@ -75,11 +113,18 @@ impl OutputPreflightConfig {
     }
 
     fn has_template_options(&self) -> bool {
-        self.template.is_some() || self.template_name.is_some() || self.template_overwrite.is_some()
+        self.template.is_some()
+            || self.template_name.is_some()
+            || self.template_overwrite.is_some()
+            || !self.component_templates.is_empty()
     }
 }
 
 impl Output {
+    pub fn new(sender: impl Sender + 'static) -> Self {
+        Self(Box::new(sender))
+    }
+
     pub async fn try_new(
         insecure: bool,
         auth: Auth,
@@ -91,78 +136,268 @@ impl Output {
     ) -> Result<Self> {
         log::trace!("{uri:?}");
         match uri.scheme() {
-            Some(scheme) if ["http", "https"].contains(&scheme.as_str()) => {
-                let url = Url::parse(uri.as_str())?;
-                let mut client_url = url.clone();
-                client_url.set_path("");
-                let client = ElasticsearchBuilder::new(client_url)
-                    .insecure(insecure)
-                    .auth(auth)
-                    .request_body_compression(request_body_compression)
-                    .build()?;
-                let output = ElasticsearchOutput::try_new(
-                    client,
-                    url,
-                    action,
-                    elasticsearch_config,
-                    preflight,
-                )
-                .await?;
-                Ok(Output::Elasticsearch(output))
-            }
             Some(scheme) if scheme.as_str() == "file" => {
                 reject_elasticsearch_options(&preflight)?;
-                let path = PathBuf::from(uri.path().as_str());
-                let output = FileOutput::try_from(path)?;
-                Ok(Output::File(output))
+                open_file_output(uri.path().as_str())
+            }
+            Some(scheme) if ["az", "gs"].contains(&scheme.as_str()) => {
+                reject_elasticsearch_options(&preflight)?;
+                open_cloud_output(&uri)
+            }
+            Some(scheme) if scheme.as_str() == "lumberjack" => {
+                reject_elasticsearch_options(&preflight)?;
+                let output =
+                    LumberjackOutput::try_new(&uri, elasticsearch_config.batch_size()).await?;
+                Ok(Output::new(output))
+            }
+            Some(scheme) if scheme.as_str() == "kafka" => {
+                reject_elasticsearch_options(&preflight)?;
+                open_kafka_output(&uri)
             }
-            Some(scheme) => {
-                let known_host = KnownHost::try_from(scheme.as_str())?;
-                let url = known_host.get_url().join(uri.path().as_str())?;
-                let client = Elasticsearch::try_from(known_host)?;
+            // http(s) URLs and known hosts
+            Some(_) => {
+                let (uri, preflight) = with_host_defaults(uri, preflight)?;
+                let (client, url) = elasticsearch_client(
+                    insecure,
+                    auth,
+                    &uri,
+                    request_body_compression,
+                    &elasticsearch_config,
+                )?;
+                let hosts = failover(&uri, client, &url, &elasticsearch_config)?;
                 let output = ElasticsearchOutput::try_new(
-                    client,
+                    hosts,
                     url,
                     action,
                     elasticsearch_config,
                     preflight,
                 )
                 .await?;
-                Ok(Output::Elasticsearch(output))
+                Ok(Output::new(output))
             }
             None => match uri.path().as_str() {
                 "-" => {
                     reject_elasticsearch_options(&preflight)?;
-                    Ok(Output::Stdout)
+                    Ok(Output::new(StdoutOutput::default()))
                 }
-                _ => {
+                path => {
                     reject_elasticsearch_options(&preflight)?;
-                    let path = PathBuf::from(uri.path().as_str());
-                    let output = FileOutput::try_from(path)?;
-                    Ok(Output::File(output))
+                    open_file_output(path)
                 }
             },
         }
     }
 
-    pub async fn send(&mut self, value: Box<RawValue>) -> Result<usize> {
-        match self {
-            Output::Elasticsearch(output) => Ok(output.send(value).await?),
-            Output::File(output) => Ok(output.send(value).await?),
-            Output::Stdout => {
-                println!("{}", value.get());
-                Ok(1)
+    /// A webhook that is sent batches of documents as NDJSON or a JSON array,
+    /// with the auth, headers, proxy, timeout, and batch size of a bulk output.
+    pub fn webhook(
+        uri: UriRef<String>,
+        body: HttpBody,
+        insecure: bool,
+        auth: Auth,
+        config: &ElasticsearchOutputConfig,
+        preflight: &OutputPreflightConfig,
+    ) -> Result<Self> {
+        reject_elasticsearch_options(preflight)?;
+        let url = match uri.scheme().map(|scheme| scheme.as_str()) {
+            Some("http" | "https") => Url::parse(uri.as_str())?,
+            _ => {
+                return Err(eyre!(
+                    "--output-format http-json needs an http:// or https:// output URL, not {uri}"
+                ));
             }
+        };
+        Ok(Self::new(WebhookOutput::try_new(
+            url, body, insecure, auth, config,
+        )?))
+    }
+
+    /// An NDJSON file, gzip-compressed when the path ends in `.ndjson.gz`.
+    pub fn file(path: PathBuf) -> Result<Self> {
+        Ok(Self::new(FileOutput::try_from(path)?))
+    }
+
+    /// Writes documents in `format`, with table columns from `fields`. Only
+    /// stdout takes a format other than NDJSON.
+    pub fn with_output_format(mut self, format: OutputFormat, fields: Vec<String>) -> Result<Self> {
+        if matches!(format, OutputFormat::Ndjson | OutputFormat::HttpJson) && fields.is_empty() {
+            return Ok(self);
         }
+        if !matches!(format, OutputFormat::Table | OutputFormat::Csv) && !fields.is_empty() {
+            return Err(eyre!("--fields requires --output-format table or csv"));
+        }
+        self.0.set_format(format, fields)?;
+        Ok(self)
+    }
+
+    pub async fn send(&mut self, value: Document) -> Result<usize> {
+        self.0.send(value).await
+    }
+
+    /// Pushes buffered documents to their destination and waits for acknowledgement,
+    /// returning documents delivered that were not yet counted by `send`.
+    pub async fn flush(&mut self) -> Result<usize> {
+        self.0.flush().await
     }
 
     pub async fn close(self) -> Result<usize> {
-        match self {
-            Output::Elasticsearch(output) => Ok(output.close().await?),
-            Output::File(output) => Ok(output.close().await?),
-            Output::Stdout => Ok(0),
+        self.0.close().await
+    }
+}
+
+/// An NDJSON file, or with `{field}` placeholders in its path, one file per
+/// combination of the documents' values of those fields.
+fn open_file_output(path: &str) -> Result<Output> {
+    Ok(match PathTemplate::parse(path)? {
+        Some(template) => Output::new(PartitionedFileOutput::new(template)?),
+        None => Output::new(FileOutput::try_from(PathBuf::from(path))?),
+    })
+}
+
+/// Builds the client for an `http(s)://` URI, or one whose scheme names a known
+/// host, returning it with the full URL of the URI's path on that cluster.
+pub fn elasticsearch_client(
+    insecure: bool,
+    auth: Auth,
+    uri: &UriRef<String>,
+    request_body_compression: bool,
+    config: &ElasticsearchOutputConfig,
+) -> Result<(Elasticsearch, Url)> {
+    match uri.scheme() {
+        Some(scheme) if ["http", "https"].contains(&scheme.as_str()) => {
+            let url = Url::parse(uri.as_str())?;
+            let mut client_url = url.clone();
+            client_url.set_path("");
+            let builder = ElasticsearchBuilder::new(client_url)
+                .insecure(insecure)
+                .auth(auth)
+                .request_body_compression(request_body_compression);
+            Ok((build_client(builder, config)?, url))
         }
+        Some(scheme) => {
+            let known_host = KnownHost::try_from(scheme.as_str())?;
+            let url = known_host.get_url().join(uri.path().as_str())?;
+            let builder = ElasticsearchBuilder::try_from(known_host)?
+                .request_body_compression(request_body_compression);
+            Ok((build_client(builder, config)?, url))
+        }
+        None => Err(eyre!("{uri} is not an Elasticsearch URL or known host")),
+    }
+}
+
+/// Applies a known host's `index_prefix` to the output URI's index and takes
+/// its `pipeline`; http(s) URLs are returned as they are.
+fn with_host_defaults(
+    uri: UriRef<String>,
+    mut preflight: OutputPreflightConfig,
+) -> Result<(UriRef<String>, OutputPreflightConfig)> {
+    let scheme = match uri.scheme().map(|scheme| scheme.as_str()) {
+        Some("http" | "https") | None => return Ok((uri, preflight)),
+        Some(scheme) => scheme.to_string(),
+    };
+    let known_host = KnownHost::try_from(scheme.as_str())?;
+    let defaults = known_host.defaults();
+    preflight.host_pipeline = defaults.pipeline.clone();
+    let uri = match &defaults.index_prefix {
+        Some(prefix) => with_index_prefix(uri, &scheme, prefix)?,
+        None => uri,
+    };
+    Ok((uri, preflight))
+}
+
+/// Puts `prefix` in front of the index of a known host output URI, so
+/// `prod:/orders` with `team-a-` becomes `prod:team-a-orders`.
+fn with_index_prefix(uri: UriRef<String>, scheme: &str, prefix: &str) -> Result<UriRef<String>> {
+    let index = uri.path().as_str().trim_start_matches('/');
+    if index.is_empty() {
+        return Ok(uri);
+    }
+    UriRef::parse(format!("{scheme}:{prefix}{index}"))
+        .map_err(|(err, _)| eyre!("index_prefix {prefix}: {err}"))
+}
+
+/// Builds the client for a known host, with its own credentials and settings.
+pub fn known_host_client(
+    known_host: KnownHost,
+    config: &ElasticsearchOutputConfig,
+) -> Result<Elasticsearch> {
+    build_client(ElasticsearchBuilder::try_from(known_host)?, config)
+}
+
+/// The output's client, followed by a client for each of a known host's
+/// `fallback_urls` to fail over to.
+fn failover(
+    uri: &UriRef<String>,
+    client: Elasticsearch,
+    url: &Url,
+    config: &ElasticsearchOutputConfig,
+) -> Result<Failover> {
+    let mut primary = url.clone();
+    primary.set_path("");
+    let mut failover = Failover::new(primary, client);
+    let Some(scheme) = uri.scheme().map(|scheme| scheme.as_str()) else {
+        return Ok(failover);
+    };
+    if ["http", "https"].contains(&scheme) {
+        return Ok(failover);
+    }
+    for host in KnownHost::try_from(scheme)?.fallbacks() {
+        let url = host.get_url();
+        let builder = ElasticsearchBuilder::try_from(host)?;
+        failover = failover.with_fallback(url, build_client(builder, config)?);
+    }
+    Ok(failover)
+}
+
+/// Applies the client options shared by URL and known host outputs, reusing a
+/// shared client with the same settings when there is one.
+fn build_client(
+    builder: ElasticsearchBuilder,
+    config: &ElasticsearchOutputConfig,
+) -> Result<Elasticsearch> {
+    let mut builder = builder
+        .timeout(config.request_timeout())
+        .socket_options(config.socket_options());
+    for (name, value) in config.headers() {
+        builder = builder.header(name.clone(), value.clone());
+    }
+    if let Some(proxy) = config.proxy() {
+        builder = builder.proxy(proxy.clone());
     }
+    if let Some(server_name) = config.tls_server_name() {
+        builder = builder.tls_server_name(server_name.to_string());
+    }
+    match config.shared_clients() {
+        Some(shared) => shared.client(builder),
+        None => builder.build(),
+    }
+}
+
+#[cfg(feature = "cloud")]
+fn open_cloud_output(uri: &UriRef<String>) -> Result<Output> {
+    Ok(Output::new(cloud::CloudOutput::try_new(uri)?))
+}
+
+#[cfg(not(feature = "cloud"))]
+fn open_cloud_output(uri: &UriRef<String>) -> Result<Output> {
+    Err(eyre!(
+        "{}:// outputs require espipe to be built with the cloud feature",
+        uri.scheme()
+            .map(|scheme| scheme.as_str())
+            .unwrap_or_default()
+    ))
+}
+
+#[cfg(feature = "kafka")]
+fn open_kafka_output(uri: &UriRef<String>) -> Result<Output> {
+    Ok(Output::new(kafka::KafkaOutput::try_new(uri)?))
+}
+
+#[cfg(not(feature = "kafka"))]
+fn open_kafka_output(_uri: &UriRef<String>) -> Result<Output> {
+    Err(eyre!(
+        "kafka:// outputs require espipe to be built with the kafka feature"
+    ))
 }
 
 fn reject_elasticsearch_options(preflight: &OutputPreflightConfig) -> Result<()> {
@@ -174,7 +409,7 @@ fn reject_elasticsearch_options(preflight: &OutputPreflightConfig) -> Result<()>
             return Err(eyre!("pipeline options require an Elasticsearch output"));
         }
         return Err(eyre!(
-            "--pipeline, --pipeline-name, --template, --template-name, and --template-overwrite require an Elasticsearch output"
+            "--pipeline, --pipeline-name, --template, --template-name, --template-overwrite, --component-template, and --ilm-policy require an Elasticsearch output"
         ));
     }
     Ok(())
@@ -182,15 +417,88 @@ fn reject_elasticsearch_options(preflight: &OutputPreflightConfig) -> Result<()>
 
 impl std::fmt::Display for Output {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Output::Elasticsearch(output) => write!(f, "{output}"),
-            Output::File(output) => write!(f, "{output}"),
-            Output::Stdout => write!(f, "stdout"),
-        }
+        self.0.fmt(f)
+    }
+}
+
+/// A document sink. Implement this to add an output; `Output::new` wraps any sender.
+///
+/// `send` may buffer documents, so each method returns how many documents were
+/// delivered by that call, and `close` delivers whatever is still buffered.
+#[async_trait]
+pub trait Sender: std::fmt::Debug + std::fmt::Display + Send {
+    async fn send(&mut self, value: Document) -> Result<usize>;
+    async fn flush(&mut self) -> Result<usize>;
+    async fn close(self: Box<Self>) -> Result<usize>;
+
+    /// Switches to a human-readable `--output-format`, which only stdout supports.
+    fn set_format(&mut self, format: OutputFormat, _fields: Vec<String>) -> Result<()> {
+        let format = format
+            .to_possible_value()
+            .map_or_else(String::new, |value| value.get_name().to_string());
+        Err(eyre!(
+            "--output-format {format} only applies to stdout output, not {self}"
+        ))
     }
 }
 
-trait Sender {
-    async fn send(&mut self, value: Box<RawValue>) -> Result<usize>;
-    async fn close(self) -> Result<usize>;
+#[cfg(test)]
+mod tests {
+    use super::{Output, Sender, with_index_prefix};
+    use crate::document::Document;
+    use async_trait::async_trait;
+    use eyre::Result;
+    use fluent_uri::UriRef;
+
+    /// Buffers documents and delivers them on flush, like a batching plug-in would.
+    #[derive(Debug, Default)]
+    struct Buffered(Vec<Document>);
+
+    #[async_trait]
+    impl Sender for Buffered {
+        async fn send(&mut self, value: Document) -> Result<usize> {
+            self.0.push(value);
+            Ok(0)
+        }
+
+        async fn flush(&mut self) -> Result<usize> {
+            Ok(std::mem::take(&mut self.0).len())
+        }
+
+        async fn close(mut self: Box<Self>) -> Result<usize> {
+            self.flush().await
+        }
+    }
+
+    impl std::fmt::Display for Buffered {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "buffered")
+        }
+    }
+
+    #[tokio::test]
+    async fn outputs_wrap_any_sender() {
+        let mut output = Output::new(Buffered::default());
+        let doc = || Document::unvalidated("{\"a\":1}").unwrap();
+
+        assert_eq!(output.send(doc()).await.unwrap(), 0);
+        assert_eq!(output.send(doc()).await.unwrap(), 0);
+        assert_eq!(output.flush().await.unwrap(), 2);
+        assert_eq!(output.send(doc()).await.unwrap(), 0);
+        assert_eq!(output.to_string(), "buffered");
+        assert_eq!(output.close().await.unwrap(), 1);
+    }
+
+    #[test]
+    fn index_prefix_goes_in_front_of_the_index() {
+        let prefixed = |uri: &str| {
+            let uri = UriRef::parse(uri.to_string()).unwrap();
+            with_index_prefix(uri, "prod", "team-a-")
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(prefixed("prod:/orders"), "prod:team-a-orders");
+        assert_eq!(prefixed("prod:orders"), "prod:team-a-orders");
+        assert_eq!(prefixed("prod:"), "prod:");
+    }
 }
@@ -0,0 +1,256 @@
+use super::{
+    Sender,
+    file::{FileWriter, is_unsupported_gzip_output},
+};
+use crate::{document::Document, field_path};
+use async_trait::async_trait;
+use eyre::{Result, eyre};
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Partition files held open at once; the least recently written is closed
+/// to make room for another.
+const MAX_OPEN_FILES: usize = 128;
+
+/// The file name part for documents without the placeholder's field.
+const MISSING: &str = "_missing";
+
+/// A file output path with `{field}` placeholders, each filled in from the
+/// document's value of that field.
+#[derive(Debug, PartialEq)]
+pub struct PathTemplate(Vec<Part>);
+
+#[derive(Debug, PartialEq)]
+enum Part {
+    Literal(String),
+    Field(String),
+}
+
+impl PathTemplate {
+    /// The template of an output path, or `None` when it has no placeholder.
+    /// Braces arrive percent-encoded, since URIs do not allow them.
+    pub fn parse(path: &str) -> Result<Option<Self>> {
+        let path = path
+            .replace("%7B", "{")
+            .replace("%7b", "{")
+            .replace("%7D", "}")
+            .replace("%7d", "}");
+        if !path.contains('{') {
+            return Ok(None);
+        }
+        let mut parts = Vec::new();
+        let mut rest = path.as_str();
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| eyre!("Output path {path} has an unclosed {{"))?;
+            let field = &rest[start + 1..end];
+            if field.is_empty() || field.contains('{') {
+                return Err(eyre!(
+                    "Output path {path} needs a field name in each {{}}, like out-{{service.name}}.ndjson"
+                ));
+            }
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            parts.push(Part::Field(field.to_string()));
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(Some(Self(parts)))
+    }
+
+    /// The file for `doc`, with each placeholder replaced by its field's value.
+    fn path(&self, doc: &Value) -> Result<PathBuf> {
+        let mut path = String::new();
+        for part in &self.0 {
+            match part {
+                Part::Literal(literal) => path.push_str(literal),
+                Part::Field(field) => path.push_str(&file_name_part(field, doc)?),
+            }
+        }
+        Ok(PathBuf::from(path))
+    }
+}
+
+impl std::fmt::Display for PathTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for part in &self.0 {
+            match part {
+                Part::Literal(literal) => f.write_str(literal)?,
+                Part::Field(field) => write!(f, "{{{field}}}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A field value as part of a file name, with path separators replaced so
+/// values cannot name files outside the template's directory.
+fn file_name_part(field: &str, doc: &Value) -> Result<String> {
+    let value = match field_path::lookup(doc, field) {
+        None | Some(Value::Null) => return Ok(MISSING.to_string()),
+        Some(Value::String(value)) => value.clone(),
+        Some(value @ (Value::Number(_) | Value::Bool(_))) => value.to_string(),
+        Some(_) => {
+            return Err(eyre!(
+                "Output path field {field} must hold a string, number, or boolean"
+            ));
+        }
+    };
+    Ok(match value.as_str() {
+        "" => MISSING.to_string(),
+        "." | ".." => "_".repeat(value.len()),
+        _ => value.replace(['/', '\\', '\0'], "_"),
+    })
+}
+
+/// Writes each document to the NDJSON file its field values name, opening
+/// files as documents first need them and keeping the most recently written
+/// ones open.
+///
+/// Each file is truncated when this run first writes to it, and appended to
+/// if it is reopened after being closed to make room.
+#[derive(Debug)]
+pub struct PartitionedFileOutput {
+    template: PathTemplate,
+    /// Open files, with the write count when each was last written.
+    writers: HashMap<PathBuf, (FileWriter, u64)>,
+    /// Every file this run has written to, open or not.
+    written: HashSet<PathBuf>,
+    writes: u64,
+}
+
+impl PartitionedFileOutput {
+    pub fn new(template: PathTemplate) -> Result<Self> {
+        if is_unsupported_gzip_output(Path::new(&template.to_string())) {
+            return Err(eyre!("Unsupported compressed output format: {template}"));
+        }
+        Ok(Self {
+            template,
+            writers: HashMap::new(),
+            written: HashSet::new(),
+            writes: 0,
+        })
+    }
+
+    fn writer(&mut self, path: PathBuf) -> Result<&mut FileWriter> {
+        self.writes += 1;
+        if !self.writers.contains_key(&path) {
+            if self.writers.len() >= MAX_OPEN_FILES
+                && let Some(oldest) = self
+                    .writers
+                    .iter()
+                    .min_by_key(|(_, (_, written))| *written)
+                    .map(|(path, _)| path.clone())
+            {
+                let (writer, _) = self.writers.remove(&oldest).expect("oldest is open");
+                writer.finish()?;
+            }
+            if let Some(parent) = path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+            {
+                std::fs::create_dir_all(parent)?;
+            }
+            let append = !self.written.insert(path.clone());
+            let writer = FileWriter::open(&path, append)
+                .map_err(|err| eyre!("Failed to open {}: {err}", path.display()))?;
+            self.writers.insert(path.clone(), (writer, 0));
+        }
+        let (writer, written) = self.writers.get_mut(&path).expect("writer was opened");
+        *written = self.writes;
+        Ok(writer)
+    }
+}
+
+#[async_trait]
+impl Sender for PartitionedFileOutput {
+    async fn send(&mut self, value: Document) -> Result<usize> {
+        let doc: Value = serde_json::from_str(value.get())?;
+        let path = self.template.path(&doc)?;
+        let writer = self.writer(path)?;
+        if let Some(action) = value.action() {
+            writeln!(writer, "{action}")?;
+        }
+        writer.write_all(value.get().as_bytes())?;
+        writeln!(writer)?;
+        Ok(1)
+    }
+
+    async fn flush(&mut self) -> Result<usize> {
+        for (writer, _) in self.writers.values_mut() {
+            writer.flush()?;
+        }
+        Ok(0)
+    }
+
+    async fn close(self: Box<Self>) -> Result<usize> {
+        log::info!("Wrote {} files for {}", self.written.len(), self.template);
+        for (_, (writer, _)) in self.writers {
+            writer.finish()?;
+        }
+        Ok(0)
+    }
+}
+
+impl std::fmt::Display for PartitionedFileOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.template)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_OPEN_FILES, PartitionedFileOutput, PathTemplate, Sender};
+    use crate::document::Document;
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn templates_fill_placeholders_from_fields() {
+        let template = PathTemplate::parse("out/%7Bservice.name%7D-{year}.ndjson")
+            .unwrap()
+            .unwrap();
+        assert_eq!(template.to_string(), "out/{service.name}-{year}.ndjson");
+        let doc = json!({"service": {"name": "../api/v1"}, "year": 2024});
+        assert_eq!(
+            template.path(&doc).unwrap().to_str(),
+            Some("out/.._api_v1-2024.ndjson")
+        );
+        assert_eq!(
+            template.path(&json!({"year": ".."})).unwrap().to_str(),
+            Some("out/_missing-__.ndjson")
+        );
+        assert!(PathTemplate::parse("out.ndjson").unwrap().is_none());
+        assert!(PathTemplate::parse("out-{service.ndjson").is_err());
+    }
+
+    #[tokio::test]
+    async fn documents_are_split_across_files_beyond_the_open_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let template = dir.path().join("{n}.ndjson").to_string_lossy().into_owned();
+        let template = PathTemplate::parse(&template).unwrap().unwrap();
+        let mut output = PartitionedFileOutput::new(template).unwrap();
+
+        // The first file is closed to make room, then reopened and appended to.
+        for n in (0..=MAX_OPEN_FILES).chain([0]) {
+            let doc = Document::unvalidated(&format!("{{\"n\":{n}}}")).unwrap();
+            output.send(doc).await.unwrap();
+        }
+        Box::new(output).close().await.unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.path().join("0.ndjson")).unwrap(),
+            "{\"n\":0}\n{\"n\":0}\n"
+        );
+        assert!(dir.path().join(format!("{MAX_OPEN_FILES}.ndjson")).exists());
+    }
+}
@@ -0,0 +1,219 @@
+use super::{OutputFormat, Sender};
+use crate::{document::Document, field_path};
+use async_trait::async_trait;
+use eyre::Result;
+use serde_json::Value;
+use std::io::Write;
+
+/// Rows a table buffers before printing, and the rows its column widths are measured on.
+const TABLE_ROWS: usize = 100;
+/// Characters a table cell is cut to, so one long message does not push every
+/// other column off the screen.
+const MAX_CELL_CHARS: usize = 40;
+
+/// Writes one document per line to standard output, after its action line when
+/// it was read from a bulk-format input, or in an `--output-format` for people.
+#[derive(Debug, Default)]
+pub struct StdoutOutput {
+    format: OutputFormat,
+    table: Table,
+}
+
+#[async_trait]
+impl Sender for StdoutOutput {
+    async fn send(&mut self, value: Document) -> Result<usize> {
+        match self.format {
+            OutputFormat::Ndjson | OutputFormat::HttpJson => {
+                if let Some(action) = value.action() {
+                    println!("{action}");
+                }
+                println!("{}", value.get());
+            }
+            OutputFormat::Pretty => {
+                if let Some(action) = value.action() {
+                    println!("{}", pretty(action));
+                }
+                println!("{}", pretty(value.get()));
+            }
+            OutputFormat::Csv => {
+                let row = self.table.values(value.get());
+                if !self.table.header_printed {
+                    self.table.header_printed = true;
+                    println!("{}", csv_line(&self.table.fields));
+                }
+                println!("{}", csv_line(&row));
+            }
+            OutputFormat::Table => {
+                self.table.push(value.get());
+                if self.table.rows.len() == TABLE_ROWS {
+                    self.table.print();
+                }
+            }
+        }
+        Ok(1)
+    }
+
+    async fn flush(&mut self) -> Result<usize> {
+        self.table.print();
+        std::io::stdout().flush()?;
+        Ok(0)
+    }
+
+    async fn close(mut self: Box<Self>) -> Result<usize> {
+        self.table.print();
+        Ok(0)
+    }
+
+    fn set_format(&mut self, format: OutputFormat, fields: Vec<String>) -> Result<()> {
+        self.format = format;
+        self.table.fields = fields;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for StdoutOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "stdout")
+    }
+}
+
+fn pretty(json: &str) -> String {
+    serde_json::from_str::<Value>(json)
+        .ok()
+        .and_then(|value| serde_json::to_string_pretty(&value).ok())
+        .unwrap_or_else(|| json.to_string())
+}
+
+/// Quotes fields holding a comma, quote, or line break, doubling their quotes.
+fn csv_line(fields: &[String]) -> String {
+    fields
+        .iter()
+        .map(|field| match field.contains([',', '"', '\n', '\r']) {
+            true => format!("\"{}\"", field.replace('"', "\"\"")),
+            false => field.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Columns of `--fields`, or of the first document's top-level fields, whose
+/// widths are set by the header and the first rows.
+#[derive(Debug, Default)]
+struct Table {
+    fields: Vec<String>,
+    widths: Vec<usize>,
+    rows: Vec<Vec<String>>,
+    header_printed: bool,
+}
+
+impl Table {
+    /// The document's value for each column, taking the columns from the
+    /// first document when none were given.
+    fn values(&mut self, json: &str) -> Vec<String> {
+        let value = serde_json::from_str::<Value>(json).unwrap_or(Value::Null);
+        if self.fields.is_empty()
+            && let Some(object) = value.as_object()
+        {
+            self.fields = object.keys().cloned().collect();
+        }
+        self.fields
+            .iter()
+            .map(|field| text(field_path::lookup(&value, field)))
+            .collect()
+    }
+
+    fn push(&mut self, json: &str) {
+        let row = self.values(json).into_iter().map(cell).collect();
+        self.rows.push(row);
+    }
+
+    fn print(&mut self) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let rows = std::mem::take(&mut self.rows);
+        if !self.header_printed {
+            self.header_printed = true;
+            self.widths = self.fields.iter().map(|field| width(field)).collect();
+            for row in &rows {
+                for (width, cell) in self.widths.iter_mut().zip(row) {
+                    *width = (*width).max(self::width(cell));
+                }
+            }
+            println!("{}", self.line(&self.fields));
+        }
+        for row in &rows {
+            println!("{}", self.line(row));
+        }
+    }
+
+    fn line(&self, cells: &[String]) -> String {
+        let line = cells
+            .iter()
+            .zip(&self.widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        line.trim_end().to_string()
+    }
+}
+
+/// Strings as they are, other values as JSON, and missing values as empty.
+fn text(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(text)) => text.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+fn cell(text: String) -> String {
+    let text = text.replace(['\n', '\r', '\t'], " ");
+    match text.chars().count() > MAX_CELL_CHARS {
+        true => {
+            let mut cut: String = text.chars().take(MAX_CELL_CHARS - 1).collect();
+            cut.push('…');
+            cut
+        }
+        false => text,
+    }
+}
+
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_CELL_CHARS, Table, cell, csv_line, text};
+    use serde_json::json;
+
+    #[test]
+    fn tables_align_selected_fields_and_cut_long_cells() {
+        let mut table = Table {
+            fields: vec!["id".to_string(), "user.name".to_string()],
+            ..Table::default()
+        };
+        table.push(r#"{"id":7,"user":{"name":"ada"}}"#);
+        table.push(r#"{"id":1234,"message":"no user"}"#);
+        table.widths = vec![4, 9];
+
+        assert_eq!(table.rows, vec![vec!["7", "ada"], vec!["1234", ""]]);
+        assert_eq!(table.line(&table.rows[0]), "7     ada");
+        assert_eq!(table.line(&table.rows[1]), "1234");
+
+        let long = cell("x".repeat(100));
+        assert_eq!(long.chars().count(), MAX_CELL_CHARS);
+        assert!(long.ends_with('…'));
+        assert_eq!(text(Some(&json!({"a": [1]}))), r#"{"a":[1]}"#);
+    }
+
+    #[test]
+    fn csv_lines_quote_only_fields_that_need_it() {
+        let fields = ["plain", "a,b", "say \"hi\"", "two\nlines", ""].map(String::from);
+        assert_eq!(
+            csv_line(&fields),
+            "plain,\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\","
+        );
+    }
+}
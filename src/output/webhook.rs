@@ -0,0 +1,185 @@
+use super::{ElasticsearchOutputConfig, Sender};
+use crate::{client::Auth, document::Document, manifest::redact_uri};
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use clap::ValueEnum;
+use eyre::{Result, eyre};
+use reqwest::{Client, Proxy, StatusCode, header::CONTENT_TYPE};
+use std::time::Duration;
+use tokio::time::sleep;
+use url::Url;
+
+/// Attempts per batch before the run fails.
+const MAX_ATTEMPTS: u32 = 6;
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How a webhook batch is laid out in the request body.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum HttpBody {
+    /// One document per line, sent as `application/x-ndjson`
+    #[default]
+    Ndjson,
+    /// A JSON array of documents, sent as `application/json`
+    Array,
+}
+
+/// POSTs batches of documents to any HTTP endpoint, for log vendors and
+/// collectors that are not Elasticsearch.
+///
+/// Batches are retried with backoff on connection errors, timeouts, `429`,
+/// and `5xx` responses; any other failure ends the run.
+#[derive(Debug)]
+pub struct WebhookOutput {
+    client: Client,
+    url: Url,
+    body: HttpBody,
+    batch_size: usize,
+    batch: Vec<String>,
+}
+
+impl WebhookOutput {
+    pub fn try_new(
+        url: Url,
+        body: HttpBody,
+        insecure: bool,
+        auth: Auth,
+        config: &ElasticsearchOutputConfig,
+    ) -> Result<Self> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in config.headers() {
+            headers.append(
+                reqwest::header::HeaderName::from_bytes(name.as_str().as_bytes())?,
+                reqwest::header::HeaderValue::from_bytes(value.as_bytes())?,
+            );
+        }
+        let authorization = match auth {
+            Auth::Apikey(apikey) => Some(format!("ApiKey {apikey}")),
+            Auth::Basic(username, password) => Some(format!(
+                "Basic {}",
+                STANDARD.encode(format!("{username}:{password}"))
+            )),
+            Auth::None => None,
+        };
+        if let Some(authorization) = authorization {
+            headers.insert(reqwest::header::AUTHORIZATION, authorization.parse()?);
+        }
+        let mut builder = Client::builder()
+            .default_headers(headers)
+            .danger_accept_invalid_certs(insecure);
+        if let Some(timeout) = config.request_timeout() {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(proxy) = config.proxy() {
+            builder = builder.proxy(Proxy::all(proxy.as_str())?);
+        }
+        log::debug!("Webhook output to {}", redact_uri(url.as_str()));
+        Ok(Self {
+            client: builder.build()?,
+            url,
+            body,
+            batch_size: config.batch_size(),
+            batch: Vec::new(),
+        })
+    }
+
+    /// Sends the pending batch, returning how many documents it held.
+    async fn post_batch(&mut self) -> Result<usize> {
+        if self.batch.is_empty() {
+            return Ok(0);
+        }
+        let (content_type, body) = request_body(self.body, &self.batch);
+        let mut backoff = Duration::from_secs(1);
+        for attempt in 1..=MAX_ATTEMPTS {
+            let response = self
+                .client
+                .post(self.url.clone())
+                .header(CONTENT_TYPE, content_type)
+                .body(body.clone())
+                .send()
+                .await;
+            let retry = match response {
+                Ok(response) if response.status().is_success() => {
+                    return Ok(std::mem::take(&mut self.batch).len());
+                }
+                Ok(response) if is_retryable(response.status()) => {
+                    format!("HTTP {}", response.status())
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let text = response.text().await.unwrap_or_default();
+                    return Err(eyre!("{self} rejected a batch with HTTP {status}: {text}"));
+                }
+                Err(err) if err.is_connect() || err.is_timeout() => err.to_string(),
+                Err(err) => return Err(err.into()),
+            };
+            if attempt < MAX_ATTEMPTS {
+                log::warn!(
+                    "Webhook request failed with {retry} (attempt {attempt}, backoff {backoff:?})"
+                );
+                sleep(backoff).await;
+                backoff = std::cmp::min(backoff * 2, MAX_BACKOFF);
+            }
+        }
+        Err(eyre!(
+            "{self} did not accept a batch after {MAX_ATTEMPTS} attempts"
+        ))
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn request_body(body: HttpBody, batch: &[String]) -> (&'static str, String) {
+    match body {
+        HttpBody::Ndjson => {
+            let mut lines = batch.join("\n");
+            lines.push('\n');
+            ("application/x-ndjson", lines)
+        }
+        HttpBody::Array => ("application/json", format!("[{}]", batch.join(","))),
+    }
+}
+
+#[async_trait]
+impl Sender for WebhookOutput {
+    async fn send(&mut self, value: Document) -> Result<usize> {
+        self.batch.push(value.get().to_string());
+        match self.batch.len() >= self.batch_size {
+            true => self.post_batch().await,
+            false => Ok(0),
+        }
+    }
+
+    async fn flush(&mut self) -> Result<usize> {
+        self.post_batch().await
+    }
+
+    async fn close(mut self: Box<Self>) -> Result<usize> {
+        self.post_batch().await
+    }
+}
+
+impl std::fmt::Display for WebhookOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", redact_uri(self.url.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HttpBody, request_body};
+
+    #[test]
+    fn batches_are_sent_as_ndjson_lines_or_a_json_array() {
+        let batch = vec![r#"{"a":1}"#.to_string(), r#"{"a":2}"#.to_string()];
+        assert_eq!(
+            request_body(HttpBody::Ndjson, &batch),
+            ("application/x-ndjson", "{\"a\":1}\n{\"a\":2}\n".to_string())
+        );
+        assert_eq!(
+            request_body(HttpBody::Array, &batch),
+            ("application/json", r#"[{"a":1},{"a":2}]"#.to_string())
+        );
+    }
+}
@@ -0,0 +1,300 @@
+use crate::{
+    Cli, Command, comma_formatted,
+    input::{Input, InputReader, ReadEvent},
+    input_encoding,
+    lint::field_type,
+    remote_options,
+};
+use eyre::{Result, eyre};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use xxhash_rust::xxh3::xxh3_64;
+
+/// Fields profiled before any others are only counted, so a mapping explosion
+/// in the input cannot exhaust memory.
+const MAX_FIELDS: usize = 10_000;
+
+/// Distinct values counted exactly before a field switches to an estimate.
+const EXACT_CARDINALITY: usize = 1024;
+
+/// Register index bits of the cardinality estimate, for about 1.6% error.
+const PRECISION: u32 = 12;
+
+/// Example values kept for each field.
+const EXAMPLES: usize = 3;
+
+/// Characters of an example value shown before it is cut off.
+const EXAMPLE_CHARS: usize = 40;
+
+/// Field statistics over every document of an input.
+#[derive(Debug, Default)]
+struct Profile {
+    docs: u64,
+    fields: BTreeMap<String, FieldProfile>,
+    /// Fields past `MAX_FIELDS`, which are only counted.
+    untracked: Cardinality,
+}
+
+#[derive(Debug, Default)]
+struct FieldProfile {
+    /// Documents with a value for the field.
+    present: u64,
+    /// Documents with the field, but only as `null` or an empty array.
+    nulls: u64,
+    /// Value counts by type, with array elements counted one by one.
+    types: BTreeMap<&'static str, u64>,
+    cardinality: Cardinality,
+    examples: Vec<String>,
+}
+
+/// A distinct count that is exact for small fields and estimated with
+/// HyperLogLog for large ones, in a few kilobytes either way.
+#[derive(Debug)]
+enum Cardinality {
+    Exact(HashSet<u64>),
+    Estimated(Vec<u8>),
+}
+
+impl Default for Cardinality {
+    fn default() -> Self {
+        Self::Exact(HashSet::new())
+    }
+}
+
+impl Cardinality {
+    fn add(&mut self, hash: u64) {
+        match self {
+            Self::Exact(hashes) => {
+                hashes.insert(hash);
+                if hashes.len() > EXACT_CARDINALITY {
+                    let mut registers = vec![0; 1 << PRECISION];
+                    for hash in hashes.iter() {
+                        Self::register(&mut registers, *hash);
+                    }
+                    *self = Self::Estimated(registers);
+                }
+            }
+            Self::Estimated(registers) => Self::register(registers, hash),
+        }
+    }
+
+    fn register(registers: &mut [u8], hash: u64) {
+        let index = (hash >> (64 - PRECISION)) as usize;
+        let rank = ((hash << PRECISION) | (1 << (PRECISION - 1))).leading_zeros() as u8 + 1;
+        registers[index] = registers[index].max(rank);
+    }
+
+    /// The distinct count, and whether it is exact.
+    fn count(&self) -> (u64, bool) {
+        match self {
+            Self::Exact(hashes) => (hashes.len() as u64, true),
+            Self::Estimated(registers) => {
+                let m = registers.len() as f64;
+                let alpha = 0.7213 / (1.0 + 1.079 / m);
+                let sum: f64 = registers.iter().map(|r| 2f64.powi(-i32::from(*r))).sum();
+                let estimate = alpha * m * m / sum;
+                let zeros = registers.iter().filter(|r| **r == 0).count();
+                let estimate = match estimate <= 2.5 * m && zeros > 0 {
+                    true => m * (m / zeros as f64).ln(),
+                    false => estimate,
+                };
+                (estimate.round() as u64, false)
+            }
+        }
+    }
+}
+
+/// Profiles every field of the inputs: how many documents have it, how many
+/// have it only as `null`, the types of its values, how many distinct values
+/// it has, and examples of them, for designing a mapping before a load.
+#[tracing::instrument(name = "espipe_profile", skip_all)]
+pub async fn run(args: Cli) -> Result<()> {
+    let Some(Command::Profile { inputs, top }) = &args.command else {
+        unreachable!("profile::run is only called for espipe profile");
+    };
+    let input =
+        Input::try_new_with_remote(inputs.clone(), args.content.clone(), remote_options(&args)?)
+            .await?
+            .with_input_encoding(input_encoding(&args))?
+            .with_archive_glob(args.archive_glob.clone(), args.zip_password.clone())?
+            .with_input_format(args.input_format)?;
+    let name = input.to_string();
+    let mut input = InputReader::spawn(input, None);
+    let mut profile = Profile::default();
+    loop {
+        match input.next().await? {
+            ReadEvent::Document(doc) => profile.add(&serde_json::from_str(doc.get())?),
+            ReadEvent::Malformed(malformed) => {
+                return Err(eyre!("{name}: {malformed}; espipe lint lists every one"));
+            }
+            ReadEvent::Checkpoint { .. } => input.commit()?,
+            ReadEvent::Flush => {}
+            ReadEvent::End => break,
+        }
+    }
+    profile.print(&name, *top);
+    Ok(())
+}
+
+impl Profile {
+    fn add(&mut self, doc: &Value) {
+        self.docs += 1;
+        // Whether each field of the document has a value, or only nulls.
+        let mut fields = BTreeMap::new();
+        if let Value::Object(object) = doc {
+            for (key, value) in object {
+                self.add_field(key.clone(), value, &mut fields);
+            }
+        }
+        for (path, has_value) in fields {
+            if let Some(field) = self.fields.get_mut(&path) {
+                match has_value {
+                    true => field.present += 1,
+                    false => field.nulls += 1,
+                }
+            }
+        }
+    }
+
+    fn add_field(&mut self, path: String, value: &Value, fields: &mut BTreeMap<String, bool>) {
+        if !self.fields.contains_key(&path) {
+            if self.fields.len() >= MAX_FIELDS {
+                self.untracked.add(xxh3_64(path.as_bytes()));
+                return;
+            }
+            self.fields.insert(path.clone(), FieldProfile::default());
+        }
+        fields.entry(path.clone()).or_insert(false);
+        if let Value::Array(items) = value {
+            for item in items {
+                self.add_field(path.clone(), item, fields);
+            }
+            return;
+        }
+        let Some(kind) = field_type(value) else {
+            return;
+        };
+        fields.insert(path.clone(), true);
+        let field = self.fields.get_mut(&path).expect("field was added");
+        *field.types.entry(kind).or_default() += 1;
+        if let Value::Object(object) = value {
+            for (key, value) in object {
+                self.add_field(format!("{path}.{key}"), value, fields);
+            }
+            return;
+        }
+        field.cardinality.add(xxh3_64(value.to_string().as_bytes()));
+        let example = match value {
+            Value::String(text) => text.clone(),
+            value => value.to_string(),
+        };
+        if field.examples.len() < EXAMPLES && !field.examples.contains(&example) {
+            field.examples.push(example);
+        }
+    }
+
+    /// Prints a row per field, in order of the documents that have it, or
+    /// only the `top` most common fields.
+    fn print(&self, name: &str, top: Option<usize>) {
+        println!("{name}: {} docs", comma_formatted(self.docs as usize));
+        let mut fields: Vec<_> = self.fields.iter().collect();
+        fields
+            .sort_by(|(a_path, a), (b_path, b)| b.present.cmp(&a.present).then(a_path.cmp(b_path)));
+        fields.truncate(top.unwrap_or(usize::MAX));
+        let rows: Vec<[String; 6]> = fields
+            .into_iter()
+            .map(|(path, field)| field.row(path, self.docs))
+            .collect();
+        let header =
+            ["FIELD", "PRESENT", "NULL", "TYPES", "DISTINCT", "EXAMPLES"].map(String::from);
+        let mut widths = header.clone().map(|column| column.chars().count());
+        for row in &rows {
+            for (width, column) in widths.iter_mut().zip(row) {
+                *width = (*width).max(column.chars().count());
+            }
+        }
+        for row in std::iter::once(&header).chain(&rows) {
+            let line: Vec<_> = row
+                .iter()
+                .zip(widths)
+                .map(|(column, width)| format!("{column:width$}"))
+                .collect();
+            println!("{}", line.join("  ").trim_end());
+        }
+        if let (untracked @ 1.., _) = self.untracked.count() {
+            println!(
+                "About {} more fields were not profiled, past the first {}",
+                comma_formatted(untracked as usize),
+                comma_formatted(MAX_FIELDS)
+            );
+        }
+    }
+}
+
+impl FieldProfile {
+    fn row(&self, path: &str, docs: u64) -> [String; 6] {
+        let percent = |count: u64| format!("{:.1}%", count as f64 * 100.0 / docs.max(1) as f64);
+        let types: Vec<_> = self.types.keys().copied().collect();
+        let distinct = match self.cardinality.count() {
+            (0, _) if self.types.contains_key("object") => "-".to_string(),
+            (count, true) => comma_formatted(count as usize),
+            (count, false) => format!("~{}", comma_formatted(count as usize)),
+        };
+        let examples: Vec<_> = self
+            .examples
+            .iter()
+            .map(|example| match example.chars().count() > EXAMPLE_CHARS {
+                true => format!(
+                    "{}...",
+                    example.chars().take(EXAMPLE_CHARS).collect::<String>()
+                ),
+                false => example.clone(),
+            })
+            .collect();
+        [
+            path.to_string(),
+            percent(self.present),
+            percent(self.nulls),
+            types.join(","),
+            distinct,
+            examples.join(" | "),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cardinality, Profile};
+    use serde_json::json;
+
+    #[test]
+    fn fields_are_profiled_by_presence_type_and_cardinality() {
+        let mut profile = Profile::default();
+        for n in 0..2000 {
+            profile.add(&json!({
+                "id": n,
+                "level": if n % 2 == 0 { "info" } else { "warn" },
+                "user": {"name": if n < 1000 { json!(null) } else { json!("ana") }},
+                "tags": if n % 4 == 0 { json!([]) } else { json!(["a", 1]) },
+            }));
+        }
+
+        let level = &profile.fields["level"];
+        assert_eq!((level.present, level.nulls), (2000, 0));
+        assert_eq!(level.cardinality.count(), (2, true));
+        assert_eq!(level.examples, ["info", "warn"]);
+        let name = &profile.fields["user.name"];
+        assert_eq!((name.present, name.nulls), (1000, 1000));
+        let tags = &profile.fields["tags"];
+        assert_eq!((tags.present, tags.nulls), (1500, 500));
+        assert_eq!(
+            tags.types.keys().copied().collect::<Vec<_>>(),
+            ["integer", "string"]
+        );
+
+        let (ids, exact) = profile.fields["id"].cardinality.count();
+        assert!(!exact);
+        assert!((1900..=2100).contains(&ids), "estimated {ids} distinct ids");
+        assert_eq!(Cardinality::default().count(), (0, true));
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{comma_formatted, input::InputFormat, metrics::METRICS};
+use eyre::Result;
+use fluent_uri::UriRef;
+use std::{
+    fmt::{self, Display, Formatter},
+    fs::File,
+    io::{BufRead, BufReader, IsTerminal, Read},
+    path::Path,
+    sync::atomic::Ordering,
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+
+/// Files up to this size have their lines counted; larger ones are estimated
+/// from the lines in their first `SAMPLE_BYTES`.
+const SCAN_LIMIT: u64 = 256 * 1024 * 1024;
+const SAMPLE_BYTES: u64 = 4 * 1024 * 1024;
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The number of documents in the inputs, counted or estimated before the
+/// run, which progress and the summary are reported against.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InputTotal {
+    docs: usize,
+    /// Whether every line was counted rather than estimated from a sample.
+    exact: bool,
+}
+
+impl InputTotal {
+    /// Counts the documents of inputs that are all local NDJSON or CSV files,
+    /// which have one per non-blank line after any CSV header. Other inputs,
+    /// like stdin, URLs, globs, or compressed files, have no known total.
+    pub fn of(inputs: &[UriRef<String>], format: InputFormat) -> Result<Option<Self>> {
+        let mut total = Self {
+            docs: 0,
+            exact: true,
+        };
+        for input in inputs {
+            if !matches!(
+                input.scheme().map(|scheme| scheme.as_str()),
+                Some("file") | None
+            ) {
+                return Ok(None);
+            }
+            let path = Path::new(input.path().as_str());
+            let extension = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .map(str::to_ascii_lowercase);
+            let header = match (extension.as_deref(), format) {
+                (Some("ndjson"), InputFormat::Auto | InputFormat::Ndjson) => false,
+                (Some("csv"), InputFormat::Auto | InputFormat::Csv) => true,
+                _ => return Ok(None),
+            };
+            if !path.is_file() {
+                return Ok(None);
+            }
+            let file = Self::of_file(path)?;
+            total.docs += file.docs.saturating_sub(usize::from(header));
+            total.exact &= file.exact;
+        }
+        Ok(Some(total))
+    }
+
+    fn of_file(path: &Path) -> Result<Self> {
+        let size = path.metadata()?.len();
+        let file = File::open(path)?;
+        if size <= SCAN_LIMIT {
+            return Ok(Self {
+                docs: count_lines(BufReader::new(file))?,
+                exact: true,
+            });
+        }
+        let sample = count_lines(BufReader::new(file.take(SAMPLE_BYTES)))?;
+        Ok(Self {
+            docs: (sample as f64 * size as f64 / SAMPLE_BYTES as f64).round() as usize,
+            exact: false,
+        })
+    }
+
+    pub fn percent(&self, read: usize) -> f64 {
+        match self.docs {
+            0 => 100.0,
+            docs => read as f64 * 100.0 / docs as f64,
+        }
+    }
+
+    /// Whether a completed run that read `read` documents stopped short of a
+    /// counted total, as it does when a file is truncated while being read.
+    pub fn is_short(&self, read: usize) -> bool {
+        self.exact && read < self.docs
+    }
+}
+
+impl Display for InputTotal {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let approximately = if self.exact { "" } else { "~" };
+        write!(f, "{approximately}{}", comma_formatted(self.docs))
+    }
+}
+
+/// Lines with anything but whitespace on them, as the NDJSON reader skips blank ones.
+fn count_lines(mut reader: impl BufRead) -> Result<usize> {
+    let mut lines = 0;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(lines);
+        }
+        if line.iter().any(|byte| !byte.is_ascii_whitespace()) {
+            lines += 1;
+        }
+    }
+}
+
+/// Reports the documents read and sent so far to stderr once a second, as a
+/// percentage of the input total when there is one.
+pub struct Progress {
+    task: JoinHandle<()>,
+    terminal: bool,
+}
+
+impl Progress {
+    pub fn start(total: Option<InputTotal>) -> Self {
+        let terminal = std::io::stderr().is_terminal();
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PROGRESS_INTERVAL);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let line = status(total);
+                match terminal {
+                    // Overwrites the previous status, padded over a longer one.
+                    true => eprint!("\r{line:<72}"),
+                    false => eprintln!("{line}"),
+                }
+            }
+        });
+        Self { task, terminal }
+    }
+
+    /// Stops reporting, clearing the status line from a terminal.
+    pub fn finish(self) {
+        self.task.abort();
+        if self.terminal {
+            eprint!("\r{:72}\r", "");
+        }
+    }
+}
+
+fn status(total: Option<InputTotal>) -> String {
+    let read = METRICS.docs_read.load(Ordering::Relaxed) as usize;
+    let sent = comma_formatted(METRICS.docs_sent.load(Ordering::Relaxed) as usize);
+    match total {
+        Some(total) => format!(
+            "Read {} of {total} docs ({:.1}%), sent {sent}",
+            comma_formatted(read),
+            total.percent(read).min(100.0)
+        ),
+        None => format!("Read {} docs, sent {sent}", comma_formatted(read)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InputTotal, count_lines};
+    use crate::input::InputFormat;
+    use fluent_uri::UriRef;
+    use std::io::Write;
+
+    fn total(name: &str, contents: &str, format: InputFormat) -> Option<InputTotal> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(contents.as_bytes())
+            .unwrap();
+        let uri = UriRef::parse(path.to_str().unwrap().to_string()).unwrap();
+        InputTotal::of(&[uri], format).unwrap()
+    }
+
+    #[test]
+    fn blank_lines_and_csv_headers_are_not_counted() {
+        assert_eq!(count_lines(&b"{}\n\n  \n{}"[..]).unwrap(), 2);
+        let ndjson = total("docs.ndjson", "{}\n{}\n\n{}\n", InputFormat::Auto).unwrap();
+        assert_eq!(ndjson.to_string(), "3");
+        let csv = total("docs.csv", "a,b\n1,2\n3,4\n", InputFormat::Auto).unwrap();
+        assert_eq!(csv.to_string(), "2");
+        assert!(csv.is_short(1));
+        assert!(!csv.is_short(2));
+    }
+
+    #[test]
+    fn other_inputs_have_no_total() {
+        assert!(total("docs.ndjson.gz", "", InputFormat::Auto).is_none());
+        assert!(total("docs.ndjson", "{}\n", InputFormat::Syslog).is_none());
+        let stdin = UriRef::parse("-".to_string()).unwrap();
+        assert!(
+            InputTotal::of(&[stdin], InputFormat::Auto)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
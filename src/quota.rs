@@ -0,0 +1,95 @@
+use crate::{comma_formatted, document::Document};
+use std::fmt::{self, Display, Formatter};
+
+/// The `--max-docs` and `--max-bytes` safety caps, which end a run once it
+/// has read that many documents or bytes of them, so an unexpectedly huge
+/// input cannot flood a shared cluster.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Quota {
+    max_docs: Option<u64>,
+    max_bytes: Option<u64>,
+    docs: u64,
+    bytes: u64,
+    reached: Option<Cap>,
+}
+
+/// The cap that ended a run before the end of its input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cap {
+    Docs(u64),
+    Bytes(u64),
+}
+
+impl Quota {
+    pub fn new(max_docs: Option<u64>, max_bytes: Option<u64>) -> Self {
+        Self {
+            max_docs,
+            max_bytes,
+            ..Self::default()
+        }
+    }
+
+    /// Counts `doc` against the caps, or returns `false` when reading it would
+    /// go over one, so the run should stop before it.
+    pub fn admits(&mut self, doc: &Document) -> bool {
+        if self.reached.is_some() {
+            return false;
+        }
+        let bytes = self.bytes + doc.get().len() as u64;
+        self.reached = match (self.max_docs, self.max_bytes) {
+            (Some(max_docs), _) if self.docs >= max_docs => Some(Cap::Docs(max_docs)),
+            (_, Some(max_bytes)) if bytes > max_bytes => Some(Cap::Bytes(max_bytes)),
+            _ => None,
+        };
+        if self.reached.is_some() {
+            return false;
+        }
+        self.docs += 1;
+        self.bytes = bytes;
+        true
+    }
+
+    /// The cap that stopped the run, if one did.
+    pub fn reached(&self) -> Option<Cap> {
+        self.reached
+    }
+}
+
+impl Display for Cap {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Cap::Docs(docs) => write!(f, "--max-docs {}", comma_formatted(*docs as usize)),
+            Cap::Bytes(bytes) => {
+                write!(f, "--max-bytes {} bytes", comma_formatted(*bytes as usize))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cap, Quota};
+    use crate::document::Document;
+
+    fn doc(json: &str) -> Document {
+        Document::unvalidated(json).unwrap()
+    }
+
+    #[test]
+    fn the_run_stops_before_the_document_over_a_cap() {
+        let mut quota = Quota::new(Some(2), None);
+        assert!(quota.admits(&doc("{}")));
+        assert!(quota.admits(&doc("{}")));
+        assert!(!quota.admits(&doc("{}")));
+        assert_eq!(quota.reached(), Some(Cap::Docs(2)));
+
+        let mut quota = Quota::new(None, Some(10));
+        assert!(quota.admits(&doc(r#"{"a":1}"#)));
+        assert!(!quota.admits(&doc(r#"{"b":2}"#)));
+        assert_eq!(quota.reached().unwrap().to_string(), "--max-bytes 10 bytes");
+
+        let mut quota = Quota::default();
+        assert!(quota.admits(&doc("{}")));
+        assert_eq!(quota.reached(), None);
+    }
+}
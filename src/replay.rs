@@ -0,0 +1,135 @@
+use crate::{document::Document, field_path};
+use jiff::{Timestamp, civil::DateTime, tz::TimeZone};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+
+/// How long documents may wait in a partial batch while a replay is paced.
+const REPLAY_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Paces documents by the gaps between their timestamps, so traffic is
+/// replayed the way it was recorded rather than as fast as possible.
+///
+/// The first timestamped document sets the clock: every later one is due when
+/// as much time has passed since the replay started as passed between the two
+/// timestamps, divided by the speed. Documents without a readable timestamp
+/// are sent straight away, as are ones already due, such as late arrivals
+/// recorded out of order.
+pub struct Replay {
+    field: String,
+    speed: f64,
+    /// The first document's timestamp in milliseconds, and when it was sent.
+    start: Option<(i64, Instant)>,
+    last_flush: Instant,
+    unpaced: usize,
+}
+
+impl Replay {
+    pub fn new(field: String, speed: f64) -> Self {
+        Self {
+            field,
+            speed,
+            start: None,
+            last_flush: Instant::now(),
+            unpaced: 0,
+        }
+    }
+
+    /// How long to hold `doc` before sending it.
+    pub fn delay(&mut self, doc: &Document) -> Duration {
+        let Some(millis) = self.timestamp(doc) else {
+            self.unpaced += 1;
+            return Duration::ZERO;
+        };
+        let Some((first, started)) = self.start else {
+            self.start = Some((millis, Instant::now()));
+            return Duration::ZERO;
+        };
+        let offset = millis.saturating_sub(first).max(0) as f64 / 1000.0 / self.speed;
+        let due = started + Duration::from_secs_f64(offset);
+        due.saturating_duration_since(Instant::now())
+    }
+
+    /// Whether documents have waited long enough in the output's batches that
+    /// they should be flushed before the next delay.
+    pub fn flush_due(&mut self, delay: Duration) -> bool {
+        let due = delay >= REPLAY_FLUSH_INTERVAL
+            || self.last_flush.elapsed() + delay >= REPLAY_FLUSH_INTERVAL;
+        if due {
+            self.last_flush = Instant::now();
+        }
+        due
+    }
+
+    /// Documents sent without pacing because their timestamp was missing or unreadable.
+    pub fn unpaced(&self) -> usize {
+        self.unpaced
+    }
+
+    fn timestamp(&self, doc: &Document) -> Option<i64> {
+        let value: Value = serde_json::from_str(doc.get()).ok()?;
+        parse_timestamp(field_path::lookup(&value, &self.field)?).map(|time| time.as_millisecond())
+    }
+}
+
+/// A timestamp field's value as an RFC 3339 string, a civil date and time
+/// taken as UTC, or epoch milliseconds.
+pub fn parse_timestamp(value: &Value) -> Option<Timestamp> {
+    match value {
+        Value::Number(millis) => Timestamp::from_millisecond(millis.as_i64()?).ok(),
+        Value::String(text) => match text.parse::<Timestamp>() {
+            Ok(time) => Some(time),
+            Err(_) => text
+                .parse::<DateTime>()
+                .ok()?
+                .to_zoned(TimeZone::UTC)
+                .ok()
+                .map(|time| time.timestamp()),
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Replay;
+    use crate::document::Document;
+    use std::time::Duration;
+
+    fn doc(json: &str) -> Document {
+        Document::unvalidated(json).unwrap()
+    }
+
+    #[test]
+    fn documents_are_held_for_the_gap_since_the_first_scaled_by_speed() {
+        let mut replay = Replay::new("event.created".to_string(), 10.0);
+        let first = doc(r#"{"event":{"created":"2024-05-01T12:00:00Z"}}"#);
+        let later = doc(r#"{"event":{"created":"2024-05-01T12:00:20Z"}}"#);
+        let earlier = doc(r#"{"event":{"created":"2024-05-01T11:59:00"}}"#);
+
+        assert_eq!(replay.delay(&first), Duration::ZERO);
+        let delay = replay.delay(&later);
+        assert!(
+            delay > Duration::from_millis(1900) && delay <= Duration::from_secs(2),
+            "{delay:?}"
+        );
+        assert_eq!(replay.delay(&earlier), Duration::ZERO);
+        assert_eq!(
+            replay.delay(&doc(r#"{"message":"no time"}"#)),
+            Duration::ZERO
+        );
+        assert_eq!(replay.unpaced(), 1);
+    }
+
+    #[test]
+    fn epoch_milliseconds_are_timestamps_too() {
+        let mut replay = Replay::new("ts".to_string(), 1.0);
+        assert_eq!(
+            replay.delay(&doc(r#"{"ts":1714564800000}"#)),
+            Duration::ZERO
+        );
+        let delay = replay.delay(&doc(r#"{"ts":1714564800500}"#));
+        assert!(delay > Duration::from_millis(400), "{delay:?}");
+        assert!(replay.flush_due(Duration::from_secs(1)));
+        assert!(!replay.flush_due(Duration::from_millis(10)));
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{
+    Cli, Command,
+    client::{Auth, elasticsearch::request},
+    comma_formatted,
+    dump::{DumpManifest, DumpedIndex},
+    output::{self, ElasticsearchOutputConfig},
+};
+use elasticsearch::{Elasticsearch, http::Method};
+use eyre::{Result, eyre};
+use fluent_uri::UriRef;
+use serde_json::Value;
+use std::{fs, path::Path};
+use tracing::Instrument;
+use url::Url;
+
+/// Recreates each index of an `espipe dump` directory on the target cluster
+/// and pipes its bulk file back in, one index at a time.
+///
+/// Indices with an `<index>.index.json` are created from it first; the rest
+/// are left for the first bulk request to create. `--rename` renames both.
+#[tracing::instrument(name = "espipe_restore", skip_all)]
+pub async fn run(args: Cli, arguments: Vec<String>) -> Result<()> {
+    let start_time = std::time::Instant::now();
+    let Some(Command::Restore { dir, target }) = &args.command else {
+        unreachable!("restore::run is only called for espipe restore");
+    };
+    let manifest = DumpManifest::read(dir)?;
+    let auth = Auth::try_new(
+        args.apikey.clone(),
+        args.username.clone(),
+        args.password.clone(),
+    )?;
+    let config = ElasticsearchOutputConfig::default()
+        .with_timeouts(args.request_timeout, None)
+        .with_proxy(args.proxy.clone())
+        .with_tls_server_name(args.tls_server_name.clone())
+        .with_socket_options(
+            args.connect_timeout,
+            args.tcp_keepalive,
+            args.pool_idle_timeout,
+        )
+        .with_headers(args.headers.clone());
+    let (client, url) =
+        output::elasticsearch_client(args.insecure, auth, target, !args.uncompressed, &config)?;
+    if !url.path().trim_start_matches('/').is_empty() {
+        return Err(eyre!(
+            "espipe restore takes a cluster without an index, the dump names each index"
+        ));
+    }
+
+    let mut documents = 0;
+    for index in manifest.indices() {
+        let name = renamed(&args.rename, &index.index);
+        let span = tracing::info_span!("restore", index = %name);
+        let output = output_uri(target, &url, &name)?;
+        documents += restore_index(&client, &args, &arguments, dir, index, &name, output)
+            .instrument(span)
+            .await?;
+    }
+    if !args.quiet {
+        println!(
+            "Restored {} docs into {} indices from {} in {:.3} seconds",
+            comma_formatted(documents),
+            comma_formatted(manifest.indices().len()),
+            dir.display(),
+            start_time.elapsed().as_secs_f32()
+        );
+    }
+    Ok(())
+}
+
+async fn restore_index(
+    client: &Elasticsearch,
+    args: &Cli,
+    arguments: &[String],
+    dir: &Path,
+    index: &DumpedIndex,
+    name: &str,
+    output: String,
+) -> Result<usize> {
+    if let Some(index_file) = &index.index_file {
+        let path = dir.join(index_file);
+        let body: Value = serde_json::from_slice(
+            &fs::read(&path).map_err(|err| eyre!("failed to read {}: {err}", path.display()))?,
+        )
+        .map_err(|err| eyre!("failed to parse {}: {err}", path.display()))?;
+        request(client, Method::Put, &format!("/{name}"), &[], Some(&body))
+            .await
+            .map_err(|err| eyre!("failed to create index {name}: {err}"))?;
+        log::info!("Created index {name} from {index_file}");
+    }
+
+    let input = dir.join(&index.file).to_string_lossy().into_owned();
+    let mut cli = args.clone();
+    cli.command = None;
+    cli.paths = [input, output]
+        .into_iter()
+        .map(|path| UriRef::parse(path).map_err(|(err, path)| eyre!("invalid URI {path}: {err}")))
+        .collect::<Result<_>>()?;
+    let counts = crate::run(cli, arguments.to_vec(), None).await?;
+    Ok(counts.output_line)
+}
+
+/// The output URI for one restored index. The bulk file's action lines carry
+/// the dumped index name, which `--rename` rewrites in the output.
+fn output_uri(target: &UriRef<String>, url: &Url, name: &str) -> Result<String> {
+    match target.scheme().map(|scheme| scheme.as_str()) {
+        Some("http" | "https") => Ok(url.join(name)?.to_string()),
+        Some(scheme) => Ok(format!("{scheme}:{name}")),
+        None => unreachable!("elasticsearch_client rejects URIs without a scheme"),
+    }
+}
+
+fn renamed(renames: &[(String, String)], index: &str) -> String {
+    renames
+        .iter()
+        .find(|(from, _)| from == index)
+        .map_or(index, |(_, to)| to)
+        .to_string()
+}
@@ -0,0 +1,117 @@
+use crate::{
+    Cli, Command,
+    client::{Auth, elasticsearch::request},
+    comma_formatted,
+    manifest::redact_uri,
+    output::{self, ElasticsearchOutputConfig},
+};
+use elasticsearch::http::Method;
+use eyre::{Result, eyre};
+use jiff::Timestamp;
+use serde_json::{Value, json};
+use std::time::{SystemTime, UNIX_EPOCH};
+use xxhash_rust::xxh3::xxh3_64;
+
+/// The field `--tag-run` stamps, and `espipe rollback` searches, by default.
+pub const RUN_ID_FIELD: &str = "espipe.run_id";
+
+/// A new run ID: the UTC start time, then a hash of the clock and process ID,
+/// like `20261016t101500-3f9a2c1b`. Lowercase so it matches as written in
+/// `text` fields as well as `keyword` ones.
+pub fn new_run_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+    let seed = format!("{nanos}-{}", std::process::id());
+    format!(
+        "{}-{:08x}",
+        Timestamp::now().strftime("%Y%m%dt%H%M%S"),
+        xxh3_64(seed.as_bytes()) as u32
+    )
+}
+
+/// Deletes the documents a `--tag-run` run stamped with its run ID from an
+/// index, to undo loading the wrong file into the wrong index.
+#[tracing::instrument(name = "espipe_rollback", skip_all)]
+pub async fn run(args: Cli) -> Result<()> {
+    let Some(Command::Rollback {
+        target,
+        run_id,
+        field,
+    }) = &args.command
+    else {
+        unreachable!("rollback::run is only called for espipe rollback");
+    };
+    let config = ElasticsearchOutputConfig::default()
+        .with_timeouts(args.request_timeout, None)
+        .with_proxy(args.proxy.clone())
+        .with_tls_server_name(args.tls_server_name.clone())
+        .with_socket_options(
+            args.connect_timeout,
+            args.tcp_keepalive,
+            args.pool_idle_timeout,
+        )
+        .with_headers(args.headers.clone());
+    let auth = Auth::try_new(
+        args.apikey.clone(),
+        args.username.clone(),
+        args.password.clone(),
+    )?;
+    let (client, url) =
+        output::elasticsearch_client(args.insecure, auth, target, !args.uncompressed, &config)?;
+    let index = url.path().trim_start_matches('/');
+    if index.is_empty() || index.contains('/') {
+        return Err(eyre!(
+            "espipe rollback needs the index the run wrote to, like {url}my-index"
+        ));
+    }
+
+    let response = request(
+        &client,
+        Method::Post,
+        &format!("/{index}/_delete_by_query"),
+        &[("conflicts", "proceed"), ("refresh", "true")],
+        Some(&run_query(field, run_id)),
+    )
+    .await?;
+    let deleted = response["deleted"].as_u64().unwrap_or_default() as usize;
+    let failures = response["failures"].as_array().map_or(0, Vec::len);
+    if !args.quiet {
+        println!(
+            "Deleted {} docs with {field} {run_id} from {}",
+            comma_formatted(deleted),
+            redact_uri(url.as_str())
+        );
+    }
+    match (failures, deleted) {
+        (0, 0) => Err(eyre!("no documents in {index} have {field} {run_id}")),
+        (0, _) => Ok(()),
+        (failures, _) => Err(eyre!(
+            "{failures} documents could not be deleted from {index}"
+        )),
+    }
+}
+
+/// Matches the run ID whether the field is mapped as `keyword` or `text`.
+fn run_query(field: &str, run_id: &str) -> Value {
+    json!({ "query": { "match_phrase": { field: run_id } } })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{new_run_id, run_query};
+    use serde_json::json;
+
+    #[test]
+    fn run_ids_are_lowercase_and_unique() {
+        let run_id = new_run_id();
+        assert_eq!(run_id.len(), "20261016t101500-3f9a2c1b".len());
+        assert_eq!(run_id, run_id.to_lowercase());
+        assert_ne!(run_id, new_run_id());
+        assert_eq!(
+            run_query("espipe.run_id", &run_id),
+            json!({ "query": { "match_phrase": { "espipe.run_id": run_id } } })
+        );
+    }
+}
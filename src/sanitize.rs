@@ -0,0 +1,221 @@
+use crate::{document::Document, metadata::is_update};
+use clap::ValueEnum;
+use eyre::{Result, eyre};
+use serde_json::{Map, Value};
+
+/// The name given to keys that are empty, or nothing but underscores.
+const EMPTY_KEY: &str = "empty_key";
+
+/// How `--sanitize-keys` fixes keys with dots in them.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum KeyStrategy {
+    /// Turn `a.b` into `{"a": {"b": ...}}`, as Elasticsearch would map it
+    #[default]
+    Nest,
+    /// Turn `a.b` into `a_b`
+    Underscore,
+}
+
+/// Renames the field names Elasticsearch rejects or that collide with its
+/// metadata fields, so documents from messy sources load cleanly.
+///
+/// Leading underscores are stripped, control characters become underscores,
+/// empty keys are named `empty_key`, and dotted keys are fixed by the
+/// [`KeyStrategy`]. Keys are fixed in the sorted order of their original
+/// names, and one that lands on a name already taken merges into it when both
+/// are objects and replaces it otherwise, so of several colliding keys the one
+/// that sorts last wins whatever their order in the document. A key that
+/// needed no fixing sorts after its underscored forms, so `id` wins over
+/// `_id`. Bulk `update` pairs have the keys of their partial `doc` fixed.
+#[derive(Debug)]
+pub struct KeySanitizer {
+    strategy: KeyStrategy,
+    sanitized: usize,
+}
+
+impl KeySanitizer {
+    pub fn new(strategy: KeyStrategy) -> Self {
+        Self {
+            strategy,
+            sanitized: 0,
+        }
+    }
+
+    pub fn apply(&mut self, doc: Document) -> Result<Document> {
+        let mut value: Value = serde_json::from_str(doc.get())
+            .map_err(|err| eyre!("Error parsing JSON for --sanitize-keys: {err}"))?;
+        let target = match doc.action().is_some_and(is_update) {
+            true => value.get_mut("doc"),
+            false => Some(&mut value),
+        };
+        let Some(Value::Object(object)) = target else {
+            return Ok(doc);
+        };
+        if !self.sanitize_object(object) {
+            return Ok(doc);
+        }
+        self.sanitized += 1;
+        Ok(doc.with_json(value.to_string()))
+    }
+
+    /// Documents that had any key renamed.
+    pub fn sanitized(&self) -> usize {
+        self.sanitized
+    }
+
+    /// Fixes the keys of an object and the objects it holds, returning whether
+    /// any changed.
+    fn sanitize_object(&self, object: &mut Map<String, Value>) -> bool {
+        let mut changed = false;
+        for value in object.values_mut() {
+            changed |= self.sanitize_value(value);
+        }
+        if !object.keys().any(|key| needs_sanitizing(key)) {
+            return changed;
+        }
+        let mut fields: Vec<_> = std::mem::take(object).into_iter().collect();
+        fields.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        for (key, value) in fields {
+            let path = match self.strategy {
+                KeyStrategy::Nest => nested_path(&key),
+                KeyStrategy::Underscore => vec![sanitize_key(&key.replace('.', "_"))],
+            };
+            insert(object, &path, value);
+        }
+        true
+    }
+
+    fn sanitize_value(&self, value: &mut Value) -> bool {
+        match value {
+            Value::Object(object) => self.sanitize_object(object),
+            Value::Array(values) => values
+                .iter_mut()
+                .fold(false, |changed, value| self.sanitize_value(value) | changed),
+            _ => false,
+        }
+    }
+}
+
+fn needs_sanitizing(key: &str) -> bool {
+    key.is_empty() || key.contains('.') || key.starts_with('_') || key.contains(char::is_control)
+}
+
+/// The keys of the objects a dotted key nests into, skipping empty parts.
+fn nested_path(key: &str) -> Vec<String> {
+    let path: Vec<String> = key
+        .split('.')
+        .filter(|part| !part.is_empty())
+        .map(sanitize_key)
+        .collect();
+    match path.is_empty() {
+        true => vec![EMPTY_KEY.to_string()],
+        false => path,
+    }
+}
+
+fn sanitize_key(key: &str) -> String {
+    let key: String = key
+        .trim_start_matches('_')
+        .chars()
+        .map(|char| match char.is_control() {
+            true => '_',
+            false => char,
+        })
+        .collect();
+    match key.is_empty() {
+        true => EMPTY_KEY.to_string(),
+        false => key,
+    }
+}
+
+/// Inserts a value at a path of already sanitized keys, merging objects that
+/// meet on the way.
+fn insert(object: &mut Map<String, Value>, path: &[String], value: Value) {
+    let (key, rest) = path.split_first().expect("split always yields a key");
+    if rest.is_empty() {
+        match (object.get_mut(key), value) {
+            (Some(Value::Object(existing)), Value::Object(fields)) => {
+                for (field, value) in fields {
+                    insert(existing, &[field], value);
+                }
+            }
+            (_, value) => {
+                object.insert(key.clone(), value);
+            }
+        }
+        return;
+    }
+    let entry = object
+        .entry(key.clone())
+        .or_insert_with(|| Value::Object(Map::new()));
+    if !entry.is_object() {
+        *entry = Value::Object(Map::new());
+    }
+    insert(
+        entry.as_object_mut().expect("entry is an object"),
+        rest,
+        value,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeySanitizer, KeyStrategy};
+    use crate::document::Document;
+    use serde_json::{Value, json};
+
+    fn sanitize(strategy: KeyStrategy, json: &str) -> Value {
+        let doc = KeySanitizer::new(strategy)
+            .apply(Document::unvalidated(json).unwrap())
+            .unwrap();
+        serde_json::from_str(doc.get()).unwrap()
+    }
+
+    #[test]
+    fn dotted_keys_are_nested_or_underscored() {
+        let json = r#"{"host.name":"a","host":{"ip":"10.0.0.1"},"tags":[{"k.v":1}]}"#;
+        assert_eq!(
+            sanitize(KeyStrategy::Nest, json),
+            json!({"host": {"ip": "10.0.0.1", "name": "a"}, "tags": [{"k": {"v": 1}}]})
+        );
+        assert_eq!(
+            sanitize(KeyStrategy::Underscore, json),
+            json!({"host": {"ip": "10.0.0.1"}, "host_name": "a", "tags": [{"k_v": 1}]})
+        );
+    }
+
+    #[test]
+    fn empty_underscored_and_control_keys_are_renamed() {
+        assert_eq!(
+            sanitize(
+                KeyStrategy::Nest,
+                r#"{"":1,"_id":2,"__":3,"a\tb":4,"x..y":5,"ok":6,".":7}"#
+            ),
+            json!({"a_b": 4, "empty_key": 3, "id": 2, "ok": 6, "x": {"y": 5}})
+        );
+        let json = r#"{"ok":{"nested":[1,2]}}"#;
+        let doc = KeySanitizer::new(KeyStrategy::Nest)
+            .apply(Document::unvalidated(json).unwrap())
+            .unwrap();
+        assert_eq!(doc.get(), json);
+    }
+
+    #[test]
+    fn colliding_keys_resolve_by_sorted_name_not_document_order() {
+        for json in [r#"{"id":1,"_id":2}"#, r#"{"_id":2,"id":1}"#] {
+            assert_eq!(sanitize(KeyStrategy::Nest, json), json!({"id": 1}));
+        }
+        for json in [r#"{"":1,"__":3,".":7}"#, r#"{".":7,"__":3,"":1}"#] {
+            assert_eq!(sanitize(KeyStrategy::Nest, json), json!({"empty_key": 3}));
+        }
+        for json in [
+            r#"{"a.b":1,"a_b":2,"a":{"c":3}}"#,
+            r#"{"a":{"c":3},"a_b":2,"a.b":1}"#,
+        ] {
+            assert_eq!(
+                sanitize(KeyStrategy::Underscore, json),
+                json!({"a": {"c": 3}, "a_b": 2})
+            );
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use crate::document::Document;
+use clap::ValueEnum;
+use eyre::{Result, eyre};
+use serde_json::value::RawValue;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+const SPOOL_FILE: &str = "spool.ndjson";
+const UNCONFIRMED_FILE: &str = "unconfirmed.ndjson";
+const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// When `--spool` writes reach the disk.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum SpoolFsync {
+    /// Sync after every document, surviving power loss at the cost of throughput
+    Always,
+    /// Sync at most once a second
+    #[default]
+    Interval,
+    /// Leave syncing to the operating system, surviving only process crashes
+    Never,
+}
+
+/// A write-ahead queue of the documents read but not yet acknowledged by the
+/// output, so documents from inputs that cannot be read again, such as stdin,
+/// sockets, and HTTP listeners, survive a crash.
+///
+/// Each document is appended to `spool.ndjson` in the spool directory before
+/// it is sent, and the file is emptied whenever a flush of the output
+/// acknowledges everything sent so far. Documents still in the file when espipe
+/// starts were never acknowledged, so they are sent again before the input is
+/// read, which makes delivery at least once. Bulk-format documents are spooled
+/// as `[action, source]` arrays to keep their action.
+///
+/// When a flush fails some documents, the spool cannot tell which, so all of
+/// them are set aside in `unconfirmed.ndjson` and sent again with the rest on
+/// the next start.
+pub struct Spool {
+    path: PathBuf,
+    unconfirmed: PathBuf,
+    file: File,
+    bytes: u64,
+    max_bytes: u64,
+    fsync: SpoolFsync,
+    last_sync: Instant,
+}
+
+impl Spool {
+    pub fn open(dir: &Path, max_bytes: u64, fsync: SpoolFsync) -> Result<Self> {
+        fs::create_dir_all(dir)
+            .map_err(|err| eyre!("Failed to create spool {}: {err}", dir.display()))?;
+        let path = dir.join(SPOOL_FILE);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|err| eyre!("Failed to open spool {}: {err}", path.display()))?;
+        // Documents set aside by the last run go back in the spool to be sent again.
+        let unconfirmed = dir.join(UNCONFIRMED_FILE);
+        if unconfirmed.exists() {
+            io::copy(&mut File::open(&unconfirmed)?, &mut file)
+                .map_err(|err| eyre!("Failed to read {}: {err}", unconfirmed.display()))?;
+            file.sync_data()?;
+            fs::remove_file(&unconfirmed)?;
+        }
+        let bytes = file.metadata()?.len();
+        Ok(Self {
+            path,
+            unconfirmed,
+            file,
+            bytes,
+            max_bytes,
+            fsync,
+            last_sync: Instant::now(),
+        })
+    }
+
+    /// The documents a previous run spooled but never saw acknowledged.
+    ///
+    /// A last line cut short by a crash is skipped, since its document was
+    /// never sent.
+    pub fn unacknowledged(&self) -> Result<impl Iterator<Item = Result<Document>> + use<>> {
+        let reader = BufReader::new(File::open(&self.path)?);
+        let source = self.path.display().to_string();
+        Ok(reader.lines().filter_map(move |line| {
+            let line = match line {
+                Ok(line) if line.is_empty() => return None,
+                Ok(line) => line,
+                Err(err) => return Some(Err(eyre!("{source}: {err}"))),
+            };
+            match decode(&line) {
+                Ok(doc) => Some(Ok(doc)),
+                Err(err) => {
+                    log::warn!("{source}: skipping an unreadable spooled document: {err}");
+                    None
+                }
+            }
+        }))
+    }
+
+    /// Whether the spool has no room for `doc` until the output is flushed.
+    pub fn is_full_for(&self, doc: &Document) -> bool {
+        self.bytes > 0 && self.bytes + encoded_len(doc) > self.max_bytes
+    }
+
+    pub fn append(&mut self, doc: &Document) -> Result<()> {
+        let line = match doc.action() {
+            Some(action) => format!("[{action},{}]\n", doc.get()),
+            None => format!("{}\n", doc.get()),
+        };
+        self.file
+            .write_all(line.as_bytes())
+            .map_err(|err| eyre!("Failed to write spool {}: {err}", self.path.display()))?;
+        self.bytes += line.len() as u64;
+        self.sync(false)
+    }
+
+    /// Empties the spool once the output has acknowledged every document in it.
+    pub fn acknowledge(&mut self) -> Result<()> {
+        if self.bytes == 0 {
+            return Ok(());
+        }
+        self.file
+            .set_len(0)
+            .map_err(|err| eyre!("Failed to clear spool {}: {err}", self.path.display()))?;
+        self.bytes = 0;
+        self.sync(true)
+    }
+
+    /// Moves the spooled documents to `unconfirmed.ndjson` after a flush that
+    /// failed some of them, so they are sent again on the next start without
+    /// filling the spool in the meantime.
+    pub fn set_aside(&mut self) -> Result<()> {
+        if self.bytes == 0 {
+            return Ok(());
+        }
+        let unconfirmed = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.unconfirmed)
+            .and_then(|mut unconfirmed| {
+                io::copy(&mut File::open(&self.path)?, &mut unconfirmed)?;
+                Ok(unconfirmed)
+            })
+            .map_err(|err| eyre!("Failed to write {}: {err}", self.unconfirmed.display()))?;
+        unconfirmed.sync_data()?;
+        log::warn!(
+            "The output failed some spooled docs, keeping them in {} to send again on the next run",
+            self.unconfirmed.display()
+        );
+        self.acknowledge()
+    }
+
+    fn sync(&mut self, cleared: bool) -> Result<()> {
+        let due = match self.fsync {
+            SpoolFsync::Always => true,
+            SpoolFsync::Interval => cleared || self.last_sync.elapsed() >= SYNC_INTERVAL,
+            SpoolFsync::Never => false,
+        };
+        if due {
+            self.file.sync_data()?;
+            self.last_sync = Instant::now();
+        }
+        Ok(())
+    }
+}
+
+fn encoded_len(doc: &Document) -> u64 {
+    let action = doc.action().map_or(0, |action| action.len() + 3);
+    (doc.get().len() + action + 1) as u64
+}
+
+fn decode(line: &str) -> Result<Document> {
+    if line.starts_with('[') {
+        let (action, source): (Box<RawValue>, Box<RawValue>) = serde_json::from_str(line)?;
+        return Ok(Document::from(source).with_action(action.get()));
+    }
+    Ok(Document::from(serde_json::from_str::<Box<RawValue>>(line)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Spool, SpoolFsync};
+    use crate::document::Document;
+    use std::{fs, io::Write};
+
+    fn spooled(spool: &Spool) -> Vec<(Option<String>, String)> {
+        spool
+            .unacknowledged()
+            .unwrap()
+            .map(|doc| {
+                let doc = doc.unwrap();
+                (doc.action().map(str::to_string), doc.get().to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unacknowledged_documents_survive_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = Spool::open(dir.path(), 1024, SpoolFsync::Always).unwrap();
+        spool
+            .append(&Document::unvalidated("{\"a\":1}").unwrap())
+            .unwrap();
+        spool.acknowledge().unwrap();
+        spool
+            .append(&Document::unvalidated("{\"a\":2}").unwrap())
+            .unwrap();
+        let bulk = Document::unvalidated("{\"a\":3}")
+            .unwrap()
+            .with_action("{\"index\":{\"_id\":\"3\"}}");
+        spool.append(&bulk).unwrap();
+        drop(spool);
+        // A crash mid-write leaves a partial last line.
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(dir.path().join("spool.ndjson"))
+            .unwrap();
+        file.write_all(b"{\"a\":").unwrap();
+
+        let spool = Spool::open(dir.path(), 1024, SpoolFsync::Never).unwrap();
+        assert_eq!(
+            spooled(&spool),
+            [
+                (None, "{\"a\":2}".to_string()),
+                (
+                    Some("{\"index\":{\"_id\":\"3\"}}".to_string()),
+                    "{\"a\":3}".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn documents_set_aside_are_sent_again_on_the_next_start() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = Spool::open(dir.path(), 1024, SpoolFsync::Never).unwrap();
+        spool
+            .append(&Document::unvalidated("{\"a\":1}").unwrap())
+            .unwrap();
+        spool.set_aside().unwrap();
+        assert!(spooled(&spool).is_empty());
+        // A later flush that confirms everything leaves the set-aside documents be.
+        spool
+            .append(&Document::unvalidated("{\"a\":2}").unwrap())
+            .unwrap();
+        spool.acknowledge().unwrap();
+        drop(spool);
+
+        let spool = Spool::open(dir.path(), 1024, SpoolFsync::Never).unwrap();
+        assert_eq!(spooled(&spool), [(None, "{\"a\":1}".to_string())]);
+        assert!(!dir.path().join("unconfirmed.ndjson").exists());
+    }
+
+    #[test]
+    fn a_full_spool_waits_for_an_acknowledgement() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = Spool::open(dir.path(), 16, SpoolFsync::Interval).unwrap();
+        let doc = Document::unvalidated("{\"a\":\"xyz\"}").unwrap();
+        assert!(!spool.is_full_for(&doc));
+        spool.append(&doc).unwrap();
+        assert!(spool.is_full_for(&doc));
+        spool.acknowledge().unwrap();
+        assert!(!spool.is_full_for(&doc));
+        assert!(spooled(&spool).is_empty());
+    }
+}
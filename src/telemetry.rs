@@ -0,0 +1,88 @@
+use eyre::Result;
+#[cfg(not(feature = "otlp"))]
+use eyre::eyre;
+use url::Url;
+
+/// OTLP trace export for `--otlp-endpoint`, shut down at exit so pending spans are sent.
+#[derive(Default)]
+pub struct Telemetry {
+    #[cfg(feature = "otlp")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Telemetry {
+    /// Exports `tracing` spans to an OTLP/HTTP collector when an endpoint is given.
+    pub fn init(endpoint: Option<&Url>) -> Result<Self> {
+        match endpoint {
+            Some(endpoint) => Self::export_to(traces_endpoint(endpoint)),
+            None => Ok(Self::default()),
+        }
+    }
+
+    #[cfg(feature = "otlp")]
+    fn export_to(endpoint: Url) -> Result<Self> {
+        use opentelemetry::trace::TracerProvider;
+        use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+        use opentelemetry_sdk::{Resource, trace::SdkTracerProvider};
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint.as_str())
+            .build()?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(Resource::builder().with_service_name("espipe").build())
+            .build();
+        let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("espipe"));
+        tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer))?;
+        log::debug!("Exporting traces to {endpoint}");
+        Ok(Self {
+            provider: Some(provider),
+        })
+    }
+
+    #[cfg(not(feature = "otlp"))]
+    fn export_to(_endpoint: Url) -> Result<Self> {
+        Err(eyre!(
+            "--otlp-endpoint requires espipe to be built with the otlp feature"
+        ))
+    }
+
+    pub fn shutdown(self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = self.provider
+            && let Err(err) = provider.shutdown()
+        {
+            log::warn!("Failed to export traces: {err}");
+        }
+    }
+}
+
+/// A bare collector address gets the standard OTLP/HTTP traces path.
+fn traces_endpoint(endpoint: &Url) -> Url {
+    match endpoint.path() {
+        "" | "/" => endpoint.join("/v1/traces").expect("static path joins"),
+        _ => endpoint.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::traces_endpoint;
+    use url::Url;
+
+    #[test]
+    fn bare_collector_addresses_get_the_traces_path() {
+        let endpoint = |url: &str| traces_endpoint(&Url::parse(url).unwrap()).to_string();
+
+        assert_eq!(
+            endpoint("http://collector:4318"),
+            "http://collector:4318/v1/traces"
+        );
+        assert_eq!(
+            endpoint("https://otel.example.com/custom/traces"),
+            "https://otel.example.com/custom/traces"
+        );
+    }
+}
@@ -0,0 +1,430 @@
+//! Clap value parsers shared by command-line flags.
+//!
+//! Byte sizes accept decimal (`KB`, `MB`, `GB`, `TB`) and binary (`KiB`, `MiB`,
+//! `GiB`, `TiB`) units, and durations accept `ms`, `s`, `m`, `h`, and `d`
+//! components such as `1m30s`. Bare numbers are bytes and seconds respectively,
+//! so flags that used to take plain integers keep accepting them.
+
+use elasticsearch::http::headers::{HeaderName, HeaderValue};
+use encoding_rs::{Encoding, REPLACEMENT};
+use fluent_uri::UriRef;
+use std::time::Duration;
+
+pub fn parse_nonzero_usize(value: &str) -> Result<usize, String> {
+    let parsed = value.parse::<usize>().map_err(|err| err.to_string())?;
+    if parsed == 0 {
+        return Err("value must be at least 1".to_string());
+    }
+    Ok(parsed)
+}
+
+/// Parses an input or output URI, escaping the braces of output file
+/// placeholders like `out-{service.name}.ndjson`, which URIs do not allow.
+pub fn parse_uri(value: &str) -> Result<UriRef<String>, String> {
+    UriRef::parse(value.to_string()).or_else(|_| {
+        UriRef::parse(value.replace('{', "%7B").replace('}', "%7D"))
+            .map_err(|(err, _)| format!("invalid URI '{value}': {err}"))
+    })
+}
+
+/// Parses a count like `500`, `10k`, `1M`, or `2.5B`, rejecting zero.
+pub fn parse_count(value: &str) -> Result<u64, String> {
+    let (number, unit) = split_number(value.trim())
+        .ok_or_else(|| format!("invalid count '{value}', expected a value like 1M"))?;
+    let multiplier: u64 = match unit.to_ascii_lowercase().as_str() {
+        "" => 1,
+        "k" => 1_000,
+        "m" => 1_000_000,
+        "b" => 1_000_000_000,
+        _ => {
+            return Err(format!(
+                "invalid count unit '{unit}' in '{value}', expected k, M, or B"
+            ));
+        }
+    };
+    let count = (number * multiplier as f64).round();
+    if count < 1.0 {
+        return Err("count must be at least 1".to_string());
+    }
+    if count > u64::MAX as f64 {
+        return Err(format!("count '{value}' is too large"));
+    }
+    Ok(count as u64)
+}
+
+/// Parses an HTTP header like `X-Tenant: abc`.
+pub fn parse_header(value: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, header_value) = value.split_once(':').ok_or_else(|| {
+        format!("invalid header '{value}', expected a value like 'X-Tenant: abc'")
+    })?;
+    http_header(name.trim(), header_value.trim())
+}
+
+/// Parses credentials like `user:pass`, splitting at the first colon so the
+/// password may contain one.
+pub fn parse_credentials(value: &str) -> Result<(String, String), String> {
+    match value.split_once(':') {
+        Some((username, password)) if !username.is_empty() => {
+            Ok((username.to_string(), password.to_string()))
+        }
+        _ => Err("invalid credentials, expected a value like 'user:pass'".to_string()),
+    }
+}
+
+/// Parses a WHATWG encoding label like `latin1`, `windows-1252`, or `utf-16le`.
+pub fn parse_encoding(value: &str) -> Result<&'static Encoding, String> {
+    match Encoding::for_label(value.trim().as_bytes()) {
+        Some(encoding) if encoding != REPLACEMENT => Ok(encoding),
+        _ => Err(format!(
+            "unknown encoding '{value}', expected a label like latin1, windows-1252, or utf-16le"
+        )),
+    }
+}
+
+/// Validates a header name and value, as given on the command line or in hosts.yml.
+pub fn http_header(name: &str, value: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let name = HeaderName::from_bytes(name.as_bytes())
+        .map_err(|_| format!("invalid header name '{name}'"))?;
+    let value =
+        HeaderValue::from_str(value).map_err(|_| format!("invalid value for header '{name}'"))?;
+    Ok((name, value))
+}
+
+/// Parses a field assignment like `ingest.run_id=42` or `data_source=nightly`.
+///
+/// Values that parse as JSON keep their type; anything else is a string.
+pub fn parse_set_field(value: &str) -> Result<(String, serde_json::Value), String> {
+    let (path, field) = value
+        .split_once('=')
+        .filter(|(path, _)| !path.is_empty() && path.split('.').all(|key| !key.is_empty()))
+        .ok_or_else(|| {
+            format!("invalid field '{value}', expected a value like 'ingest.run_id=42'")
+        })?;
+    let field = serde_json::from_str(field)
+        .unwrap_or_else(|_| serde_json::Value::String(field.to_string()));
+    Ok((path.to_string(), field))
+}
+
+/// Parses an index rename like `logs-2025=logs-archive`.
+pub fn parse_rename(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((from, to)) if !from.is_empty() && !to.is_empty() => {
+            Ok((from.to_string(), to.to_string()))
+        }
+        _ => Err(format!(
+            "invalid rename '{value}', expected a value like 'logs-old=logs-new'"
+        )),
+    }
+}
+
+/// Parses a replay field and speed like `@timestamp` or `@timestamp:10`, with a
+/// speed of 1 when none is given.
+pub fn parse_replay_timing(value: &str) -> Result<(String, f64), String> {
+    let (field, speed) = match value.rsplit_once(':') {
+        Some((field, speed)) => {
+            let speed = speed
+                .trim_end_matches('x')
+                .parse::<f64>()
+                .ok()
+                .filter(|speed| speed.is_finite() && *speed > 0.0)
+                .ok_or_else(|| {
+                    format!("invalid replay speed '{speed}', expected a number above 0")
+                })?;
+            (field, speed)
+        }
+        None => (value, 1.0),
+    };
+    if field.is_empty() {
+        return Err(format!(
+            "invalid replay timing '{value}', expected a value like '@timestamp:10'"
+        ));
+    }
+    Ok((field.to_string(), speed))
+}
+
+/// Parses a byte size like `512`, `64KB`, `8MiB`, or `1.5 GB`.
+pub fn parse_byte_size(value: &str) -> Result<u64, String> {
+    let (number, unit) = split_number(value.trim())
+        .ok_or_else(|| format!("invalid byte size '{value}', expected a value like 8MiB"))?;
+    let multiplier: u64 = match unit.to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1_000,
+        "m" | "mb" => 1_000_000,
+        "g" | "gb" => 1_000_000_000,
+        "t" | "tb" => 1_000_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => {
+            return Err(format!(
+                "invalid byte size unit '{unit}' in '{value}', expected B, KB, MB, GB, TB, KiB, MiB, GiB, or TiB"
+            ));
+        }
+    };
+    let bytes = number * multiplier as f64;
+    if bytes > u64::MAX as f64 {
+        return Err(format!("byte size '{value}' is too large"));
+    }
+    Ok(bytes.round() as u64)
+}
+
+pub fn parse_nonzero_byte_size(value: &str) -> Result<u64, String> {
+    let bytes = parse_byte_size(value)?;
+    if bytes == 0 {
+        return Err("byte size must be greater than zero".to_string());
+    }
+    Ok(bytes)
+}
+
+/// Parses a duration like `30`, `500ms`, `2s`, `5m`, or `1h30m`.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Err("invalid duration '', expected a value like 2s".to_string());
+    }
+    if let Ok(seconds) = trimmed.parse::<f64>() {
+        return seconds_to_duration(seconds, value);
+    }
+
+    let mut rest = trimmed;
+    let mut seconds = 0.0;
+    while !rest.is_empty() {
+        let (number, after_number) = take_number(rest)
+            .ok_or_else(|| format!("invalid duration '{value}', expected a value like 2s"))?;
+        let unit_len = after_number
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(after_number.len());
+        let (unit, after_unit) = after_number.split_at(unit_len);
+        let scale = match unit {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3_600.0,
+            "d" => 86_400.0,
+            "" => {
+                return Err(format!(
+                    "duration '{value}' is missing a unit after {number}, expected ms, s, m, h, or d"
+                ));
+            }
+            unit => {
+                return Err(format!(
+                    "invalid duration unit '{unit}' in '{value}', expected ms, s, m, h, or d"
+                ));
+            }
+        };
+        seconds += number * scale;
+        rest = after_unit.trim_start();
+    }
+    seconds_to_duration(seconds, value)
+}
+
+/// Parses a duration like [`parse_duration`], rejecting zero.
+pub fn parse_nonzero_duration(value: &str) -> Result<Duration, String> {
+    let duration = parse_duration(value)?;
+    if duration.is_zero() {
+        return Err("duration must be greater than zero".to_string());
+    }
+    Ok(duration)
+}
+
+fn seconds_to_duration(seconds: f64, value: &str) -> Result<Duration, String> {
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("duration '{value}' is out of range"))
+}
+
+/// Splits a value into its leading number and the unit that follows it.
+fn split_number(value: &str) -> Option<(f64, &str)> {
+    let (number, unit) = take_number(value)?;
+    Some((number, unit.trim()))
+}
+
+fn take_number(value: &str) -> Option<(f64, &str)> {
+    let end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let number = value[..end].parse::<f64>().ok()?;
+    Some((number, &value[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_byte_size, parse_count, parse_credentials, parse_duration, parse_encoding,
+        parse_header, parse_nonzero_byte_size, parse_nonzero_duration, parse_nonzero_usize,
+        parse_rename, parse_replay_timing, parse_set_field, parse_uri,
+    };
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[test]
+    fn replay_timing_defaults_to_real_time() {
+        assert_eq!(
+            parse_replay_timing("@timestamp"),
+            Ok(("@timestamp".to_string(), 1.0))
+        );
+        assert_eq!(
+            parse_replay_timing("event.created:10x"),
+            Ok(("event.created".to_string(), 10.0))
+        );
+        assert_eq!(parse_replay_timing("ts:0.5"), Ok(("ts".to_string(), 0.5)));
+        assert!(parse_replay_timing("ts:0").is_err());
+        assert!(parse_replay_timing("ts:fast").is_err());
+        assert!(parse_replay_timing(":10").is_err());
+    }
+
+    #[test]
+    fn nonzero_usize_rejects_zero() {
+        assert_eq!(parse_nonzero_usize("3"), Ok(3));
+        assert_eq!(
+            parse_nonzero_usize("0"),
+            Err("value must be at least 1".to_string())
+        );
+    }
+
+    #[test]
+    fn counts_accept_thousands_millions_and_billions() {
+        assert_eq!(parse_count("500"), Ok(500));
+        assert_eq!(parse_count("10k"), Ok(10_000));
+        assert_eq!(parse_count("1M"), Ok(1_000_000));
+        assert_eq!(parse_count("2.5b"), Ok(2_500_000_000));
+        assert!(parse_count("0").is_err());
+        assert!(
+            parse_count("1G")
+                .unwrap_err()
+                .contains("invalid count unit 'G'")
+        );
+    }
+
+    #[test]
+    fn byte_sizes_accept_decimal_and_binary_units() {
+        assert_eq!(parse_byte_size("512"), Ok(512));
+        assert_eq!(parse_byte_size("64KB"), Ok(64_000));
+        assert_eq!(parse_byte_size("8MiB"), Ok(8 * 1024 * 1024));
+        assert_eq!(parse_byte_size("1.5 GB"), Ok(1_500_000_000));
+        assert_eq!(parse_byte_size("2gib"), Ok(2 << 30));
+    }
+
+    #[test]
+    fn byte_sizes_reject_unknown_units() {
+        let err = parse_byte_size("8MX").unwrap_err();
+        assert!(err.contains("invalid byte size unit 'MX'"), "{err}");
+        assert!(parse_byte_size("MiB").is_err());
+    }
+
+    #[test]
+    fn nonzero_byte_size_rejects_zero() {
+        assert_eq!(parse_nonzero_byte_size("100MB"), Ok(100_000_000));
+        assert_eq!(
+            parse_nonzero_byte_size("0KB"),
+            Err("byte size must be greater than zero".to_string())
+        );
+    }
+
+    #[test]
+    fn durations_accept_units_and_compound_values() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2s"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5_400)));
+        assert_eq!(parse_duration("1.5m"), Ok(Duration::from_secs(90)));
+    }
+
+    #[test]
+    fn durations_reject_unknown_or_missing_units() {
+        assert!(
+            parse_duration("2w")
+                .unwrap_err()
+                .contains("invalid duration unit 'w'")
+        );
+        assert!(
+            parse_duration("1m30")
+                .unwrap_err()
+                .contains("missing a unit")
+        );
+        assert!(parse_duration("-1s").is_err());
+    }
+
+    #[test]
+    fn nonzero_duration_rejects_zero() {
+        assert_eq!(parse_nonzero_duration("1s"), Ok(Duration::from_secs(1)));
+        assert_eq!(
+            parse_nonzero_duration("0ms"),
+            Err("duration must be greater than zero".to_string())
+        );
+    }
+
+    #[test]
+    fn headers_split_on_the_first_colon() {
+        let (name, value) = parse_header("X-Trace: a:b ").unwrap();
+        assert_eq!(name.as_str(), "x-trace");
+        assert_eq!(value.to_str().unwrap(), "a:b");
+        assert!(
+            parse_header("X-Tenant")
+                .unwrap_err()
+                .contains("X-Tenant: abc")
+        );
+        assert!(
+            parse_header("Bad Name: x")
+                .unwrap_err()
+                .contains("invalid header name")
+        );
+    }
+
+    #[test]
+    fn credentials_split_on_the_first_colon() {
+        assert_eq!(
+            parse_credentials("ci:p:w"),
+            Ok(("ci".to_string(), "p:w".to_string()))
+        );
+        assert!(parse_credentials("ci").is_err());
+        assert!(parse_credentials(":pw").is_err());
+    }
+
+    #[test]
+    fn encodings_are_looked_up_by_label() {
+        assert_eq!(parse_encoding("latin1").unwrap().name(), "windows-1252");
+        assert_eq!(parse_encoding("UTF-16LE").unwrap().name(), "UTF-16LE");
+        assert!(parse_encoding("iso-2022-kr").is_err());
+        assert!(parse_encoding("ebcdic").is_err());
+    }
+
+    #[test]
+    fn set_fields_keep_json_types_and_default_to_strings() {
+        assert_eq!(
+            parse_set_field("ingest.run_id=42"),
+            Ok(("ingest.run_id".to_string(), json!(42)))
+        );
+        assert_eq!(
+            parse_set_field("env=prod=eu"),
+            Ok(("env".to_string(), json!("prod=eu")))
+        );
+        assert_eq!(
+            parse_set_field(r#"version="1.10""#),
+            Ok(("version".to_string(), json!("1.10")))
+        );
+        assert!(parse_set_field("no_value").is_err());
+        assert!(parse_set_field("a..b=1").is_err());
+    }
+
+    #[test]
+    fn renames_need_both_names() {
+        assert_eq!(
+            parse_rename("logs-old=logs-new"),
+            Ok(("logs-old".to_string(), "logs-new".to_string()))
+        );
+        assert!(parse_rename("logs-old=").is_err());
+        assert!(parse_rename("logs-old").is_err());
+    }
+
+    #[test]
+    fn uris_with_placeholders_escape_their_braces() {
+        assert_eq!(
+            parse_uri("out-{service.name}.ndjson").unwrap().as_str(),
+            "out-%7Bservice.name%7D.ndjson"
+        );
+        assert_eq!(
+            parse_uri("http://localhost:9200/logs").unwrap().as_str(),
+            "http://localhost:9200/logs"
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use crate::{
+    Cli, Command, canonical,
+    client::{Auth, elasticsearch::request},
+    comma_formatted,
+    manifest::redact_uri,
+    output::{self, ElasticsearchOutputConfig},
+};
+use elasticsearch::{Elasticsearch, http::Method};
+use eyre::{Result, eyre};
+use fluent_uri::UriRef;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use url::Url;
+use xxhash_rust::xxh3::xxh3_128;
+
+/// The documents `espipe verify --sample` found missing or changed in the target.
+#[derive(Debug, Default, PartialEq)]
+struct SampleReport {
+    sampled: usize,
+    missing: Vec<String>,
+    mismatched: Vec<String>,
+}
+
+/// Compares an index on two clusters: their document counts, and with
+/// `--sample`, the content of random source documents looked up in the target
+/// by `_id`. Prints a report and fails when the indices differ.
+#[tracing::instrument(name = "espipe_verify", skip_all)]
+pub async fn run(args: Cli) -> Result<()> {
+    let Some(Command::Verify {
+        source,
+        target,
+        sample,
+    }) = &args.command
+    else {
+        unreachable!("verify::run is only called for espipe verify");
+    };
+    let config = ElasticsearchOutputConfig::default()
+        .with_timeouts(args.request_timeout, None)
+        .with_proxy(args.proxy.clone())
+        .with_tls_server_name(args.tls_server_name.clone())
+        .with_socket_options(
+            args.connect_timeout,
+            args.tcp_keepalive,
+            args.pool_idle_timeout,
+        )
+        .with_headers(args.headers.clone());
+    let (source_client, source_url, source_index) = connect(&args, &config, source)?;
+    let (target_client, target_url, target_index) = connect(&args, &config, target)?;
+
+    let source_count = count(&source_client, &source_index).await?;
+    let target_count = count(&target_client, &target_index).await?;
+    println!(
+        "Source: {} has {} docs",
+        redact_uri(source_url.as_str()),
+        comma_formatted(source_count)
+    );
+    println!(
+        "Target: {} has {} docs",
+        redact_uri(target_url.as_str()),
+        comma_formatted(target_count)
+    );
+    let mut differs = source_count != target_count;
+
+    if let Some(sample) = sample {
+        let hits = sample_hits(&source_client, &source_index, *sample).await?;
+        let found = lookup(&target_client, &target_index, &hits).await?;
+        let report = compare(&hits, &found)?;
+        println!(
+            "Sampled {} docs: {} missing, {} mismatched",
+            comma_formatted(report.sampled),
+            comma_formatted(report.missing.len()),
+            comma_formatted(report.mismatched.len())
+        );
+        for id in &report.missing {
+            println!("  missing    {id}");
+        }
+        for id in &report.mismatched {
+            println!("  mismatched {id}");
+        }
+        differs |= !report.missing.is_empty() || !report.mismatched.is_empty();
+    }
+    match differs {
+        true => Err(eyre!("{target_index} does not match {source_index}")),
+        false => Ok(()),
+    }
+}
+
+/// Builds a client for one side of the comparison, along with its index.
+fn connect(
+    args: &Cli,
+    config: &ElasticsearchOutputConfig,
+    uri: &UriRef<String>,
+) -> Result<(Elasticsearch, Url, String)> {
+    let auth = Auth::try_new(
+        args.apikey.clone(),
+        args.username.clone(),
+        args.password.clone(),
+    )?;
+    let (client, url) =
+        output::elasticsearch_client(args.insecure, auth, uri, !args.uncompressed, config)?;
+    let index = url.path().trim_start_matches('/');
+    if index.is_empty() || index.contains('/') {
+        return Err(eyre!(
+            "espipe verify needs an index on each cluster, like {url}my-index"
+        ));
+    }
+    let index = index.to_string();
+    Ok((client, url, index))
+}
+
+async fn count(client: &Elasticsearch, index: &str) -> Result<usize> {
+    let response = request(client, Method::Get, &format!("/{index}/_count"), &[], None).await?;
+    response["count"]
+        .as_u64()
+        .map(|count| count as usize)
+        .ok_or_else(|| eyre!("{index}/_count response has no count"))
+}
+
+/// Random documents from the source index, each with its `_id`, routing, and `_source`.
+async fn sample_hits(client: &Elasticsearch, index: &str, size: usize) -> Result<Vec<Value>> {
+    let body = json!({
+        "size": size,
+        "query": { "function_score": { "random_score": {} } },
+        "track_total_hits": false,
+    });
+    let response = request(
+        client,
+        Method::Post,
+        &format!("/{index}/_search"),
+        &[],
+        Some(&body),
+    )
+    .await?;
+    Ok(response["hits"]["hits"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default())
+}
+
+/// Fetches the sampled IDs from the target index, keyed by `_id`.
+async fn lookup(
+    client: &Elasticsearch,
+    index: &str,
+    hits: &[Value],
+) -> Result<HashMap<String, Value>> {
+    if hits.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let docs: Vec<Value> = hits
+        .iter()
+        .map(|hit| match hit.get("_routing") {
+            Some(routing) => json!({ "_id": hit["_id"], "routing": routing }),
+            None => json!({ "_id": hit["_id"] }),
+        })
+        .collect();
+    let response = request(
+        client,
+        Method::Post,
+        &format!("/{index}/_mget"),
+        &[],
+        Some(&json!({ "docs": docs })),
+    )
+    .await?;
+    Ok(response["docs"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|doc| doc["found"] == true)
+        .filter_map(|doc| Some((doc["_id"].as_str()?.to_string(), doc.clone())))
+        .collect())
+}
+
+fn compare(hits: &[Value], found: &HashMap<String, Value>) -> Result<SampleReport> {
+    let mut report = SampleReport {
+        sampled: hits.len(),
+        ..SampleReport::default()
+    };
+    for hit in hits {
+        let id = hit["_id"]
+            .as_str()
+            .ok_or_else(|| eyre!("sampled document has no _id"))?;
+        match found.get(id) {
+            None => report.missing.push(id.to_string()),
+            Some(doc) if content_hash(&doc["_source"]) != content_hash(&hit["_source"]) => {
+                report.mismatched.push(id.to_string())
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(report)
+}
+
+/// Hashes with object keys sorted, so documents whose fields were reordered
+/// in transit hash the same.
+fn content_hash(source: &Value) -> u128 {
+    xxh3_128(canonical::to_string(source).as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SampleReport, compare};
+    use serde_json::{Value, json};
+    use std::collections::HashMap;
+
+    #[test]
+    fn samples_report_missing_and_changed_documents() {
+        let hits = vec![
+            json!({ "_id": "1", "_source": { "a": 1, "b": 2 } }),
+            json!({ "_id": "2", "_source": { "a": 1 } }),
+            json!({ "_id": "3", "_source": { "a": 1 } }),
+        ];
+        let found: HashMap<String, Value> = [
+            ("1", json!({ "_id": "1", "_source": { "b": 2, "a": 1 } })),
+            ("2", json!({ "_id": "2", "_source": { "a": 2 } })),
+        ]
+        .into_iter()
+        .map(|(id, doc)| (id.to_string(), doc))
+        .collect();
+
+        assert_eq!(
+            compare(&hits, &found).unwrap(),
+            SampleReport {
+                sampled: 3,
+                missing: vec!["3".to_string()],
+                mismatched: vec!["2".to_string()],
+            }
+        );
+    }
+}
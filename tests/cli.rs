@@ -18,3 +18,107 @@ fn cli_prints_version() {
         format!("espipe {}\n", env!("CARGO_PKG_VERSION"))
     );
 }
+
+#[cfg(not(feature = "kafka"))]
+#[test]
+fn cli_rejects_kafka_output_without_kafka_feature() {
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("-")
+        .arg("kafka://localhost:9092/events")
+        .output()
+        .expect("run espipe");
+
+    assert!(
+        !output.status.success(),
+        "espipe should reject kafka output"
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("kafka:// outputs require espipe to be built with the kafka feature"),
+        "stderr should explain the missing feature: {stderr}"
+    );
+}
+
+#[cfg(not(feature = "sql"))]
+#[test]
+fn cli_rejects_postgres_input_without_sql_feature() {
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("postgres://localhost/shop?query=SELECT+1")
+        .arg("-")
+        .output()
+        .expect("run espipe");
+
+    assert!(
+        !output.status.success(),
+        "espipe should reject postgres input"
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("postgres:// inputs require espipe to be built with the sql feature"),
+        "stderr should explain the missing feature: {stderr}"
+    );
+}
+
+#[cfg(not(feature = "cloud"))]
+#[test]
+fn cli_rejects_cloud_storage_uris_without_cloud_feature() {
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("gs://exports/logs.ndjson")
+        .arg("az://archive/logs.ndjson.gz")
+        .output()
+        .expect("run espipe");
+
+    assert!(
+        !output.status.success(),
+        "espipe should reject cloud storage input"
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("gs:// inputs require espipe to be built with the cloud feature"),
+        "stderr should explain the missing feature: {stderr}"
+    );
+}
+
+#[test]
+fn cli_prints_shell_completions_and_examples() {
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .args(["completions", "bash"])
+        .output()
+        .expect("run espipe");
+    assert!(output.status.success(), "espipe completions should succeed");
+    let script = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        script.contains("complete -F _espipe") && script.contains("--batch-size"),
+        "completions should cover espipe's flags: {script}"
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("examples")
+        .output()
+        .expect("run espipe");
+    assert!(output.status.success(), "espipe examples should succeed");
+    let examples = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        examples.starts_with("# Load an NDJSON file into an index\nespipe docs.ndjson "),
+        "examples should start with a file to cluster load: {examples}"
+    );
+}
+
+#[test]
+fn cli_rejects_password_stdin_with_stdin_input() {
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .args(["--username", "elastic", "--password-stdin", "-"])
+        .arg("http://localhost:9200/logs")
+        .output()
+        .expect("run espipe");
+
+    assert!(
+        !output.status.success(),
+        "espipe should not read both the password and documents from stdin"
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("--password-stdin cannot be used with stdin input"),
+        "stderr should explain the conflict: {stderr}"
+    );
+}
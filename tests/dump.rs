@@ -0,0 +1,597 @@
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde_json::{Value, json};
+use std::{
+    fs,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    process::Command,
+    sync::{Arc, Mutex},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+fn temp_dir(prefix: &str) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time went backwards")
+        .as_nanos();
+    let dir = std::env::temp_dir().join(format!("{prefix}-{}-{nanos}", std::process::id()));
+    fs::create_dir_all(&dir).expect("create temp dir");
+    dir
+}
+
+type Requests = Arc<Mutex<Vec<(String, String)>>>;
+
+/// Serves one index, `logs-a`, holding two documents, and records each request
+/// line with its body.
+fn spawn_cluster() -> (String, Requests) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let thread_requests = Arc::clone(&requests);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                break;
+            };
+            let requests = Arc::clone(&thread_requests);
+            thread::spawn(move || handle_connection(stream, requests));
+        }
+    });
+    (format!("http://{addr}"), requests)
+}
+
+fn handle_connection(mut stream: TcpStream, requests: Requests) {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 1024];
+    let header_end = loop {
+        let read = stream.read(&mut chunk).unwrap();
+        if read == 0 {
+            return;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some(index) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break index;
+        }
+    };
+    let headers = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let content_length = headers
+        .lines()
+        .find_map(|line| {
+            line.to_ascii_lowercase()
+                .strip_prefix("content-length: ")
+                .map(|value| value.trim().parse::<usize>().unwrap())
+        })
+        .unwrap_or(0);
+    while buffer.len() < header_end + 4 + content_length {
+        let read = stream.read(&mut chunk).unwrap();
+        if read == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+    let request_line = headers.lines().next().unwrap().to_string();
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap();
+    let path = parts.next().unwrap().split('?').next().unwrap();
+    // Like Elasticsearch, inflate the request bodies espipe gzips.
+    let raw_body = &buffer[header_end + 4..];
+    let body = match headers
+        .to_ascii_lowercase()
+        .contains("content-encoding: gzip")
+    {
+        true => {
+            let mut body = String::new();
+            GzDecoder::new(raw_body).read_to_string(&mut body).unwrap();
+            body
+        }
+        false => String::from_utf8_lossy(raw_body).to_string(),
+    };
+    requests
+        .lock()
+        .unwrap()
+        .push((format!("{method} {path}"), body.clone()));
+
+    let response = match (method, path) {
+        ("GET", "/_cat/indices/logs-*") => json!([{ "index": "logs-a" }]),
+        ("POST", "/logs-a/_pit") => json!({ "id": "pit-1" }),
+        ("POST", "/logs-a/_search") if body.contains("\"aggs\"") => json!({
+            "aggregations": { "services": { "buckets": [
+                { "key": "api", "doc_count": 2, "bytes": { "value": 30.0 } },
+                { "key": "web", "doc_count": 1, "bytes": { "value": 5.0 } },
+            ] } },
+        }),
+        ("POST", "/_search" | "/logs-a/_search") => json!({
+            "pit_id": "pit-2",
+            "hits": { "hits": [
+                { "_index": "logs-a", "_id": "1", "_source": { "n": 1 }, "sort": [0] },
+                { "_index": "logs-a", "_id": "2", "_routing": "r", "_source": { "n": 2 }, "sort": [1] },
+            ] },
+        }),
+        ("DELETE", "/_pit") => json!({ "succeeded": true, "num_freed": 1 }),
+        ("GET", "/logs-a/_mapping") => {
+            json!({ "logs-a": { "mappings": { "properties": { "n": { "type": "long" } } } } })
+        }
+        ("GET", "/logs-b/_mapping") => {
+            json!({ "logs-b": { "mappings": { "properties": { "n": { "type": "long" } } } } })
+        }
+        ("GET", "/logs-a/_count" | "/logs-b/_count") => json!({ "count": 2 }),
+        ("POST", "/logs-b/_mget") => json!({ "docs": [
+            { "_index": "logs-b", "_id": "1", "found": true, "_source": { "n": 1 } },
+            { "_index": "logs-b", "_id": "2", "found": false },
+        ] }),
+        ("PUT", "/logs-b") => json!({ "acknowledged": true, "index": "logs-b" }),
+        ("POST", "/logs-b/_bulk") => {
+            let items = (0..body.lines().count() / 2)
+                .map(|id| json!({ "index": { "_index": "logs-b", "_id": id.to_string(), "status": 201 } }))
+                .collect::<Vec<_>>();
+            json!({ "errors": false, "items": items })
+        }
+        // Every document already exists, so only `index` operations succeed.
+        ("POST", "/logs-c/_bulk") => {
+            let items = body
+                .lines()
+                .step_by(2)
+                .map(|line| match line.starts_with("{\"create\"") {
+                    true => json!({ "create": { "_index": "logs-c", "_id": "1", "status": 409, "error": {
+                        "type": "version_conflict_engine_exception",
+                        "reason": "document already exists",
+                    } } }),
+                    false => json!({ "index": { "_index": "logs-c", "_id": "1", "status": 200 } }),
+                })
+                .collect::<Vec<_>>();
+            json!({ "errors": body.contains("{\"create\""), "items": items })
+        }
+        ("POST", "/hooks/logs") => json!({ "ok": true }),
+        ("POST", "/logs-b/_delete_by_query") => json!({ "deleted": 2, "failures": [] }),
+        _ => json!({ "error": "unexpected request" }),
+    }
+    .to_string();
+    let response = format!(
+        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{response}",
+        response.len()
+    );
+    stream.write_all(response.as_bytes()).unwrap();
+}
+
+#[test]
+fn cli_dumps_matching_indices_as_replayable_bulk_files() {
+    let dir = temp_dir("espipe-dump");
+    let (base_url, requests) = spawn_cluster();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("dump")
+        .arg(format!("{base_url}/logs-*"))
+        .arg(&dir)
+        .arg("--mappings")
+        .output()
+        .expect("run espipe");
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Dumped 2 docs from 1 indices"));
+    let mut bulk = String::new();
+    GzDecoder::new(fs::File::open(dir.join("logs-a.ndjson.gz")).unwrap())
+        .read_to_string(&mut bulk)
+        .unwrap();
+    let bulk: Vec<Value> = bulk
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        bulk,
+        [
+            json!({ "index": { "_id": "1", "_index": "logs-a" } }),
+            json!({ "n": 1 }),
+            json!({ "index": { "_id": "2", "_index": "logs-a", "routing": "r" } }),
+            json!({ "n": 2 }),
+        ]
+    );
+    let index: Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("logs-a.index.json")).unwrap()).unwrap();
+    assert_eq!(
+        index,
+        json!({ "mappings": { "properties": { "n": { "type": "long" } } } })
+    );
+    let manifest: Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("manifest.json")).unwrap()).unwrap();
+    assert_eq!(
+        manifest["indices"],
+        json!([{
+            "index": "logs-a",
+            "documents": 2,
+            "file": "logs-a.ndjson.gz",
+            "index_file": "logs-a.index.json",
+        }])
+    );
+    let requests = requests.lock().unwrap();
+    assert_eq!(
+        requests.last().map(|(request, _)| request.as_str()),
+        Some("DELETE /_pit"),
+        "the point in time should be closed: {requests:?}"
+    );
+}
+
+#[test]
+fn cli_restores_a_dump_under_new_index_names() {
+    let dir = temp_dir("espipe-restore");
+    let mut bulk = GzEncoder::new(
+        fs::File::create(dir.join("logs-a.ndjson.gz")).unwrap(),
+        Compression::default(),
+    );
+    bulk.write_all(
+        concat!(
+            "{\"index\":{\"_id\":\"1\",\"_index\":\"logs-a\"}}\n{\"n\":1}\n",
+            "{\"index\":{\"_id\":\"2\",\"_index\":\"logs-a\",\"routing\":\"r\"}}\n{\"n\":2}\n",
+        )
+        .as_bytes(),
+    )
+    .unwrap();
+    bulk.finish().unwrap();
+    let mappings = json!({ "mappings": { "properties": { "n": { "type": "long" } } } });
+    fs::write(dir.join("logs-a.index.json"), mappings.to_string()).unwrap();
+    let manifest = json!({
+        "espipe_version": "0.4.0",
+        "source": "http://localhost:9200/logs-*",
+        "started_at": "2026-01-01T00:00:00Z",
+        "finished_at": "2026-01-01T00:00:01Z",
+        "indices": [{
+            "index": "logs-a",
+            "documents": 2,
+            "file": "logs-a.ndjson.gz",
+            "index_file": "logs-a.index.json",
+        }],
+    });
+    fs::write(dir.join("manifest.json"), manifest.to_string()).unwrap();
+    let (base_url, requests) = spawn_cluster();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("restore")
+        .arg(&dir)
+        .arg(&base_url)
+        .args(["--rename", "logs-a=logs-b", "--uncompressed"])
+        .output()
+        .expect("run espipe");
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Restored 2 docs into 1 indices"));
+    let requests = requests.lock().unwrap();
+    let create = requests
+        .iter()
+        .position(|(request, _)| request == "PUT /logs-b")
+        .expect("the index should be created");
+    assert_eq!(
+        serde_json::from_str::<Value>(&requests[create].1).unwrap(),
+        mappings
+    );
+    let bulk = requests
+        .iter()
+        .position(|(request, _)| request == "POST /logs-b/_bulk")
+        .expect("the documents should be bulk indexed");
+    assert!(create < bulk, "{requests:?}");
+    assert_eq!(
+        requests[bulk].1,
+        concat!(
+            "{\"index\":{\"_id\":\"1\",\"_index\":\"logs-b\"}}\n{\"n\":1}\n",
+            "{\"index\":{\"_id\":\"2\",\"_index\":\"logs-b\",\"routing\":\"r\"}}\n{\"n\":2}\n",
+        )
+    );
+}
+
+#[test]
+fn cli_verify_reports_documents_missing_from_the_target() {
+    let (base_url, requests) = spawn_cluster();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("verify")
+        .arg(format!("{base_url}/logs-a"))
+        .arg(format!("{base_url}/logs-b"))
+        .args(["--sample", "2", "--uncompressed"])
+        .output()
+        .expect("run espipe");
+
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("/logs-b has 2 docs"), "{stdout}");
+    assert!(
+        stdout.contains("Sampled 2 docs: 1 missing, 0 mismatched\n  missing    2\n"),
+        "{stdout}"
+    );
+    let requests = requests.lock().unwrap();
+    let (_, mget) = requests
+        .iter()
+        .find(|(request, _)| request == "POST /logs-b/_mget")
+        .expect("the sample should be looked up in the target");
+    assert_eq!(
+        serde_json::from_str::<Value>(mget).unwrap(),
+        json!({ "docs": [{ "_id": "1" }, { "_id": "2", "routing": "r" }] })
+    );
+}
+
+#[test]
+fn cli_exports_the_hits_of_a_kql_search() {
+    let dir = temp_dir("espipe-search");
+    let output_path = dir.join("errors.ndjson");
+    let (base_url, requests) = spawn_cluster();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg(format!(
+            "{base_url}/logs-a?kql=status:error%20and%20not%20n%20%3C%202"
+        ))
+        .arg(&output_path)
+        .output()
+        .expect("run espipe");
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        fs::read_to_string(&output_path).unwrap(),
+        "{\"n\":1}\n{\"n\":2}\n"
+    );
+    let requests = requests.lock().unwrap();
+    let (_, search) = requests
+        .iter()
+        .find(|(request, _)| request == "POST /_search")
+        .expect("the hits should be searched");
+    assert_eq!(
+        serde_json::from_str::<Value>(search).unwrap()["query"],
+        json!({ "bool": { "filter": [
+            { "match": { "status": "error" } },
+            { "bool": { "must_not": [{ "range": { "n": { "lt": "2" } } }] } },
+        ] } })
+    );
+    assert_eq!(
+        requests.last().map(|(request, _)| request.as_str()),
+        Some("DELETE /_pit")
+    );
+}
+
+#[test]
+fn cli_exports_one_document_per_aggregation_bucket() {
+    let dir = temp_dir("espipe-agg");
+    let agg = dir.join("agg.json");
+    fs::write(
+        &agg,
+        r#"{"services":{"terms":{"field":"service"},"aggs":{"bytes":{"sum":{"field":"bytes"}}}}}"#,
+    )
+    .unwrap();
+    let output_path = dir.join("services.ndjson");
+    let (base_url, requests) = spawn_cluster();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg(format!("{base_url}/logs-a?kql=status:error"))
+        .arg(&output_path)
+        .arg("--agg")
+        .arg(&agg)
+        .output()
+        .expect("run espipe");
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let documents: Vec<Value> = fs::read_to_string(&output_path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        documents,
+        [
+            json!({ "services": "api", "doc_count": 2, "bytes": 30.0 }),
+            json!({ "services": "web", "doc_count": 1, "bytes": 5.0 }),
+        ]
+    );
+    let requests = requests.lock().unwrap();
+    let (_, search) = requests
+        .iter()
+        .find(|(request, _)| request == "POST /logs-a/_search")
+        .expect("the aggregation should be searched");
+    let search: Value = serde_json::from_str(search).unwrap();
+    assert_eq!(search["size"], 0);
+    assert_eq!(search["query"], json!({ "match": { "status": "error" } }));
+    assert_eq!(search["aggs"]["services"]["terms"]["field"], "service");
+}
+
+#[test]
+fn cli_posts_batches_to_a_webhook_as_a_json_array() {
+    let dir = temp_dir("espipe-webhook");
+    let input = dir.join("logs.ndjson");
+    fs::write(&input, "{\"n\":1}\n{\"n\":2}\n{\"n\":3}\n").unwrap();
+    let (base_url, requests) = spawn_cluster();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .args(["--output-format", "http-json", "--http-body", "array"])
+        .args(["--batch-size", "2", "--header", "Authorization: Bearer abc"])
+        .arg(&input)
+        .arg(format!("{base_url}/hooks/logs"))
+        .output()
+        .expect("run espipe");
+
+    assert!(
+        output.status.success(),
+        "espipe exited with failure: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Piped 3 of 3 docs"));
+    let requests = requests.lock().unwrap();
+    assert_eq!(
+        *requests,
+        vec![
+            (
+                "POST /hooks/logs".to_string(),
+                r#"[{"n":1},{"n":2}]"#.to_string()
+            ),
+            ("POST /hooks/logs".to_string(), r#"[{"n":3}]"#.to_string()),
+        ]
+    );
+}
+
+#[test]
+fn cli_rolls_back_the_documents_of_a_tagged_run() {
+    let dir = temp_dir("espipe-rollback");
+    let input = dir.join("logs.ndjson");
+    fs::write(&input, "{\"n\":1}\n{\"n\":2}\n").unwrap();
+    let (base_url, requests) = spawn_cluster();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .args(["--tag-run", "--uncompressed"])
+        .arg(&input)
+        .arg(format!("{base_url}/logs-b"))
+        .output()
+        .expect("run espipe");
+    assert!(
+        output.status.success(),
+        "espipe exited with failure: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let run_id = stderr
+        .split_once("--run-id ")
+        .and_then(|(_, rest)| rest.split_whitespace().next())
+        .expect("espipe should print the run ID");
+    let bulk = requests
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(request, _)| request == "POST /logs-b/_bulk")
+        .map(|(_, body)| body.clone())
+        .expect("espipe should send a bulk request");
+    for source in bulk.lines().skip(1).step_by(2) {
+        let source: Value = serde_json::from_str(source).unwrap();
+        assert_eq!(source["espipe"]["run_id"], run_id);
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .args([
+            "rollback",
+            &format!("{base_url}/logs-b"),
+            "--run-id",
+            run_id,
+            "--uncompressed",
+        ])
+        .output()
+        .expect("run espipe");
+    assert!(
+        output.status.success(),
+        "espipe rollback exited with failure: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        String::from_utf8_lossy(&output.stdout).starts_with("Deleted 2 docs with espipe.run_id ")
+    );
+    let requests = requests.lock().unwrap();
+    let (_, body) = requests.last().unwrap();
+    assert_eq!(
+        serde_json::from_str::<Value>(body).unwrap(),
+        json!({ "query": { "match_phrase": { "espipe.run_id": run_id } } })
+    );
+}
+
+#[test]
+fn cli_skips_overwrites_or_fails_on_existing_ids() {
+    let dir = temp_dir("espipe-on-conflict");
+    let input = dir.join("logs.ndjson");
+    fs::write(&input, "{\"n\":1}\n{\"n\":2}\n").unwrap();
+    let (base_url, requests) = spawn_cluster();
+    let espipe = |policy: &str| {
+        Command::new(env!("CARGO_BIN_EXE_espipe"))
+            .args(["--uncompressed", "--on-conflict", policy])
+            .arg(&input)
+            .arg(format!("{base_url}/logs-c"))
+            .output()
+            .expect("run espipe")
+    };
+
+    let output = espipe("skip");
+    assert!(
+        output.status.success(),
+        "espipe exited with failure: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("(2 existing docs skipped)"),
+        "stdout: {}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    requests.lock().unwrap().clear();
+    let output = espipe("overwrite");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("(2 existing docs overwritten)"));
+    let bulks: Vec<String> = requests
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(request, _)| request == "POST /logs-c/_bulk")
+        .map(|(_, body)| body.clone())
+        .collect();
+    assert_eq!(bulks.len(), 2);
+    assert_eq!(
+        bulks[1],
+        "{\"index\":{}}\n{\"n\":1}\n{\"index\":{}}\n{\"n\":2}\n"
+    );
+
+    let output = espipe("fail");
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("2 docs already exist in logs-c, stopping for --on-conflict fail")
+    );
+}
+
+#[test]
+fn cli_coerces_documents_to_the_target_mapping() {
+    let dir = temp_dir("espipe-coerce");
+    let input = dir.join("logs.ndjson");
+    fs::write(&input, "{\"n\":\"1\",\"extra\":true}\n{\"n\":2}\n").unwrap();
+    let (base_url, requests) = spawn_cluster();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .args(["--coerce-to-mapping", "--uncompressed"])
+        .arg(&input)
+        .arg(format!("{base_url}/logs-b"))
+        .output()
+        .expect("run espipe");
+    assert!(
+        output.status.success(),
+        "espipe exited with failure: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("Field extra is not in the mapping of logs-b and will be mapped dynamically")
+    );
+    let bulk = requests
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(request, _)| request == "POST /logs-b/_bulk")
+        .map(|(_, body)| body.clone())
+        .expect("espipe should send a bulk request");
+    let bulk: Vec<Value> = bulk
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        bulk,
+        [
+            json!({ "create": {} }),
+            json!({ "n": 1, "extra": true }),
+            json!({ "create": {} }),
+            json!({ "n": 2 }),
+        ]
+    );
+}
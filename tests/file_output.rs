@@ -216,3 +216,642 @@ fn cli_rejects_unsupported_gzip_file_output_before_writing() {
     );
     assert!(!output_path.exists());
 }
+
+#[test]
+fn cli_merge_by_interleaves_two_files_in_timestamp_order() {
+    let first_input = temp_output_path("a.ndjson");
+    let second_input = first_input.with_file_name("b.ndjson");
+    fs::write(
+        &first_input,
+        "{\"@timestamp\":\"2024-05-01T12:00:00Z\",\"n\":1}\n{\"@timestamp\":\"2024-05-01T12:00:02Z\",\"n\":3}\n",
+    )
+    .expect("write first input");
+    fs::write(
+        &second_input,
+        "{\"@timestamp\":\"2024-05-01T12:00:01Z\",\"n\":2}\n{\"@timestamp\":\"2024-05-01T12:00:03Z\",\"n\":4}\n",
+    )
+    .expect("write second input");
+    let output_path = first_input.with_file_name("merged.ndjson");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("--merge-by")
+        .arg("@timestamp")
+        .arg(&first_input)
+        .arg(&second_input)
+        .arg(&output_path)
+        .status()
+        .expect("run espipe");
+
+    assert!(status.success(), "espipe exited with failure");
+    let contents = fs::read_to_string(&output_path).expect("read output file");
+    let order: Vec<i64> = contents
+        .lines()
+        .map(|line| {
+            serde_json::from_str::<Value>(line).expect("output json")["n"]
+                .as_i64()
+                .expect("n")
+        })
+        .collect();
+    assert_eq!(order, [1, 2, 3, 4]);
+}
+
+#[test]
+fn cli_dedupe_drops_repeated_documents_and_reports_count() {
+    let input_path = temp_output_path("dupes.ndjson");
+    fs::write(
+        &input_path,
+        "{\"id\":\"1\",\"n\":1}\n{\"id\":\"2\",\"n\":2}\n{\"id\":\"1\",\"n\":3}\n{\"n\":2,\"id\":\"2\"}\n",
+    )
+    .expect("write input");
+    let content_output = temp_output_path("content.ndjson");
+    let field_output = temp_output_path("field.ndjson");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("--dedupe")
+        .arg(&input_path)
+        .arg(&content_output)
+        .output()
+        .expect("run espipe");
+    assert!(output.status.success(), "espipe exited with failure");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Piped 3 of 4 docs") && stdout.contains("(1 duplicates dropped)"),
+        "summary should report dropped duplicates: {stdout}"
+    );
+
+    let status = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("--dedupe=id")
+        .arg(&input_path)
+        .arg(&field_output)
+        .status()
+        .expect("run espipe");
+    assert!(status.success(), "espipe exited with failure");
+    let contents = fs::read_to_string(&field_output).expect("read output file");
+    assert_eq!(contents, "{\"id\":\"1\",\"n\":1}\n{\"id\":\"2\",\"n\":2}\n");
+}
+
+#[test]
+fn cli_explodes_an_array_into_one_document_per_element() {
+    let input_path = temp_output_path("orders.ndjson");
+    fs::write(
+        &input_path,
+        "{\"id\":1,\"items\":[{\"sku\":\"a\"},{\"sku\":\"b\"}]}\n{\"id\":2}\n",
+    )
+    .expect("write input");
+    let output_path = temp_output_path("items.ndjson");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .args(["--explode", "items"])
+        .arg(&input_path)
+        .arg(&output_path)
+        .output()
+        .expect("run espipe");
+    assert!(output.status.success(), "espipe exited with failure");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Piped 3 of 2 docs"));
+    assert_eq!(
+        fs::read_to_string(&output_path).expect("read output file"),
+        "{\"id\":1,\"items\":{\"sku\":\"a\"}}\n{\"id\":1,\"items\":{\"sku\":\"b\"}}\n{\"id\":2}\n"
+    );
+}
+
+#[test]
+fn cli_enriches_documents_from_a_lookup_csv() {
+    let input_path = temp_output_path("hosts.ndjson");
+    fs::write(&input_path, "{\"host\":\"web-1\"}\n{\"host\":\"db-1\"}\n").expect("write input");
+    let lookup_path = temp_output_path("owners.csv");
+    fs::write(&lookup_path, "host,team\nweb-1,search\n").expect("write lookup");
+    let output_path = temp_output_path("enriched.ndjson");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("--enrich")
+        .arg(&lookup_path)
+        .args(["--enrich-key", "host"])
+        .arg(&input_path)
+        .arg(&output_path)
+        .status()
+        .expect("run espipe");
+    assert!(status.success(), "espipe exited with failure");
+    assert_eq!(
+        fs::read_to_string(&output_path).expect("read output file"),
+        "{\"host\":\"web-1\",\"team\":\"search\"}\n{\"host\":\"db-1\"}\n"
+    );
+}
+
+#[test]
+fn cli_skips_or_truncates_lines_over_max_line_bytes() {
+    let input_path = temp_output_path("long-lines.ndjson");
+    let long = "x".repeat(64);
+    fs::write(
+        &input_path,
+        format!("{{\"n\":1}}\n{{\"message\":\"{long}\"}}\n{{\"n\":3}}\n"),
+    )
+    .expect("write input");
+
+    let skipped_path = temp_output_path("skipped.ndjson");
+    let dead_letter_path = temp_output_path("long.ndjson");
+    let status = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .args(["--max-line-bytes", "32B"])
+        .arg("--dead-letter")
+        .arg(&dead_letter_path)
+        .arg(&input_path)
+        .arg(&skipped_path)
+        .status()
+        .expect("run espipe");
+    assert!(status.success(), "espipe exited with failure");
+    assert_eq!(
+        fs::read_to_string(&skipped_path).expect("read output file"),
+        "{\"n\":1}\n{\"n\":3}\n"
+    );
+    let dead_letter = fs::read_to_string(&dead_letter_path).expect("read dead letter file");
+    assert!(dead_letter.contains("\"line\":2"), "{dead_letter}");
+
+    let truncated_path = temp_output_path("truncated.ndjson");
+    let status = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .args(["--max-line-bytes", "16B", "--on-long-line", "truncate"])
+        .arg(&input_path)
+        .arg(&truncated_path)
+        .status()
+        .expect("run espipe");
+    assert!(status.success(), "espipe exited with failure");
+    assert_eq!(
+        fs::read_to_string(&truncated_path).expect("read output file"),
+        "{\"n\":1}\n{\"message\":\"{\\\"message\\\":\\\"xxxx\",\"tags\":[\"truncated\"]}\n{\"n\":3}\n"
+    );
+}
+
+#[test]
+fn cli_flattens_nested_objects_into_joined_keys() {
+    let input_path = temp_output_path("nested-hosts.ndjson");
+    fs::write(
+        &input_path,
+        "{\"host\":{\"os\":{\"name\":\"linux\"}},\"n\":1}\n",
+    )
+    .expect("write input");
+    let output_path = temp_output_path("flat.ndjson");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("--flatten=_")
+        .arg(&input_path)
+        .arg(&output_path)
+        .status()
+        .expect("run espipe");
+    assert!(status.success(), "espipe exited with failure");
+    assert_eq!(
+        fs::read_to_string(&output_path).expect("read output file"),
+        "{\"host_os_name\":\"linux\",\"n\":1}\n"
+    );
+}
+
+#[test]
+fn cli_dead_letters_documents_over_the_field_limits() {
+    let input_path = temp_output_path("nested.ndjson");
+    fs::write(
+        &input_path,
+        "{\"a\":{\"b\":{\"c\":1}}}\n{\"a\":{\"b\":2}}\n",
+    )
+    .expect("write input");
+    let dead_letter_path = temp_output_path("too-deep.ndjson");
+    let output_path = temp_output_path("shallow.ndjson");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .args(["--max-depth", "2", "--over-limit", "dead-letter"])
+        .arg("--dead-letter")
+        .arg(&dead_letter_path)
+        .arg(&input_path)
+        .arg(&output_path)
+        .status()
+        .expect("run espipe");
+    assert!(status.success(), "espipe exited with failure");
+    assert_eq!(
+        fs::read_to_string(&output_path).expect("read output file"),
+        "{\"a\":{\"b\":2}}\n"
+    );
+    assert_eq!(
+        fs::read_to_string(&dead_letter_path).expect("read dead letter file"),
+        "{\"a\":{\"b\":{\"c\":1}}}\n"
+    );
+}
+
+#[test]
+fn cli_set_merges_constant_fields_into_every_document() {
+    let input_path = temp_output_path("plain.ndjson");
+    fs::write(
+        &input_path,
+        "{\"n\":1}\n{\"n\":2,\"ingest\":{\"host\":\"a\"}}\n",
+    )
+    .expect("write input");
+    let meta_path = temp_output_path("meta.json");
+    fs::write(
+        &meta_path,
+        "{\"env\":\"staging\",\"data_source\":\"export\"}",
+    )
+    .expect("write meta");
+    let output_path = temp_output_path("tagged.ndjson");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("--set-file")
+        .arg(&meta_path)
+        .arg("--set")
+        .arg("env=prod")
+        .arg("--set")
+        .arg("ingest.run_id=42")
+        .arg(&input_path)
+        .arg(&output_path)
+        .status()
+        .expect("run espipe");
+
+    assert!(status.success(), "espipe exited with failure");
+    let contents = fs::read_to_string(&output_path).expect("read output file");
+    let docs: Vec<Value> = contents
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("output json"))
+        .collect();
+    assert_eq!(
+        docs,
+        [
+            serde_json::json!({"data_source": "export", "env": "prod", "ingest": {"run_id": 42}, "n": 1}),
+            serde_json::json!({"data_source": "export", "env": "prod", "ingest": {"host": "a", "run_id": 42}, "n": 2}),
+        ]
+    );
+}
+
+#[test]
+fn cli_writes_manifest_for_failed_runs() {
+    let input_path = temp_output_path("partly-bad.ndjson");
+    fs::write(&input_path, "{\"a\":1}\nnot json\n").expect("write input");
+    let output_path = temp_output_path("partly-bad-out.ndjson");
+    let manifest_path = temp_output_path("manifest.json");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("--manifest")
+        .arg(&manifest_path)
+        .arg("--password")
+        .arg("hunter2")
+        .arg("--username")
+        .arg("elastic")
+        .arg(&input_path)
+        .arg(&output_path)
+        .output()
+        .expect("run espipe");
+
+    assert!(!output.status.success(), "espipe should reject bad input");
+    let manifest: Value =
+        serde_json::from_str(&fs::read_to_string(&manifest_path).expect("read manifest"))
+            .expect("manifest is JSON");
+    assert_eq!(manifest["status"], "failed");
+    assert!(
+        manifest["error"]
+            .as_str()
+            .unwrap()
+            .contains("Error parsing JSON")
+    );
+    assert_eq!(manifest["documents"]["read"], 1);
+    assert_eq!(manifest["inputs"][0], input_path.display().to_string());
+    assert!(
+        !manifest["arguments"].to_string().contains("hunter2"),
+        "manifest should redact the password: {manifest}"
+    );
+}
+
+#[test]
+fn cli_skips_malformed_lines_within_the_parse_error_budget() {
+    let input_path = temp_output_path("some-bad.ndjson");
+    fs::write(&input_path, "{\"a\":1}\nnot json\n{\"a\":3}\n[4]\n").expect("write input");
+    let output_path = temp_output_path("some-bad-out.ndjson");
+    let dead_letter_path = temp_output_path("some-bad-dead.ndjson");
+    let run = |max_parse_errors: &str| {
+        Command::new(env!("CARGO_BIN_EXE_espipe"))
+            .arg("--max-parse-errors")
+            .arg(max_parse_errors)
+            .arg("--dead-letter")
+            .arg(&dead_letter_path)
+            .arg(&input_path)
+            .arg(&output_path)
+            .output()
+            .expect("run espipe")
+    };
+
+    let output = run("1");
+    assert!(
+        !output.status.success(),
+        "the second bad line is over budget"
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("line 4, byte 25") && stderr.contains("more than 1 malformed records"),
+        "{stderr}"
+    );
+
+    fs::remove_file(&dead_letter_path).expect("remove dead letter");
+    let output = run("2");
+    assert!(output.status.success(), "espipe exited with failure");
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("(2 malformed records skipped)"),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    assert_eq!(
+        fs::read_to_string(&output_path).expect("read output"),
+        "{\"a\":1}\n{\"a\":3}\n"
+    );
+    let dead_letters: Vec<Value> = fs::read_to_string(&dead_letter_path)
+        .expect("read dead letter")
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("dead letter is NDJSON"))
+        .collect();
+    assert_eq!(dead_letters.len(), 2);
+    assert_eq!(dead_letters[0]["line"], 2);
+    assert_eq!(dead_letters[0]["offset"], 8);
+    assert_eq!(dead_letters[0]["record"], "not json");
+    assert!(
+        dead_letters[0]["error"]
+            .as_str()
+            .unwrap()
+            .contains("Error parsing JSON")
+    );
+    assert_eq!(dead_letters[1]["line"], 4);
+}
+
+#[test]
+fn cli_run_executes_each_pipeline_in_the_config() {
+    let input_path = temp_output_path("pipeline-input.ndjson");
+    fs::write(&input_path, "{\"id\":\"1\"}\n{\"id\":\"1\"}\n").expect("write input");
+    let first_output = temp_output_path("first.ndjson");
+    let second_output = temp_output_path("second.ndjson");
+    let config_path = temp_output_path("pipelines.yml");
+    fs::write(
+        &config_path,
+        format!(
+            "pipelines:\n  first:\n    input: {}\n    output: {}\n  second:\n    inputs: [{}]\n    output: {}\n    options:\n      dedupe: id\n      quiet: true\n",
+            input_path.display(),
+            first_output.display(),
+            input_path.display(),
+            second_output.display(),
+        ),
+    )
+    .expect("write config");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("run")
+        .arg("--config")
+        .arg(&config_path)
+        .output()
+        .expect("run espipe");
+
+    assert!(output.status.success(), "espipe run exited with failure");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("pipeline second: 1 runs, 0 failed, piped 1 of 2 docs"),
+        "missing per-pipeline stats: {stdout}"
+    );
+    assert_eq!(
+        fs::read_to_string(&first_output).unwrap(),
+        "{\"id\":\"1\"}\n{\"id\":\"1\"}\n"
+    );
+    assert_eq!(
+        fs::read_to_string(&second_output).unwrap(),
+        "{\"id\":\"1\"}\n"
+    );
+}
+
+#[test]
+fn cli_prints_stdout_documents_as_a_table_or_pretty_json() {
+    let input_path = temp_output_path("people.ndjson");
+    fs::write(
+        &input_path,
+        "{\"name\":\"ada\",\"user\":{\"id\":7}}\n{\"name\":\"grace hopper\",\"user\":{\"id\":1234}}\n",
+    )
+    .expect("write input");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .args([
+            "--quiet",
+            "--output-format",
+            "table",
+            "--fields",
+            "user.id,name",
+        ])
+        .arg(&input_path)
+        .arg("-")
+        .output()
+        .expect("run espipe");
+    assert!(output.status.success(), "espipe exited with failure");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "user.id  name\n7        ada\n1234     grace hopper\n"
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .args(["--quiet", "--output-format", "pretty"])
+        .arg(&input_path)
+        .arg("-")
+        .output()
+        .expect("run espipe");
+    assert!(output.status.success(), "espipe exited with failure");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.starts_with("{\n  \"name\": \"ada\",\n  \"user\": {\n    \"id\": 7\n  }\n}\n"),
+        "documents should be indented: {stdout}"
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .args(["--output-format", "table"])
+        .arg(&input_path)
+        .arg(temp_output_path("table.ndjson"))
+        .output()
+        .expect("run espipe");
+    assert!(
+        !output.status.success(),
+        "table output to a file should fail"
+    );
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("only applies to stdout output"),
+        "unexpected error: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn cli_sniffs_the_format_of_stdin_and_extensionless_files() {
+    use flate2::{Compression, write::GzEncoder};
+    use std::{io::Write, process::Stdio};
+
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(b"id,name\n1,ada\n2,grace\n").unwrap();
+    let gzip = gzip.finish().unwrap();
+    let csv_output = temp_output_path("stdin.ndjson");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("-")
+        .arg(&csv_output)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("run espipe");
+    child.stdin.take().unwrap().write_all(&gzip).unwrap();
+    assert!(
+        child.wait().unwrap().success(),
+        "espipe exited with failure"
+    );
+    let contents = fs::read_to_string(&csv_output).expect("read output file");
+    let rows: Vec<Value> = contents
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("output json"))
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            serde_json::json!({ "id": "1", "name": "ada" }),
+            serde_json::json!({ "id": "2", "name": "grace" }),
+        ]
+    );
+
+    let array_input = temp_output_path("export");
+    fs::write(&array_input, "[{\"a\":1},\n {\"a\":2}]\n").expect("write input");
+    let array_output = temp_output_path("array.ndjson");
+    let status = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg(&array_input)
+        .arg(&array_output)
+        .status()
+        .expect("run espipe");
+    assert!(status.success(), "espipe exited with failure");
+    let contents = fs::read_to_string(&array_output).expect("read output file");
+    assert_eq!(contents, "{\"a\":1}\n{\"a\":2}\n");
+}
+
+#[test]
+fn cli_reads_and_writes_csv_through_stdin_and_stdout_with_format_flags() {
+    use std::{io::Write, process::Stdio};
+
+    // A single column is too little to sniff as CSV.
+    let mut child = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .args(["-", "-", "--input-format", "csv", "--output-format", "csv"])
+        .args(["--quiet", "--fields", "name,note"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("run espipe");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"name\nada\n\"hopper, grace\"\n")
+        .unwrap();
+    let output = child.wait_with_output().expect("wait for espipe");
+    assert!(output.status.success(), "espipe exited with failure");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "name,note\nada,\n\"hopper, grace\",\n"
+    );
+}
+
+#[test]
+fn cli_reads_concatenated_pretty_printed_json() {
+    let input = temp_output_path("pretty.json");
+    fs::write(
+        &input,
+        "{\n  \"b\": 1,\n  \"a\": \"x y\"\n}\n{\n  \"b\": 2\n}{\"b\": 3}\n",
+    )
+    .expect("write input");
+    let output_path = input.with_file_name("pretty.ndjson");
+
+    for format in ["auto", "concatenated"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+            .args(["--quiet", "--input-format", format])
+            .arg(&input)
+            .arg(&output_path)
+            .output()
+            .expect("run espipe");
+        assert!(
+            output.status.success(),
+            "espipe exited with failure: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(
+            fs::read_to_string(&output_path).unwrap(),
+            "{\"b\":1,\"a\":\"x y\"}\n{\"b\":2}\n{\"b\": 3}\n"
+        );
+        fs::remove_file(&output_path).unwrap();
+    }
+}
+
+#[test]
+fn cli_generates_documents_from_a_template() {
+    let template = temp_output_path("order-template.json");
+    fs::write(
+        &template,
+        r#"{"order":"{{seq}}","id":"order-{{seq}}","status":"{{choice:paid=3|open}}"}"#,
+    )
+    .expect("write template");
+    let output_path = template.with_file_name("orders.ndjson");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("--quiet")
+        .arg(format!("gen://{}?count=3", template.display()))
+        .arg(&output_path)
+        .output()
+        .expect("run espipe");
+    assert!(
+        output.status.success(),
+        "espipe exited with failure: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let written = fs::read_to_string(&output_path).expect("read output file");
+    let docs: Vec<Value> = written
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("valid JSON"))
+        .collect();
+    assert_eq!(docs.len(), 3, "{written}");
+    for (seq, doc) in docs.iter().enumerate() {
+        assert_eq!(doc["order"], seq);
+        assert_eq!(doc["id"], format!("order-{seq}"));
+        assert!(["paid", "open"].contains(&doc["status"].as_str().unwrap()));
+    }
+}
+
+#[test]
+fn cli_takes_default_flags_from_a_config_file() {
+    let input = temp_output_path("docs.ndjson");
+    fs::write(&input, "{\"name\":\"ada\",\"id\":1}\n").expect("write input");
+    let config = input.with_file_name("config.yml");
+    fs::write(
+        &config,
+        "quiet: true\noutput-format: csv\nfields: [name, id]\n",
+    )
+    .expect("write config");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("--config")
+        .arg(&config)
+        .arg(&input)
+        .arg("-")
+        .output()
+        .expect("run espipe");
+    assert!(output.status.success(), "espipe exited with failure");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "name,id\nada,1\n");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("--config")
+        .arg(&config)
+        .args(["--output-format", "table", "--fields", "id"])
+        .arg(&input)
+        .arg("-")
+        .output()
+        .expect("run espipe");
+    assert!(output.status.success(), "espipe exited with failure");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "id\n1\n");
+
+    fs::write(&config, "batch-sise: 10\n").expect("write config");
+    let output = Command::new(env!("CARGO_BIN_EXE_espipe"))
+        .arg("--config")
+        .arg(&config)
+        .arg(&input)
+        .arg("-")
+        .output()
+        .expect("run espipe");
+    assert!(!output.status.success(), "espipe should reject the config");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("unknown option batch-sise"),
+        "stderr should name the option: {stderr}"
+    );
+}
@@ -61,6 +61,13 @@ fn run_espipe(args: &[String]) -> Output {
 }
 
 fn spawn_server(template_status: u16) -> (String, Arc<Mutex<Vec<RecordedRequest>>>) {
+    spawn_server_running("9.1.0", template_status)
+}
+
+fn spawn_server_running(
+    version: &'static str,
+    template_status: u16,
+) -> (String, Arc<Mutex<Vec<RecordedRequest>>>) {
     let listener = TcpListener::bind("127.0.0.1:0").unwrap();
     let addr = listener.local_addr().unwrap();
     let requests = Arc::new(Mutex::new(Vec::new()));
@@ -72,7 +79,7 @@ fn spawn_server(template_status: u16) -> (String, Arc<Mutex<Vec<RecordedRequest>
                 break;
             };
             let requests = Arc::clone(&thread_requests);
-            thread::spawn(move || handle_connection(stream, template_status, requests));
+            thread::spawn(move || handle_connection(stream, version, template_status, requests));
         }
     });
 
@@ -81,6 +88,7 @@ fn spawn_server(template_status: u16) -> (String, Arc<Mutex<Vec<RecordedRequest>
 
 fn handle_connection(
     mut stream: TcpStream,
+    version: &str,
     template_status: u16,
     requests: Arc<Mutex<Vec<RecordedRequest>>>,
 ) {
@@ -132,26 +140,47 @@ fn handle_connection(
     let body =
         String::from_utf8_lossy(&buffer[body_start..body_start + content_length]).to_string();
 
-    requests.lock().unwrap().push(RecordedRequest {
-        method: method.clone(),
-        path: path.clone(),
-        content_type,
-        body,
-    });
+    // The version check is answered but not recorded, so tests see the requests that change state.
+    if method == "GET" && path == "/" {
+        let info = format!(r#"{{"version":{{"number":"{version}"}}}}"#);
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{info}",
+            info.len()
+        );
+        stream.write_all(response.as_bytes()).unwrap();
+        return;
+    }
 
     let (status, response_body) = if path.contains("/_bulk") {
+        // One item per action line, as Elasticsearch answers.
+        let items: Vec<String> = body
+            .lines()
+            .step_by(2)
+            .enumerate()
+            .map(|(id, _)| {
+                format!(r#"{{"create":{{"_index":"logs-docs","_id":"{id}","status":201}}}}"#)
+            })
+            .collect();
         (
             "200 OK",
-            r#"{"errors":false,"items":[{"create":{"_index":"logs-docs","_id":"1","status":201}},{"create":{"_index":"logs-docs","_id":"2","status":201}}]}"#,
+            format!(r#"{{"errors":false,"items":[{}]}}"#, items.join(",")),
         )
     } else if template_status == 200 {
-        ("200 OK", r#"{"acknowledged":true}"#)
+        ("200 OK", r#"{"acknowledged":true}"#.to_string())
     } else {
         (
             "409 Conflict",
-            r#"{"error":{"type":"resource_already_exists_exception","reason":"exists"},"status":409}"#,
+            r#"{"error":{"type":"resource_already_exists_exception","reason":"exists"},"status":409}"#
+                .to_string(),
         )
     };
+    requests.lock().unwrap().push(RecordedRequest {
+        method,
+        path,
+        content_type,
+        body,
+    });
+
     let response = format!(
         "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{response_body}",
         response_body.len()
@@ -213,6 +242,72 @@ fn cli_installs_template_before_bulk_with_default_name_and_put() {
     );
 }
 
+#[test]
+fn cli_installs_ilm_policy_and_component_templates_before_index_template() {
+    let dir = temp_dir("espipe-template-components");
+    let input = write_input_file(&dir);
+    let policy = write_template_file(
+        &dir,
+        "logs-policy.yml",
+        "policy:\n  phases:\n    hot:\n      actions: {}\n",
+    );
+    let mappings = write_template_file(
+        &dir,
+        "logs-mappings.json",
+        r#"{"template":{"mappings":{"properties":{"message":{"type":"text"}}}}}"#,
+    );
+    let settings = write_template_file(
+        &dir,
+        "logs-settings.json",
+        r#"{"template":{"settings":{"index.lifecycle.name":"logs-policy"}}}"#,
+    );
+    let template = write_template_file(
+        &dir,
+        "logs-docs.json",
+        r#"{"index_patterns":["logs-*"],"composed_of":["logs-mappings","logs-settings"]}"#,
+    );
+    let (base_url, requests) = spawn_server(200);
+
+    let output = run_espipe(&[
+        input.display().to_string(),
+        format!("{base_url}/logs-docs"),
+        "--ilm-policy".to_string(),
+        policy.display().to_string(),
+        "--component-template".to_string(),
+        mappings.display().to_string(),
+        "--component-template".to_string(),
+        settings.display().to_string(),
+        "--template".to_string(),
+        template.display().to_string(),
+        "--uncompressed".to_string(),
+    ]);
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let requests = requests.lock().unwrap();
+    let paths: Vec<_> = requests
+        .iter()
+        .map(|request| request.path.as_str())
+        .collect();
+    assert_eq!(
+        paths[..4],
+        [
+            "/_ilm/policy/logs-policy",
+            "/_component_template/logs-mappings",
+            "/_component_template/logs-settings",
+            "/_index_template/logs-docs",
+        ]
+    );
+    assert_eq!(
+        serde_json::from_str::<Value>(&requests[0].body).unwrap()["policy"]["phases"]["hot"],
+        serde_json::json!({"actions": {}})
+    );
+    assert!(paths[4..].iter().all(|path| *path == "/logs-docs/_bulk"));
+}
+
 #[test]
 fn cli_installs_pipeline_then_template_then_bulk_when_template_references_pipeline() {
     let dir = temp_dir("espipe-template-pipeline");
@@ -479,6 +574,73 @@ fn cli_without_template_sends_only_bulk_requests() {
     );
 }
 
+#[test]
+fn cli_replays_bulk_files_with_their_actions() {
+    let dir = temp_dir("espipe-bulk-input");
+    let input = dir.join("export.ndjson");
+    let bulk = concat!(
+        "{\"index\":{\"_index\":\"logs-old\",\"_id\":\"1\"}}\n",
+        "{\"message\":\"one\"}\n",
+        "{\"create\":{\"_id\":\"2\"}}\n",
+        "{\"message\":\"two\"}\n",
+    );
+    fs::write(&input, bulk).unwrap();
+    let (base_url, requests) = spawn_server(200);
+
+    let output = run_espipe(&[
+        input.display().to_string(),
+        format!("{base_url}/logs-docs"),
+        "--uncompressed".to_string(),
+    ]);
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].path, "/logs-docs/_bulk");
+    assert_eq!(requests[0].body, bulk);
+}
+
+#[test]
+fn cli_sends_documents_to_their_own_index_without_a_url_index() {
+    let dir = temp_dir("espipe-index-from-doc");
+    let input = dir.join("combined.ndjson");
+    fs::write(
+        &input,
+        "{\"_index\":\"orders\",\"n\":1}\n{\"_index\":\"customers\",\"n\":2}\n",
+    )
+    .unwrap();
+    let (base_url, requests) = spawn_server(200);
+
+    let output = run_espipe(&[
+        input.display().to_string(),
+        format!("{base_url}/"),
+        "--index-from-doc".to_string(),
+        "_index".to_string(),
+        "--uncompressed".to_string(),
+    ]);
+
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].path, "/_bulk");
+    assert_eq!(
+        requests[0].body,
+        "{\"create\":{\"_index\":\"orders\"}}\n{\"n\":1}\n{\"create\":{\"_index\":\"customers\"}}\n{\"n\":2}\n"
+    );
+
+    let output = run_espipe(&[input.display().to_string(), format!("{base_url}/")]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--index-from-doc"));
+}
+
 #[test]
 fn no_template_file_output_preserves_input_first_failure_order() {
     let dir = temp_dir("espipe-template-no-template-order");
@@ -529,6 +691,32 @@ fn rejected_template_aborts_before_bulk() {
     );
 }
 
+#[test]
+fn templates_fail_before_install_on_clusters_without_them() {
+    let dir = temp_dir("espipe-template-old-cluster");
+    let input = write_input_file(&dir);
+    let template = write_template_file(&dir, "logs.json", r#"{"index_patterns":["logs-*"]}"#);
+    let (base_url, requests) = spawn_server_running("7.7.1", 200);
+
+    let output = run_espipe(&[
+        input.display().to_string(),
+        format!("{base_url}/logs-docs"),
+        "--template".to_string(),
+        template.display().to_string(),
+        "--uncompressed".to_string(),
+    ]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(
+            "Elasticsearch 7.7.1 does not support composable index and component templates"
+        ),
+        "stderr: {stderr}"
+    );
+    assert!(requests.lock().unwrap().is_empty());
+}
+
 #[test]
 fn invalid_template_arguments_fail_before_input_access() {
     let dir = temp_dir("espipe-template-invalid-args");